@@ -0,0 +1,348 @@
+use crate::doc_comment;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Attribute, Data, DeriveInput, Fields, LitStr};
+
+/// Serde attributes that change the JSON form
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    skip: bool,
+    default: bool,
+}
+
+impl SerdeAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut serde = SerdeAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    serde.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("rename_all") {
+                    serde.rename_all = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                    serde.skip = true;
+                } else if meta.path.is_ident("default") {
+                    serde.default = true;
+                    if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse::<syn::Expr>()?;
+                    }
+                } else if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<syn::Expr>()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    let _content;
+                    syn::parenthesized!(_content in meta.input);
+                }
+                Ok(())
+            })?;
+        }
+        Ok(serde)
+    }
+}
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let description = doc_comment(&input.attrs);
+
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param
+            .bounds
+            .push(syn::parse_quote!(::ai_rs::agent::JsonSchema));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (arguments, body) = match &input.data {
+        Data::Struct(data) => {
+            let Fields::Named(fields) = &data.fields else {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "JsonSchema can only be derived for structs with named fields",
+                ));
+            };
+            let mut properties = Vec::new();
+            for field in &fields.named {
+                let serde = SerdeAttrs::parse(&field.attrs)?;
+                if serde.skip {
+                    continue;
+                }
+                let ident = field.ident.as_ref().expect("named field");
+                let key = serde.rename.unwrap_or_else(|| {
+                    rename_field(&ident.to_string(), container.rename_all.as_deref())
+                });
+                let ty = &field.ty;
+                let describe = doc_comment(&field.attrs).map(|text| {
+                    quote! {
+                        if let Some(object) = schema.as_object_mut() {
+                            object.insert("description".to_string(), #text.into());
+                        }
+                    }
+                });
+                let optional = serde.default || container.default;
+                properties.push(quote! {
+                    let mut schema = <#ty as ::ai_rs::agent::JsonSchema>::json_schema();
+                    #describe
+                    properties.insert(#key.to_string(), schema);
+                    if !#optional && !<#ty as ::ai_rs::agent::JsonSchema>::OPTIONAL {
+                        required.push(#key.into());
+                    }
+                });
+            }
+            let body = quote! {
+                let mut properties = ::ai_rs::agent::support::serde_json::Map::new();
+                let mut required =
+                    ::std::vec::Vec::<::ai_rs::agent::support::serde_json::Value>::new();
+                #(#properties)*
+                ::ai_rs::agent::support::serde_json::json!({
+                    "type": "object",
+                    "properties": properties,
+                    "required": required,
+                })
+            };
+            (true, body)
+        }
+        Data::Enum(data) => {
+            let mut values = Vec::new();
+            for variant in &data.variants {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        "JsonSchema can only be derived for enums of unit variants",
+                    ));
+                }
+                let serde = SerdeAttrs::parse(&variant.attrs)?;
+                if serde.skip {
+                    continue;
+                }
+                values.push(serde.rename.unwrap_or_else(|| {
+                    rename_variant(&variant.ident.to_string(), container.rename_all.as_deref())
+                }));
+            }
+            let body = quote! {
+                ::ai_rs::agent::support::serde_json::json!({
+                    "type": "string",
+                    "enum": [#(#values),*],
+                })
+            };
+            (false, body)
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "JsonSchema cannot be derived for unions",
+            ))
+        }
+    };
+
+    let describe = description.map(|text| {
+        quote! {
+            if let Some(object) = schema.as_object_mut() {
+                object.insert("description".to_string(), #text.into());
+            }
+        }
+    });
+    Ok(quote! {
+        impl #impl_generics ::ai_rs::agent::JsonSchema for #name #ty_generics #where_clause {
+            const ARGUMENTS: bool = #arguments;
+
+            fn json_schema() -> ::ai_rs::agent::support::serde_json::Value {
+                let mut schema = { #body };
+                #describe
+                schema
+            }
+        }
+    })
+}
+
+/// Applies a serde `rename_all` rule to a snake_case field name
+fn rename_field(field: &str, rule: Option<&str>) -> String {
+    let field = field.strip_prefix("r#").unwrap_or(field);
+    match rule {
+        Some("UPPERCASE") | Some("SCREAMING_SNAKE_CASE") => field.to_ascii_uppercase(),
+        Some("PascalCase") => pascal_case(field),
+        Some("camelCase") => lower_first(&pascal_case(field)),
+        Some("kebab-case") => field.replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => field.to_ascii_uppercase().replace('_', "-"),
+        _ => field.to_string(),
+    }
+}
+
+/// Applies a serde `rename_all` rule to a PascalCase variant name
+fn rename_variant(variant: &str, rule: Option<&str>) -> String {
+    let variant = variant.strip_prefix("r#").unwrap_or(variant);
+    let snake = || {
+        let mut snake = String::new();
+        for (index, c) in variant.char_indices() {
+            if c.is_uppercase() && index > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        }
+        snake
+    };
+    match rule {
+        Some("lowercase") => variant.to_ascii_lowercase(),
+        Some("UPPERCASE") => variant.to_ascii_uppercase(),
+        Some("camelCase") => lower_first(variant),
+        Some("snake_case") => snake(),
+        Some("SCREAMING_SNAKE_CASE") => snake().to_ascii_uppercase(),
+        Some("kebab-case") => snake().replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => snake().to_ascii_uppercase().replace('_', "-"),
+        _ => variant.to_string(),
+    }
+}
+
+/// Converts a snake_case name to PascalCase
+pub(crate) fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn lower_first(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn expanded(input: DeriveInput) -> String {
+        expand(input).unwrap().to_string()
+    }
+
+    #[test]
+    fn structs_describe_each_field() {
+        let output = expanded(parse_quote! {
+            /// Where to search
+            struct Query {
+                /// The search terms
+                text: String,
+                limit: Option<u32>,
+                #[serde(skip)]
+                cursor: String,
+            }
+        });
+        assert!(output.contains("impl :: ai_rs :: agent :: JsonSchema for Query"));
+        assert!(output.contains("const ARGUMENTS : bool = true"));
+        assert!(output.contains("properties . insert (\"text\" . to_string () , schema)"));
+        assert!(output.contains("properties . insert (\"limit\" . to_string () , schema)"));
+        assert!(!output.contains("\"cursor\""));
+        assert!(output.contains("\"The search terms\" . into ()"));
+        assert!(output.contains("\"Where to search\" . into ()"));
+        // Optional types are left out of `required` by their `OPTIONAL` constant
+        assert!(
+            output.contains("< Option < u32 > as :: ai_rs :: agent :: JsonSchema > :: OPTIONAL")
+        );
+    }
+
+    #[test]
+    fn follows_serde_renames_and_defaults() {
+        let output = expanded(parse_quote! {
+            #[serde(rename_all = "camelCase", deny_unknown_fields)]
+            struct Options {
+                max_results: u32,
+                #[serde(rename = "lang", default)]
+                language: String,
+            }
+        });
+        assert!(output.contains("\"maxResults\" . to_string ()"));
+        assert!(output.contains("\"lang\" . to_string ()"));
+        assert!(output.contains("if ! true &&"));
+        assert!(output.contains("if ! false &&"));
+    }
+
+    #[test]
+    fn generic_parameters_must_implement_json_schema() {
+        let output = expanded(parse_quote! {
+            struct Page<T> {
+                items: Vec<T>,
+            }
+        });
+        assert!(output.contains("impl < T : :: ai_rs :: agent :: JsonSchema >"));
+        assert!(output.contains("for Page < T >"));
+    }
+
+    #[test]
+    fn enums_list_their_variants() {
+        let output = expanded(parse_quote! {
+            #[serde(rename_all = "snake_case")]
+            enum Unit {
+                DegreesCelsius,
+                Fahrenheit,
+                #[serde(rename = "K")]
+                Kelvin,
+                #[serde(skip)]
+                Unknown,
+            }
+        });
+        assert!(output.contains("const ARGUMENTS : bool = false"));
+        assert!(output.contains("\"enum\" : [\"degrees_celsius\" , \"fahrenheit\" , \"K\"]"));
+    }
+
+    #[test]
+    fn rejects_unsupported_types() {
+        let error = |input: DeriveInput| expand(input).unwrap_err().to_string();
+        assert_eq!(
+            error(parse_quote!(
+                struct Pair(u8, u8);
+            )),
+            "JsonSchema can only be derived for structs with named fields"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                enum Shape {
+                    Circle(f64),
+                }
+            )),
+            "JsonSchema can only be derived for enums of unit variants"
+        );
+        assert_eq!(
+            error(parse_quote!(union Bits { a: u32, b: f32 })),
+            "JsonSchema cannot be derived for unions"
+        );
+    }
+
+    #[test]
+    fn renames_follow_serde_rules() {
+        assert_eq!(pascal_case("get_weather"), "GetWeather");
+        assert_eq!(pascal_case("_leading__double"), "LeadingDouble");
+        assert_eq!(rename_field("max_results", Some("camelCase")), "maxResults");
+        assert_eq!(
+            rename_field("max_results", Some("kebab-case")),
+            "max-results"
+        );
+        assert_eq!(rename_field("r#type", Some("UPPERCASE")), "TYPE");
+        assert_eq!(rename_field("max_results", None), "max_results");
+        assert_eq!(
+            rename_variant("InProgress", Some("snake_case")),
+            "in_progress"
+        );
+        assert_eq!(
+            rename_variant("InProgress", Some("SCREAMING-KEBAB-CASE")),
+            "IN-PROGRESS"
+        );
+        assert_eq!(
+            rename_variant("InProgress", Some("camelCase")),
+            "inProgress"
+        );
+        assert_eq!(
+            rename_variant("InProgress", Some("lowercase")),
+            "inprogress"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Procedural macros for `ai_rs` tools
+//!
+//! Use them through `ai_rs::agent` with the `macros` feature of `ai_rs`.
+
+mod derive;
+mod tool;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, ItemFn};
+
+/// Turns a function into a tool for `ToolRegistry::register_handler`
+///
+/// Generates a unit struct named after the function in PascalCase with a `Tool` suffix
+/// (e.g. `GetWeatherTool` for `get_weather`) that implements `ToolHandler`. Its declaration
+/// takes the name of the function, the description from its doc comments, and a JSON
+/// schema with one property per parameter. A single parameter whose type derives
+/// `JsonSchema` is used as the whole arguments object instead.
+///
+/// The function may be async. It returns a serializable value or a `Result` whose error
+/// implements `Display`; errors are sent back to the model.
+///
+/// Options: `#[tool(name = "...", description = "...")]`.
+#[proc_macro_attribute]
+pub fn tool(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as ItemFn);
+    tool::expand(attr.into(), function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implements `JsonSchema` for a struct with named fields or an enum of unit variants
+///
+/// Doc comments become descriptions, `Option` fields are not required, and serde's
+/// `rename` and `rename_all` attributes are followed.
+#[proc_macro_derive(JsonSchema, attributes(serde))]
+pub fn derive_json_schema(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    derive::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Joins `///` comments into a description, dropping the space after `///`
+pub(crate) fn doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(text),
+                        ..
+                    }),
+                ..
+            }) => Some(text.value()),
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').unwrap_or(&line).to_string())
+        .collect();
+    let text = lines.join("\n").trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
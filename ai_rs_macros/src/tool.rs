@@ -0,0 +1,355 @@
+use crate::derive::pascal_case;
+use crate::doc_comment;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{FnArg, ItemFn, LitStr, Pat, ReturnType, Type};
+
+/// Options of `#[tool(...)]`
+#[derive(Default)]
+struct ToolAttrs {
+    name: Option<String>,
+    description: Option<String>,
+}
+
+pub(crate) fn expand(attr: TokenStream, function: ItemFn) -> syn::Result<TokenStream> {
+    let mut options = ToolAttrs::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            options.name = Some(meta.value()?.parse::<LitStr>()?.value());
+            Ok(())
+        } else if meta.path.is_ident("description") {
+            options.description = Some(meta.value()?.parse::<LitStr>()?.value());
+            Ok(())
+        } else {
+            Err(meta.error("expected `name` or `description`"))
+        }
+    });
+    syn::parse::Parser::parse2(parser, attr)?;
+
+    let signature = &function.sig;
+    if !signature.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &signature.generics,
+            "#[tool] functions cannot be generic",
+        ));
+    }
+
+    let mut params = Vec::new();
+    let mut types = Vec::new();
+    for input in &signature.inputs {
+        let FnArg::Typed(typed) = input else {
+            return Err(syn::Error::new_spanned(
+                input,
+                "#[tool] functions cannot take `self`",
+            ));
+        };
+        let Pat::Ident(pat) = typed.pat.as_ref() else {
+            return Err(syn::Error::new_spanned(
+                &typed.pat,
+                "#[tool] parameters must be plain names",
+            ));
+        };
+        if matches!(typed.ty.as_ref(), Type::Reference(_)) {
+            return Err(syn::Error::new_spanned(
+                &typed.ty,
+                "#[tool] parameters must be owned types",
+            ));
+        }
+        params.push(pat.ident.clone());
+        types.push(typed.ty.as_ref().clone());
+    }
+
+    let function_name = &signature.ident;
+    let name = options.name.unwrap_or_else(|| {
+        function_name
+            .to_string()
+            .trim_start_matches("r#")
+            .to_string()
+    });
+    let description = options
+        .description
+        .or_else(|| doc_comment(&function.attrs))
+        .unwrap_or_default();
+    let handler = format_ident!(
+        "{}Tool",
+        pascal_case(function_name.to_string().trim_start_matches("r#")),
+        span = function_name.span()
+    );
+    let visibility = &function.vis;
+    let handler_doc = format!(
+        "Tool handler for [`{}`], generated by `#[tool]`",
+        function_name
+    );
+
+    let keys: Vec<String> = params
+        .iter()
+        .map(|param| param.to_string().trim_start_matches("r#").to_string())
+        .collect();
+    let properties_schema = quote! {{
+        let mut properties = ::ai_rs::agent::support::serde_json::Map::new();
+        let mut required =
+            ::std::vec::Vec::<::ai_rs::agent::support::serde_json::Value>::new();
+        #(
+            properties.insert(
+                #keys.to_string(),
+                <#types as ::ai_rs::agent::JsonSchema>::json_schema(),
+            );
+            if !<#types as ::ai_rs::agent::JsonSchema>::OPTIONAL {
+                required.push(#keys.into());
+            }
+        )*
+        ::ai_rs::agent::support::serde_json::json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        })
+    }};
+
+    let awaited = signature.asyncness.map(|_| quote!(.await));
+    let call_with_fields = quote! {{
+        #[derive(::ai_rs::agent::support::serde::Deserialize)]
+        #[serde(crate = "::ai_rs::agent::support::serde")]
+        struct Arguments {
+            #(#params: #types,)*
+        }
+        let Arguments { #(#params,)* } =
+            ::ai_rs::agent::support::serde_json::from_value(args)
+                .map_err(::ai_rs::agent::ToolError::from)?;
+        #function_name(#(#params),*)#awaited
+    }};
+
+    // A single argument struct deriving `JsonSchema` is the whole arguments object
+    let (schema, call) = match types.as_slice() {
+        [only] => (
+            quote! {
+                if <#only as ::ai_rs::agent::JsonSchema>::ARGUMENTS {
+                    <#only as ::ai_rs::agent::JsonSchema>::json_schema()
+                } else {
+                    #properties_schema
+                }
+            },
+            quote! {
+                if <#only as ::ai_rs::agent::JsonSchema>::ARGUMENTS {
+                    let arguments: #only = ::ai_rs::agent::support::serde_json::from_value(args)
+                        .map_err(::ai_rs::agent::ToolError::from)?;
+                    #function_name(arguments)#awaited
+                } else {
+                    #call_with_fields
+                }
+            },
+        ),
+        _ => (properties_schema, call_with_fields),
+    };
+
+    let output = if returns_result(&signature.output) {
+        quote! {
+            match output {
+                Ok(value) => ::ai_rs::agent::support::tool_output(value),
+                Err(err) => Err(::ai_rs::agent::support::tool_error(err)),
+            }
+        }
+    } else {
+        quote!(::ai_rs::agent::support::tool_output(output))
+    };
+
+    Ok(quote! {
+        #function
+
+        #[doc = #handler_doc]
+        #[derive(Debug, Clone, Copy, Default)]
+        #visibility struct #handler;
+
+        impl ::ai_rs::agent::ToolHandler for #handler {
+            fn declaration(&self) -> ::ai_rs::gemini::types::FunctionDeclaration {
+                ::ai_rs::gemini::types::FunctionDeclaration {
+                    name: #name.to_string(),
+                    description: #description.to_string(),
+                    parameters: #schema,
+                }
+            }
+
+            fn call(
+                &self,
+                args: ::ai_rs::agent::support::serde_json::Value,
+            ) -> ::ai_rs::agent::support::BoxFuture<
+                '_,
+                ::std::result::Result<
+                    ::ai_rs::agent::support::serde_json::Value,
+                    ::ai_rs::agent::ToolError,
+                >,
+            > {
+                ::std::boxed::Box::pin(async move {
+                    // Models send `null` or nothing for tools without required arguments
+                    let args = if args.is_null() {
+                        ::ai_rs::agent::support::serde_json::json!({})
+                    } else {
+                        args
+                    };
+                    let output = #call;
+                    #output
+                })
+            }
+        }
+    })
+}
+
+/// Returns `true` if the function returns a `Result`, judged by the last path segment
+fn returns_result(output: &ReturnType) -> bool {
+    match output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Result"),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn expanded(attr: TokenStream, function: ItemFn) -> String {
+        expand(attr, function).unwrap().to_string()
+    }
+
+    fn error(attr: TokenStream, function: ItemFn) -> String {
+        expand(attr, function).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn generates_a_handler_named_after_the_function() {
+        let output = expanded(
+            quote!(),
+            parse_quote! {
+                /// Gets the weather
+                ///
+                /// in a city
+                pub async fn get_weather(city: String) -> Result<String, String> {
+                    Ok(city)
+                }
+            },
+        );
+        assert!(output.contains("pub struct GetWeatherTool"));
+        assert!(output.contains("impl :: ai_rs :: agent :: ToolHandler for GetWeatherTool"));
+        assert!(output.contains("name : \"get_weather\""));
+        assert!(output.contains("description : \"Gets the weather\\n\\nin a city\""));
+        // The function itself is kept
+        assert!(output.contains("pub async fn get_weather"));
+        assert!(output.contains("get_weather (city) . await"));
+        assert!(output.contains("tool_error (err)"));
+        assert!(output.contains("\"city\" . to_string ()"));
+    }
+
+    #[test]
+    fn plain_functions_are_not_awaited() {
+        let output = expanded(
+            quote!(),
+            parse_quote! {
+                fn add(a: i64, b: i64) -> i64 {
+                    a + b
+                }
+            },
+        );
+        assert!(output.contains("struct AddTool"));
+        assert!(output.contains("add (a , b)"));
+        assert!(!output.contains("await"));
+        assert!(!output.contains("tool_error"));
+        assert!(output.contains("tool_output (output)"));
+        // Several parameters always use one property each
+        assert!(!output.contains("ARGUMENTS"));
+    }
+
+    #[test]
+    fn single_parameters_may_be_the_arguments_object() {
+        let output = expanded(
+            quote!(),
+            parse_quote! {
+                fn search(query: Query) -> Vec<String> {
+                    Vec::new()
+                }
+            },
+        );
+        assert!(output.contains("< Query as :: ai_rs :: agent :: JsonSchema > :: ARGUMENTS"));
+        assert!(output.contains("search (arguments)"));
+        assert!(output.contains("search (query)"));
+    }
+
+    #[test]
+    fn options_override_name_and_description() {
+        let output = expanded(
+            quote!(name = "lookup", description = "Looks things up"),
+            parse_quote! {
+                /// Ignored
+                fn r#find() -> String {
+                    String::new()
+                }
+            },
+        );
+        assert!(output.contains("struct FindTool"));
+        assert!(output.contains("name : \"lookup\""));
+        assert!(output.contains("description : \"Looks things up\""));
+        assert!(!output.contains("description : \"Ignored\""));
+    }
+
+    #[test]
+    fn rejects_unsupported_functions() {
+        assert_eq!(
+            error(
+                quote!(),
+                parse_quote!(
+                    fn pick<T>(value: T) {}
+                )
+            ),
+            "#[tool] functions cannot be generic"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                parse_quote!(
+                    fn run(&self) {}
+                )
+            ),
+            "#[tool] functions cannot take `self`"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                parse_quote!(
+                    fn pair((a, b): (u8, u8)) {}
+                )
+            ),
+            "#[tool] parameters must be plain names"
+        );
+        assert_eq!(
+            error(
+                quote!(),
+                parse_quote!(
+                    fn greet(name: &str) {}
+                )
+            ),
+            "#[tool] parameters must be owned types"
+        );
+        assert_eq!(
+            error(
+                quote!(title = "x"),
+                parse_quote!(
+                    fn greet() {}
+                )
+            ),
+            "expected `name` or `description`"
+        );
+    }
+
+    #[test]
+    fn detects_result_returns() {
+        assert!(returns_result(&parse_quote!(-> Result<u8, String>)));
+        assert!(returns_result(&parse_quote!(-> std::io::Result<u8>)));
+        assert!(!returns_result(&parse_quote!(-> Option<u8>)));
+        assert!(!returns_result(&ReturnType::Default));
+    }
+}
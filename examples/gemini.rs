@@ -34,6 +34,7 @@ async fn main() {
         top_k: Some(40),
         candidate_count: None,
         stop_sequences: None,
+        response_mime_type: None,
     };
 
     match client
@@ -56,7 +57,7 @@ async fn main() {
             role: "user".to_string(),
             parts: vec![Part {
                 text: Some("Explain quantum computing in simple terms".to_string()),
-                inline_data: None,
+                ..Default::default()
             }],
         }],
         generation_config: Some(GenerationConfig {
@@ -66,9 +67,11 @@ async fn main() {
             top_k: Some(50),
             candidate_count: None,
             stop_sequences: None,
+            response_mime_type: None,
         }),
         safety_settings: None,
         tools: None,
+        template_version: None,
     };
 
     match client.generate_content_with_request(request).await {
@@ -104,6 +107,7 @@ async fn main() {
     }
 
     println!("\n=== Backward Compatibility (Sync Method) ===");
+    #[allow(deprecated)]
     let sync_response = client.generate_content_sync("What is the capital of France?");
     println!("Sync Response: {}", sync_response);
 }
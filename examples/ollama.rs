@@ -1,4 +1,4 @@
-use ai_rs::{init_logging, ollama::GenerateRequest, OllamaClient};
+use ai_rs::{ollama::GenerateRequest, OllamaClient};
 use tokio::runtime::Runtime;
 
 const URL: &str = "http://localhost:11434";
@@ -38,6 +38,7 @@ async fn ollama() {
         prompt: "Hello, llama!".to_string(),
         stream: None,
         options: None,
+        template_version: None,
     };
 
     match ollama.generate_completion(generate_request).await {
@@ -48,7 +49,7 @@ async fn ollama() {
 
 fn main() {
     // uncomment to not get logs
-    // init_logging();
+    // ai_rs::init_logging();
 
     // Create a new Tokio runtime to run the async ollama function
     let rt = Runtime::new().unwrap();
@@ -30,6 +30,8 @@ case $yn in
 esac
 
 echo "==> Publishing to crates.io..."
-cargo publish
+# The macros crate goes first, ai_rs depends on it
+cargo publish -p ai_rs_macros
+cargo publish -p ai_rs
 
 echo "==> Done! Package published."
\ No newline at end of file
@@ -0,0 +1,11 @@
+pub mod registry;
+pub mod runner;
+pub mod schema;
+#[doc(hidden)]
+pub mod support;
+
+#[cfg(feature = "macros")]
+pub use ai_rs_macros::{tool, JsonSchema};
+pub use registry::{ToolError, ToolHandler, ToolRegistry};
+pub use runner::{Agent, AgentError, AgentModel, AgentOutcome, OllamaChat, ToolResult, Turn};
+pub use schema::JsonSchema;
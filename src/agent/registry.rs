@@ -0,0 +1,270 @@
+use crate::gemini::types::{FunctionCall, FunctionDeclaration, Tool};
+use futures_util::future::BoxFuture;
+use serde_json::Value;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+/// Error returned by a tool; it is sent back to the model rather than ending the run
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolError {
+    /// The model called a tool that is not registered
+    UnknownTool(String),
+    /// The arguments do not match the tool's parameters
+    InvalidArguments(String),
+    /// The tool ran and failed
+    Failed(String),
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolError::UnknownTool(name) => write!(f, "Unknown tool: {}", name),
+            ToolError::InvalidArguments(msg) => write!(f, "Invalid arguments: {}", msg),
+            ToolError::Failed(msg) => write!(f, "Tool failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+impl From<serde_json::Error> for ToolError {
+    fn from(err: serde_json::Error) -> Self {
+        ToolError::InvalidArguments(err.to_string())
+    }
+}
+
+/// A function the model can call
+///
+/// `ToolRegistry::register` wraps an async closure in a `ToolHandler`; implement the trait
+/// directly for tools that keep state or build their declaration at runtime.
+pub trait ToolHandler: Send + Sync {
+    /// The name, description and JSON schema of the parameters
+    fn declaration(&self) -> FunctionDeclaration;
+
+    /// Runs the tool with the arguments sent by the model
+    fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, ToolError>>;
+}
+
+/// A `ToolHandler` built from a declaration and an async closure
+struct FnTool<F> {
+    declaration: FunctionDeclaration,
+    handler: F,
+}
+
+impl<F, Fut> ToolHandler for FnTool<F>
+where
+    F: Fn(Value) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Value, ToolError>> + Send + 'static,
+{
+    fn declaration(&self) -> FunctionDeclaration {
+        self.declaration.clone()
+    }
+
+    fn call(&self, args: Value) -> BoxFuture<'_, Result<Value, ToolError>> {
+        Box::pin((self.handler)(args))
+    }
+}
+
+/// The tools available to an `Agent`, by name
+///
+/// Clones of a registry share their handlers.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<(String, Arc<dyn ToolHandler>)>,
+}
+
+impl fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.tools.iter().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+impl ToolRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        ToolRegistry::default()
+    }
+
+    /// Registers an async function as a tool, replacing any tool with the same name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The function name the model calls
+    /// * `description` - What the tool does, for the model
+    /// * `parameters` - The JSON schema of the arguments object
+    /// * `handler` - Called with the arguments; its result is sent back to the model
+    pub fn register<F, Fut>(
+        self,
+        name: &str,
+        description: &str,
+        parameters: Value,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, ToolError>> + Send + 'static,
+    {
+        self.register_handler(FnTool {
+            declaration: FunctionDeclaration {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters,
+            },
+            handler,
+        })
+    }
+
+    /// Registers a `ToolHandler`, replacing any tool with the same name
+    pub fn register_handler(mut self, handler: impl ToolHandler + 'static) -> Self {
+        let name = handler.declaration().name;
+        self.tools.retain(|(existing, _)| *existing != name);
+        self.tools.push((name, Arc::new(handler)));
+        self
+    }
+
+    /// Returns the tool with this name
+    pub fn get(&self, name: &str) -> Option<Arc<dyn ToolHandler>> {
+        self.tools
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, handler)| handler.clone())
+    }
+
+    /// Returns `true` if no tool is registered
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// The declarations of the registered tools, in registration order
+    pub fn declarations(&self) -> Vec<FunctionDeclaration> {
+        self.tools
+            .iter()
+            .map(|(_, handler)| handler.declaration())
+            .collect()
+    }
+
+    /// The registered tools as a `Tool` list for `GenerateContentRequest::tools`
+    ///
+    /// Use `mistral::Tool::from_tools` or `ollama::Tool::from_tools` for other providers.
+    pub fn tools(&self) -> Vec<Tool> {
+        if self.tools.is_empty() {
+            return Vec::new();
+        }
+        vec![Tool {
+            function_declarations: self.declarations(),
+        }]
+    }
+
+    /// Runs the tool requested by a function call
+    ///
+    /// # Arguments
+    ///
+    /// * `call` - The function call from the model
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tool output or a `ToolError`
+    pub async fn call(&self, call: &FunctionCall) -> Result<Value, ToolError> {
+        let handler = self
+            .get(&call.name)
+            .ok_or_else(|| ToolError::UnknownTool(call.name.clone()))?;
+        handler.call(call.args.clone()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn registry() -> ToolRegistry {
+        ToolRegistry::new()
+            .register(
+                "add",
+                "Adds two numbers",
+                json!({"type": "object"}),
+                |args: Value| async move {
+                    let a = args["a"].as_i64().ok_or(ToolError::InvalidArguments(
+                        "a must be a number".to_string(),
+                    ))?;
+                    let b = args["b"].as_i64().unwrap_or_default();
+                    Ok(json!(a + b))
+                },
+            )
+            .register(
+                "echo",
+                "Echoes its arguments",
+                json!({"type": "object"}),
+                |args: Value| async move { Ok(args) },
+            )
+    }
+
+    fn call(name: &str, args: Value) -> FunctionCall {
+        FunctionCall {
+            name: name.to_string(),
+            args,
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatches_calls_by_name() {
+        let registry = registry();
+        assert_eq!(
+            registry.call(&call("add", json!({"a": 2, "b": 3}))).await,
+            Ok(json!(5))
+        );
+        assert_eq!(
+            registry.call(&call("echo", json!({"x": 1}))).await,
+            Ok(json!({"x": 1}))
+        );
+        assert_eq!(
+            registry.call(&call("add", json!({}))).await,
+            Err(ToolError::InvalidArguments(
+                "a must be a number".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_tools_are_errors() {
+        assert_eq!(
+            registry().call(&call("missing", json!({}))).await,
+            Err(ToolError::UnknownTool("missing".to_string()))
+        );
+        assert!(ToolRegistry::new().get("add").is_none());
+    }
+
+    #[tokio::test]
+    async fn registering_a_name_again_replaces_the_tool() {
+        let registry =
+            registry().register("add", "Always zero", json!({"type": "object"}), |_| async {
+                Ok(json!(0))
+            });
+        let names: Vec<String> = registry
+            .declarations()
+            .into_iter()
+            .map(|declaration| declaration.name)
+            .collect();
+        assert_eq!(names, ["echo", "add"]);
+        assert_eq!(
+            registry.call(&call("add", json!({"a": 2}))).await,
+            Ok(json!(0))
+        );
+    }
+
+    #[test]
+    fn tools_wrap_every_declaration() {
+        assert!(ToolRegistry::new().tools().is_empty());
+        let tools = registry().tools();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].function_declarations.len(), 2);
+        assert_eq!(
+            tools[0].function_declarations[0].description,
+            "Adds two numbers"
+        );
+        assert_eq!(format!("{:?}", registry()), r#"["add", "echo"]"#);
+    }
+}
@@ -0,0 +1,605 @@
+use crate::agent::registry::{ToolError, ToolRegistry};
+use crate::conversation::session::gemini_contents;
+use crate::conversation::types::{Message, Role};
+use crate::gemini::client::{GeminiClient, GeminiClientError};
+use crate::gemini::types::{
+    Content, FunctionCall, FunctionResponse, GenerateContentRequest, Part, Tool,
+};
+use crate::ollama::client::{OllamaClient, OllamaClientError};
+use crate::ollama::types::{ChatMessage, ChatRequest, Tool as OllamaTool};
+use crate::utils::now;
+use futures_util::future::{join_all, BoxFuture};
+use serde_json::{json, Value};
+use std::fmt;
+use tracing::{info, warn, Instrument};
+
+/// Default number of model calls before `Agent::run` gives up
+const DEFAULT_MAX_ITERATIONS: usize = 10;
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum AgentError {
+    /// Error from the Gemini client
+    GeminiError(GeminiClientError),
+    /// Error from the Ollama client
+    OllamaError(OllamaClientError),
+    /// The model returned no candidate
+    EmptyResponse,
+    /// The model still requested tools after the maximum number of iterations
+    MaxIterations(usize),
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentError::GeminiError(err) => write!(f, "Gemini error: {}", err),
+            AgentError::OllamaError(err) => write!(f, "Ollama error: {}", err),
+            AgentError::EmptyResponse => write!(f, "The model returned no response"),
+            AgentError::MaxIterations(iterations) => {
+                write!(f, "No final answer after {} iterations", iterations)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+impl From<GeminiClientError> for AgentError {
+    fn from(err: GeminiClientError) -> Self {
+        AgentError::GeminiError(err)
+    }
+}
+
+impl From<OllamaClientError> for AgentError {
+    fn from(err: OllamaClientError) -> Self {
+        AgentError::OllamaError(err)
+    }
+}
+
+/// One model reply: text, requested tool calls, or both
+#[derive(Debug, Clone)]
+pub struct Turn {
+    /// The text of the reply, empty if the model only called tools
+    pub text: String,
+    /// The tool calls requested by the model, in order
+    pub calls: Vec<FunctionCall>,
+}
+
+/// A tool call and its outcome
+#[derive(Debug, Clone)]
+pub struct ToolResult {
+    /// The call requested by the model
+    pub call: FunctionCall,
+    /// The tool output, or the error sent back to the model
+    pub output: Result<Value, ToolError>,
+}
+
+impl ToolResult {
+    /// The output as a JSON object: objects as they are, other values as `{"result": ...}`
+    /// and errors as `{"error": "..."}`
+    pub fn response(&self) -> Value {
+        match &self.output {
+            Ok(Value::Object(object)) => Value::Object(object.clone()),
+            Ok(value) => json!({ "result": value }),
+            Err(err) => json!({ "error": err.to_string() }),
+        }
+    }
+
+    /// The output as message text: strings as they are, other values as JSON
+    pub fn content(&self) -> String {
+        match &self.output {
+            Ok(Value::String(text)) => text.clone(),
+            Ok(value) => value.to_string(),
+            Err(_) => self.response().to_string(),
+        }
+    }
+}
+
+/// A chat model that can call tools
+///
+/// The history is kept in the provider's own message format, so tool calls and results
+/// are sent back exactly as the provider expects. Implemented for `GeminiClient` and
+/// `OllamaChat`.
+pub trait AgentModel: Send + Sync {
+    /// The provider-specific message list
+    type History: Send;
+
+    /// Converts the starting messages into a history
+    fn history(&self, messages: &[Message]) -> Self::History;
+
+    /// Sends the history with the tools and appends the model reply to it
+    fn turn<'a>(
+        &'a self,
+        history: &'a mut Self::History,
+        tools: &'a [Tool],
+    ) -> BoxFuture<'a, Result<Turn, AgentError>>;
+
+    /// Appends the results of the tool calls of the last turn to the history
+    fn add_results(&self, history: &mut Self::History, results: &[ToolResult]);
+}
+
+impl AgentModel for GeminiClient {
+    type History = Vec<Content>;
+
+    fn history(&self, messages: &[Message]) -> Vec<Content> {
+        gemini_contents(messages)
+    }
+
+    fn turn<'a>(
+        &'a self,
+        history: &'a mut Vec<Content>,
+        tools: &'a [Tool],
+    ) -> BoxFuture<'a, Result<Turn, AgentError>> {
+        Box::pin(async move {
+            let request = GenerateContentRequest {
+                contents: history.clone(),
+                generation_config: None,
+                safety_settings: None,
+                tools: (!tools.is_empty()).then(|| tools.to_vec()),
+                template_version: None,
+            };
+            let response = self.generate_content_with_request(request).await?;
+            let calls = response.function_calls();
+            let content = response
+                .candidates
+                .into_iter()
+                .next()
+                .ok_or(AgentError::EmptyResponse)?
+                .content;
+            let text = content
+                .parts
+                .iter()
+                .filter_map(|part| part.text.as_deref())
+                .collect();
+            history.push(content);
+            Ok(Turn { text, calls })
+        })
+    }
+
+    fn add_results(&self, history: &mut Vec<Content>, results: &[ToolResult]) {
+        history.push(Content {
+            role: "user".to_string(),
+            parts: results
+                .iter()
+                .map(|result| Part {
+                    function_response: Some(FunctionResponse {
+                        name: result.call.name.clone(),
+                        response: result.response(),
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+        });
+    }
+}
+
+/// An `OllamaClient` with the model to chat with, for use with `Agent`
+#[derive(Debug)]
+pub struct OllamaChat<'a> {
+    client: &'a OllamaClient,
+    model: String,
+    options: Option<Value>,
+}
+
+impl<'a> OllamaChat<'a> {
+    /// Creates a chat model
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The Ollama client
+    /// * `model` - A model that supports tools (e.g., "llama3.1")
+    pub fn new(client: &'a OllamaClient, model: &str) -> Self {
+        OllamaChat {
+            client,
+            model: model.to_string(),
+            options: None,
+        }
+    }
+
+    /// Sets the model options sent with every turn (e.g., `{"temperature": 0}`)
+    pub fn options(mut self, options: Value) -> Self {
+        self.options = Some(options);
+        self
+    }
+}
+
+impl AgentModel for OllamaChat<'_> {
+    type History = Vec<ChatMessage>;
+
+    fn history(&self, messages: &[Message]) -> Vec<ChatMessage> {
+        messages
+            .iter()
+            .map(|message| ChatMessage::new(message.role.as_str(), &message.content))
+            .collect()
+    }
+
+    fn turn<'a>(
+        &'a self,
+        history: &'a mut Vec<ChatMessage>,
+        tools: &'a [Tool],
+    ) -> BoxFuture<'a, Result<Turn, AgentError>> {
+        Box::pin(async move {
+            let tools = OllamaTool::from_tools(tools);
+            let request = ChatRequest {
+                model: self.model.clone(),
+                messages: history.clone(),
+                tools: (!tools.is_empty()).then_some(tools),
+                format: None,
+                stream: None,
+                options: self.options.clone(),
+            };
+            let response = self.client.chat(request).await?;
+            let calls = response.function_calls();
+            let text = response.message.content.clone();
+            history.push(response.message);
+            Ok(Turn { text, calls })
+        })
+    }
+
+    fn add_results(&self, history: &mut Vec<ChatMessage>, results: &[ToolResult]) {
+        history.extend(
+            results
+                .iter()
+                .map(|result| ChatMessage::tool_result(&result.call.name, &result.content())),
+        );
+    }
+}
+
+/// The final answer of an agent run
+#[derive(Debug, Clone)]
+pub struct AgentOutcome {
+    /// The text of the final reply
+    pub text: String,
+    /// The number of model calls
+    pub iterations: usize,
+    /// Every tool call made during the run, in order
+    pub tool_results: Vec<ToolResult>,
+}
+
+/// Runs the tool-calling loop: call the model, run the tools it asks for, send the
+/// results back, and repeat until it answers without calling a tool
+#[derive(Debug, Clone)]
+pub struct Agent {
+    registry: ToolRegistry,
+    max_iterations: usize,
+    parallel: bool,
+}
+
+impl Agent {
+    /// Creates an agent with the given tools
+    pub fn new(registry: ToolRegistry) -> Self {
+        Agent {
+            registry,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            parallel: true,
+        }
+    }
+
+    /// Sets the maximum number of model calls (default 10)
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations.max(1);
+        self
+    }
+
+    /// Sets whether the calls of one turn run concurrently (default) or one after the other
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// The tools of the agent
+    pub fn registry(&self) -> &ToolRegistry {
+        &self.registry
+    }
+
+    /// Runs the loop, starting from a single user prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model, e.g. a `GeminiClient` or an `OllamaChat`
+    /// * `prompt` - The user prompt
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `AgentOutcome` or an `AgentError`
+    pub async fn run_prompt<M: AgentModel>(
+        &self,
+        model: &M,
+        prompt: &str,
+    ) -> Result<AgentOutcome, AgentError> {
+        let message = Message {
+            role: Role::User,
+            content: prompt.to_string(),
+            created_at: now(),
+        };
+        self.run(model, &[message]).await
+    }
+
+    /// Runs the loop, starting from a list of messages
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model, e.g. a `GeminiClient` or an `OllamaChat`
+    /// * `messages` - The starting messages, e.g. a system prompt and the user request
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `AgentOutcome`, or `AgentError::MaxIterations` if the
+    /// model still calls tools after `max_iterations` model calls
+    pub async fn run<M: AgentModel>(
+        &self,
+        model: &M,
+        messages: &[Message],
+    ) -> Result<AgentOutcome, AgentError> {
+        let tools = self.registry.tools();
+        let mut history = model.history(messages);
+        let mut tool_results = Vec::new();
+        for iteration in 1..=self.max_iterations {
+            let turn = model.turn(&mut history, &tools).await?;
+            if turn.calls.is_empty() {
+                info!("Agent finished after {} iterations", iteration);
+                return Ok(AgentOutcome {
+                    text: turn.text,
+                    iterations: iteration,
+                    tool_results,
+                });
+            }
+            info!("Model requested {} tool calls", turn.calls.len());
+            let results = self.execute(turn.calls).await;
+            model.add_results(&mut history, &results);
+            tool_results.extend(results);
+        }
+        warn!("Agent stopped after {} iterations", self.max_iterations);
+        Err(AgentError::MaxIterations(self.max_iterations))
+    }
+
+    /// Runs the calls of one turn, keeping their order in the results
+    async fn execute(&self, calls: Vec<FunctionCall>) -> Vec<ToolResult> {
+        let runs = calls.into_iter().map(|call| self.execute_one(call));
+        if self.parallel {
+            join_all(runs).await
+        } else {
+            let mut results = Vec::new();
+            for run in runs {
+                results.push(run.await);
+            }
+            results
+        }
+    }
+
+    async fn execute_one(&self, call: FunctionCall) -> ToolResult {
+        let span = tracing::info_span!(
+            "gen_ai",
+            otel.name = %format!("execute_tool {}", call.name),
+            gen_ai.operation.name = "execute_tool",
+            gen_ai.tool.name = %call.name,
+        );
+        let output = self.registry.call(&call).instrument(span).await;
+        if let Err(err) = &output {
+            warn!("Tool {} failed: {}", call.name, err);
+        }
+        ToolResult { call, output }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Plays back scripted turns and records the history entries it is given
+    struct ScriptedModel {
+        turns: Mutex<Vec<Turn>>,
+    }
+
+    impl ScriptedModel {
+        fn new(turns: Vec<Turn>) -> Self {
+            ScriptedModel {
+                turns: Mutex::new(turns),
+            }
+        }
+    }
+
+    impl AgentModel for ScriptedModel {
+        type History = Vec<String>;
+
+        fn history(&self, messages: &[Message]) -> Vec<String> {
+            messages.iter().map(|m| m.content.clone()).collect()
+        }
+
+        fn turn<'a>(
+            &'a self,
+            history: &'a mut Vec<String>,
+            _tools: &'a [Tool],
+        ) -> BoxFuture<'a, Result<Turn, AgentError>> {
+            let mut turns = self.turns.lock().unwrap();
+            let turn = if turns.len() > 1 {
+                turns.remove(0)
+            } else {
+                turns[0].clone()
+            };
+            history.push(format!("model: {}", turn.text));
+            Box::pin(async move { Ok(turn) })
+        }
+
+        fn add_results(&self, history: &mut Vec<String>, results: &[ToolResult]) {
+            history.extend(results.iter().map(|result| result.content()));
+        }
+    }
+
+    fn calls(names: &[&str]) -> Turn {
+        Turn {
+            text: String::new(),
+            calls: names
+                .iter()
+                .map(|name| FunctionCall {
+                    name: name.to_string(),
+                    args: json!({"city": "Paris"}),
+                })
+                .collect(),
+        }
+    }
+
+    fn answer(text: &str) -> Turn {
+        Turn {
+            text: text.to_string(),
+            calls: Vec::new(),
+        }
+    }
+
+    fn registry() -> ToolRegistry {
+        ToolRegistry::new()
+            .register(
+                "weather",
+                "Gets the weather",
+                json!({"type": "object"}),
+                |args: Value| async move {
+                    Ok(json!(format!(
+                        "Sunny in {}",
+                        args["city"].as_str().unwrap()
+                    )))
+                },
+            )
+            .register(
+                "slow",
+                "Answers after a while",
+                json!({"type": "object"}),
+                |_| async {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    Ok(json!({"done": true}))
+                },
+            )
+    }
+
+    #[tokio::test]
+    async fn runs_tools_until_the_model_answers() {
+        let model = ScriptedModel::new(vec![calls(&["slow", "weather"]), answer("It is sunny")]);
+        let outcome = Agent::new(registry())
+            .run_prompt(&model, "Weather in Paris?")
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.text, "It is sunny");
+        assert_eq!(outcome.iterations, 2);
+        // Results keep the order of the calls, although "slow" finishes last
+        let names: Vec<&str> = outcome
+            .tool_results
+            .iter()
+            .map(|result| result.call.name.as_str())
+            .collect();
+        assert_eq!(names, ["slow", "weather"]);
+        assert_eq!(outcome.tool_results[0].response(), json!({"done": true}));
+        assert_eq!(outcome.tool_results[1].content(), "Sunny in Paris");
+        assert_eq!(
+            outcome.tool_results[1].response(),
+            json!({"result": "Sunny in Paris"})
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_calls_one_after_the_other_when_not_parallel() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let overlapped = Arc::new(AtomicUsize::new(0));
+        let (counter, overlaps) = (running.clone(), overlapped.clone());
+        let registry = ToolRegistry::new().register(
+            "step",
+            "One step",
+            json!({"type": "object"}),
+            move |_| {
+                let (running, overlapped) = (counter.clone(), overlaps.clone());
+                async move {
+                    if running.fetch_add(1, Ordering::SeqCst) > 0 {
+                        overlapped.fetch_add(1, Ordering::SeqCst);
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(json!(null))
+                }
+            },
+        );
+        let model = ScriptedModel::new(vec![calls(&["step", "step", "step"]), answer("done")]);
+
+        let outcome = Agent::new(registry)
+            .parallel(false)
+            .run_prompt(&model, "Go")
+            .await
+            .unwrap();
+        assert_eq!(outcome.tool_results.len(), 3);
+        assert_eq!(overlapped.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn unknown_tools_are_reported_to_the_model() {
+        let model = ScriptedModel::new(vec![calls(&["missing"]), answer("Sorry")]);
+        let agent = Agent::new(registry());
+        let outcome = agent.run_prompt(&model, "Do it").await.unwrap();
+
+        let result = &outcome.tool_results[0];
+        assert_eq!(
+            result.output,
+            Err(ToolError::UnknownTool("missing".to_string()))
+        );
+        assert_eq!(result.response(), json!({"error": "Unknown tool: missing"}));
+        assert_eq!(outcome.text, "Sorry");
+    }
+
+    #[tokio::test]
+    async fn stops_after_max_iterations() {
+        let model = ScriptedModel::new(vec![calls(&["weather"])]);
+        let agent = Agent::new(registry()).max_iterations(3);
+
+        match agent.run_prompt(&model, "Loop").await {
+            Err(AgentError::MaxIterations(iterations)) => assert_eq!(iterations, 3),
+            other => panic!("expected MaxIterations, got {:?}", other),
+        }
+        // At least one model call is always made
+        match Agent::new(registry())
+            .max_iterations(0)
+            .run_prompt(&model, "Loop")
+            .await
+        {
+            Err(AgentError::MaxIterations(iterations)) => assert_eq!(iterations, 1),
+            other => panic!("expected MaxIterations, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn gemini_sends_tool_results_back() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("POST", "/models/gemini-1.5-flash:generateContent")
+            .expect(1)
+            .with_body(
+                r#"{"candidates": [{"content": {"role": "model", "parts": [{"functionCall": {"name": "weather", "args": {"city": "Paris"}}}]}, "index": 0}]}"#,
+            )
+            .create_async()
+            .await;
+        let second = server
+            .mock("POST", "/models/gemini-1.5-flash:generateContent")
+            .match_body(mockito::Matcher::Regex(
+                r#""function_response":\{"name":"weather","response":\{"result":"Sunny in Paris"\}"#
+                    .to_string(),
+            ))
+            .with_body(
+                r#"{"candidates": [{"content": {"role": "model", "parts": [{"text": "It is sunny"}]}, "index": 0}]}"#,
+            )
+            .create_async()
+            .await;
+        let client = GeminiClient::builder()
+            .api_key("key")
+            .model("gemini-1.5-flash")
+            .base_url(&server.url())
+            .build()
+            .unwrap();
+
+        let outcome = Agent::new(registry())
+            .run_prompt(&client, "Weather in Paris?")
+            .await
+            .unwrap();
+        assert_eq!(outcome.text, "It is sunny");
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+}
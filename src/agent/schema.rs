@@ -0,0 +1,76 @@
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// A type that describes its JSON form with a JSON schema
+///
+/// Tool arguments implement it so their schema is generated from the Rust types. With the
+/// `macros` feature, `#[derive(JsonSchema)]` implements it for structs with named fields
+/// and for enums of unit variants.
+pub trait JsonSchema {
+    /// `true` for `Option`, whose properties are left out of `required`
+    const OPTIONAL: bool = false;
+
+    /// `true` for derived structs, which `#[tool]` uses as the whole arguments object
+    /// when they are a function's only parameter
+    const ARGUMENTS: bool = false;
+
+    /// The JSON schema of the type
+    fn json_schema() -> Value;
+}
+
+macro_rules! impl_json_schema {
+    ($kind:literal => $($ty:ty),+) => {
+        $(
+            impl JsonSchema for $ty {
+                fn json_schema() -> Value {
+                    json!({ "type": $kind })
+                }
+            }
+        )+
+    };
+}
+
+impl_json_schema!("string" => String, char);
+impl_json_schema!("boolean" => bool);
+impl_json_schema!("integer" => i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+impl_json_schema!("number" => f32, f64);
+
+impl JsonSchema for Value {
+    fn json_schema() -> Value {
+        json!({})
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Box<T> {
+    const OPTIONAL: bool = T::OPTIONAL;
+
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Vec<T> {
+    fn json_schema() -> Value {
+        json!({ "type": "array", "items": T::json_schema() })
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for HashMap<String, T> {
+    fn json_schema() -> Value {
+        json!({ "type": "object", "additionalProperties": T::json_schema() })
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for BTreeMap<String, T> {
+    fn json_schema() -> Value {
+        json!({ "type": "object", "additionalProperties": T::json_schema() })
+    }
+}
@@ -0,0 +1,23 @@
+//! Items used by the code that `#[tool]` and `#[derive(JsonSchema)]` generate
+
+use crate::agent::registry::ToolError;
+use std::any::Any;
+use std::fmt::Display;
+
+pub use futures_util::future::BoxFuture;
+pub use serde;
+pub use serde_json;
+
+/// Converts the error of a tool function, keeping a `ToolError` as it is
+pub fn tool_error<E: Display + 'static>(err: E) -> ToolError {
+    match (&err as &dyn Any).downcast_ref::<ToolError>() {
+        Some(err) => err.clone(),
+        None => ToolError::Failed(err.to_string()),
+    }
+}
+
+/// Converts the output of a tool function to JSON
+pub fn tool_output<T: serde::Serialize>(output: T) -> Result<serde_json::Value, ToolError> {
+    serde_json::to_value(output)
+        .map_err(|err| ToolError::Failed(format!("invalid tool output: {}", err)))
+}
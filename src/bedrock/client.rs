@@ -0,0 +1,800 @@
+use crate::bedrock::event_stream::{EventStreamDecoder, EventStreamError, Message as EventMessage};
+use crate::bedrock::sigv4::{uri_encode, Credentials, SigV4Signer};
+use crate::bedrock::types::{
+    ConverseRequest, ConverseResponse, ConverseStreamEvent, Message, TokenUsage,
+};
+use crate::cache::{CacheMode, ResponseCache};
+use crate::logging;
+use crate::middleware::{HttpError, Middleware, Request, Stack};
+use crate::rate_limit::{RateLimitPermit, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::telemetry::GenAiSpan;
+use crate::usage::{
+    MaxOutputTokens, ModelName, TokenUsage as LedgerUsage, UsageError, UsageLedger, UsagePermit,
+};
+use futures_util::{Stream, StreamExt};
+use reqwest::Client;
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{debug, error, info, trace, Instrument};
+
+/// Signing name of the Bedrock runtime service
+const SIGNING_SERVICE: &str = "bedrock";
+
+/// `gen_ai.system` value of the OpenTelemetry GenAI conventions
+const GEN_AI_SYSTEM: &str = "aws.bedrock";
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum BedrockClientError {
+    /// Error related to the request
+    RequestError(String),
+    /// Network-related error
+    NetworkError(reqwest::Error),
+    /// Error while parsing JSON
+    ParseError(serde_json::Error),
+    /// Exception returned by Bedrock
+    ApiError(String),
+    /// Missing or invalid AWS credentials
+    CredentialsError(String),
+    /// Error while decoding the event stream
+    EventStreamError(EventStreamError),
+    /// The request would exceed a budget of the usage ledger and was not sent
+    BudgetExceeded(UsageError),
+}
+
+impl fmt::Display for BedrockClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BedrockClientError::RequestError(msg) => write!(f, "Request error: {}", msg),
+            BedrockClientError::NetworkError(err) => write!(f, "Network error: {}", err),
+            BedrockClientError::ParseError(err) => write!(f, "Parse error: {}", err),
+            BedrockClientError::ApiError(msg) => write!(f, "API error: {}", msg),
+            BedrockClientError::CredentialsError(msg) => write!(f, "Credentials error: {}", msg),
+            BedrockClientError::EventStreamError(err) => write!(f, "Event stream error: {}", err),
+            BedrockClientError::BudgetExceeded(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for BedrockClientError {}
+
+impl BedrockClientError {
+    /// Short error type recorded on the tracing span
+    fn error_type(&self) -> &'static str {
+        match self {
+            BedrockClientError::RequestError(_) => "request",
+            BedrockClientError::NetworkError(_) => "network",
+            BedrockClientError::ParseError(_) => "parse",
+            BedrockClientError::ApiError(_) => "api",
+            BedrockClientError::CredentialsError(_) => "credentials",
+            BedrockClientError::EventStreamError(_) => "event_stream",
+            BedrockClientError::BudgetExceeded(_) => "budget_exceeded",
+        }
+    }
+}
+
+impl From<reqwest::Error> for BedrockClientError {
+    fn from(err: reqwest::Error) -> Self {
+        BedrockClientError::NetworkError(err)
+    }
+}
+
+impl From<serde_json::Error> for BedrockClientError {
+    fn from(err: serde_json::Error) -> Self {
+        BedrockClientError::ParseError(err)
+    }
+}
+
+impl From<EventStreamError> for BedrockClientError {
+    fn from(err: EventStreamError) -> Self {
+        BedrockClientError::EventStreamError(err)
+    }
+}
+
+impl From<HttpError> for BedrockClientError {
+    fn from(err: HttpError) -> Self {
+        match err {
+            HttpError::Status { body, .. } => BedrockClientError::RequestError(body),
+            HttpError::Network(err) => BedrockClientError::NetworkError(err),
+            HttpError::Middleware(msg) => BedrockClientError::RequestError(msg),
+            HttpError::BudgetExceeded(err) => BedrockClientError::BudgetExceeded(err),
+        }
+    }
+}
+
+/// Client for interacting with the AWS Bedrock Converse API
+#[derive(Debug)]
+pub struct BedrockClient {
+    model: String,
+    base_url: String,
+    signer: SigV4Signer,
+    client: Client,
+    stack: Stack,
+}
+
+impl BedrockClient {
+    /// Creates a new instance of `BedrockClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - The AWS region (e.g., "us-east-1")
+    /// * `model` - The model id or inference profile (e.g., "anthropic.claude-3-haiku-20240307-v1:0")
+    /// * `credentials` - The credentials used to sign requests
+    ///
+    /// # Returns
+    ///
+    /// A new `BedrockClient` instance
+    pub fn new(region: &str, model: &str, credentials: Credentials) -> Self {
+        info!(
+            "Creating new BedrockClient with region: {} and model: {}",
+            region, model
+        );
+        BedrockClient {
+            model: model.to_string(),
+            base_url: format!("https://bedrock-runtime.{}.amazonaws.com", region),
+            signer: SigV4Signer::new(credentials, region, SIGNING_SERVICE),
+            client: Client::new(),
+            stack: Stack::default(),
+        }
+    }
+
+    /// Creates a new instance of `BedrockClient` from environment variables
+    ///
+    /// The region is read from `AWS_REGION` or `AWS_DEFAULT_REGION`, and the credentials
+    /// from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model id or inference profile
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `BedrockClient` or a `BedrockClientError`
+    pub fn from_env(model: &str) -> Result<Self, BedrockClientError> {
+        let region = std::env::var("AWS_REGION")
+            .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+            .map_err(|_| {
+                BedrockClientError::CredentialsError(
+                    "AWS_REGION or AWS_DEFAULT_REGION must be set".to_string(),
+                )
+            })?;
+        let credentials = Credentials::from_env().ok_or_else(|| {
+            BedrockClientError::CredentialsError(
+                "AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set".to_string(),
+            )
+        })?;
+        Ok(Self::new(&region, model, credentials))
+    }
+
+    /// Sets the model to use
+    pub fn model(mut self, model: &str) -> Self {
+        info!("Setting model to {}", model);
+        self.model = model.to_string();
+        self
+    }
+
+    /// Overrides the endpoint, e.g. for VPC endpoints or a local mock server
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        info!("Setting base_url to {}", base_url);
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sets the policy used to retry failed requests; without one, requests are not retried
+    ///
+    /// Retries resend the signed request, which AWS accepts for five minutes.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.stack.retry_policy = policy;
+        self
+    }
+
+    /// Sets a cache for model responses; cache hits skip the rate limiter and the provider
+    pub fn cache(mut self, cache: ResponseCache) -> Self {
+        self.stack.cache = Some(cache);
+        self
+    }
+
+    /// Sets a rate limiter to wait on before each attempt; it may be shared with other clients
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.stack.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Sets a usage ledger that checks budgets before each request and records its cost
+    pub fn usage_ledger(mut self, ledger: UsageLedger) -> Self {
+        self.stack.ledger = Some(ledger);
+        self
+    }
+
+    /// Adds a middleware; it runs inside the retries and the rate limiter, once per attempt
+    ///
+    /// Middleware that changes signed headers or the body invalidates the signature.
+    pub fn layer(mut self, middleware: impl Middleware) -> Self {
+        self.stack.layers.push(Arc::new(middleware));
+        self
+    }
+
+    /// Sends a text prompt using the `Converse` operation
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ConverseResponse` or a `BedrockClientError`
+    pub async fn converse(&self, prompt: &str) -> Result<ConverseResponse, BedrockClientError> {
+        let request = ConverseRequest {
+            messages: vec![Message::user(prompt)],
+            ..Default::default()
+        };
+
+        self.converse_with_request(request).await
+    }
+
+    /// Sends a structured request using the `Converse` operation
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ConverseRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ConverseResponse` or a `BedrockClientError`
+    pub async fn converse_with_request(
+        &self,
+        request: ConverseRequest,
+    ) -> Result<ConverseResponse, BedrockClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "chat", &self.model);
+        let span = telemetry.span().clone();
+        let result = self
+            .converse_once(request, &telemetry)
+            .instrument(span)
+            .await;
+        if let Err(err) = &result {
+            telemetry.error(err.error_type());
+        }
+        result
+    }
+
+    /// Calls `Converse`, recording the response on `telemetry`
+    async fn converse_once(
+        &self,
+        request: ConverseRequest,
+        telemetry: &GenAiSpan,
+    ) -> Result<ConverseResponse, BedrockClientError> {
+        info!("Calling Converse for model: {}", self.model);
+        trace!("ConverseRequest: {}", logging::payload_json(&request));
+
+        let response = self
+            .send_signed("converse", &request, telemetry)
+            .await
+            .inspect_err(|err| error!("Failed to call Converse: {}", err))?;
+        let permit = response.extensions().get::<RateLimitPermit>().cloned();
+        let usage_permit = response.extensions().get::<UsagePermit>().cloned();
+
+        let response_text = response.text().await?;
+        trace!("Response text: {}", logging::payload(&response_text));
+        let converse_response: ConverseResponse = serde_json::from_str(&response_text)?;
+        if let Some(permit) = permit {
+            permit.reconcile(converse_response.usage.total_tokens.max(0) as u32);
+        }
+        if let Some(permit) = usage_permit {
+            permit.record(LedgerUsage::from(converse_response.usage.clone()));
+        }
+        telemetry.finish_reasons(&converse_response.stop_reason);
+        record_usage(telemetry, &converse_response.usage);
+        info!("Successfully completed Converse call.");
+        trace!(
+            "ConverseResponse: {}",
+            logging::payload_json(&converse_response)
+        );
+        Ok(converse_response)
+    }
+
+    /// Streams a response to a text prompt using the `ConverseStream` operation
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `ConverseStreamEvent`s or a `BedrockClientError`
+    pub async fn converse_stream(
+        &self,
+        prompt: &str,
+    ) -> Result<
+        impl Stream<Item = Result<ConverseStreamEvent, BedrockClientError>>,
+        BedrockClientError,
+    > {
+        let request = ConverseRequest {
+            messages: vec![Message::user(prompt)],
+            ..Default::default()
+        };
+
+        self.converse_stream_with_request(request).await
+    }
+
+    /// Streams a response to a structured request using the `ConverseStream` operation
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ConverseRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `ConverseStreamEvent`s or a `BedrockClientError`
+    pub async fn converse_stream_with_request(
+        &self,
+        request: ConverseRequest,
+    ) -> Result<
+        impl Stream<Item = Result<ConverseStreamEvent, BedrockClientError>>,
+        BedrockClientError,
+    > {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "chat", &self.model);
+        let span = telemetry.span().clone();
+        self.start_stream(request, telemetry).instrument(span).await
+    }
+
+    /// Calls `ConverseStream`; `telemetry` moves into the stream task and ends with it
+    async fn start_stream(
+        &self,
+        request: ConverseRequest,
+        mut telemetry: GenAiSpan,
+    ) -> Result<
+        impl Stream<Item = Result<ConverseStreamEvent, BedrockClientError>>,
+        BedrockClientError,
+    > {
+        info!("Calling ConverseStream for model: {}", self.model);
+        trace!("StreamRequest: {}", logging::payload_json(&request));
+
+        // Only failures before the first chunk arrives are retried
+        let response = self
+            .send_signed("converse-stream", &request, &telemetry)
+            .await
+            .inspect_err(|err| {
+                error!("Failed to start streaming: {}", err);
+                telemetry.error(err.error_type());
+            })?;
+        let mut permit = response.extensions().get::<RateLimitPermit>().cloned();
+        let mut usage_permit = response.extensions().get::<UsagePermit>().cloned();
+
+        let (tx, rx) = mpsc::channel(100);
+        let stream = response.bytes_stream();
+
+        tokio::spawn(
+            async move {
+                let mut stream = stream;
+                let mut decoder = EventStreamDecoder::new();
+
+                while let Some(chunk) = stream.next().await {
+                    let bytes = match chunk {
+                        Ok(bytes) => bytes,
+                        Err(e) => {
+                            error!("Stream error: {}", e);
+                            telemetry.error("network");
+                            let _ = tx.send(Err(BedrockClientError::NetworkError(e))).await;
+                            return;
+                        }
+                    };
+                    decoder.push(&bytes);
+
+                    loop {
+                        let message = match decoder.next_message() {
+                            Ok(Some(message)) => message,
+                            Ok(None) => break,
+                            Err(e) => {
+                                // Framing is lost once a checksum fails, so the stream cannot continue
+                                error!("Failed to decode event stream: {}", e);
+                                telemetry.error("event_stream");
+                                let _ = tx.send(Err(e.into())).await;
+                                return;
+                            }
+                        };
+
+                        let event = match decode_event(&message) {
+                            Ok(Some(ConverseStreamEvent::Metadata(metadata))) => {
+                                if let Some(permit) = permit.take() {
+                                    permit.reconcile(metadata.usage.total_tokens.max(0) as u32);
+                                }
+                                if let Some(permit) = usage_permit.take() {
+                                    permit.record(LedgerUsage::from(metadata.usage.clone()));
+                                }
+                                record_usage(&telemetry, &metadata.usage);
+                                Ok(ConverseStreamEvent::Metadata(metadata))
+                            }
+                            Ok(Some(ConverseStreamEvent::MessageStop(stop))) => {
+                                telemetry.finish_reasons(&stop.stop_reason);
+                                Ok(ConverseStreamEvent::MessageStop(stop))
+                            }
+                            Ok(Some(event)) => {
+                                telemetry.first_token();
+                                Ok(event)
+                            }
+                            Ok(None) => continue,
+                            Err(e) => {
+                                telemetry.error(e.error_type());
+                                Err(e)
+                            }
+                        };
+                        if tx.send(event).await.is_err() {
+                            // Receiver dropped, stop reading
+                            return;
+                        }
+                    }
+                }
+
+                if !decoder.is_empty() {
+                    telemetry.error("event_stream");
+                    let _ = tx
+                        .send(Err(BedrockClientError::EventStreamError(
+                            EventStreamError::InvalidMessage(
+                                "stream ended in the middle of a message".to_string(),
+                            ),
+                        )))
+                        .await;
+                }
+            }
+            .instrument(tracing::Span::current()),
+        );
+
+        Ok(ReceiverStream::new(rx))
+    }
+
+    /// Serializes and signs a request, then posts it to the given operation through the
+    /// middleware stack, with the model and reply length limit for the ledger
+    async fn send_signed(
+        &self,
+        operation: &str,
+        request: &ConverseRequest,
+        telemetry: &GenAiSpan,
+    ) -> Result<reqwest::Response, BedrockClientError> {
+        let url = format!(
+            "{}/model/{}/{}",
+            self.base_url,
+            uri_encode(&self.model),
+            operation
+        );
+        let url = reqwest::Url::parse(&url)
+            .map_err(|e| BedrockClientError::RequestError(format!("Invalid URL {}: {}", url, e)))?;
+        info!("Sending Bedrock request to URL: {}", url);
+
+        let body = serde_json::to_vec(request)?;
+        let signing_headers = self.signer.sign(
+            "POST",
+            &url,
+            &[("content-type", "application/json")],
+            &body,
+            SystemTime::now(),
+        );
+
+        let mut builder = self
+            .client
+            .post(url)
+            .header("content-type", "application/json");
+        for (name, value) in signing_headers {
+            builder = builder.header(name, value);
+        }
+
+        let mut http_request = Request::new(builder.body(body).build()?);
+        http_request
+            .extensions_mut()
+            .insert(ModelName(self.model.clone()));
+        if let Some(max_output_tokens) = request
+            .inference_config
+            .as_ref()
+            .and_then(|config| u32::try_from(config.max_tokens?).ok())
+        {
+            http_request
+                .extensions_mut()
+                .insert(MaxOutputTokens(max_output_tokens));
+        }
+        http_request.extensions_mut().insert(CacheMode::current());
+        Ok(self
+            .stack
+            .send(&self.client, http_request)
+            .await
+            .inspect_err(|err| telemetry.http_error(err))?)
+    }
+}
+
+/// Records the token usage reported by Bedrock
+fn record_usage(telemetry: &GenAiSpan, usage: &TokenUsage) {
+    telemetry.usage(
+        Some(usage.input_tokens.max(0) as u32),
+        Some(usage.output_tokens.max(0) as u32),
+    );
+}
+
+/// Converts an event-stream message into a `ConverseStreamEvent`
+///
+/// Returns `Ok(None)` for event types this client does not know about.
+fn decode_event(message: &EventMessage) -> Result<Option<ConverseStreamEvent>, BedrockClientError> {
+    match message.header_str(":message-type") {
+        Some("event") => {}
+        Some("exception") | Some("error") => {
+            let kind = message
+                .header_str(":exception-type")
+                .or_else(|| message.header_str(":error-code"))
+                .unwrap_or("UnknownException");
+            let payload = String::from_utf8_lossy(&message.payload);
+            error!("Bedrock stream exception {}: {}", kind, payload);
+            return Err(BedrockClientError::ApiError(format!(
+                "{}: {}",
+                kind, payload
+            )));
+        }
+        other => {
+            return Err(BedrockClientError::EventStreamError(
+                EventStreamError::InvalidMessage(format!("unexpected message type {:?}", other)),
+            ))
+        }
+    }
+
+    let payload = &message.payload;
+    let event = match message.header_str(":event-type") {
+        Some("messageStart") => ConverseStreamEvent::MessageStart(serde_json::from_slice(payload)?),
+        Some("contentBlockStart") => {
+            ConverseStreamEvent::ContentBlockStart(serde_json::from_slice(payload)?)
+        }
+        Some("contentBlockDelta") => {
+            ConverseStreamEvent::ContentBlockDelta(serde_json::from_slice(payload)?)
+        }
+        Some("contentBlockStop") => {
+            ConverseStreamEvent::ContentBlockStop(serde_json::from_slice(payload)?)
+        }
+        Some("messageStop") => ConverseStreamEvent::MessageStop(serde_json::from_slice(payload)?),
+        Some("metadata") => ConverseStreamEvent::Metadata(serde_json::from_slice(payload)?),
+        other => {
+            debug!("Skipping unknown event type: {:?}", other);
+            return Ok(None);
+        }
+    };
+    trace!(
+        "Received event: {}",
+        logging::payload(&String::from_utf8_lossy(payload))
+    );
+    Ok(Some(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedrock::event_stream::encode_message;
+    use mockito::Matcher;
+
+    const MODEL: &str = "anthropic.claude-3-haiku-20240307-v1:0";
+
+    fn client(server: &mockito::Server) -> BedrockClient {
+        BedrockClient::new(
+            "us-east-1",
+            MODEL,
+            Credentials::new("AKIDEXAMPLE", "secret", Some("token")),
+        )
+        .base_url(&server.url())
+    }
+
+    fn event(event_type: &str, payload: &str) -> Vec<u8> {
+        encode_message(
+            &[(":message-type", "event"), (":event-type", event_type)],
+            payload.as_bytes(),
+        )
+    }
+
+    #[tokio::test]
+    async fn converse_round_trip() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "POST",
+                "/model/anthropic.claude-3-haiku-20240307-v1%3A0/converse",
+            )
+            .match_header(
+                "authorization",
+                Matcher::Regex(
+                    "^AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/\\d{8}/us-east-1/bedrock/aws4_request, \
+                     SignedHeaders=content-type;host;x-amz-date;x-amz-security-token, \
+                     Signature=[0-9a-f]{64}$"
+                        .to_string(),
+                ),
+            )
+            .match_header("x-amz-security-token", "token")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"messages":[{"role":"user","content":[{"text":"Hello"}]}]}"#.to_string(),
+            ))
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{
+                    "output": {"message": {"role": "assistant", "content": [{"text": "Hi there"}]}},
+                    "stopReason": "end_turn",
+                    "usage": {"inputTokens": 3, "outputTokens": 2, "totalTokens": 5},
+                    "metrics": {"latencyMs": 120}
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let response = client(&server).converse("Hello").await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(response.get_text().as_deref(), Some("Hi there"));
+        assert_eq!(response.stop_reason, "end_turn");
+        assert_eq!(response.usage.total_tokens, 5);
+    }
+
+    #[tokio::test]
+    async fn converse_maps_error_status() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", Matcher::Any)
+            .with_status(400)
+            .with_body(r#"{"message":"Malformed input request"}"#)
+            .create_async()
+            .await;
+
+        let err = client(&server).converse("Hello").await.unwrap_err();
+        assert!(matches!(
+            err,
+            BedrockClientError::RequestError(body) if body.contains("Malformed input request")
+        ));
+    }
+
+    #[tokio::test]
+    async fn converse_stream_round_trip() {
+        let mut body = Vec::new();
+        body.extend(event("messageStart", r#"{"role":"assistant"}"#));
+        body.extend(event(
+            "contentBlockDelta",
+            r#"{"contentBlockIndex":0,"delta":{"text":"Hi"}}"#,
+        ));
+        body.extend(event(
+            "contentBlockDelta",
+            r#"{"contentBlockIndex":0,"delta":{"text":" there"}}"#,
+        ));
+        body.extend(event("contentBlockStop", r#"{"contentBlockIndex":0}"#));
+        body.extend(event("messageStop", r#"{"stopReason":"end_turn"}"#));
+        body.extend(event("somethingNew", "{}"));
+        body.extend(event(
+            "metadata",
+            r#"{"usage":{"inputTokens":3,"outputTokens":2,"totalTokens":5},"metrics":{"latencyMs":80}}"#,
+        ));
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "POST",
+                "/model/anthropic.claude-3-haiku-20240307-v1%3A0/converse-stream",
+            )
+            .with_header("content-type", "application/vnd.amazon.eventstream")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let events: Vec<ConverseStreamEvent> = client(&server)
+            .converse_stream("Hello")
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        mock.assert_async().await;
+        assert_eq!(events.len(), 6);
+        let text: String = events.iter().filter_map(|event| event.get_text()).collect();
+        assert_eq!(text, "Hi there");
+        assert!(matches!(
+            &events[4],
+            ConverseStreamEvent::MessageStop(stop) if stop.stop_reason == "end_turn"
+        ));
+        assert!(matches!(
+            &events[5],
+            ConverseStreamEvent::Metadata(metadata) if metadata.usage.total_tokens == 5
+        ));
+    }
+
+    #[tokio::test]
+    async fn converse_stream_reports_exceptions_and_bad_frames() {
+        let mut body = event("messageStart", r#"{"role":"assistant"}"#);
+        body.extend(encode_message(
+            &[
+                (":message-type", "exception"),
+                (":exception-type", "throttlingException"),
+            ],
+            br#"{"message":"Too many requests"}"#,
+        ));
+        let mut corrupted = event("messageStop", r#"{"stopReason":"end_turn"}"#);
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0x01;
+        body.extend(corrupted);
+
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", Matcher::Any)
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let events: Vec<_> = client(&server)
+            .converse_stream("Hello")
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            events[0],
+            Ok(ConverseStreamEvent::MessageStart(_))
+        ));
+        assert!(matches!(
+            &events[1],
+            Err(BedrockClientError::ApiError(message)) if message.starts_with("throttlingException")
+        ));
+        assert!(matches!(
+            events[2],
+            Err(BedrockClientError::EventStreamError(
+                EventStreamError::MessageChecksum { .. }
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn usage_ledger_records_streamed_usage_and_enforces_budgets() {
+        use crate::bedrock::types::InferenceConfiguration;
+        use crate::usage::{BudgetScope, ModelPrice, PricingTable};
+
+        let mut body = event("messageStop", r#"{"stopReason":"end_turn"}"#);
+        body.extend(event(
+            "metadata",
+            r#"{"usage":{"inputTokens":300,"outputTokens":200,"totalTokens":500},"metrics":{"latencyMs":80}}"#,
+        ));
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "POST",
+                "/model/anthropic.claude-3-haiku-20240307-v1%3A0/converse-stream",
+            )
+            .with_header("content-type", "application/vnd.amazon.eventstream")
+            .with_body(body)
+            .create_async()
+            .await;
+        let ledger = UsageLedger::new()
+            .pricing(
+                PricingTable::new().price("anthropic.claude-3-haiku", ModelPrice::new(1.0, 1.0)),
+            )
+            .budget(BudgetScope::Total, 0.001);
+        let client = client(&server).usage_ledger(ledger.clone());
+
+        let request = ConverseRequest {
+            messages: vec![Message::user("Hello")],
+            inference_config: Some(InferenceConfiguration {
+                max_tokens: Some(200),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let events: Vec<_> = client
+            .converse_stream_with_request(request)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(events.len(), 2);
+        let records = ledger.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].model, MODEL);
+        assert_eq!(records[0].usage, LedgerUsage::new(300, 200));
+
+        // Without a limit, the default 1024 reply tokens no longer fit in the $0.0005 left
+        match client.converse("Hello").await {
+            Err(BedrockClientError::BudgetExceeded(_)) => {}
+            other => panic!("expected BudgetExceeded, got {:?}", other),
+        }
+        mock.assert_async().await;
+    }
+}
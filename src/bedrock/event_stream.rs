@@ -0,0 +1,391 @@
+use bytes::{Buf, Bytes, BytesMut};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Length of the prelude: total length, headers length and prelude CRC
+const PRELUDE_LENGTH: usize = 12;
+/// Length of the trailing message CRC
+const MESSAGE_CRC_LENGTH: usize = 4;
+/// Smallest possible message: a prelude and a message CRC with no headers or payload
+const MIN_MESSAGE_LENGTH: usize = PRELUDE_LENGTH + MESSAGE_CRC_LENGTH;
+/// Upper bound on a single message, as enforced by the AWS SDKs
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Error raised while decoding an `application/vnd.amazon.eventstream` body
+#[derive(Debug)]
+pub enum EventStreamError {
+    /// The prelude CRC did not match
+    PreludeChecksum { expected: u32, actual: u32 },
+    /// The message CRC did not match
+    MessageChecksum { expected: u32, actual: u32 },
+    /// The message structure is invalid
+    InvalidMessage(String),
+}
+
+impl fmt::Display for EventStreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventStreamError::PreludeChecksum { expected, actual } => write!(
+                f,
+                "Prelude checksum mismatch: expected {:#010x}, got {:#010x}",
+                expected, actual
+            ),
+            EventStreamError::MessageChecksum { expected, actual } => write!(
+                f,
+                "Message checksum mismatch: expected {:#010x}, got {:#010x}",
+                expected, actual
+            ),
+            EventStreamError::InvalidMessage(msg) => write!(f, "Invalid message: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EventStreamError {}
+
+/// Value of an event-stream header
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderValue {
+    Bool(bool),
+    Byte(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    ByteArray(Bytes),
+    String(String),
+    /// Milliseconds since the Unix epoch
+    Timestamp(i64),
+    Uuid([u8; 16]),
+}
+
+impl HeaderValue {
+    /// Returns the value as a string slice if it is a string header
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            HeaderValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// A single decoded event-stream message
+#[derive(Debug, Clone)]
+pub struct Message {
+    /// The message headers
+    pub headers: HashMap<String, HeaderValue>,
+    /// The message payload
+    pub payload: Bytes,
+}
+
+impl Message {
+    /// Gets a string header by name
+    pub fn header_str(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(HeaderValue::as_str)
+    }
+}
+
+/// Incremental decoder for the AWS event-stream binary framing
+///
+/// Bytes can be pushed in arbitrary chunks; complete messages are returned as soon as
+/// they are fully buffered.
+#[derive(Debug, Default)]
+pub struct EventStreamDecoder {
+    buffer: BytesMut,
+}
+
+impl EventStreamDecoder {
+    /// Creates a new, empty decoder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends raw bytes received from the network
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Returns `true` if no partial message is buffered
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Decodes the next complete message, if one is buffered
+    ///
+    /// # Returns
+    ///
+    /// `Ok(None)` if more bytes are needed, the decoded `Message`, or an `EventStreamError`
+    pub fn next_message(&mut self) -> Result<Option<Message>, EventStreamError> {
+        if self.buffer.len() < PRELUDE_LENGTH {
+            return Ok(None);
+        }
+
+        let total_length = u32::from_be_bytes(self.buffer[0..4].try_into().unwrap()) as usize;
+        let headers_length = u32::from_be_bytes(self.buffer[4..8].try_into().unwrap()) as usize;
+        let prelude_crc = u32::from_be_bytes(self.buffer[8..12].try_into().unwrap());
+
+        let actual = crc32fast::hash(&self.buffer[0..8]);
+        if actual != prelude_crc {
+            return Err(EventStreamError::PreludeChecksum {
+                expected: prelude_crc,
+                actual,
+            });
+        }
+        if !(MIN_MESSAGE_LENGTH..=MAX_MESSAGE_LENGTH).contains(&total_length) {
+            return Err(EventStreamError::InvalidMessage(format!(
+                "message length {} out of range",
+                total_length
+            )));
+        }
+        if headers_length > total_length - MIN_MESSAGE_LENGTH {
+            return Err(EventStreamError::InvalidMessage(format!(
+                "headers length {} exceeds message length {}",
+                headers_length, total_length
+            )));
+        }
+        if self.buffer.len() < total_length {
+            return Ok(None);
+        }
+
+        let frame = self.buffer.split_to(total_length).freeze();
+        let crc_offset = total_length - MESSAGE_CRC_LENGTH;
+        let message_crc = u32::from_be_bytes(frame[crc_offset..].try_into().unwrap());
+        let actual = crc32fast::hash(&frame[..crc_offset]);
+        if actual != message_crc {
+            return Err(EventStreamError::MessageChecksum {
+                expected: message_crc,
+                actual,
+            });
+        }
+
+        let headers_end = PRELUDE_LENGTH + headers_length;
+        let headers = decode_headers(frame.slice(PRELUDE_LENGTH..headers_end))?;
+        let payload = frame.slice(headers_end..crc_offset);
+
+        Ok(Some(Message { headers, payload }))
+    }
+}
+
+fn decode_headers(mut buf: Bytes) -> Result<HashMap<String, HeaderValue>, EventStreamError> {
+    let mut headers = HashMap::new();
+    while buf.has_remaining() {
+        let name_length = buf.get_u8() as usize;
+        let name = take_string(&mut buf, name_length)?;
+        ensure_remaining(&buf, 1)?;
+        let value = match buf.get_u8() {
+            0 => HeaderValue::Bool(true),
+            1 => HeaderValue::Bool(false),
+            2 => {
+                ensure_remaining(&buf, 1)?;
+                HeaderValue::Byte(buf.get_i8())
+            }
+            3 => {
+                ensure_remaining(&buf, 2)?;
+                HeaderValue::Int16(buf.get_i16())
+            }
+            4 => {
+                ensure_remaining(&buf, 4)?;
+                HeaderValue::Int32(buf.get_i32())
+            }
+            5 => {
+                ensure_remaining(&buf, 8)?;
+                HeaderValue::Int64(buf.get_i64())
+            }
+            6 => {
+                ensure_remaining(&buf, 2)?;
+                let length = buf.get_u16() as usize;
+                ensure_remaining(&buf, length)?;
+                HeaderValue::ByteArray(buf.split_to(length))
+            }
+            7 => {
+                ensure_remaining(&buf, 2)?;
+                let length = buf.get_u16() as usize;
+                HeaderValue::String(take_string(&mut buf, length)?)
+            }
+            8 => {
+                ensure_remaining(&buf, 8)?;
+                HeaderValue::Timestamp(buf.get_i64())
+            }
+            9 => {
+                ensure_remaining(&buf, 16)?;
+                let mut uuid = [0u8; 16];
+                buf.copy_to_slice(&mut uuid);
+                HeaderValue::Uuid(uuid)
+            }
+            other => {
+                return Err(EventStreamError::InvalidMessage(format!(
+                    "unknown header value type {}",
+                    other
+                )))
+            }
+        };
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
+
+fn ensure_remaining(buf: &Bytes, length: usize) -> Result<(), EventStreamError> {
+    if buf.remaining() < length {
+        return Err(EventStreamError::InvalidMessage(
+            "header extends past the end of the header block".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn take_string(buf: &mut Bytes, length: usize) -> Result<String, EventStreamError> {
+    ensure_remaining(buf, length)?;
+    let raw = buf.split_to(length);
+    String::from_utf8(raw.to_vec())
+        .map_err(|_| EventStreamError::InvalidMessage("header is not valid UTF-8".to_string()))
+}
+
+/// Encodes a message with string headers, for tests that need event-stream bodies
+#[cfg(test)]
+pub(crate) fn encode_message(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+    use bytes::BufMut;
+
+    let mut header_block = BytesMut::new();
+    for (name, value) in headers {
+        header_block.put_u8(name.len() as u8);
+        header_block.put_slice(name.as_bytes());
+        header_block.put_u8(7);
+        header_block.put_u16(value.len() as u16);
+        header_block.put_slice(value.as_bytes());
+    }
+
+    encode_frame(&header_block, payload)
+}
+
+/// Frames an already encoded header block and a payload
+#[cfg(test)]
+fn encode_frame(header_block: &[u8], payload: &[u8]) -> Vec<u8> {
+    use bytes::BufMut;
+
+    let total_length = MIN_MESSAGE_LENGTH + header_block.len() + payload.len();
+    let mut frame = BytesMut::with_capacity(total_length);
+    frame.put_u32(total_length as u32);
+    frame.put_u32(header_block.len() as u32);
+    frame.put_u32(crc32fast::hash(&frame[..8]));
+    frame.put_slice(header_block);
+    frame.put_slice(payload);
+    frame.put_u32(crc32fast::hash(&frame));
+    frame.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(payload: &[u8]) -> Vec<u8> {
+        encode_message(
+            &[
+                (":message-type", "event"),
+                (":event-type", "contentBlockDelta"),
+            ],
+            payload,
+        )
+    }
+
+    #[test]
+    fn decodes_empty_message_vector() {
+        // The empty message from the AWS SDK event-stream test vectors
+        let mut decoder = EventStreamDecoder::new();
+        decoder.push(&[
+            0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x05, 0xc2, 0x48, 0xeb, 0x7d, 0x98,
+            0xc8, 0xff,
+        ]);
+
+        let message = decoder.next_message().unwrap().unwrap();
+        assert!(message.headers.is_empty());
+        assert!(message.payload.is_empty());
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn decodes_headers_and_payload() {
+        let mut decoder = EventStreamDecoder::new();
+        decoder.push(&event(b"{\"delta\":{}}"));
+
+        let message = decoder.next_message().unwrap().unwrap();
+        assert_eq!(message.header_str(":message-type"), Some("event"));
+        assert_eq!(message.header_str(":event-type"), Some("contentBlockDelta"));
+        assert_eq!(message.header_str(":missing"), None);
+        assert_eq!(&message.payload[..], b"{\"delta\":{}}");
+        assert!(decoder.next_message().unwrap().is_none());
+    }
+
+    #[test]
+    fn waits_for_complete_messages_across_chunks() {
+        let mut bytes = event(b"first");
+        bytes.extend(event(b"second"));
+
+        let mut decoder = EventStreamDecoder::new();
+        let mut payloads = Vec::new();
+        for byte in &bytes {
+            decoder.push(std::slice::from_ref(byte));
+            while let Some(message) = decoder.next_message().unwrap() {
+                payloads.push(message.payload);
+            }
+        }
+
+        assert_eq!(payloads, vec![&b"first"[..], &b"second"[..]]);
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn rejects_corrupted_prelude() {
+        let mut bytes = event(b"payload");
+        bytes[3] ^= 0x01;
+
+        let mut decoder = EventStreamDecoder::new();
+        decoder.push(&bytes);
+        assert!(matches!(
+            decoder.next_message(),
+            Err(EventStreamError::PreludeChecksum { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_corrupted_payload() {
+        let mut bytes = event(b"payload");
+        let last_payload_byte = bytes.len() - MESSAGE_CRC_LENGTH - 1;
+        bytes[last_payload_byte] ^= 0x01;
+
+        let mut decoder = EventStreamDecoder::new();
+        decoder.push(&bytes);
+        match decoder.next_message() {
+            Err(EventStreamError::MessageChecksum { expected, actual }) => {
+                assert_ne!(expected, actual)
+            }
+            other => panic!("expected a message checksum error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_length() {
+        let mut prelude = Vec::new();
+        prelude.extend_from_slice(&8u32.to_be_bytes());
+        prelude.extend_from_slice(&0u32.to_be_bytes());
+        let crc = crc32fast::hash(&prelude);
+        prelude.extend_from_slice(&crc.to_be_bytes());
+
+        let mut decoder = EventStreamDecoder::new();
+        decoder.push(&prelude);
+        assert!(matches!(
+            decoder.next_message(),
+            Err(EventStreamError::InvalidMessage(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        // A string header claiming ten bytes of value with only one present
+        let bytes = encode_frame(&[1, b'a', 7, 0, 10, b'x'], b"");
+
+        let mut decoder = EventStreamDecoder::new();
+        decoder.push(&bytes);
+        assert!(matches!(
+            decoder.next_message(),
+            Err(EventStreamError::InvalidMessage(_))
+        ));
+    }
+}
@@ -0,0 +1,11 @@
+pub mod client;
+pub mod event_stream;
+pub mod sigv4;
+pub mod types;
+
+pub use client::{BedrockClient, BedrockClientError};
+pub use sigv4::{Credentials, SigV4Signer};
+pub use types::{
+    ContentBlock, ConverseRequest, ConverseResponse, ConverseStreamEvent, InferenceConfiguration,
+    Message, TokenUsage, ToolConfiguration,
+};
@@ -0,0 +1,419 @@
+use crate::utils::utc_components;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::SystemTime;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// AWS credentials used to sign requests
+#[derive(Clone)]
+pub struct Credentials {
+    /// The access key id
+    pub access_key_id: String,
+    /// The secret access key
+    pub secret_access_key: String,
+    /// The session token for temporary credentials
+    pub session_token: Option<String>,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"<redacted>")
+            .field(
+                "session_token",
+                &self.session_token.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+impl Credentials {
+    /// Creates static credentials
+    ///
+    /// # Arguments
+    ///
+    /// * `access_key_id` - The AWS access key id
+    /// * `secret_access_key` - The AWS secret access key
+    /// * `session_token` - An optional session token for temporary credentials
+    pub fn new(access_key_id: &str, secret_access_key: &str, session_token: Option<&str>) -> Self {
+        Credentials {
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+            session_token: session_token.map(|token| token.to_string()),
+        }
+    }
+
+    /// Reads credentials from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`
+    ///
+    /// # Returns
+    ///
+    /// `Some(Credentials)` if both the access key id and the secret access key are set
+    pub fn from_env() -> Option<Self> {
+        let access_key_id = std::env::var("AWS_ACCESS_KEY_ID").ok()?;
+        let secret_access_key = std::env::var("AWS_SECRET_ACCESS_KEY").ok()?;
+        let session_token = std::env::var("AWS_SESSION_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+        Some(Credentials {
+            access_key_id,
+            secret_access_key,
+            session_token,
+        })
+    }
+}
+
+/// Signs HTTP requests with AWS Signature Version 4
+#[derive(Debug, Clone)]
+pub struct SigV4Signer {
+    credentials: Credentials,
+    region: String,
+    service: String,
+}
+
+impl SigV4Signer {
+    /// Creates a new signer for the given region and service
+    ///
+    /// # Arguments
+    ///
+    /// * `credentials` - The credentials to sign with
+    /// * `region` - The AWS region (e.g., "us-east-1")
+    /// * `service` - The signing name of the service (e.g., "bedrock")
+    pub fn new(credentials: Credentials, region: &str, service: &str) -> Self {
+        SigV4Signer {
+            credentials,
+            region: region.to_string(),
+            service: service.to_string(),
+        }
+    }
+
+    /// Computes the signing headers for a request
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The HTTP method
+    /// * `url` - The full request URL, with the path already percent-encoded as it goes on the wire
+    /// * `headers` - The headers to sign, excluding `host` and `x-amz-*` which are added here
+    /// * `payload` - The request body
+    /// * `time` - The signing time
+    ///
+    /// # Returns
+    ///
+    /// The headers to add to the request, including `authorization`
+    pub fn sign(
+        &self,
+        method: &str,
+        url: &reqwest::Url,
+        headers: &[(&str, &str)],
+        payload: &[u8],
+        time: SystemTime,
+    ) -> Vec<(String, String)> {
+        let amz_date = format_amz_date(time);
+        let date = &amz_date[..8];
+
+        let mut signed: Vec<(String, String)> = headers
+            .iter()
+            .map(|(name, value)| (name.to_lowercase(), normalize_header_value(value)))
+            .collect();
+        signed.push(("host".to_string(), host_header(url)));
+        signed.push(("x-amz-date".to_string(), amz_date.clone()));
+        if let Some(token) = &self.credentials.session_token {
+            signed.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        signed.sort();
+
+        let signed_headers = signed
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = canonical_request(method, url, &signed, payload);
+
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = string_to_sign(&amz_date, &scope, &canonical_request);
+
+        let signing_key = self.signing_key(date);
+        let signature = hex::encode(hmac(&signing_key, string_to_sign.as_bytes()));
+
+        let mut result = vec![
+            ("x-amz-date".to_string(), amz_date.clone()),
+            (
+                "authorization".to_string(),
+                format!(
+                    "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+                    ALGORITHM, self.credentials.access_key_id, scope, signed_headers, signature
+                ),
+            ),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            result.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        result
+    }
+
+    fn signing_key(&self, date: &str) -> Vec<u8> {
+        let secret = format!("AWS4{}", self.credentials.secret_access_key);
+        let k_date = hmac(secret.as_bytes(), date.as_bytes());
+        let k_region = hmac(&k_date, self.region.as_bytes());
+        let k_service = hmac(&k_region, self.service.as_bytes());
+        hmac(&k_service, b"aws4_request")
+    }
+}
+
+/// Builds the canonical request from headers that are already lowercased and sorted
+fn canonical_request(
+    method: &str,
+    url: &reqwest::Url,
+    headers: &[(String, String)],
+    payload: &[u8],
+) -> String {
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        canonical_uri(url.path()),
+        canonical_query(url),
+        canonical_headers,
+        signed_headers,
+        hex::encode(Sha256::digest(payload)),
+    )
+}
+
+fn string_to_sign(amz_date: &str, scope: &str, canonical_request: &str) -> String {
+    format!(
+        "{}\n{}\n{}\n{}",
+        ALGORITHM,
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes())),
+    )
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn host_header(url: &reqwest::Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+fn normalize_header_value(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Percent-encodes everything except the RFC 3986 unreserved characters
+pub(crate) fn uri_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Non-S3 services encode each path segment a second time
+fn canonical_uri(path: &str) -> String {
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    path.split('/')
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn canonical_query(url: &reqwest::Url) -> String {
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| (uri_encode(&key), uri_encode(&value)))
+        .collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Formats a time as `YYYYMMDD'T'HHMMSS'Z'`
+fn format_amz_date(time: SystemTime) -> String {
+    let c = utc_components(time);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        c.year, c.month, c.day, c.hour, c.minute, c.second
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    // Vectors from the AWS Signature Version 4 test suite
+    const ACCESS_KEY_ID: &str = "AKIDEXAMPLE";
+    const SECRET_ACCESS_KEY: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+
+    fn suite_signer() -> SigV4Signer {
+        SigV4Signer::new(
+            Credentials::new(ACCESS_KEY_ID, SECRET_ACCESS_KEY, None),
+            "us-east-1",
+            "service",
+        )
+    }
+
+    /// 2015-08-30T12:36:00Z, the signing time of the test suite
+    fn suite_time() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_440_938_160)
+    }
+
+    fn suite_headers(url: &reqwest::Url) -> Vec<(String, String)> {
+        vec![
+            ("host".to_string(), host_header(url)),
+            ("x-amz-date".to_string(), "20150830T123600Z".to_string()),
+        ]
+    }
+
+    fn signature(headers: &[(String, String)]) -> &str {
+        let authorization = headers
+            .iter()
+            .find(|(name, _)| name == "authorization")
+            .map(|(_, value)| value.as_str())
+            .unwrap();
+        authorization.rsplit("Signature=").next().unwrap()
+    }
+
+    #[test]
+    fn get_vanilla_canonical_request() {
+        let url = reqwest::Url::parse("https://example.amazonaws.com/").unwrap();
+        assert_eq!(
+            canonical_request("GET", &url, &suite_headers(&url), b""),
+            "GET\n/\n\nhost:example.amazonaws.com\nx-amz-date:20150830T123600Z\n\nhost;x-amz-date\n\
+             e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn get_vanilla_string_to_sign() {
+        let url = reqwest::Url::parse("https://example.amazonaws.com/").unwrap();
+        let canonical_request = canonical_request("GET", &url, &suite_headers(&url), b"");
+        assert_eq!(
+            string_to_sign(
+                "20150830T123600Z",
+                "20150830/us-east-1/service/aws4_request",
+                &canonical_request
+            ),
+            "AWS4-HMAC-SHA256\n20150830T123600Z\n20150830/us-east-1/service/aws4_request\n\
+             bb579772317eb040ac9ed261061d46c1f17a8133879d6129b6e1c25292927e63"
+        );
+    }
+
+    #[test]
+    fn get_vanilla_signature() {
+        let url = reqwest::Url::parse("https://example.amazonaws.com/").unwrap();
+        let headers = suite_signer().sign("GET", &url, &[], b"", suite_time());
+
+        assert_eq!(
+            headers[0],
+            ("x-amz-date".to_string(), "20150830T123600Z".to_string())
+        );
+        assert_eq!(
+            headers[1].1,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn post_vanilla_signature() {
+        let url = reqwest::Url::parse("https://example.amazonaws.com/").unwrap();
+        let headers = suite_signer().sign("POST", &url, &[], b"", suite_time());
+        assert_eq!(
+            signature(&headers),
+            "5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+        );
+    }
+
+    #[test]
+    fn query_parameters_are_sorted() {
+        let url = reqwest::Url::parse("https://example.amazonaws.com/?Param2=value2&Param1=value1")
+            .unwrap();
+        assert_eq!(canonical_query(&url), "Param1=value1&Param2=value2");
+
+        let headers = suite_signer().sign("GET", &url, &[], b"", suite_time());
+        assert_eq!(
+            signature(&headers),
+            "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"
+        );
+    }
+
+    #[test]
+    fn signing_key_matches_aws_example() {
+        // From the AWS documentation on deriving a signing key
+        let signer = SigV4Signer::new(
+            Credentials::new(ACCESS_KEY_ID, SECRET_ACCESS_KEY, None),
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(signer.signing_key("20120215")),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn session_token_is_signed_and_returned() {
+        let signer = SigV4Signer::new(
+            Credentials::new(ACCESS_KEY_ID, SECRET_ACCESS_KEY, Some("token")),
+            "us-east-1",
+            "service",
+        );
+        let url = reqwest::Url::parse("https://example.amazonaws.com/").unwrap();
+        let headers = signer.sign("GET", &url, &[], b"", suite_time());
+
+        assert!(headers[1]
+            .1
+            .contains("SignedHeaders=host;x-amz-date;x-amz-security-token,"));
+        assert_eq!(
+            headers[2],
+            ("x-amz-security-token".to_string(), "token".to_string())
+        );
+    }
+
+    #[test]
+    fn path_segments_are_encoded_twice() {
+        assert_eq!(uri_encode("model:0"), "model%3A0");
+        assert_eq!(
+            canonical_uri("/model/model%3A0/converse"),
+            "/model/model%253A0/converse"
+        );
+        assert_eq!(canonical_uri(""), "/");
+    }
+
+    #[test]
+    fn header_values_are_trimmed() {
+        assert_eq!(normalize_header_value("  a   b  c "), "a b c");
+    }
+}
@@ -0,0 +1,427 @@
+use crate::gemini::types::{FunctionCall, FunctionDeclaration, Tool, UsageMetadata};
+use crate::usage::TokenUsage as LedgerUsage;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Request structure for the Bedrock `Converse` and `ConverseStream` operations
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConverseRequest {
+    /// The conversation messages
+    pub messages: Vec<Message>,
+    /// System prompts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<Vec<SystemContentBlock>>,
+    /// Inference parameters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inference_config: Option<InferenceConfiguration>,
+    /// Tools the model may use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<ToolConfiguration>,
+    /// Model-specific request fields passed through unchanged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub additional_model_request_fields: Option<serde_json::Value>,
+}
+
+/// A message in a Bedrock conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    /// The role of the message ("user" or "assistant")
+    pub role: String,
+    /// The content blocks of the message
+    pub content: Vec<ContentBlock>,
+}
+
+impl Message {
+    /// Creates a user message holding a single text block
+    pub fn user(text: &str) -> Self {
+        Message {
+            role: "user".to_string(),
+            content: vec![ContentBlock::Text(text.to_string())],
+        }
+    }
+}
+
+/// A block of message content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ContentBlock {
+    /// Text content
+    Text(String),
+    /// An image
+    Image(ImageBlock),
+    /// A tool call requested by the model
+    ToolUse(ToolUseBlock),
+    /// The result of a tool call
+    ToolResult(ToolResultBlock),
+}
+
+/// Image content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageBlock {
+    /// The image format ("png", "jpeg", "gif" or "webp")
+    pub format: String,
+    /// The image source
+    pub source: ImageSource,
+}
+
+/// Source of an image
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ImageSource {
+    /// Base64-encoded image bytes
+    pub bytes: String,
+}
+
+// Only the size of the base64 data is shown, so logging a request never dumps it
+impl fmt::Debug for ImageSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageSource")
+            .field(
+                "bytes",
+                &format_args!("<{} bytes of base64>", self.bytes.len()),
+            )
+            .finish()
+    }
+}
+
+/// Tool call requested by the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolUseBlock {
+    /// Identifier of this tool call
+    pub tool_use_id: String,
+    /// Name of the tool
+    pub name: String,
+    /// Input for the tool as a JSON object
+    pub input: serde_json::Value,
+}
+
+impl From<ToolUseBlock> for FunctionCall {
+    fn from(tool_use: ToolUseBlock) -> Self {
+        FunctionCall {
+            name: tool_use.name,
+            args: tool_use.input,
+        }
+    }
+}
+
+/// Result of a tool call sent back to the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolResultBlock {
+    /// Identifier of the tool call this result answers
+    pub tool_use_id: String,
+    /// The result content
+    pub content: Vec<ToolResultContentBlock>,
+    /// "success" or "error"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+/// Content of a tool result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ToolResultContentBlock {
+    /// Text result
+    Text(String),
+    /// JSON result
+    Json(serde_json::Value),
+}
+
+/// System prompt content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SystemContentBlock {
+    /// Text system prompt
+    Text(String),
+}
+
+/// Inference parameters for Bedrock
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InferenceConfiguration {
+    /// Maximum number of tokens to generate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<i32>,
+    /// Temperature for generation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Top-p sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Stop sequences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+}
+
+/// Tool configuration for Bedrock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolConfiguration {
+    /// The tools the model may use
+    pub tools: Vec<BedrockTool>,
+    /// How the model should choose a tool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<serde_json::Value>,
+}
+
+impl ToolConfiguration {
+    /// Builds a tool configuration from the crate's `Tool` definitions
+    pub fn from_tools(tools: &[Tool]) -> Self {
+        ToolConfiguration {
+            tools: tools
+                .iter()
+                .flat_map(|tool| tool.function_declarations.iter())
+                .map(|declaration| BedrockTool {
+                    tool_spec: ToolSpecification::from(declaration),
+                })
+                .collect(),
+            tool_choice: None,
+        }
+    }
+}
+
+/// A tool entry in a tool configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BedrockTool {
+    /// The tool specification
+    pub tool_spec: ToolSpecification,
+}
+
+/// Specification of a tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolSpecification {
+    /// Name of the tool
+    pub name: String,
+    /// Description of the tool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON schema of the tool input
+    pub input_schema: ToolInputSchema,
+}
+
+impl From<&FunctionDeclaration> for ToolSpecification {
+    fn from(declaration: &FunctionDeclaration) -> Self {
+        ToolSpecification {
+            name: declaration.name.clone(),
+            description: Some(declaration.description.clone()),
+            input_schema: ToolInputSchema {
+                json: declaration.parameters.clone(),
+            },
+        }
+    }
+}
+
+/// JSON schema wrapper for tool input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolInputSchema {
+    /// The JSON schema
+    pub json: serde_json::Value,
+}
+
+/// Response structure for the `Converse` operation
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConverseResponse {
+    /// The generated output
+    pub output: ConverseOutput,
+    /// Why the model stopped generating
+    pub stop_reason: String,
+    /// Token usage
+    pub usage: TokenUsage,
+    /// Call metrics
+    pub metrics: Option<ConverseMetrics>,
+}
+
+impl ConverseResponse {
+    /// Gets the text of the first text block in the output message
+    pub fn get_text(&self) -> Option<String> {
+        self.output
+            .message
+            .content
+            .iter()
+            .find_map(|block| match block {
+                ContentBlock::Text(text) => Some(text.clone()),
+                _ => None,
+            })
+    }
+
+    /// Gets the tool calls requested by the model
+    pub fn function_calls(&self) -> Vec<FunctionCall> {
+        self.output
+            .message
+            .content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse(tool_use) => Some(FunctionCall::from(tool_use.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Output of the `Converse` operation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConverseOutput {
+    /// The message generated by the model
+    pub message: Message,
+}
+
+/// Token usage reported by Bedrock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    /// Input token count
+    pub input_tokens: i32,
+    /// Output token count
+    pub output_tokens: i32,
+    /// Total token count
+    pub total_tokens: i32,
+}
+
+impl From<TokenUsage> for LedgerUsage {
+    fn from(usage: TokenUsage) -> Self {
+        LedgerUsage::new(
+            usage.input_tokens.max(0) as u32,
+            usage.output_tokens.max(0) as u32,
+        )
+    }
+}
+
+impl From<TokenUsage> for UsageMetadata {
+    fn from(usage: TokenUsage) -> Self {
+        UsageMetadata {
+            prompt_token_count: usage.input_tokens,
+            candidates_token_count: usage.output_tokens,
+            total_token_count: usage.total_tokens,
+            cached_content_token_count: 0,
+        }
+    }
+}
+
+/// Metrics reported by Bedrock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConverseMetrics {
+    /// Latency of the call in milliseconds
+    pub latency_ms: u64,
+}
+
+/// Event emitted by the `ConverseStream` operation
+#[derive(Debug, Clone)]
+pub enum ConverseStreamEvent {
+    /// The start of the assistant message
+    MessageStart(MessageStartEvent),
+    /// The start of a content block
+    ContentBlockStart(ContentBlockStartEvent),
+    /// An incremental piece of a content block
+    ContentBlockDelta(ContentBlockDeltaEvent),
+    /// The end of a content block
+    ContentBlockStop(ContentBlockStopEvent),
+    /// The end of the assistant message
+    MessageStop(MessageStopEvent),
+    /// Usage and metrics for the call
+    Metadata(ConverseStreamMetadataEvent),
+}
+
+impl ConverseStreamEvent {
+    /// Gets the text carried by a text delta
+    pub fn get_text(&self) -> Option<String> {
+        match self {
+            ConverseStreamEvent::ContentBlockDelta(event) => match &event.delta {
+                ContentBlockDelta::Text(text) => Some(text.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Payload of a `messageStart` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageStartEvent {
+    /// The role of the message
+    pub role: String,
+}
+
+/// Payload of a `contentBlockStart` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentBlockStartEvent {
+    /// Index of the content block
+    pub content_block_index: usize,
+    /// The start of the block
+    pub start: ContentBlockStart,
+}
+
+/// Start of a content block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ContentBlockStart {
+    /// The start of a tool call
+    ToolUse(ToolUseBlockStart),
+}
+
+/// Start of a tool call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolUseBlockStart {
+    /// Identifier of the tool call
+    pub tool_use_id: String,
+    /// Name of the tool
+    pub name: String,
+}
+
+/// Payload of a `contentBlockDelta` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentBlockDeltaEvent {
+    /// Index of the content block
+    pub content_block_index: usize,
+    /// The delta
+    pub delta: ContentBlockDelta,
+}
+
+/// Incremental content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ContentBlockDelta {
+    /// A piece of text
+    Text(String),
+    /// A piece of tool call input
+    ToolUse(ToolUseBlockDelta),
+}
+
+/// Incremental tool call input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolUseBlockDelta {
+    /// A fragment of the JSON input
+    pub input: String,
+}
+
+/// Payload of a `contentBlockStop` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentBlockStopEvent {
+    /// Index of the content block
+    pub content_block_index: usize,
+}
+
+/// Payload of a `messageStop` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageStopEvent {
+    /// Why the model stopped generating
+    pub stop_reason: String,
+}
+
+/// Payload of a `metadata` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConverseStreamMetadataEvent {
+    /// Token usage
+    pub usage: TokenUsage,
+    /// Call metrics
+    pub metrics: Option<ConverseMetrics>,
+}
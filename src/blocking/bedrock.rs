@@ -0,0 +1,124 @@
+use crate::bedrock::client::{BedrockClient as AsyncBedrockClient, BedrockClientError};
+use crate::bedrock::sigv4::Credentials;
+use crate::bedrock::types::{ConverseRequest, ConverseResponse, ConverseStreamEvent};
+use crate::blocking::{block_on, block_on_stream, StreamIter};
+use std::sync::Arc;
+
+/// Blocking client for the AWS Bedrock Converse API
+///
+/// Configure an async `BedrockClient` and convert it with `From`.
+#[derive(Debug, Clone)]
+pub struct BedrockClient {
+    inner: Arc<AsyncBedrockClient>,
+}
+
+impl From<AsyncBedrockClient> for BedrockClient {
+    fn from(client: AsyncBedrockClient) -> Self {
+        BedrockClient {
+            inner: Arc::new(client),
+        }
+    }
+}
+
+impl BedrockClient {
+    /// Creates a new instance of `BedrockClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - The AWS region (e.g., "us-east-1")
+    /// * `model` - The model id or inference profile (e.g., "anthropic.claude-3-haiku-20240307-v1:0")
+    /// * `credentials` - The credentials used to sign requests
+    ///
+    /// # Returns
+    ///
+    /// A new `BedrockClient` instance
+    pub fn new(region: &str, model: &str, credentials: Credentials) -> Self {
+        AsyncBedrockClient::new(region, model, credentials).into()
+    }
+
+    /// Creates a new instance of `BedrockClient` from environment variables
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model id or inference profile
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `BedrockClient` or a `BedrockClientError`
+    pub fn from_env(model: &str) -> Result<Self, BedrockClientError> {
+        AsyncBedrockClient::from_env(model).map(Self::from)
+    }
+
+    /// Sends a text prompt using the `Converse` operation
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ConverseResponse` or a `BedrockClientError`
+    pub fn converse(&self, prompt: &str) -> Result<ConverseResponse, BedrockClientError> {
+        let inner = self.inner.clone();
+        let prompt = prompt.to_string();
+        block_on(async move { inner.converse(&prompt).await })
+    }
+
+    /// Sends a structured request using the `Converse` operation
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ConverseRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ConverseResponse` or a `BedrockClientError`
+    pub fn converse_with_request(
+        &self,
+        request: ConverseRequest,
+    ) -> Result<ConverseResponse, BedrockClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.converse_with_request(request).await })
+    }
+
+    /// Streams a response to a text prompt using the `ConverseStream` operation
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `ConverseStreamEvent`s or a `BedrockClientError`
+    pub fn converse_stream(
+        &self,
+        prompt: &str,
+    ) -> Result<StreamIter<ConverseStreamEvent, BedrockClientError>, BedrockClientError> {
+        let inner = self.inner.clone();
+        let prompt = prompt.to_string();
+        block_on_stream(move |sender| async move {
+            sender.forward(inner.converse_stream(&prompt).await).await
+        })
+    }
+
+    /// Streams a response to a structured request using the `ConverseStream` operation
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ConverseRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `ConverseStreamEvent`s or a `BedrockClientError`
+    pub fn converse_stream_with_request(
+        &self,
+        request: ConverseRequest,
+    ) -> Result<StreamIter<ConverseStreamEvent, BedrockClientError>, BedrockClientError> {
+        let inner = self.inner.clone();
+        block_on_stream(move |sender| async move {
+            sender
+                .forward(inner.converse_stream_with_request(request).await)
+                .await
+        })
+    }
+}
@@ -0,0 +1,167 @@
+use crate::blocking::{block_on, block_on_stream, StreamIter};
+use crate::cohere::client::{CohereClient as AsyncCohereClient, CohereClientError};
+use crate::cohere::types::{
+    ChatRequest, ChatResponse, ChatStreamEvent, EmbedResponse, RerankResult,
+};
+use std::sync::Arc;
+
+/// Blocking client for the Cohere API
+///
+/// Configure an async `CohereClient` and convert it with `From`.
+#[derive(Debug, Clone)]
+pub struct CohereClient {
+    inner: Arc<AsyncCohereClient>,
+}
+
+impl From<AsyncCohereClient> for CohereClient {
+    fn from(client: AsyncCohereClient) -> Self {
+        CohereClient {
+            inner: Arc::new(client),
+        }
+    }
+}
+
+impl CohereClient {
+    /// Creates a new instance of `CohereClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The Cohere API key
+    /// * `model` - The chat model to use (e.g., "command-r-plus")
+    ///
+    /// # Returns
+    ///
+    /// A new `CohereClient` instance
+    pub fn new(api_key: &str, model: &str) -> Self {
+        AsyncCohereClient::new(api_key, model).into()
+    }
+
+    /// Sends a single user prompt as a chat request
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ChatResponse` or a `CohereClientError`
+    pub fn chat(&self, prompt: &str) -> Result<ChatResponse, CohereClientError> {
+        let inner = self.inner.clone();
+        let prompt = prompt.to_string();
+        block_on(async move { inner.chat(&prompt).await })
+    }
+
+    /// Sends a structured chat request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ChatResponse` or a `CohereClientError`
+    pub fn chat_with_request(
+        &self,
+        request: ChatRequest,
+    ) -> Result<ChatResponse, CohereClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.chat_with_request(request).await })
+    }
+
+    /// Streams a chat response for a single user prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `ChatStreamEvent`s or a `CohereClientError`
+    pub fn stream_chat(
+        &self,
+        prompt: &str,
+    ) -> Result<StreamIter<ChatStreamEvent, CohereClientError>, CohereClientError> {
+        let inner = self.inner.clone();
+        let prompt = prompt.to_string();
+        block_on_stream(move |sender| async move {
+            sender.forward(inner.stream_chat(&prompt).await).await
+        })
+    }
+
+    /// Streams a structured chat request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `ChatStreamEvent`s or a `CohereClientError`
+    pub fn stream_chat_with_request(
+        &self,
+        request: ChatRequest,
+    ) -> Result<StreamIter<ChatStreamEvent, CohereClientError>, CohereClientError> {
+        let inner = self.inner.clone();
+        block_on_stream(move |sender| async move {
+            sender
+                .forward(inner.stream_chat_with_request(request).await)
+                .await
+        })
+    }
+
+    /// Creates float embeddings for the given texts
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The embedding model (e.g., "embed-english-v3.0")
+    /// * `texts` - The texts to embed
+    /// * `input_type` - "search_document", "search_query", "classification" or "clustering"
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `EmbedResponse` or a `CohereClientError`
+    pub fn embed(
+        &self,
+        model: &str,
+        texts: &[&str],
+        input_type: &str,
+    ) -> Result<EmbedResponse, CohereClientError> {
+        let inner = self.inner.clone();
+        let model = model.to_string();
+        let texts: Vec<String> = texts.iter().map(|item| item.to_string()).collect();
+        let input_type = input_type.to_string();
+        block_on(async move {
+            let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+            inner.embed(&model, &texts, &input_type).await
+        })
+    }
+
+    /// Ranks documents by relevance to a query
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The rerank model (e.g., "rerank-v3.5")
+    /// * `query` - The search query
+    /// * `documents` - The documents to rank
+    /// * `top_n` - Number of results to return, or all documents if `None`
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RerankResult`s, most relevant first, or a `CohereClientError`
+    pub fn rerank(
+        &self,
+        model: &str,
+        query: &str,
+        documents: &[&str],
+        top_n: Option<usize>,
+    ) -> Result<Vec<RerankResult>, CohereClientError> {
+        let inner = self.inner.clone();
+        let model = model.to_string();
+        let query = query.to_string();
+        let documents: Vec<String> = documents.iter().map(|item| item.to_string()).collect();
+        block_on(async move {
+            let documents: Vec<&str> = documents.iter().map(String::as_str).collect();
+            inner.rerank(&model, &query, &documents, top_n).await
+        })
+    }
+}
@@ -0,0 +1,227 @@
+use crate::blocking::{block_on, block_on_stream, StreamIter};
+use crate::gemini::client::{GeminiClient as AsyncGeminiClient, GeminiClientError};
+use crate::gemini::types::{
+    GenerateContentRequest, GenerateContentResponse, GenerationConfig, ModelInfo,
+    StreamGenerateContentResponse,
+};
+use std::sync::Arc;
+
+/// Blocking client for the Gemini API
+///
+/// Configure an async `GeminiClient`, e.g. with its builder, and convert it with `From`.
+#[derive(Debug, Clone)]
+pub struct GeminiClient {
+    inner: Arc<AsyncGeminiClient>,
+}
+
+impl From<AsyncGeminiClient> for GeminiClient {
+    fn from(client: AsyncGeminiClient) -> Self {
+        GeminiClient {
+            inner: Arc::new(client),
+        }
+    }
+}
+
+impl GeminiClient {
+    /// Creates a new instance of `GeminiClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The API key for Google AI Studio
+    /// * `model` - The model to use (e.g., "gemini-1.5-pro")
+    ///
+    /// # Returns
+    ///
+    /// A new `GeminiClient` instance
+    pub fn new(api_key: &str, model: &str) -> Self {
+        AsyncGeminiClient::new(api_key, model).into()
+    }
+
+    /// Generates content based on a text prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate content for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GenerateContentResponse` or a `GeminiClientError`
+    pub fn generate_content(
+        &self,
+        prompt: &str,
+    ) -> Result<GenerateContentResponse, GeminiClientError> {
+        let inner = self.inner.clone();
+        let prompt = prompt.to_string();
+        block_on(async move { inner.generate_content(&prompt).await })
+    }
+
+    /// Generates content with a custom request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateContentRequest` to send
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GenerateContentResponse` or a `GeminiClientError`
+    pub fn generate_content_with_request(
+        &self,
+        request: GenerateContentRequest,
+    ) -> Result<GenerateContentResponse, GeminiClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.generate_content_with_request(request).await })
+    }
+
+    /// Generates content with a custom generation configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate content for
+    /// * `config` - The generation configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GenerateContentResponse` or a `GeminiClientError`
+    pub fn generate_content_with_config(
+        &self,
+        prompt: &str,
+        config: GenerationConfig,
+    ) -> Result<GenerateContentResponse, GeminiClientError> {
+        let inner = self.inner.clone();
+        let prompt = prompt.to_string();
+        block_on(async move { inner.generate_content_with_config(&prompt, config).await })
+    }
+
+    /// Streams content generation based on a text prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate content for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `StreamGenerateContentResponse` chunks or a `GeminiClientError`
+    pub fn stream_content(
+        &self,
+        prompt: &str,
+    ) -> Result<StreamIter<StreamGenerateContentResponse, GeminiClientError>, GeminiClientError>
+    {
+        let inner = self.inner.clone();
+        let prompt = prompt.to_string();
+        block_on_stream(move |sender| async move {
+            sender.forward(inner.stream_content(&prompt).await).await
+        })
+    }
+
+    /// Streams content generation with a custom request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateContentRequest` to send
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `StreamGenerateContentResponse` chunks or a `GeminiClientError`
+    pub fn stream_content_with_request(
+        &self,
+        request: GenerateContentRequest,
+    ) -> Result<StreamIter<StreamGenerateContentResponse, GeminiClientError>, GeminiClientError>
+    {
+        let inner = self.inner.clone();
+        block_on_stream(move |sender| async move {
+            sender
+                .forward(inner.stream_content_with_request(request).await)
+                .await
+        })
+    }
+
+    /// Gets the details of the client's model, such as its token limits
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ModelInfo` or a `GeminiClientError`
+    pub fn model_info(&self) -> Result<ModelInfo, GeminiClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.model_info().await })
+    }
+
+    /// Counts the prompt tokens of a request with the `countTokens` endpoint
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateContentRequest` to count, including its tools
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of tokens or a `GeminiClientError`
+    pub fn count_tokens(&self, request: &GenerateContentRequest) -> Result<u32, GeminiClientError> {
+        let inner = self.inner.clone();
+        let request = request.clone();
+        block_on(async move { inner.count_tokens(&request).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPLY: &str = r#"{"candidates": [{"content": {"role": "model", "parts": [{"text": "Hi"}]}, "index": 0}]}"#;
+
+    fn client(server: &mockito::Server) -> GeminiClient {
+        AsyncGeminiClient::builder()
+            .api_key("key")
+            .model("gemini-1.5-flash")
+            .base_url(&server.url())
+            .build()
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn generates_and_streams_content() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/models/gemini-1.5-flash:generateContent")
+            .match_header("x-goog-api-key", "key")
+            .with_body(REPLY)
+            .create();
+        server
+            .mock(
+                "POST",
+                "/models/gemini-1.5-flash:streamGenerateContent?alt=sse",
+            )
+            .with_header("content-type", "text/event-stream")
+            .with_body(format!("data: {}\n\ndata: {}\n\n", REPLY, REPLY))
+            .create();
+        let client = client(&server);
+
+        let response = client.generate_content("Hello").unwrap();
+        assert_eq!(response.get_text().as_deref(), Some("Hi"));
+        let chunks: Vec<_> = client
+            .stream_content("Hello")
+            .unwrap()
+            .map(|chunk| chunk.unwrap().candidates[0].content.parts[0].text.clone())
+            .collect();
+        assert_eq!(chunks, [Some("Hi".to_string()), Some("Hi".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn works_inside_a_runtime() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/models/gemini-1.5-flash:generateContent")
+            .with_status(429)
+            .with_body("slow down")
+            .create_async()
+            .await;
+        let client = client(&server);
+
+        match client.generate_content("Hello") {
+            Err(GeminiClientError::StatusError(status, body)) => {
+                assert_eq!(status.as_u16(), 429);
+                assert_eq!(body, "slow down");
+            }
+            other => panic!("expected StatusError, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::blocking::{block_on, block_on_stream, StreamIter};
+use crate::huggingface::client::{HuggingFaceClientError, TgiClient as AsyncTgiClient};
+use crate::huggingface::tei::TeiClient as AsyncTeiClient;
+use crate::huggingface::types::{
+    TeiRank, TgiGenerateRequest, TgiGenerateResponse, TgiInfo, TgiStreamResponse,
+};
+use crate::ollama::types::{GenerateRequest, GenerateResponse};
+use std::sync::Arc;
+
+/// Blocking client for a Hugging Face Text Generation Inference server
+///
+/// Configure an async `TgiClient` and convert it with `From`.
+#[derive(Debug, Clone)]
+pub struct TgiClient {
+    inner: Arc<AsyncTgiClient>,
+}
+
+impl From<AsyncTgiClient> for TgiClient {
+    fn from(client: AsyncTgiClient) -> Self {
+        TgiClient {
+            inner: Arc::new(client),
+        }
+    }
+}
+
+impl TgiClient {
+    /// Creates a new instance of `TgiClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The base URL of the TGI server
+    /// * `api_key` - The Hugging Face token, if the endpoint requires one
+    ///
+    /// # Returns
+    ///
+    /// A new `TgiClient` instance
+    pub fn new(base_url: &str, api_key: Option<&str>) -> Self {
+        AsyncTgiClient::new(base_url, api_key).into()
+    }
+
+    /// Checks if the TGI server is healthy
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the server is healthy, or a `HuggingFaceClientError`
+    pub fn active(&self) -> Result<bool, HuggingFaceClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.active().await })
+    }
+
+    /// Gets information about the served model
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TgiInfo` or a `HuggingFaceClientError`
+    pub fn info(&self) -> Result<TgiInfo, HuggingFaceClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.info().await })
+    }
+
+    /// Generates text with TGI's native request format
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `TgiGenerateRequest` containing the prompt and parameters
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TgiGenerateResponse` or a `HuggingFaceClientError`
+    pub fn generate(
+        &self,
+        request: TgiGenerateRequest,
+    ) -> Result<TgiGenerateResponse, HuggingFaceClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.generate(request).await })
+    }
+
+    /// Streams generated tokens with TGI's native request format
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `TgiGenerateRequest` containing the prompt and parameters
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `TgiStreamResponse` tokens or a `HuggingFaceClientError`
+    pub fn stream_generate(
+        &self,
+        request: TgiGenerateRequest,
+    ) -> Result<StreamIter<TgiStreamResponse, HuggingFaceClientError>, HuggingFaceClientError> {
+        let inner = self.inner.clone();
+        block_on_stream(move |sender| async move {
+            sender.forward(inner.stream_generate(request).await).await
+        })
+    }
+
+    /// Generates a completion from an Ollama-style `GenerateRequest`
+    ///
+    /// Ollama `options` such as `num_predict`, `temperature`, `top_p`, `top_k`,
+    /// `repeat_penalty`, `seed` and `stop` are translated to TGI parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the model and prompt
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GenerateResponse` or a `HuggingFaceClientError`
+    pub fn generate_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<GenerateResponse, HuggingFaceClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.generate_completion(request).await })
+    }
+
+    /// Streams a completion from an Ollama-style `GenerateRequest` chunk by chunk
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the model and prompt
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `GenerateResponse` chunks or a `HuggingFaceClientError`
+    pub fn stream_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<StreamIter<GenerateResponse, HuggingFaceClientError>, HuggingFaceClientError> {
+        let inner = self.inner.clone();
+        block_on_stream(move |sender| async move {
+            sender.forward(inner.stream_completion(request).await).await
+        })
+    }
+}
+
+/// Blocking client for a Hugging Face Text Embeddings Inference server
+///
+/// Configure an async `TeiClient` and convert it with `From`.
+#[derive(Debug, Clone)]
+pub struct TeiClient {
+    inner: Arc<AsyncTeiClient>,
+}
+
+impl From<AsyncTeiClient> for TeiClient {
+    fn from(client: AsyncTeiClient) -> Self {
+        TeiClient {
+            inner: Arc::new(client),
+        }
+    }
+}
+
+impl TeiClient {
+    /// Creates a new instance of `TeiClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The base URL of the TEI server
+    /// * `api_key` - The Hugging Face token, if the endpoint requires one
+    ///
+    /// # Returns
+    ///
+    /// A new `TeiClient` instance
+    pub fn new(base_url: &str, api_key: Option<&str>) -> Self {
+        AsyncTeiClient::new(base_url, api_key).into()
+    }
+
+    /// Checks if the TEI server is healthy
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the server is healthy, or a `HuggingFaceClientError`
+    pub fn active(&self) -> Result<bool, HuggingFaceClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.active().await })
+    }
+
+    /// Creates embeddings for the given texts
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The texts to embed
+    /// * `normalize` - Whether to L2-normalize the embeddings
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing one embedding per input or a `HuggingFaceClientError`
+    pub fn embed(
+        &self,
+        inputs: &[&str],
+        normalize: bool,
+    ) -> Result<Vec<Vec<f32>>, HuggingFaceClientError> {
+        let inner = self.inner.clone();
+        let inputs: Vec<String> = inputs.iter().map(|item| item.to_string()).collect();
+        block_on(async move {
+            let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+            inner.embed(&inputs, normalize).await
+        })
+    }
+
+    /// Ranks texts by relevance to a query using a cross-encoder
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The search query
+    /// * `texts` - The texts to rank
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TeiRank`s, most relevant first, or a `HuggingFaceClientError`
+    pub fn rerank(
+        &self,
+        query: &str,
+        texts: &[&str],
+    ) -> Result<Vec<TeiRank>, HuggingFaceClientError> {
+        let inner = self.inner.clone();
+        let query = query.to_string();
+        let texts: Vec<String> = texts.iter().map(|item| item.to_string()).collect();
+        block_on(async move {
+            let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+            inner.rerank(&query, &texts).await
+        })
+    }
+}
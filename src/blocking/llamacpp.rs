@@ -0,0 +1,188 @@
+use crate::blocking::{block_on, block_on_stream, StreamIter};
+use crate::llamacpp::client::{LlamaCppClient as AsyncLlamaCppClient, LlamaCppClientError};
+use crate::llamacpp::types::{
+    CompletionRequest, CompletionResponse, HealthStatus, SlotActionResponse, SlotInfo,
+};
+use std::sync::Arc;
+
+/// Blocking client for a llama.cpp server
+///
+/// Configure an async `LlamaCppClient` and convert it with `From`.
+#[derive(Debug, Clone)]
+pub struct LlamaCppClient {
+    inner: Arc<AsyncLlamaCppClient>,
+}
+
+impl From<AsyncLlamaCppClient> for LlamaCppClient {
+    fn from(client: AsyncLlamaCppClient) -> Self {
+        LlamaCppClient {
+            inner: Arc::new(client),
+        }
+    }
+}
+
+impl LlamaCppClient {
+    /// Creates a new instance of `LlamaCppClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The base URL of the llama.cpp server (e.g., "http://localhost:8080")
+    /// * `api_key` - The API key if the server was started with `--api-key`
+    ///
+    /// # Returns
+    ///
+    /// A new `LlamaCppClient` instance
+    pub fn new(base_url: &str, api_key: Option<&str>) -> Self {
+        AsyncLlamaCppClient::new(base_url, api_key).into()
+    }
+
+    /// Checks the health of the server
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `HealthStatus` or a `LlamaCppClientError`
+    pub fn health(&self) -> Result<HealthStatus, LlamaCppClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.health().await })
+    }
+
+    /// Generates a completion, optionally constrained by a grammar or JSON schema
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `CompletionRequest` containing the prompt and sampling settings
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `CompletionResponse` or a `LlamaCppClientError`
+    pub fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse, LlamaCppClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.completion(request).await })
+    }
+
+    /// Streams a completion chunk by chunk
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `CompletionRequest` containing the prompt and sampling settings
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `CompletionResponse` chunks or a `LlamaCppClientError`
+    pub fn stream_completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<StreamIter<CompletionResponse, LlamaCppClientError>, LlamaCppClientError> {
+        let inner = self.inner.clone();
+        block_on_stream(move |sender| async move {
+            sender.forward(inner.stream_completion(request).await).await
+        })
+    }
+
+    /// Converts text into token ids using the loaded model's tokenizer
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The text to tokenize
+    /// * `add_special` - Whether to add special tokens such as BOS
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the token ids or a `LlamaCppClientError`
+    pub fn tokenize(
+        &self,
+        content: &str,
+        add_special: bool,
+    ) -> Result<Vec<i32>, LlamaCppClientError> {
+        let inner = self.inner.clone();
+        let content = content.to_string();
+        block_on(async move { inner.tokenize(&content, add_special).await })
+    }
+
+    /// Converts token ids back into text
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - The token ids to decode
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decoded text or a `LlamaCppClientError`
+    pub fn detokenize(&self, tokens: &[i32]) -> Result<String, LlamaCppClientError> {
+        let inner = self.inner.clone();
+        let tokens = tokens.to_vec();
+        block_on(async move { inner.detokenize(&tokens).await })
+    }
+
+    /// Creates an embedding for the given text
+    ///
+    /// The server must be started with `--embedding`.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The text to embed
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the pooled embedding or a `LlamaCppClientError`
+    pub fn embedding(&self, content: &str) -> Result<Vec<f32>, LlamaCppClientError> {
+        let inner = self.inner.clone();
+        let content = content.to_string();
+        block_on(async move { inner.embedding(&content).await })
+    }
+
+    /// Lists the server slots
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SlotInfo` of each slot or a `LlamaCppClientError`
+    pub fn slots(&self) -> Result<Vec<SlotInfo>, LlamaCppClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.slots().await })
+    }
+
+    /// Saves the KV cache of a slot to a file on the server
+    ///
+    /// # Arguments
+    ///
+    /// * `id_slot` - The slot to save
+    /// * `filename` - The file name, relative to the server's `--slot-save-path`
+    pub fn save_slot(
+        &self,
+        id_slot: i32,
+        filename: &str,
+    ) -> Result<SlotActionResponse, LlamaCppClientError> {
+        let inner = self.inner.clone();
+        let filename = filename.to_string();
+        block_on(async move { inner.save_slot(id_slot, &filename).await })
+    }
+
+    /// Restores the KV cache of a slot from a file on the server
+    ///
+    /// # Arguments
+    ///
+    /// * `id_slot` - The slot to restore into
+    /// * `filename` - The file name, relative to the server's `--slot-save-path`
+    pub fn restore_slot(
+        &self,
+        id_slot: i32,
+        filename: &str,
+    ) -> Result<SlotActionResponse, LlamaCppClientError> {
+        let inner = self.inner.clone();
+        let filename = filename.to_string();
+        block_on(async move { inner.restore_slot(id_slot, &filename).await })
+    }
+
+    /// Erases the KV cache of a slot
+    ///
+    /// # Arguments
+    ///
+    /// * `id_slot` - The slot to erase
+    pub fn erase_slot(&self, id_slot: i32) -> Result<SlotActionResponse, LlamaCppClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.erase_slot(id_slot).await })
+    }
+}
@@ -0,0 +1,120 @@
+use crate::blocking::{block_on, block_on_stream, StreamIter};
+use crate::local::client::{LocalClient as AsyncLocalClient, LocalClientError};
+use crate::ollama::types::{GenerateRequest, GenerateResponse};
+use std::sync::Arc;
+
+/// Blocking client for in-process CPU inference
+///
+/// Configure an async `LocalClient` and convert it with `From`.
+#[derive(Debug, Clone)]
+pub struct LocalClient {
+    inner: Arc<AsyncLocalClient>,
+}
+
+impl From<AsyncLocalClient> for LocalClient {
+    fn from(client: AsyncLocalClient) -> Self {
+        LocalClient {
+            inner: Arc::new(client),
+        }
+    }
+}
+
+impl LocalClient {
+    /// Loads a quantized llama-family model from a GGUF file for text generation
+    ///
+    /// # Arguments
+    ///
+    /// * `model_path` - Path to the `.gguf` file
+    /// * `tokenizer_path` - Path to the model's `tokenizer.json`
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `LocalClient` or a `LocalClientError`
+    pub fn from_gguf(model_path: &str, tokenizer_path: &str) -> Result<Self, LocalClientError> {
+        AsyncLocalClient::from_gguf(model_path, tokenizer_path).map(Self::from)
+    }
+
+    /// Loads a BERT-family embedding model from a directory
+    ///
+    /// # Arguments
+    ///
+    /// * `model_dir` - Path to the model directory
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `LocalClient` or a `LocalClientError`
+    pub fn from_safetensors(model_dir: &str) -> Result<Self, LocalClientError> {
+        AsyncLocalClient::from_safetensors(model_dir).map(Self::from)
+    }
+
+    /// Returns the model name, taken from the model path
+    pub fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    /// Generates a completion based on the provided request
+    ///
+    /// The request's `model` is echoed back; the loaded model is always used.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the prompt and options
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GenerateResponse` or a `LocalClientError`
+    pub fn generate_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<GenerateResponse, LocalClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.generate_completion(request).await })
+    }
+
+    /// Streams a completion response token by token based on the provided request
+    ///
+    /// Chunks carry `done: false`; a final chunk with an empty `response`,
+    /// `done: true` and the timing statistics ends the stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the prompt and options
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `GenerateResponse` chunks or a `LocalClientError`
+    pub fn stream_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<StreamIter<GenerateResponse, LocalClientError>, LocalClientError> {
+        let inner = self.inner.clone();
+        block_on_stream(move |sender| async move {
+            sender.forward(inner.stream_completion(request).await).await
+        })
+    }
+
+    /// Creates embeddings for the given texts
+    ///
+    /// Token embeddings are mean-pooled over the attention mask.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The texts to embed
+    /// * `normalize` - Whether to L2-normalize the embeddings
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing one embedding per input or a `LocalClientError`
+    pub fn embed(
+        &self,
+        inputs: &[&str],
+        normalize: bool,
+    ) -> Result<Vec<Vec<f32>>, LocalClientError> {
+        let inner = self.inner.clone();
+        let inputs: Vec<String> = inputs.iter().map(|item| item.to_string()).collect();
+        block_on(async move {
+            let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+            inner.embed(&inputs, normalize).await
+        })
+    }
+}
@@ -0,0 +1,135 @@
+use crate::blocking::{block_on, block_on_stream, StreamIter};
+use crate::mistral::client::{MistralClient as AsyncMistralClient, MistralClientError};
+use crate::mistral::types::{
+    ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, EmbeddingResponse,
+};
+use std::sync::Arc;
+
+/// Blocking client for the Mistral API
+///
+/// Configure an async `MistralClient` and convert it with `From`.
+#[derive(Debug, Clone)]
+pub struct MistralClient {
+    inner: Arc<AsyncMistralClient>,
+}
+
+impl From<AsyncMistralClient> for MistralClient {
+    fn from(client: AsyncMistralClient) -> Self {
+        MistralClient {
+            inner: Arc::new(client),
+        }
+    }
+}
+
+impl MistralClient {
+    /// Creates a new instance of `MistralClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The Mistral API key
+    /// * `model` - The model to use (e.g., "mistral-small-latest")
+    ///
+    /// # Returns
+    ///
+    /// A new `MistralClient` instance
+    pub fn new(api_key: &str, model: &str) -> Self {
+        AsyncMistralClient::new(api_key, model).into()
+    }
+
+    /// Sends a single user prompt as a chat completion
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ChatCompletionResponse` or a `MistralClientError`
+    pub fn chat(&self, prompt: &str) -> Result<ChatCompletionResponse, MistralClientError> {
+        let inner = self.inner.clone();
+        let prompt = prompt.to_string();
+        block_on(async move { inner.chat(&prompt).await })
+    }
+
+    /// Sends a structured chat completion request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatCompletionRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ChatCompletionResponse` or a `MistralClientError`
+    pub fn chat_with_request(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, MistralClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.chat_with_request(request).await })
+    }
+
+    /// Streams a chat completion for a single user prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `ChatCompletionChunk`s or a `MistralClientError`
+    pub fn stream_chat(
+        &self,
+        prompt: &str,
+    ) -> Result<StreamIter<ChatCompletionChunk, MistralClientError>, MistralClientError> {
+        let inner = self.inner.clone();
+        let prompt = prompt.to_string();
+        block_on_stream(move |sender| async move {
+            sender.forward(inner.stream_chat(&prompt).await).await
+        })
+    }
+
+    /// Streams a structured chat completion request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatCompletionRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `ChatCompletionChunk`s or a `MistralClientError`
+    pub fn stream_chat_with_request(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<StreamIter<ChatCompletionChunk, MistralClientError>, MistralClientError> {
+        let inner = self.inner.clone();
+        block_on_stream(move |sender| async move {
+            sender
+                .forward(inner.stream_chat_with_request(request).await)
+                .await
+        })
+    }
+
+    /// Creates embeddings for the given texts
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The embedding model (e.g., "mistral-embed")
+    /// * `inputs` - The texts to embed
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `EmbeddingResponse` or a `MistralClientError`
+    pub fn embeddings(
+        &self,
+        model: &str,
+        inputs: &[&str],
+    ) -> Result<EmbeddingResponse, MistralClientError> {
+        let inner = self.inner.clone();
+        let model = model.to_string();
+        let inputs: Vec<String> = inputs.iter().map(|item| item.to_string()).collect();
+        block_on(async move {
+            let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+            inner.embeddings(&model, &inputs).await
+        })
+    }
+}
@@ -0,0 +1,219 @@
+pub mod bedrock;
+pub mod cohere;
+pub mod gemini;
+pub mod huggingface;
+pub mod llamacpp;
+#[cfg(feature = "local")]
+pub mod local;
+pub mod mistral;
+pub mod ollama;
+
+pub use bedrock::BedrockClient;
+pub use cohere::CohereClient;
+pub use gemini::GeminiClient;
+pub use huggingface::{TeiClient, TgiClient};
+pub use llamacpp::LlamaCppClient;
+#[cfg(feature = "local")]
+pub use local::LocalClient;
+pub use mistral::MistralClient;
+pub use ollama::OllamaClient;
+
+use futures_util::{Stream, StreamExt};
+use std::future::Future;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::OnceLock;
+use tokio::runtime::{Builder, Runtime};
+
+/// The runtime shared by every blocking client, started on first use
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("ai-rs-blocking")
+            .enable_all()
+            .build()
+            .expect("failed to start the ai_rs blocking runtime")
+    })
+}
+
+/// Runs a future on the shared runtime and waits for its output
+///
+/// The future runs on a runtime thread, so this also works from inside an async
+/// context, although it blocks the calling thread.
+pub(crate) fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    runtime().spawn(async move {
+        let _ = tx.send(future.await);
+    });
+    rx.recv()
+        .expect("an ai_rs blocking call panicked on the runtime")
+}
+
+/// Starts a stream on the shared runtime and returns an iterator over its items
+///
+/// `start` receives a `StreamSender` and must call `forward` with the result of
+/// the async streaming call.
+pub(crate) fn block_on_stream<T, E, F, Fut>(start: F) -> Result<StreamIter<T, E>, E>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+    F: FnOnce(StreamSender<T, E>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (started_tx, started_rx) = mpsc::channel();
+    let (items_tx, items_rx) = mpsc::channel();
+    runtime().spawn(start(StreamSender {
+        started: started_tx,
+        items: items_tx,
+    }));
+    started_rx
+        .recv()
+        .expect("an ai_rs blocking stream panicked on the runtime")?;
+    Ok(StreamIter { items: items_rx })
+}
+
+/// Forwards an async stream to a `StreamIter`
+pub(crate) struct StreamSender<T, E> {
+    started: Sender<Result<(), E>>,
+    items: Sender<Result<T, E>>,
+}
+
+impl<T, E> StreamSender<T, E> {
+    /// Reports whether the stream started, then forwards its items until it ends
+    /// or the iterator is dropped
+    pub async fn forward<S>(self, started: Result<S, E>)
+    where
+        S: Stream<Item = Result<T, E>>,
+    {
+        let stream = match started {
+            Ok(stream) => stream,
+            Err(err) => {
+                let _ = self.started.send(Err(err));
+                return;
+            }
+        };
+        if self.started.send(Ok(())).is_err() {
+            return;
+        }
+        let mut stream = std::pin::pin!(stream);
+        while let Some(item) = stream.next().await {
+            if self.items.send(item).is_err() {
+                // Iterator dropped, stop reading
+                return;
+            }
+        }
+    }
+}
+
+/// Iterator over the items of a streaming call
+///
+/// Each call to `next` blocks until the next item arrives. Dropping the iterator
+/// stops the stream.
+#[derive(Debug)]
+pub struct StreamIter<T, E> {
+    items: Receiver<Result<T, E>>,
+}
+
+impl<T, E> Iterator for StreamIter<T, E> {
+    type Item = Result<T, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.items.recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    fn numbers(count: usize) -> Result<StreamIter<usize, String>, String> {
+        block_on_stream(move |sender| async move {
+            sender
+                .forward(Ok::<_, String>(stream::iter((0..count).map(Ok))))
+                .await
+        })
+    }
+
+    #[test]
+    fn block_on_returns_the_output() {
+        assert_eq!(block_on(async { 1 + 1 }), 2);
+    }
+
+    #[tokio::test]
+    async fn block_on_works_inside_a_runtime() {
+        let output = block_on(async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            "done"
+        });
+        assert_eq!(output, "done");
+    }
+
+    #[test]
+    fn stream_iter_yields_every_item() {
+        let items: Vec<usize> = numbers(3).unwrap().map(|item| item.unwrap()).collect();
+        assert_eq!(items, [0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn stream_iter_works_inside_a_runtime() {
+        assert_eq!(numbers(5).unwrap().count(), 5);
+    }
+
+    #[test]
+    fn stream_start_error_is_returned() {
+        let result: Result<StreamIter<usize, String>, String> =
+            block_on_stream(move |sender| async move {
+                sender
+                    .forward(Err::<stream::Empty<Result<usize, String>>, _>(
+                        "refused".to_string(),
+                    ))
+                    .await
+            });
+        match result {
+            Err(err) => assert_eq!(err, "refused"),
+            Ok(_) => panic!("expected the start error"),
+        }
+    }
+
+    #[test]
+    fn dropping_the_iterator_stops_the_stream() {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (counter, flag) = (sent.clone(), stopped.clone());
+        let mut items = block_on_stream(move |sender| async move {
+            let endless = stream::repeat(()).then(move |_| {
+                let counter = counter.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    Ok::<_, String>(counter.fetch_add(1, Ordering::SeqCst))
+                }
+            });
+            sender.forward(Ok::<_, String>(endless)).await;
+            flag.store(true, Ordering::SeqCst);
+        })
+        .unwrap();
+        assert_eq!(items.next().unwrap().unwrap(), 0);
+        drop(items);
+
+        let started = Instant::now();
+        while !stopped.load(Ordering::SeqCst) {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "stream kept running"
+            );
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let count = sent.load(Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(sent.load(Ordering::SeqCst), count);
+    }
+}
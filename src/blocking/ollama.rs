@@ -0,0 +1,244 @@
+use crate::blocking::{block_on, block_on_stream, StreamIter};
+use crate::ollama::client::{OllamaClient as AsyncOllamaClient, OllamaClientError};
+use crate::ollama::types::{
+    ChatRequest, ChatResponse, GenerateRequest, GenerateResponse, ListModelsResponse,
+    RunningModelsResponse,
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Blocking client for the Ollama API
+///
+/// Configure an async `OllamaClient`, e.g. with its builder, and convert it with `From`.
+#[derive(Debug, Clone)]
+pub struct OllamaClient {
+    inner: Arc<AsyncOllamaClient>,
+}
+
+impl From<AsyncOllamaClient> for OllamaClient {
+    fn from(client: AsyncOllamaClient) -> Self {
+        OllamaClient {
+            inner: Arc::new(client),
+        }
+    }
+}
+
+impl OllamaClient {
+    /// Creates a new instance of `OllamaClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The base URL of the Ollama API
+    /// * `api_key` - The API key for authentication
+    ///
+    /// # Returns
+    ///
+    /// A new `OllamaClient` instance
+    pub fn new(base_url: &str, api_key: &str) -> Self {
+        AsyncOllamaClient::new(base_url, api_key).into()
+    }
+
+    /// Checks if the Ollama service is active
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the service is active, or an `OllamaClientError` otherwise
+    pub fn active(&self) -> Result<bool, OllamaClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.active().await })
+    }
+
+    /// Generates a completion based on the provided request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the model and prompt
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GenerateResponse` or an `OllamaClientError`
+    pub fn generate_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<GenerateResponse, OllamaClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.generate_completion(request).await })
+    }
+
+    /// Streams a completion based on the provided request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the model and prompt
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `GenerateResponse` chunks or an `OllamaClientError`
+    pub fn stream_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<StreamIter<GenerateResponse, OllamaClientError>, OllamaClientError> {
+        let inner = self.inner.clone();
+        block_on_stream(move |sender| async move {
+            sender.forward(inner.stream_completion(request).await).await
+        })
+    }
+
+    /// Sends a chat request, with tools if any, and waits for the whole reply
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatRequest` containing the model, messages and tools
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ChatResponse` or an `OllamaClientError`
+    pub fn chat(&self, request: ChatRequest) -> Result<ChatResponse, OllamaClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.chat(request).await })
+    }
+
+    /// Streams a chat reply chunk by chunk
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatRequest` containing the model, messages and tools
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an iterator over `ChatResponse` chunks or an `OllamaClientError`
+    pub fn stream_chat(
+        &self,
+        request: ChatRequest,
+    ) -> Result<StreamIter<ChatResponse, OllamaClientError>, OllamaClientError> {
+        let inner = self.inner.clone();
+        block_on_stream(move |sender| async move {
+            sender.forward(inner.stream_chat(request).await).await
+        })
+    }
+
+    /// Lists the models available on the Ollama server
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ListModelsResponse` or an `OllamaClientError`
+    pub fn list_models(&self) -> Result<ListModelsResponse, OllamaClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.list_models().await })
+    }
+
+    /// Lists the models loaded in memory
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RunningModelsResponse` or an `OllamaClientError`
+    pub fn running_models(&self) -> Result<RunningModelsResponse, OllamaClientError> {
+        let inner = self.inner.clone();
+        block_on(async move { inner.running_models().await })
+    }
+
+    /// Shows the details of a model
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The name of the model
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the model information as JSON or an `OllamaClientError`
+    pub fn show_model_info(&self, model: &str) -> Result<Value, OllamaClientError> {
+        let inner = self.inner.clone();
+        let model = model.to_string();
+        block_on(async move { inner.show_model_info(&model).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHUNKS: &str = "{\"model\": \"llama3\", \"created_at\": \"2024-01-01T00:00:00Z\", \"response\": \"Hel\", \"done\": false}\n\
+        {\"model\": \"llama3\", \"created_at\": \"2024-01-01T00:00:00Z\", \"response\": \"lo\", \"done\": true}\n";
+
+    fn request() -> GenerateRequest {
+        GenerateRequest {
+            model: "llama3".to_string(),
+            prompt: "Hello".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn generates_and_streams_completions() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/generate")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"stream": false}"#.to_string(),
+            ))
+            .with_body(
+                r#"{"model": "llama3", "created_at": "2024-01-01T00:00:00Z", "response": "Hello", "done": true}"#,
+            )
+            .create();
+        server
+            .mock("POST", "/api/generate")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"stream": true}"#.to_string(),
+            ))
+            .with_body(CHUNKS)
+            .create();
+        let client = OllamaClient::new(&server.url(), "");
+
+        let single = GenerateRequest {
+            stream: Some(false),
+            ..request()
+        };
+        assert_eq!(
+            client.generate_completion(single).unwrap().response,
+            "Hello"
+        );
+        let chunks: Vec<String> = client
+            .stream_completion(request())
+            .unwrap()
+            .map(|chunk| chunk.unwrap().response)
+            .collect();
+        assert_eq!(chunks, ["Hel", "lo"]);
+    }
+
+    #[test]
+    fn returns_status_errors() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/generate")
+            .with_status(404)
+            .with_body("model not found")
+            .create();
+        let client = OllamaClient::new(&server.url(), "");
+
+        match client.generate_completion(request()) {
+            Err(OllamaClientError::StatusError(status, body)) => {
+                assert_eq!(status.as_u16(), 404);
+                assert_eq!(body, "model not found");
+            }
+            other => panic!("expected StatusError, got {:?}", other),
+        }
+        match client.stream_completion(request()) {
+            Err(OllamaClientError::StatusError(status, _)) => assert_eq!(status.as_u16(), 404),
+            other => panic!("expected StatusError, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn works_inside_a_runtime() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/generate")
+            .with_body(CHUNKS)
+            .create_async()
+            .await;
+        let client = OllamaClient::new(&server.url(), "");
+
+        let chunks = client.stream_completion(request()).unwrap().count();
+        assert_eq!(chunks, 2);
+    }
+}
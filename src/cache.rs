@@ -0,0 +1,873 @@
+use crate::middleware::{map_body, HttpError, Middleware, Next, Request};
+use crate::utils::canonical_json;
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
+use futures_util::{Stream, StreamExt};
+use reqwest::header::{HeaderName, HeaderValue, CONNECTION, TRANSFER_ENCODING};
+use reqwest::{Body, Response, ResponseBuilderExt, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+tokio::task_local! {
+    static MODE: CacheMode;
+}
+
+/// How a model call uses the response cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheMode {
+    /// Serve from the cache when possible and store new responses
+    #[default]
+    Use,
+    /// Neither read nor store
+    Bypass,
+    /// Skip the cached response but store the new one
+    Refresh,
+}
+
+impl CacheMode {
+    /// The mode set by the enclosing `with_mode`, or `CacheMode::Use`
+    pub fn current() -> CacheMode {
+        MODE.try_with(|mode| *mode).unwrap_or_default()
+    }
+}
+
+/// Runs `future` with the given cache mode for every model call it makes
+///
+/// # Arguments
+///
+/// * `mode` - The cache mode, e.g. `CacheMode::Bypass` to always call the provider
+/// * `future` - The calls to run
+///
+/// # Returns
+///
+/// The output of `future`
+pub async fn with_mode<F: Future>(mode: CacheMode, future: F) -> F::Output {
+    MODE.scope(mode, future).await
+}
+
+/// A stored response
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    /// The HTTP status
+    pub status: u16,
+    /// The response headers
+    pub headers: Vec<(String, String)>,
+    /// The full response body; for streams, every event in order
+    pub body: Bytes,
+    /// When the response was stored
+    pub stored_at: SystemTime,
+}
+
+impl CachedResponse {
+    fn size(&self) -> usize {
+        self.body.len()
+            + self
+                .headers
+                .iter()
+                .map(|(name, value)| name.len() + value.len())
+                .sum::<usize>()
+    }
+}
+
+/// Storage for cached responses
+pub trait CacheBackend: Send + Sync + 'static {
+    /// Returns the entry stored under `key`, if any
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+    /// Stores an entry, replacing any previous one
+    fn put(&self, key: &str, response: CachedResponse);
+    /// Removes the entry stored under `key`
+    fn remove(&self, key: &str);
+    /// Removes every entry
+    fn clear(&self);
+    /// Whether calls do blocking I/O and must run on the blocking thread pool
+    fn is_blocking(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    entries: HashMap<String, (CachedResponse, u64)>,
+    // Last use of each key, oldest first
+    order: BTreeMap<u64, String>,
+    tick: u64,
+    bytes: usize,
+}
+
+impl Lru {
+    fn touch(&mut self, key: &str) {
+        if let Some((_, used)) = self.entries.get_mut(key) {
+            self.order.remove(used);
+            self.tick += 1;
+            *used = self.tick;
+            self.order.insert(self.tick, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((response, used)) = self.entries.remove(key) {
+            self.order.remove(&used);
+            self.bytes -= response.size();
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some((_, key)) = self.order.pop_first() {
+            if let Some((response, _)) = self.entries.remove(&key) {
+                self.bytes -= response.size();
+            }
+        }
+    }
+}
+
+/// In-memory cache that evicts the least recently used entries
+#[derive(Debug)]
+pub struct MemoryCache {
+    lru: Mutex<Lru>,
+    max_entries: usize,
+    max_bytes: Option<usize>,
+}
+
+impl MemoryCache {
+    /// Creates a cache holding at most `max_entries` responses
+    pub fn new(max_entries: usize) -> Self {
+        MemoryCache {
+            lru: Mutex::new(Lru::default()),
+            max_entries,
+            max_bytes: None,
+        }
+    }
+
+    /// Limits the total size of the stored bodies and headers
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Number of stored responses
+    pub fn len(&self) -> usize {
+        self.lru().entries.len()
+    }
+
+    /// Returns `true` if nothing is stored
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lru(&self) -> MutexGuard<'_, Lru> {
+        self.lru
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl CacheBackend for MemoryCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut lru = self.lru();
+        lru.touch(key);
+        lru.entries.get(key).map(|(response, _)| response.clone())
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        let size = response.size();
+        if self.max_entries == 0 || self.max_bytes.is_some_and(|max| size > max) {
+            return;
+        }
+        let mut lru = self.lru();
+        lru.remove(key);
+        while lru.entries.len() >= self.max_entries
+            || self
+                .max_bytes
+                .is_some_and(|max| lru.bytes + size > max && !lru.entries.is_empty())
+        {
+            lru.evict_oldest();
+        }
+        lru.tick += 1;
+        let tick = lru.tick;
+        lru.order.insert(tick, key.to_string());
+        lru.entries.insert(key.to_string(), (response, tick));
+        lru.bytes += size;
+    }
+
+    fn remove(&self, key: &str) {
+        self.lru().remove(key);
+    }
+
+    fn clear(&self) {
+        *self.lru() = Lru::default();
+    }
+}
+
+/// Metadata line written before the body in a cache file
+#[derive(Serialize, Deserialize)]
+struct FileHeader {
+    status: u16,
+    headers: Vec<(String, String)>,
+    stored_at: u64,
+}
+
+/// Persistent cache storing one file per response in a directory
+///
+/// When the directory grows past `max_bytes`, the least recently written files are removed.
+#[derive(Debug)]
+pub struct FileCache {
+    dir: PathBuf,
+    max_bytes: Option<u64>,
+}
+
+impl FileCache {
+    /// Opens a cache in `dir`, creating the directory if needed
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory holding the cache files
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `FileCache`, or an `io::Error` if the directory cannot be created
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        Ok(FileCache {
+            dir,
+            max_bytes: None,
+        })
+    }
+
+    /// Limits the total size of the cache files
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.cache", key))
+    }
+
+    fn read(&self, key: &str) -> io::Result<CachedResponse> {
+        let content = std::fs::read(self.path(key))?;
+        let newline = content
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing header"))?;
+        let header: FileHeader = serde_json::from_slice(&content[..newline])?;
+        Ok(CachedResponse {
+            status: header.status,
+            headers: header.headers,
+            body: Bytes::copy_from_slice(&content[newline + 1..]),
+            stored_at: UNIX_EPOCH + Duration::from_secs(header.stored_at),
+        })
+    }
+
+    fn write(&self, key: &str, response: &CachedResponse) -> io::Result<()> {
+        let header = FileHeader {
+            status: response.status,
+            headers: response.headers.clone(),
+            stored_at: response
+                .stored_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+        // Write to a temporary file first so readers never see a partial entry. The name is
+        // unique per write, so concurrent writers of the same key never share a file.
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let temporary = self.dir.join(format!(
+            "{}.{}-{}.tmp",
+            key,
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = std::fs::File::create(&temporary).and_then(|mut file| {
+            serde_json::to_writer(&mut file, &header)?;
+            file.write_all(b"\n")?;
+            file.write_all(&response.body)?;
+            drop(file);
+            std::fs::rename(&temporary, self.path(key))
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temporary);
+        }
+        result
+    }
+
+    /// Removes the oldest files until the directory fits in `max_bytes`
+    fn enforce_limit(&self) -> io::Result<()> {
+        let max_bytes = match self.max_bytes {
+            Some(max_bytes) => max_bytes,
+            None => return Ok(()),
+        };
+        let mut files = Vec::new();
+        let mut total = 0;
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "cache")
+            {
+                let metadata = entry.metadata()?;
+                total += metadata.len();
+                files.push((metadata.modified()?, metadata.len(), path));
+            }
+        }
+        files.sort();
+        for (_, len, path) in files {
+            if total <= max_bytes {
+                break;
+            }
+            std::fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+impl CacheBackend for FileCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        match self.read(key) {
+            Ok(response) => Some(response),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                warn!("Failed to read cache entry {}: {}", key, err);
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        if let Err(err) = self
+            .write(key, &response)
+            .and_then(|_| self.enforce_limit())
+        {
+            warn!("Failed to write cache entry {}: {}", key, err);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = std::fs::remove_file(self.path(key));
+    }
+
+    fn is_blocking(&self) -> bool {
+        true
+    }
+
+    fn clear(&self) {
+        match std::fs::read_dir(&self.dir) {
+            Ok(entries) => {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if path
+                        .extension()
+                        .is_some_and(|extension| extension == "cache")
+                    {
+                        let _ = std::fs::remove_file(path);
+                    }
+                }
+            }
+            Err(err) => warn!("Failed to clear cache {}: {}", self.dir.display(), err),
+        }
+    }
+}
+
+/// Cache of full model responses, keyed by the normalized request
+///
+/// The key hashes the method, the URL and the JSON body with its object keys
+/// sorted, so it covers the model, contents, generation config and tools but not
+/// the credentials. Streamed responses are stored once the stream completes and
+/// replayed as streams. Clones share the same backend.
+#[derive(Clone)]
+pub struct ResponseCache {
+    backend: Arc<dyn CacheBackend>,
+    ttl: Option<Duration>,
+    max_entry_bytes: Option<usize>,
+}
+
+impl fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("ttl", &self.ttl)
+            .field("max_entry_bytes", &self.max_entry_bytes)
+            .finish()
+    }
+}
+
+impl ResponseCache {
+    /// Creates a cache on top of a backend
+    pub fn new(backend: impl CacheBackend) -> Self {
+        ResponseCache {
+            backend: Arc::new(backend),
+            ttl: None,
+            max_entry_bytes: None,
+        }
+    }
+
+    /// Creates an in-memory LRU cache holding at most `max_entries` responses
+    pub fn memory(max_entries: usize) -> Self {
+        Self::new(MemoryCache::new(max_entries))
+    }
+
+    /// Creates a persistent cache in `dir`
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ResponseCache`, or an `io::Error` if the directory cannot be created
+    pub fn file(dir: impl AsRef<Path>) -> io::Result<Self> {
+        FileCache::new(dir).map(Self::new)
+    }
+
+    /// Sets how long entries stay valid; by default they never expire
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Skips storing responses whose body is larger than `max_bytes`
+    pub fn max_entry_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_entry_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Removes every entry from the backend
+    pub fn clear(&self) {
+        self.backend.clear();
+    }
+
+    /// Runs a backend call, on the blocking thread pool if the backend does I/O
+    ///
+    /// Returns `None` if the blocking task failed.
+    async fn run<T: Send + 'static>(
+        &self,
+        call: impl FnOnce(&dyn CacheBackend) -> T + Send + 'static,
+    ) -> Option<T> {
+        if !self.backend.is_blocking() {
+            return Some(call(self.backend.as_ref()));
+        }
+        let backend = self.backend.clone();
+        match tokio::task::spawn_blocking(move || call(backend.as_ref())).await {
+            Ok(output) => Some(output),
+            Err(err) => {
+                warn!("Cache task failed: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Returns the stored entry for `key` unless it has expired
+    async fn lookup(&self, key: &str) -> Option<CachedResponse> {
+        let owned_key = key.to_string();
+        let response = self
+            .run(move |backend| backend.get(&owned_key))
+            .await
+            .flatten()?;
+        let age = response.stored_at.elapsed().unwrap_or_default();
+        if self.ttl.is_some_and(|ttl| age > ttl) {
+            debug!("Cache entry {} expired", key);
+            let owned_key = key.to_string();
+            self.run(move |backend| backend.remove(&owned_key)).await;
+            return None;
+        }
+        Some(response)
+    }
+
+    /// Stores an entry in the backend
+    async fn store(&self, key: String, response: CachedResponse) {
+        let stored_key = key.clone();
+        if self
+            .run(move |backend| backend.put(&stored_key, response))
+            .await
+            .is_some()
+        {
+            debug!("Stored response in cache entry {}", key);
+        }
+    }
+
+    /// Wraps the body so the response is stored once it has been read to the end
+    fn store_on_completion(&self, key: String, response: Response) -> Result<Response, HttpError> {
+        let status = response.status().as_u16();
+        let headers: Vec<(String, String)> = response
+            .headers()
+            .iter()
+            .filter(|(name, _)| **name != TRANSFER_ENCODING && **name != CONNECTION)
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let cache = self.clone();
+        map_body(response, move |body| Recording {
+            inner: body,
+            buffer: Some(Vec::new()),
+            storing: None,
+            cache,
+            key,
+            status,
+            headers,
+        })
+    }
+
+    /// Builds a response that replays a stored entry
+    fn replay(&self, url: reqwest::Url, cached: CachedResponse) -> Option<Response> {
+        let mut builder = http::Response::builder()
+            .status(StatusCode::from_u16(cached.status).ok()?)
+            .url(url);
+        for (name, value) in &cached.headers {
+            builder = builder.header(
+                HeaderName::from_bytes(name.as_bytes()).ok()?,
+                HeaderValue::from_str(value).ok()?,
+            );
+        }
+        builder
+            .body(Body::from(cached.body))
+            .ok()
+            .map(Response::from)
+    }
+}
+
+impl Middleware for ResponseCache {
+    fn handle<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response, HttpError>> {
+        Box::pin(async move {
+            // Only model calls carry a mode; other requests are never cached
+            let mode = match request.extensions().get::<CacheMode>() {
+                Some(CacheMode::Bypass) | None => return next.run(request).await,
+                Some(mode) => *mode,
+            };
+            let key = match cache_key(&request) {
+                Some(key) => key,
+                None => return next.run(request).await,
+            };
+            if mode == CacheMode::Use {
+                if let Some(cached) = self.lookup(&key).await {
+                    if let Some(response) = self.replay(request.url().clone(), cached) {
+                        debug!("Cache hit for {} {}", request.method(), request.url());
+                        return Ok(response);
+                    }
+                }
+            }
+            let response = next.run(request).await?;
+            if response.status().is_success() {
+                self.store_on_completion(key, response)
+            } else {
+                Ok(response)
+            }
+        })
+    }
+}
+
+/// Passes the body through and stores it in the cache when it ends without error
+///
+/// The stream ends only once the entry is stored, so a following lookup finds it.
+struct Recording {
+    inner: BoxStream<'static, reqwest::Result<Bytes>>,
+    buffer: Option<Vec<u8>>,
+    storing: Option<BoxFuture<'static, ()>>,
+    cache: ResponseCache,
+    key: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+impl Stream for Recording {
+    type Item = reqwest::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(storing) = &mut this.storing {
+            ready!(storing.as_mut().poll(cx));
+            this.storing = None;
+            return Poll::Ready(None);
+        }
+        let poll = this.inner.poll_next_unpin(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(buffer) = &mut this.buffer {
+                    buffer.extend_from_slice(chunk);
+                    if this
+                        .cache
+                        .max_entry_bytes
+                        .is_some_and(|max| buffer.len() > max)
+                    {
+                        debug!("Response too large to cache");
+                        this.buffer = None;
+                    }
+                }
+            }
+            // An interrupted body is never stored
+            Poll::Ready(Some(Err(_))) => this.buffer = None,
+            Poll::Ready(None) => {
+                if let Some(body) = this.buffer.take() {
+                    let cache = this.cache.clone();
+                    let key = std::mem::take(&mut this.key);
+                    let response = CachedResponse {
+                        status: this.status,
+                        headers: std::mem::take(&mut this.headers),
+                        body: Bytes::from(body),
+                        stored_at: SystemTime::now(),
+                    };
+                    let mut storing: BoxFuture<'static, ()> =
+                        Box::pin(async move { cache.store(key, response).await });
+                    if storing.as_mut().poll(cx).is_pending() {
+                        this.storing = Some(storing);
+                        return Poll::Pending;
+                    }
+                }
+            }
+            Poll::Pending => {}
+        }
+        poll
+    }
+}
+
+/// Hashes the method, URL and normalized JSON body of a request
+fn cache_key(request: &Request) -> Option<String> {
+    let body: Value = serde_json::from_slice(request.body_bytes()?).ok()?;
+    let mut hasher = Sha256::new();
+    hasher.update(request.method().as_str());
+    hasher.update(b" ");
+    hasher.update(request.url().as_str());
+    hasher.update(b"\n");
+    hasher.update(canonical_json(&body));
+    Some(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::Stack;
+    use crate::retry::RetryPolicy;
+
+    fn response(body: &str) -> CachedResponse {
+        CachedResponse {
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Bytes::from(body.to_string()),
+            stored_at: SystemTime::now(),
+        }
+    }
+
+    /// A fresh directory under the system temp dir, removed on drop
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("ai_rs-cache-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn memory_cache_evicts_least_recently_used() {
+        let cache = MemoryCache::new(2);
+        cache.put("a", response("1"));
+        cache.put("b", response("2"));
+        // Reading "a" makes "b" the oldest entry
+        assert!(cache.get("a").is_some());
+        cache.put("c", response("3"));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+
+        // Replacing an entry does not evict another one
+        cache.put("c", response("4"));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("c").unwrap().body, "4");
+
+        cache.remove("a");
+        assert_eq!(cache.len(), 1);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn memory_cache_respects_max_bytes() {
+        let entry_size = response("0123456789").size();
+        let cache = MemoryCache::new(10).max_bytes(entry_size * 2);
+        cache.put("a", response("0123456789"));
+        cache.put("b", response("0123456789"));
+        cache.put("c", response("0123456789"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("a").is_none());
+
+        // Entries larger than the whole cache are not stored
+        cache.put("big", response(&"x".repeat(entry_size * 2)));
+        assert!(cache.get("big").is_none());
+        assert_eq!(cache.len(), 2);
+
+        let disabled = MemoryCache::new(0);
+        disabled.put("a", response("1"));
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn file_cache_round_trips_entries() {
+        let dir = TempDir::new("round-trip");
+        let cache = FileCache::new(&dir.0).unwrap();
+        assert!(cache.get("missing").is_none());
+
+        let stored = response("{\"text\":\"hi\"}\nsecond line");
+        cache.put("key", stored.clone());
+        let read = cache.get("key").unwrap();
+        assert_eq!(read.status, stored.status);
+        assert_eq!(read.headers, stored.headers);
+        assert_eq!(read.body, stored.body);
+
+        cache.remove("key");
+        assert!(cache.get("key").is_none());
+
+        cache.put("other", stored);
+        cache.clear();
+        assert!(cache.get("other").is_none());
+    }
+
+    #[test]
+    fn file_cache_handles_concurrent_writes_of_a_key() {
+        let dir = TempDir::new("concurrent");
+        let cache = Arc::new(FileCache::new(&dir.0).unwrap());
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let cache = cache.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        cache.write("key", &response(&i.to_string())).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let body = cache.get("key").unwrap().body;
+        assert!((0..8).any(|i| body == i.to_string()));
+        // Every temporary file was renamed into place
+        let files: Vec<_> = std::fs::read_dir(&dir.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["key.cache"]);
+    }
+
+    #[test]
+    fn file_cache_removes_oldest_files_past_the_limit() {
+        let dir = TempDir::new("limit");
+        let cache = FileCache::new(&dir.0).unwrap();
+        cache.put("a", response("0123456789"));
+        let file_size = std::fs::metadata(cache.path("a")).unwrap().len();
+        let cache = cache.max_bytes(file_size * 2);
+
+        let age = |key: &str, secs: u64| {
+            std::fs::File::options()
+                .write(true)
+                .open(cache.path(key))
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(secs))
+                .unwrap();
+        };
+        age("a", 30);
+        cache.put("b", response("0123456789"));
+        age("b", 20);
+        cache.put("c", response("0123456789"));
+
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("b").is_some());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[tokio::test]
+    async fn response_cache_expires_entries() {
+        let cache = ResponseCache::memory(10).ttl(Duration::from_secs(60));
+        let mut old = response("old");
+        old.stored_at = SystemTime::now() - Duration::from_secs(120);
+        cache.backend.put("old", old);
+        cache.backend.put("new", response("new"));
+
+        assert!(cache.lookup("old").await.is_none());
+        assert!(cache.backend.get("old").is_none());
+        assert!(cache.lookup("new").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn middleware_serves_repeated_calls_from_the_cache() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/generate")
+            .with_body("{\"text\":\"hi\"}")
+            .expect(2)
+            .create_async()
+            .await;
+
+        let dir = TempDir::new("middleware");
+        let stack = Stack {
+            cache: Some(ResponseCache::file(&dir.0).unwrap()),
+            retry_policy: RetryPolicy::none(),
+            ..Default::default()
+        };
+        let client = reqwest::Client::new();
+        let call = |body: &'static str, mode: CacheMode| {
+            let mut request = Request::new(
+                client
+                    .post(format!("{}/generate", server.url()))
+                    .body(body)
+                    .build()
+                    .unwrap(),
+            );
+            request.extensions_mut().insert(mode);
+            let stack = &stack;
+            let client = &client;
+            async move {
+                stack
+                    .send(client, request)
+                    .await
+                    .unwrap()
+                    .text()
+                    .await
+                    .unwrap()
+            }
+        };
+
+        assert_eq!(
+            call(r#"{"a":1,"b":2}"#, CacheMode::Use).await,
+            "{\"text\":\"hi\"}"
+        );
+        // Key order does not change the cache key
+        assert_eq!(
+            call(r#"{"b":2,"a":1}"#, CacheMode::Use).await,
+            "{\"text\":\"hi\"}"
+        );
+        call(r#"{"a":1,"b":2}"#, CacheMode::Bypass).await;
+        call(r#"{"a":1,"b":2}"#, CacheMode::Use).await;
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn with_mode_sets_the_current_mode() {
+        assert_eq!(CacheMode::current(), CacheMode::Use);
+        let mode = with_mode(CacheMode::Refresh, async { CacheMode::current() }).await;
+        assert_eq!(mode, CacheMode::Refresh);
+    }
+}
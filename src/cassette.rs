@@ -0,0 +1,820 @@
+use crate::middleware::{map_body, HttpError, Middleware, Next, Request};
+use crate::utils::canonical_json;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, BoxStream};
+use futures_util::{Stream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Body, Response, ResponseBuilderExt, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, warn};
+
+/// Value written in place of redacted credentials
+const REDACTED: &str = "REDACTED";
+
+/// Headers redacted by default
+const REDACTED_HEADERS: [&str; 8] = [
+    "authorization",
+    "proxy-authorization",
+    "x-goog-api-key",
+    "x-api-key",
+    "api-key",
+    "x-amz-security-token",
+    "cookie",
+    "set-cookie",
+];
+
+/// Query parameters redacted by default, including those of SigV4 presigned URLs
+const REDACTED_QUERY_PARAMS: [&str; 6] = [
+    "key",
+    "api_key",
+    "access_token",
+    "X-Amz-Credential",
+    "X-Amz-Signature",
+    "X-Amz-Security-Token",
+];
+
+/// Response headers that describe the original transfer rather than the content
+const SKIPPED_RESPONSE_HEADERS: [&str; 3] = ["connection", "content-length", "transfer-encoding"];
+
+/// Error while loading a cassette
+#[derive(Debug)]
+pub enum CassetteError {
+    /// The cassette file could not be read
+    Io(io::Error),
+    /// The cassette file is not a valid cassette
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for CassetteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CassetteError::Io(err) => write!(f, "IO error: {}", err),
+            CassetteError::Parse(err) => write!(f, "Parse error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for CassetteError {}
+
+impl From<io::Error> for CassetteError {
+    fn from(err: io::Error) -> Self {
+        CassetteError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for CassetteError {
+    fn from(err: serde_json::Error) -> Self {
+        CassetteError::Parse(err)
+    }
+}
+
+/// A part of the request compared when looking for a recorded interaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchRule {
+    /// The HTTP method
+    Method,
+    /// The full URL, with redacted query parameters
+    Url,
+    /// The URL path only
+    Path,
+    /// The query parameters, in any order
+    Query,
+    /// The body, compared as JSON with object keys in any order
+    Body,
+    /// The value of a request header
+    Header(String),
+}
+
+/// Contents of a cassette file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// JSON bodies are kept as JSON, other bodies as a string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    chunks: Vec<RecordedChunk>,
+}
+
+/// A body chunk and the time since the previous one, or since the response headers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedChunk {
+    delay_ms: u64,
+    #[serde(flatten)]
+    body: ChunkBody,
+}
+
+/// Chunk bytes, kept as text when they are valid UTF-8
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ChunkBody {
+    Data(String),
+    Base64(String),
+}
+
+impl ChunkBody {
+    fn new(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => ChunkBody::Data(text),
+            Err(err) => ChunkBody::Base64(BASE64.encode(err.as_bytes())),
+        }
+    }
+
+    fn into_bytes(self) -> Result<Bytes, base64::DecodeError> {
+        match self {
+            ChunkBody::Data(text) => Ok(Bytes::from(text)),
+            ChunkBody::Base64(encoded) => BASE64.decode(encoded).map(Bytes::from),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Mode {
+    Record(Arc<Recorder>),
+    Replay {
+        interactions: Vec<Interaction>,
+        used: Mutex<Vec<bool>>,
+    },
+}
+
+/// Interactions captured in record mode, written to the file as each one completes
+#[derive(Debug)]
+struct Recorder {
+    path: PathBuf,
+    file: Mutex<CassetteFile>,
+}
+
+impl Recorder {
+    /// Adds an interaction and rewrites the file on the blocking thread pool
+    async fn push(self: Arc<Self>, interaction: Interaction) {
+        let recorder = self.clone();
+        let written = tokio::task::spawn_blocking(move || {
+            // The lock is held while writing so the file always ends with the latest list
+            let mut file = lock(&recorder.file);
+            file.interactions.push(interaction);
+            recorder.write(&file)
+        })
+        .await;
+        match written {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => error!("Failed to write cassette {}: {}", self.path.display(), err),
+            Err(err) => warn!("Cassette task failed: {}", err),
+        }
+    }
+
+    fn write(&self, file: &CassetteFile) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(file)?;
+        std::fs::write(&self.path, json)
+    }
+}
+
+/// Record/replay transport for tests that run without a live model
+///
+/// In record mode, every HTTP interaction is passed through and written to a JSON
+/// fixture file, with streamed chunks and their timing and with API keys redacted.
+/// In replay mode, responses are served from the file and a request without a
+/// recorded match fails with `HttpError::Middleware`. Add it with `layer`, after any
+/// middleware that changes the request.
+#[derive(Debug)]
+pub struct Cassette {
+    mode: Mode,
+    path: PathBuf,
+    match_rules: Vec<MatchRule>,
+    redacted_headers: Vec<String>,
+    redacted_query_params: Vec<String>,
+    replay_timing: bool,
+    allow_repeats: bool,
+}
+
+impl Cassette {
+    fn with_mode(path: &Path, mode: Mode) -> Self {
+        Cassette {
+            mode,
+            path: path.to_path_buf(),
+            match_rules: vec![MatchRule::Method, MatchRule::Url, MatchRule::Body],
+            redacted_headers: REDACTED_HEADERS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            redacted_query_params: REDACTED_QUERY_PARAMS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            replay_timing: false,
+            allow_repeats: false,
+        }
+    }
+
+    /// Creates a cassette that records real interactions into `path`, replacing its contents
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let recorder = Recorder {
+            path: path.to_path_buf(),
+            file: Mutex::new(CassetteFile::default()),
+        };
+        Self::with_mode(path, Mode::Record(Arc::new(recorder)))
+    }
+
+    /// Loads a cassette that serves the interactions recorded in `path`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The cassette file written in record mode
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Cassette`, or a `CassetteError` if the file cannot be read
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self, CassetteError> {
+        let path = path.as_ref();
+        let file: CassetteFile = serde_json::from_slice(&std::fs::read(path)?)?;
+        debug!(
+            "Loaded {} interactions from cassette {}",
+            file.interactions.len(),
+            path.display()
+        );
+        let used = Mutex::new(vec![false; file.interactions.len()]);
+        Ok(Self::with_mode(
+            path,
+            Mode::Replay {
+                interactions: file.interactions,
+                used,
+            },
+        ))
+    }
+
+    /// Sets the parts of the request that must match a recording (default: method, URL and body)
+    pub fn match_on(mut self, rules: Vec<MatchRule>) -> Self {
+        self.match_rules = rules;
+        self
+    }
+
+    /// Redacts another request or response header
+    pub fn redact_header(mut self, name: &str) -> Self {
+        self.redacted_headers.push(name.to_ascii_lowercase());
+        self
+    }
+
+    /// Redacts another URL query parameter
+    pub fn redact_query_param(mut self, name: &str) -> Self {
+        self.redacted_query_params.push(name.to_string());
+        self
+    }
+
+    /// Replays chunks with their recorded delays instead of all at once
+    pub fn replay_timing(mut self, enabled: bool) -> Self {
+        self.replay_timing = enabled;
+        self
+    }
+
+    /// Lets a recorded interaction answer more than one request
+    ///
+    /// Unused interactions are still preferred, so repeated identical requests are
+    /// served in recording order first.
+    pub fn allow_repeats(mut self, allow: bool) -> Self {
+        self.allow_repeats = allow;
+        self
+    }
+
+    /// Captures the request as it will be written to or compared with the file
+    fn capture(&self, request: &Request) -> RecordedRequest {
+        let body = request.body_bytes().map(|body| {
+            serde_json::from_slice(body)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()))
+        });
+        RecordedRequest {
+            method: request.method().to_string(),
+            url: self.redact_url(request.url()).to_string(),
+            headers: self.capture_headers(request.headers(), &[]),
+            body,
+        }
+    }
+
+    fn capture_headers(&self, headers: &HeaderMap, skipped: &[&str]) -> BTreeMap<String, String> {
+        headers
+            .iter()
+            .filter(|(name, _)| !skipped.contains(&name.as_str()))
+            .map(|(name, value)| {
+                let value = if self
+                    .redacted_headers
+                    .iter()
+                    .any(|redacted| redacted == name.as_str())
+                {
+                    REDACTED.to_string()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).into_owned()
+                };
+                (name.to_string(), value)
+            })
+            .collect()
+    }
+
+    fn redact_url(&self, url: &Url) -> Url {
+        if url.query().is_none() {
+            return url.clone();
+        }
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(name, value)| {
+                if self
+                    .redacted_query_params
+                    .iter()
+                    .any(|redacted| *redacted == name)
+                {
+                    (name.into_owned(), REDACTED.to_string())
+                } else {
+                    (name.into_owned(), value.into_owned())
+                }
+            })
+            .collect();
+        let mut url = url.clone();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+        url
+    }
+
+    fn matches(&self, recorded: &RecordedRequest, request: &RecordedRequest) -> bool {
+        self.match_rules.iter().all(|rule| match rule {
+            MatchRule::Method => recorded.method.eq_ignore_ascii_case(&request.method),
+            MatchRule::Url => recorded.url == request.url,
+            MatchRule::Path => match (Url::parse(&recorded.url), Url::parse(&request.url)) {
+                (Ok(recorded), Ok(request)) => recorded.path() == request.path(),
+                _ => false,
+            },
+            MatchRule::Query => match (Url::parse(&recorded.url), Url::parse(&request.url)) {
+                (Ok(recorded), Ok(request)) => sorted_query(&recorded) == sorted_query(&request),
+                _ => false,
+            },
+            MatchRule::Body => {
+                recorded.body.as_ref().map(canonical_json)
+                    == request.body.as_ref().map(canonical_json)
+            }
+            MatchRule::Header(name) => {
+                let name = name.to_ascii_lowercase();
+                recorded.headers.get(&name) == request.headers.get(&name)
+            }
+        })
+    }
+
+    /// Finds the interaction for a request, marking it as used
+    fn find(
+        &self,
+        interactions: &[Interaction],
+        used: &Mutex<Vec<bool>>,
+        request: &RecordedRequest,
+    ) -> Option<RecordedResponse> {
+        let mut used = lock(used);
+        let candidates = || {
+            interactions
+                .iter()
+                .enumerate()
+                .filter(|(_, interaction)| self.matches(&interaction.request, request))
+        };
+        let unused = candidates().find(|(index, _)| !used[*index]);
+        let (index, interaction) = match unused {
+            Some(found) => found,
+            None if self.allow_repeats => candidates().last()?,
+            None => return None,
+        };
+        used[index] = true;
+        Some(interaction.response.clone())
+    }
+
+    /// Builds a response from a recording
+    fn replay_response(&self, url: Url, recorded: RecordedResponse) -> Result<Response, HttpError> {
+        let status = StatusCode::from_u16(recorded.status)
+            .map_err(|err| HttpError::Middleware(format!("Invalid recorded status: {}", err)))?;
+        let mut builder = http::Response::builder().status(status).url(url);
+        for (name, value) in &recorded.headers {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => builder = builder.header(name, value),
+                _ => warn!("Skipping invalid recorded header {}", name),
+            }
+        }
+        let replay_timing = self.replay_timing;
+        let chunks = stream::iter(recorded.chunks).then(move |chunk| async move {
+            if replay_timing && chunk.delay_ms > 0 {
+                tokio::time::sleep(Duration::from_millis(chunk.delay_ms)).await;
+            }
+            chunk
+                .body
+                .into_bytes()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        });
+        builder
+            .body(Body::wrap_stream(chunks))
+            .map(Response::from)
+            .map_err(|err| HttpError::Middleware(err.to_string()))
+    }
+}
+
+impl Middleware for Cassette {
+    fn handle<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response, HttpError>> {
+        Box::pin(async move {
+            let captured = self.capture(&request);
+            match &self.mode {
+                Mode::Record(recorder) => {
+                    let response = next.run(request).await?;
+                    let status = response.status().as_u16();
+                    let headers =
+                        self.capture_headers(response.headers(), &SKIPPED_RESPONSE_HEADERS);
+                    let recorder = recorder.clone();
+                    map_body(response, move |body| Tape {
+                        inner: body,
+                        last_chunk: Instant::now(),
+                        chunks: Vec::new(),
+                        pending: Vec::new(),
+                        writing: None,
+                        finish: Some(Box::new(move |chunks| {
+                            Box::pin(recorder.push(Interaction {
+                                request: captured,
+                                response: RecordedResponse {
+                                    status,
+                                    headers,
+                                    chunks,
+                                },
+                            }))
+                        })),
+                    })
+                }
+                Mode::Replay { interactions, used } => {
+                    match self.find(interactions, used, &captured) {
+                        Some(recorded) => {
+                            debug!("Replaying {} {}", captured.method, captured.url);
+                            self.replay_response(request.url().clone(), recorded)
+                        }
+                        None => Err(HttpError::Middleware(format!(
+                            "No recorded interaction in cassette {} matches {} {}",
+                            self.path.display(),
+                            captured.method,
+                            captured.url
+                        ))),
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Passes the body through while recording its chunks and their timing
+///
+/// The stream ends only once the interaction is written, so the file is complete when
+/// the body has been read.
+struct Tape {
+    inner: BoxStream<'static, reqwest::Result<Bytes>>,
+    last_chunk: Instant,
+    chunks: Vec<RecordedChunk>,
+    // Bytes of a UTF-8 sequence split across network chunks
+    pending: Vec<u8>,
+    writing: Option<BoxFuture<'static, ()>>,
+    finish: Option<Finish>,
+}
+
+/// Writes the recorded chunks once the body has ended
+type Finish = Box<dyn FnOnce(Vec<RecordedChunk>) -> BoxFuture<'static, ()> + Send>;
+
+impl Tape {
+    fn record(&mut self, chunk: &[u8]) {
+        let now = Instant::now();
+        let delay_ms = (now - self.last_chunk).as_millis() as u64;
+        self.last_chunk = now;
+        self.pending.extend_from_slice(chunk);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            // Keep an incomplete sequence at the end for the next chunk
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        let data: Vec<u8> = self.pending.drain(..valid).collect();
+        self.chunks.push(RecordedChunk {
+            delay_ms,
+            body: ChunkBody::new(data),
+        });
+    }
+}
+
+impl Stream for Tape {
+    type Item = reqwest::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(writing) = &mut this.writing {
+            ready!(writing.as_mut().poll(cx));
+            this.writing = None;
+            return Poll::Ready(None);
+        }
+        let poll = this.inner.poll_next_unpin(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => this.record(chunk),
+            // An interrupted body is not recorded
+            Poll::Ready(Some(Err(_))) => this.finish = None,
+            Poll::Ready(None) => {
+                if !this.pending.is_empty() {
+                    let rest = std::mem::take(&mut this.pending);
+                    this.chunks.push(RecordedChunk {
+                        delay_ms: 0,
+                        body: ChunkBody::new(rest),
+                    });
+                }
+                if let Some(finish) = this.finish.take() {
+                    let mut writing = finish(std::mem::take(&mut this.chunks));
+                    if writing.as_mut().poll(cx).is_pending() {
+                        this.writing = Some(writing);
+                        return Poll::Pending;
+                    }
+                }
+            }
+            Poll::Pending => {}
+        }
+        poll
+    }
+}
+
+fn sorted_query(url: &Url) -> Vec<(String, String)> {
+    let mut pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    pairs.sort();
+    pairs
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::Stack;
+
+    /// A cassette path under the system temp dir, removed on drop
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "ai_rs-cassette-{}-{}.json",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_file(&path);
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn stack(cassette: Cassette) -> Stack {
+        Stack {
+            layers: vec![Arc::new(cassette)],
+            ..Default::default()
+        }
+    }
+
+    async fn send(stack: &Stack, request: reqwest::RequestBuilder) -> Result<Response, HttpError> {
+        let client = reqwest::Client::new();
+        stack
+            .send(&client, Request::new(request.build().unwrap()))
+            .await
+    }
+
+    /// Reads a body chunk by chunk
+    async fn chunks(response: Response) -> Vec<Bytes> {
+        response
+            .bytes_stream()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn replays_a_recorded_response() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/generate")
+            .with_header("content-type", "application/json")
+            .with_body("{\"text\":\"hi\"}")
+            .expect(1)
+            .create_async()
+            .await;
+        let file = TempFile::new("plain");
+        let client = reqwest::Client::new();
+        let request = || {
+            client
+                .post(format!("{}/generate", server.url()))
+                .body(r#"{"a":1,"b":2}"#)
+        };
+
+        let recording = stack(Cassette::record(&file.0));
+        let response = send(&recording, request()).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "{\"text\":\"hi\"}");
+
+        let replaying = stack(Cassette::replay(&file.0).unwrap());
+        let response = send(&replaying, request()).await.unwrap();
+        assert_eq!(
+            response.headers()["content-type"],
+            HeaderValue::from_static("application/json")
+        );
+        assert_eq!(response.text().await.unwrap(), "{\"text\":\"hi\"}");
+        mock.assert_async().await;
+
+        // Each recording answers one request
+        match send(&replaying, request()).await {
+            Err(HttpError::Middleware(message)) => {
+                assert!(message.contains("No recorded interaction"))
+            }
+            other => panic!("expected a middleware error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_streamed_chunks_with_their_delays() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/stream")
+            .with_chunked_body(|w| {
+                w.write_all(b"data: one\n\n")?;
+                w.flush()?;
+                std::thread::sleep(Duration::from_millis(200));
+                w.write_all(b"data: two\n\n")
+            })
+            .create_async()
+            .await;
+        let file = TempFile::new("stream");
+        let url = format!("{}/stream", server.url());
+
+        let recording = stack(Cassette::record(&file.0));
+        let response = send(&recording, reqwest::Client::new().get(&url))
+            .await
+            .unwrap();
+        let recorded = chunks(response).await;
+
+        let cassette: CassetteFile =
+            serde_json::from_slice(&std::fs::read(&file.0).unwrap()).unwrap();
+        let chunks_on_file = &cassette.interactions[0].response.chunks;
+        assert_eq!(chunks_on_file.len(), recorded.len());
+        assert!(chunks_on_file.last().unwrap().delay_ms >= 150);
+
+        let replaying = stack(Cassette::replay(&file.0).unwrap().replay_timing(true));
+        let started = Instant::now();
+        let response = send(&replaying, reqwest::Client::new().get(&url))
+            .await
+            .unwrap();
+        assert_eq!(chunks(response).await, recorded);
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(recorded.concat(), b"data: one\n\ndata: two\n\n");
+    }
+
+    #[tokio::test]
+    async fn keeps_bytes_that_are_not_utf8() {
+        let mut server = mockito::Server::new_async().await;
+        let body = vec![0xff, 0xfe, b'a', 0x80];
+        server
+            .mock("GET", "/binary")
+            .with_body(body.clone())
+            .create_async()
+            .await;
+        let file = TempFile::new("binary");
+        let url = format!("{}/binary", server.url());
+
+        let recording = stack(Cassette::record(&file.0));
+        let response = send(&recording, reqwest::Client::new().get(&url))
+            .await
+            .unwrap();
+        assert_eq!(response.bytes().await.unwrap(), body);
+        assert!(std::fs::read_to_string(&file.0)
+            .unwrap()
+            .contains("\"base64\""));
+
+        let replaying = stack(Cassette::replay(&file.0).unwrap());
+        let response = send(&replaying, reqwest::Client::new().get(&url))
+            .await
+            .unwrap();
+        assert_eq!(response.bytes().await.unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn redacts_credentials() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/models")
+            .match_query(mockito::Matcher::Any)
+            .with_header("set-cookie", "session=secret-cookie")
+            .with_body("[]")
+            .create_async()
+            .await;
+        let file = TempFile::new("redact");
+        let url = format!(
+            "{}/models?key=secret-key&X-Amz-Signature=secret-signature&page=2",
+            server.url()
+        );
+        let request = || {
+            reqwest::Client::new()
+                .get(&url)
+                .header("authorization", "Bearer secret-token")
+                .header("x-amz-security-token", "secret-session")
+                .header("x-custom-auth", "secret-custom")
+                .header("accept", "application/json")
+        };
+
+        let recording = stack(Cassette::record(&file.0).redact_header("X-Custom-Auth"));
+        send(&recording, request())
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        let written = std::fs::read_to_string(&file.0).unwrap();
+        assert!(!written.contains("secret-"), "{}", written);
+        assert!(written.contains("page=2"));
+        assert!(written.contains("application/json"));
+        let cassette: CassetteFile = serde_json::from_str(&written).unwrap();
+        let headers = &cassette.interactions[0].request.headers;
+        assert_eq!(headers["authorization"], REDACTED);
+        assert_eq!(headers["x-amz-security-token"], REDACTED);
+        assert_eq!(headers["x-custom-auth"], REDACTED);
+
+        // The redacted URL still matches the live one
+        let replaying = stack(Cassette::replay(&file.0).unwrap());
+        let response = send(&replaying, request()).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "[]");
+    }
+
+    #[tokio::test]
+    async fn fails_requests_without_a_recording() {
+        let file = TempFile::new("no-match");
+        std::fs::write(&file.0, r#"{"interactions":[]}"#).unwrap();
+        let replaying = stack(Cassette::replay(&file.0).unwrap());
+
+        let result = send(
+            &replaying,
+            reqwest::Client::new().get("http://localhost:1/missing"),
+        )
+        .await;
+        match result {
+            Err(HttpError::Middleware(message)) => {
+                assert!(message.contains("GET http://localhost:1/missing"))
+            }
+            other => panic!("expected a middleware error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn replay_rejects_invalid_files() {
+        let file = TempFile::new("invalid");
+        match Cassette::replay(&file.0) {
+            Err(CassetteError::Io(_)) => {}
+            other => panic!("expected an IO error, got {:?}", other.map(|_| ())),
+        }
+        std::fs::write(&file.0, "not json").unwrap();
+        match Cassette::replay(&file.0) {
+            Err(CassetteError::Parse(_)) => {}
+            other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
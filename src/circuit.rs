@@ -0,0 +1,537 @@
+use crate::retry::ErrorClass;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// State of a circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CircuitState {
+    /// Requests are sent; failures are counted
+    Closed,
+    /// Requests are rejected until the open duration has passed
+    Open,
+    /// A limited number of probe requests decide whether the circuit closes again
+    HalfOpen,
+}
+
+/// The endpoint a circuit protects: one model at one base URL
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CircuitKey {
+    /// The base URL of the API
+    pub base_url: String,
+    /// The model
+    pub model: String,
+}
+
+impl CircuitKey {
+    /// Creates a key
+    pub fn new(base_url: &str, model: &str) -> Self {
+        CircuitKey {
+            base_url: base_url.to_string(),
+            model: model.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CircuitKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.model, self.base_url)
+    }
+}
+
+/// A state change, passed to the hooks of the breaker
+#[derive(Debug, Clone)]
+pub struct CircuitEvent {
+    /// The circuit that changed
+    pub key: CircuitKey,
+    /// The previous state
+    pub from: CircuitState,
+    /// The new state
+    pub to: CircuitState,
+    /// The failure that opened the circuit, if it was opened
+    pub class: Option<ErrorClass>,
+}
+
+/// Error returned when a request is rejected because its circuit is open
+#[derive(Debug, Clone)]
+pub struct CircuitOpen {
+    /// The open circuit
+    pub key: CircuitKey,
+    /// The time until the circuit lets a probe request through
+    pub retry_in: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Circuit open for {}, retry in {:?}",
+            self.key, self.retry_in
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    failures: u32,
+    probes: u32,
+    opened_at: Instant,
+}
+
+impl Default for Circuit {
+    fn default() -> Self {
+        Circuit {
+            state: CircuitState::Closed,
+            failures: 0,
+            probes: 0,
+            opened_at: Instant::now(),
+        }
+    }
+}
+
+type Hook = Arc<dyn Fn(&CircuitEvent) + Send + Sync>;
+
+/// Circuit breakers for a set of endpoints, one circuit per base URL and model
+///
+/// A circuit opens after `failure_threshold` consecutive failures of a tripping class
+/// and rejects requests for `open_duration`. It then lets `half_open_requests` probe
+/// requests through: a successful probe closes it, a failed one opens it again. Other
+/// errors, such as invalid requests, show that the endpoint is up and count as successes.
+///
+/// Clones share their circuits.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    half_open_requests: u32,
+    trip_on: HashSet<ErrorClass>,
+    on_state_change: Vec<Hook>,
+    circuits: Arc<Mutex<HashMap<CircuitKey, Circuit>>>,
+}
+
+impl fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("failure_threshold", &self.failure_threshold)
+            .field("open_duration", &self.open_duration)
+            .field("half_open_requests", &self.half_open_requests)
+            .field("trip_on", &self.trip_on)
+            .field("on_state_change", &self.on_state_change.len())
+            .finish()
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+            half_open_requests: 1,
+            trip_on: [
+                ErrorClass::RateLimited,
+                ErrorClass::Overloaded,
+                ErrorClass::ServerError,
+                ErrorClass::Timeout,
+                ErrorClass::Connection,
+            ]
+            .into_iter()
+            .collect(),
+            on_state_change: Vec::new(),
+            circuits: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// Creates a breaker that opens after 5 consecutive failures for 30 seconds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of consecutive failures that open a circuit
+    pub fn failure_threshold(mut self, failures: u32) -> Self {
+        self.failure_threshold = failures.max(1);
+        self
+    }
+
+    /// Sets how long an open circuit rejects requests before probing the endpoint
+    pub fn open_duration(mut self, duration: Duration) -> Self {
+        self.open_duration = duration;
+        self
+    }
+
+    /// Sets the number of probe requests allowed at once while half-open
+    pub fn half_open_requests(mut self, requests: u32) -> Self {
+        self.half_open_requests = requests.max(1);
+        self
+    }
+
+    /// Replaces the error classes counted as failures
+    ///
+    /// By default rate limiting, overload, server errors, timeouts and connection
+    /// failures trip the circuit.
+    pub fn trip_on(mut self, classes: &[ErrorClass]) -> Self {
+        self.trip_on = classes.iter().copied().collect();
+        self
+    }
+
+    /// Registers a hook called when a circuit changes state
+    pub fn on_state_change(mut self, hook: impl Fn(&CircuitEvent) + Send + Sync + 'static) -> Self {
+        self.on_state_change.push(Arc::new(hook));
+        self
+    }
+
+    fn circuits(&self) -> MutexGuard<'_, HashMap<CircuitKey, Circuit>> {
+        self.circuits
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns the state of a circuit; circuits that never failed are closed
+    pub fn state(&self, key: &CircuitKey) -> CircuitState {
+        let circuits = self.circuits();
+        match circuits.get(key) {
+            Some(circuit)
+                if circuit.state == CircuitState::Open
+                    && circuit.opened_at.elapsed() >= self.open_duration =>
+            {
+                CircuitState::HalfOpen
+            }
+            Some(circuit) => circuit.state,
+            None => CircuitState::Closed,
+        }
+    }
+
+    /// Asks to send a request to an endpoint
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The endpoint
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a `CircuitPermit` to report the outcome with, or `CircuitOpen`
+    /// if the request must not be sent
+    pub fn acquire(&self, key: &CircuitKey) -> Result<CircuitPermit, CircuitOpen> {
+        let mut event = None;
+        let result = {
+            let mut circuits = self.circuits();
+            let circuit = circuits.entry(key.clone()).or_default();
+            if circuit.state == CircuitState::Open {
+                let elapsed = circuit.opened_at.elapsed();
+                if elapsed < self.open_duration {
+                    return Err(CircuitOpen {
+                        key: key.clone(),
+                        retry_in: self.open_duration - elapsed,
+                    });
+                }
+                circuit.state = CircuitState::HalfOpen;
+                circuit.probes = 0;
+                event = Some(self.event(key, CircuitState::Open, CircuitState::HalfOpen, None));
+            }
+            match circuit.state {
+                CircuitState::HalfOpen if circuit.probes >= self.half_open_requests => {
+                    Err(CircuitOpen {
+                        key: key.clone(),
+                        retry_in: Duration::ZERO,
+                    })
+                }
+                CircuitState::HalfOpen => {
+                    circuit.probes += 1;
+                    Ok(self.permit(key, true))
+                }
+                _ => Ok(self.permit(key, false)),
+            }
+        };
+        self.notify(event);
+        result
+    }
+
+    fn permit(&self, key: &CircuitKey, probe: bool) -> CircuitPermit {
+        CircuitPermit {
+            breaker: self.clone(),
+            key: key.clone(),
+            probe,
+            done: false,
+        }
+    }
+
+    fn event(
+        &self,
+        key: &CircuitKey,
+        from: CircuitState,
+        to: CircuitState,
+        class: Option<ErrorClass>,
+    ) -> CircuitEvent {
+        match to {
+            CircuitState::Open => warn!("Circuit for {} opened after {:?}", key, class),
+            CircuitState::HalfOpen => info!("Circuit for {} is half-open, probing", key),
+            CircuitState::Closed => info!("Circuit for {} closed", key),
+        }
+        CircuitEvent {
+            key: key.clone(),
+            from,
+            to,
+            class,
+        }
+    }
+
+    fn notify(&self, event: Option<CircuitEvent>) {
+        if let Some(event) = event {
+            for hook in &self.on_state_change {
+                hook(&event);
+            }
+        }
+    }
+
+    /// Records the outcome of a request sent with a permit
+    fn record(&self, key: &CircuitKey, probe: bool, failure: Option<ErrorClass>) {
+        let failure = failure.filter(|class| self.trip_on.contains(class));
+        let event = {
+            let mut circuits = self.circuits();
+            let circuit = circuits.entry(key.clone()).or_default();
+            match (circuit.state, failure) {
+                (CircuitState::Closed, None) => {
+                    circuit.failures = 0;
+                    None
+                }
+                (CircuitState::Closed, Some(class)) => {
+                    circuit.failures += 1;
+                    (circuit.failures >= self.failure_threshold).then(|| {
+                        circuit.state = CircuitState::Open;
+                        circuit.opened_at = Instant::now();
+                        self.event(key, CircuitState::Closed, CircuitState::Open, Some(class))
+                    })
+                }
+                // Requests sent before the circuit opened do not decide the probes
+                (CircuitState::HalfOpen, _) if !probe => None,
+                (CircuitState::HalfOpen, None) => {
+                    circuit.probes = 0;
+                    circuit.state = CircuitState::Closed;
+                    circuit.failures = 0;
+                    Some(self.event(key, CircuitState::HalfOpen, CircuitState::Closed, None))
+                }
+                (CircuitState::HalfOpen, Some(class)) => {
+                    circuit.probes = 0;
+                    circuit.state = CircuitState::Open;
+                    circuit.opened_at = Instant::now();
+                    Some(self.event(key, CircuitState::HalfOpen, CircuitState::Open, Some(class)))
+                }
+                (CircuitState::Open, _) => None,
+            }
+        };
+        self.notify(event);
+    }
+
+    /// Frees the probe slot of a permit dropped without an outcome
+    fn release(&self, key: &CircuitKey) {
+        let mut circuits = self.circuits();
+        if let Some(circuit) = circuits.get_mut(key) {
+            if circuit.state == CircuitState::HalfOpen {
+                circuit.probes = circuit.probes.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Permission to send one request, returned by `CircuitBreaker::acquire`
+///
+/// Report the outcome with `success` or `failure`. A permit dropped without an outcome
+/// does not change the circuit.
+#[derive(Debug)]
+pub struct CircuitPermit {
+    breaker: CircuitBreaker,
+    key: CircuitKey,
+    probe: bool,
+    done: bool,
+}
+
+impl CircuitPermit {
+    /// Returns `true` if the request probes a half-open circuit
+    pub fn is_probe(&self) -> bool {
+        self.probe
+    }
+
+    /// Reports that the endpoint answered
+    pub fn success(mut self) {
+        self.done = true;
+        self.breaker.record(&self.key, self.probe, None);
+    }
+
+    /// Reports a failure; classes the breaker does not trip on count as a success
+    pub fn failure(mut self, class: ErrorClass) {
+        self.done = true;
+        self.breaker.record(&self.key, self.probe, Some(class));
+    }
+}
+
+impl Drop for CircuitPermit {
+    fn drop(&mut self) {
+        if !self.done && self.probe {
+            self.breaker.release(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> CircuitKey {
+        CircuitKey::new("http://localhost:11434", "llama3")
+    }
+
+    fn fail(breaker: &CircuitBreaker, times: u32) {
+        for _ in 0..times {
+            breaker
+                .acquire(&key())
+                .unwrap()
+                .failure(ErrorClass::ServerError);
+        }
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(3)
+            .open_duration(Duration::from_secs(60));
+        fail(&breaker, 2);
+        // A success resets the count
+        breaker.acquire(&key()).unwrap().success();
+        fail(&breaker, 2);
+        assert_eq!(breaker.state(&key()), CircuitState::Closed);
+
+        fail(&breaker, 1);
+        assert_eq!(breaker.state(&key()), CircuitState::Open);
+        let rejected = breaker.acquire(&key()).unwrap_err();
+        assert_eq!(rejected.key, key());
+        assert!(rejected.retry_in > Duration::from_secs(59));
+
+        // Other endpoints are not affected
+        let other = CircuitKey::new("http://localhost:11434", "mistral");
+        assert!(breaker.acquire(&other).is_ok());
+    }
+
+    #[test]
+    fn non_tripping_errors_count_as_success() {
+        let breaker = CircuitBreaker::new().failure_threshold(2);
+        fail(&breaker, 1);
+        breaker
+            .acquire(&key())
+            .unwrap()
+            .failure(ErrorClass::ClientError);
+        fail(&breaker, 1);
+        assert_eq!(breaker.state(&key()), CircuitState::Closed);
+
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .trip_on(&[ErrorClass::Timeout]);
+        fail(&breaker, 1);
+        assert_eq!(breaker.state(&key()), CircuitState::Closed);
+        breaker
+            .acquire(&key())
+            .unwrap()
+            .failure(ErrorClass::Timeout);
+        assert_eq!(breaker.state(&key()), CircuitState::Open);
+    }
+
+    #[test]
+    fn successful_probe_closes_the_circuit() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .open_duration(Duration::ZERO);
+        fail(&breaker, 1);
+        assert_eq!(breaker.state(&key()), CircuitState::HalfOpen);
+
+        let probe = breaker.acquire(&key()).unwrap();
+        assert!(probe.is_probe());
+        // Only one probe at a time
+        assert_eq!(
+            breaker.acquire(&key()).unwrap_err().retry_in,
+            Duration::ZERO
+        );
+
+        probe.success();
+        assert_eq!(breaker.state(&key()), CircuitState::Closed);
+        assert!(!breaker.acquire(&key()).unwrap().is_probe());
+    }
+
+    #[test]
+    fn failed_probe_reopens_the_circuit() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .open_duration(Duration::from_millis(20));
+        fail(&breaker, 1);
+        std::thread::sleep(Duration::from_millis(30));
+
+        breaker
+            .acquire(&key())
+            .unwrap()
+            .failure(ErrorClass::Connection);
+        assert_eq!(breaker.state(&key()), CircuitState::Open);
+        assert!(breaker.acquire(&key()).is_err());
+    }
+
+    #[test]
+    fn dropped_probe_frees_its_slot() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .open_duration(Duration::ZERO);
+        fail(&breaker, 1);
+
+        drop(breaker.acquire(&key()).unwrap());
+        assert_eq!(breaker.state(&key()), CircuitState::HalfOpen);
+        assert!(breaker.acquire(&key()).unwrap().is_probe());
+    }
+
+    #[test]
+    fn late_results_do_not_decide_probes() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .open_duration(Duration::ZERO);
+        let late = breaker.acquire(&key()).unwrap();
+        fail(&breaker, 1);
+        let probe = breaker.acquire(&key()).unwrap();
+
+        late.success();
+        assert_eq!(breaker.state(&key()), CircuitState::HalfOpen);
+        assert!(breaker.acquire(&key()).is_err());
+        probe.success();
+        assert_eq!(breaker.state(&key()), CircuitState::Closed);
+    }
+
+    #[test]
+    fn hooks_see_every_transition() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .open_duration(Duration::ZERO)
+            .on_state_change(move |event| {
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push((event.from, event.to, event.class))
+            });
+        fail(&breaker, 1);
+        breaker.acquire(&key()).unwrap().success();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                (
+                    CircuitState::Closed,
+                    CircuitState::Open,
+                    Some(ErrorClass::ServerError)
+                ),
+                (CircuitState::Open, CircuitState::HalfOpen, None),
+                (CircuitState::HalfOpen, CircuitState::Closed, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,800 @@
+use crate::cache::{CacheMode, ResponseCache};
+use crate::cohere::types::{
+    ChatMessage, ChatRequest, ChatResponse, ChatStreamEvent, EmbedRequest, EmbedResponse,
+    RerankRequest, RerankResponse, RerankResult, Usage,
+};
+use crate::gemini::types::UsageMetadata;
+use crate::logging;
+use crate::middleware::{HttpError, Middleware, Request, Stack};
+use crate::rate_limit::{RateLimitPermit, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
+use crate::sse::spawn_sse_stream;
+use crate::telemetry::GenAiSpan;
+use crate::usage::{MaxOutputTokens, ModelName, TokenUsage, UsageError, UsageLedger, UsagePermit};
+use futures_util::Stream;
+use reqwest::{Client, RequestBuilder};
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{error, info, trace, Instrument};
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum CohereClientError {
+    /// Error related to the request
+    RequestError(String),
+    /// Network-related error
+    NetworkError(reqwest::Error),
+    /// Error while parsing JSON
+    ParseError(serde_json::Error),
+    /// The request would exceed a budget of the usage ledger and was not sent
+    BudgetExceeded(UsageError),
+}
+
+impl fmt::Display for CohereClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CohereClientError::RequestError(msg) => write!(f, "Request error: {}", msg),
+            CohereClientError::NetworkError(err) => write!(f, "Network error: {}", err),
+            CohereClientError::ParseError(err) => write!(f, "Parse error: {}", err),
+            CohereClientError::BudgetExceeded(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CohereClientError {}
+
+impl CohereClientError {
+    /// Short error type recorded on the tracing span
+    fn error_type(&self) -> &'static str {
+        match self {
+            CohereClientError::RequestError(_) => "request",
+            CohereClientError::NetworkError(_) => "network",
+            CohereClientError::ParseError(_) => "parse",
+            CohereClientError::BudgetExceeded(_) => "budget_exceeded",
+        }
+    }
+}
+
+impl From<reqwest::Error> for CohereClientError {
+    fn from(err: reqwest::Error) -> Self {
+        CohereClientError::NetworkError(err)
+    }
+}
+
+impl From<serde_json::Error> for CohereClientError {
+    fn from(err: serde_json::Error) -> Self {
+        CohereClientError::ParseError(err)
+    }
+}
+
+impl From<HttpError> for CohereClientError {
+    fn from(err: HttpError) -> Self {
+        match err {
+            HttpError::Status { body, .. } => CohereClientError::RequestError(body),
+            HttpError::Network(err) => CohereClientError::NetworkError(err),
+            HttpError::Middleware(msg) => CohereClientError::RequestError(msg),
+            HttpError::BudgetExceeded(err) => CohereClientError::BudgetExceeded(err),
+        }
+    }
+}
+
+/// `gen_ai.system` value of the OpenTelemetry GenAI conventions
+const GEN_AI_SYSTEM: &str = "cohere";
+
+/// Request body with the client's model and streaming flag added
+#[derive(Serialize)]
+struct ModelRequest<'a, T: Serialize> {
+    model: &'a str,
+    stream: bool,
+    #[serde(flatten)]
+    request: &'a T,
+}
+
+/// Client for interacting with the Cohere v2 API
+#[derive(Debug)]
+pub struct CohereClient {
+    api_key: Secret,
+    model: String,
+    base_url: String,
+    client: Client,
+    stack: Stack,
+}
+
+impl CohereClient {
+    /// Creates a new instance of `CohereClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The Cohere API key
+    /// * `model` - The chat model to use (e.g., "command-r-plus")
+    ///
+    /// # Returns
+    ///
+    /// A new `CohereClient` instance
+    pub fn new(api_key: &str, model: &str) -> Self {
+        info!("Creating new CohereClient with model: {}", model);
+        CohereClient {
+            api_key: Secret::new(api_key),
+            model: model.to_string(),
+            base_url: "https://api.cohere.com/v2".to_string(),
+            client: Client::new(),
+            stack: Stack::default(),
+        }
+    }
+
+    /// Sets the model to use
+    pub fn model(mut self, model: &str) -> Self {
+        info!("Setting model to {}", model);
+        self.model = model.to_string();
+        self
+    }
+
+    /// Overrides the API base URL
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        info!("Setting base_url to {}", base_url);
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sets the policy used to retry failed requests; without one, requests are not retried
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.stack.retry_policy = policy;
+        self
+    }
+
+    /// Sets a cache for model responses; cache hits skip the rate limiter and the provider
+    pub fn cache(mut self, cache: ResponseCache) -> Self {
+        self.stack.cache = Some(cache);
+        self
+    }
+
+    /// Sets a rate limiter to wait on before each attempt; it may be shared with other clients
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.stack.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Sets a usage ledger that checks budgets before chat and embed requests and records
+    /// their cost; reranking is billed per search and is not recorded
+    pub fn usage_ledger(mut self, ledger: UsageLedger) -> Self {
+        self.stack.ledger = Some(ledger);
+        self
+    }
+
+    /// Adds a middleware; it runs inside the retries and the rate limiter, once per attempt
+    pub fn layer(mut self, middleware: impl Middleware) -> Self {
+        self.stack.layers.push(Arc::new(middleware));
+        self
+    }
+
+    /// Adds the API key and sends the request through the middleware stack
+    ///
+    /// Only model calls are cached. Calls billed per token also pass their model and reply
+    /// length limit for the ledger.
+    async fn send(
+        &self,
+        builder: RequestBuilder,
+        model_call: bool,
+        billed: Option<(&str, Option<u32>)>,
+    ) -> Result<reqwest::Response, HttpError> {
+        let mut request = Request::new(builder.bearer_auth(self.api_key.expose()).build()?);
+        if let Some((model, max_output_tokens)) = billed {
+            request
+                .extensions_mut()
+                .insert(ModelName(model.to_string()));
+            if let Some(max_output_tokens) = max_output_tokens {
+                request
+                    .extensions_mut()
+                    .insert(MaxOutputTokens(max_output_tokens));
+            }
+        }
+        if model_call {
+            request.extensions_mut().insert(CacheMode::current());
+        }
+        self.stack.send(&self.client, request).await
+    }
+
+    /// Sends a single user prompt as a chat request
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ChatResponse` or a `CohereClientError`
+    pub async fn chat(&self, prompt: &str) -> Result<ChatResponse, CohereClientError> {
+        let request = ChatRequest {
+            messages: vec![ChatMessage::new("user", prompt)],
+            ..Default::default()
+        };
+
+        self.chat_with_request(request).await
+    }
+
+    /// Sends a structured chat request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ChatResponse` or a `CohereClientError`
+    pub async fn chat_with_request(
+        &self,
+        request: ChatRequest,
+    ) -> Result<ChatResponse, CohereClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "chat", &self.model);
+        let span = telemetry.span().clone();
+        let result = self.send_chat(request, &telemetry).instrument(span).await;
+        if let Err(err) = &result {
+            telemetry.error(err.error_type());
+        }
+        result
+    }
+
+    /// Sends a chat request, recording the response on `telemetry`
+    async fn send_chat(
+        &self,
+        request: ChatRequest,
+        telemetry: &GenAiSpan,
+    ) -> Result<ChatResponse, CohereClientError> {
+        let url = format!("{}/chat", self.base_url);
+        info!("Generating chat response with URL: {}", url);
+        trace!("ChatRequest: {}", logging::payload_json(&request));
+
+        let response = self
+            .send(
+                self.client.post(&url).json(&ModelRequest {
+                    model: &self.model,
+                    stream: false,
+                    request: &request,
+                }),
+                true,
+                Some((&self.model, max_tokens(&request))),
+            )
+            .await
+            .map_err(|err| {
+                error!("Failed to generate chat response: {}", err);
+                telemetry.http_error(&err);
+                CohereClientError::from(err)
+            })?;
+        let permit = response.extensions().get::<RateLimitPermit>().cloned();
+        let usage_permit = response.extensions().get::<UsagePermit>().cloned();
+
+        let response_text = response.text().await?;
+        trace!("Response text: {}", logging::payload(&response_text));
+        let chat_response: ChatResponse = serde_json::from_str(&response_text)?;
+        if let (Some(permit), Some(usage)) = (permit, &chat_response.usage) {
+            permit.reconcile(total_tokens(usage));
+        }
+        if let (Some(permit), Some(usage)) = (usage_permit, &chat_response.usage) {
+            permit.record(usage.token_usage());
+        }
+        record_response(
+            telemetry,
+            chat_response.finish_reason.as_deref(),
+            chat_response.usage.as_ref(),
+        );
+        info!("Successfully generated chat response.");
+        trace!("ChatResponse: {}", logging::payload_json(&chat_response));
+        Ok(chat_response)
+    }
+
+    /// Streams a chat response for a single user prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `ChatStreamEvent`s or a `CohereClientError`
+    pub async fn stream_chat(
+        &self,
+        prompt: &str,
+    ) -> Result<impl Stream<Item = Result<ChatStreamEvent, CohereClientError>>, CohereClientError>
+    {
+        let request = ChatRequest {
+            messages: vec![ChatMessage::new("user", prompt)],
+            ..Default::default()
+        };
+
+        self.stream_chat_with_request(request).await
+    }
+
+    /// Streams a structured chat request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `ChatStreamEvent`s or a `CohereClientError`
+    pub async fn stream_chat_with_request(
+        &self,
+        request: ChatRequest,
+    ) -> Result<impl Stream<Item = Result<ChatStreamEvent, CohereClientError>>, CohereClientError>
+    {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "chat", &self.model);
+        let span = telemetry.span().clone();
+        self.start_stream(request, telemetry).instrument(span).await
+    }
+
+    /// Opens a streaming request; `telemetry` moves into the stream and ends with it
+    async fn start_stream(
+        &self,
+        request: ChatRequest,
+        mut telemetry: GenAiSpan,
+    ) -> Result<impl Stream<Item = Result<ChatStreamEvent, CohereClientError>>, CohereClientError>
+    {
+        let url = format!("{}/chat", self.base_url);
+        info!("Streaming chat response with URL: {}", url);
+        trace!("StreamRequest: {}", logging::payload_json(&request));
+
+        // Only failures before the first chunk arrives are retried
+        let response = self
+            .send(
+                self.client.post(&url).json(&ModelRequest {
+                    model: &self.model,
+                    stream: true,
+                    request: &request,
+                }),
+                true,
+                Some((&self.model, max_tokens(&request))),
+            )
+            .await
+            .map_err(|err| {
+                error!("Failed to start streaming: {}", err);
+                telemetry.http_error(&err);
+                CohereClientError::from(err)
+            })?;
+        let mut permit = response.extensions().get::<RateLimitPermit>().cloned();
+        let mut usage_permit = response.extensions().get::<UsagePermit>().cloned();
+
+        Ok(spawn_sse_stream(
+            response,
+            move |event| match serde_json::from_str::<ChatStreamEvent>(&event.data) {
+                Ok(stream_event) => {
+                    telemetry.first_token();
+                    let usage = stream_event
+                        .delta
+                        .as_ref()
+                        .and_then(|delta| delta.usage.as_ref());
+                    if let (Some(usage), Some(permit)) = (usage, permit.take()) {
+                        permit.reconcile(total_tokens(usage));
+                    }
+                    if let (Some(usage), Some(permit)) = (usage, usage_permit.take()) {
+                        permit.record(usage.token_usage());
+                    }
+                    if stream_event.event_type == "message-end" {
+                        if let Some(delta) = &stream_event.delta {
+                            record_response(
+                                &telemetry,
+                                delta.finish_reason.as_deref(),
+                                delta.usage.as_ref(),
+                            );
+                        }
+                    }
+                    Some(Ok(stream_event))
+                }
+                Err(e) => {
+                    error!("Failed to parse stream event: {}", e);
+                    telemetry.error("parse");
+                    Some(Err(CohereClientError::ParseError(e)))
+                }
+            },
+        ))
+    }
+
+    /// Creates float embeddings for the given texts
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The embedding model (e.g., "embed-english-v3.0")
+    /// * `texts` - The texts to embed
+    /// * `input_type` - "search_document", "search_query", "classification" or "clustering"
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `EmbedResponse` or a `CohereClientError`
+    pub async fn embed(
+        &self,
+        model: &str,
+        texts: &[&str],
+        input_type: &str,
+    ) -> Result<EmbedResponse, CohereClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "embeddings", model);
+        let span = telemetry.span().clone();
+        let result = self
+            .send_embed(model, texts, input_type, &telemetry)
+            .instrument(span)
+            .await;
+        if let Err(err) = &result {
+            telemetry.error(err.error_type());
+        }
+        result
+    }
+
+    /// Sends an embed request, recording failures and usage on `telemetry`
+    async fn send_embed(
+        &self,
+        model: &str,
+        texts: &[&str],
+        input_type: &str,
+        telemetry: &GenAiSpan,
+    ) -> Result<EmbedResponse, CohereClientError> {
+        let url = format!("{}/embed", self.base_url);
+        info!("Creating embeddings with URL: {}", url);
+
+        let request = EmbedRequest {
+            model: model.to_string(),
+            texts: texts.iter().map(|text| text.to_string()).collect(),
+            input_type: input_type.to_string(),
+            embedding_types: vec!["float".to_string()],
+        };
+
+        let response = self
+            .send(
+                self.client.post(&url).json(&request),
+                true,
+                Some((model, Some(0))),
+            )
+            .await
+            .map_err(|err| {
+                error!("Failed to create embeddings: {}", err);
+                telemetry.http_error(&err);
+                CohereClientError::from(err)
+            })?;
+        let usage_permit = response.extensions().get::<UsagePermit>().cloned();
+
+        let embed_response: EmbedResponse = response.json().await?;
+        if let Some(tokens) = embed_response
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.billed_units.as_ref())
+        {
+            telemetry.usage(Some(tokens.input_tokens as u32), None);
+            if let Some(permit) = usage_permit {
+                permit.record(TokenUsage::new(tokens.input_tokens as u32, 0));
+            }
+        }
+        info!(
+            "Successfully created {} embeddings.",
+            embed_response.embeddings.float.len()
+        );
+        Ok(embed_response)
+    }
+
+    /// Ranks documents by relevance to a query
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The rerank model (e.g., "rerank-v3.5")
+    /// * `query` - The search query
+    /// * `documents` - The documents to rank
+    /// * `top_n` - Number of results to return, or all documents if `None`
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RerankResult`s, most relevant first, or a `CohereClientError`
+    pub async fn rerank(
+        &self,
+        model: &str,
+        query: &str,
+        documents: &[&str],
+        top_n: Option<usize>,
+    ) -> Result<Vec<RerankResult>, CohereClientError> {
+        let url = format!("{}/rerank", self.base_url);
+        info!("Reranking {} documents with URL: {}", documents.len(), url);
+
+        let request = RerankRequest {
+            model: model.to_string(),
+            query: query.to_string(),
+            documents: documents.iter().map(|doc| doc.to_string()).collect(),
+            top_n,
+        };
+
+        let response = self
+            .send(self.client.post(&url).json(&request), true, None)
+            .await
+            .map_err(|err| {
+                error!("Failed to rerank documents: {}", err);
+                CohereClientError::from(err)
+            })?;
+
+        let rerank_response: RerankResponse = response.json().await?;
+        info!("Successfully reranked documents.");
+        trace!(
+            "RerankResponse: {}",
+            logging::payload_json(&rerank_response)
+        );
+        Ok(rerank_response.results)
+    }
+}
+
+/// Returns the reply length limit of a request, if it sets one
+fn max_tokens(request: &ChatRequest) -> Option<u32> {
+    request
+        .max_tokens
+        .and_then(|tokens| u32::try_from(tokens).ok())
+}
+
+/// Records the finish reason and token usage of a chat response
+fn record_response(telemetry: &GenAiSpan, finish_reason: Option<&str>, usage: Option<&Usage>) {
+    if let Some(reason) = finish_reason {
+        telemetry.finish_reasons(reason);
+    }
+    if let Some(usage) = usage {
+        let usage = UsageMetadata::from(usage.clone());
+        telemetry.usage(
+            Some(usage.prompt_token_count.max(0) as u32),
+            Some(usage.candidates_token_count.max(0) as u32),
+        );
+    }
+}
+
+/// Total tokens of a response, preferring actual counts over billed ones
+fn total_tokens(usage: &Usage) -> u32 {
+    UsageMetadata::from(usage.clone()).total_token_count.max(0) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use mockito::Matcher;
+
+    fn client(server: &mockito::Server) -> CohereClient {
+        CohereClient::new("key", "command-r-plus")
+            .base_url(&server.url())
+            .retry_policy(RetryPolicy::none())
+    }
+
+    #[tokio::test]
+    async fn chat_round_trip() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat")
+            .match_header("authorization", "Bearer key")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "model": "command-r-plus",
+                "stream": false,
+                "messages": [{"role": "user", "content": "Hello"}],
+            })))
+            .with_body(
+                r#"{
+                    "id": "resp-1",
+                    "finish_reason": "COMPLETE",
+                    "message": {"role": "assistant", "content": [{"type": "text", "text": "Hi"}]},
+                    "usage": {
+                        "billed_units": {"input_tokens": 2, "output_tokens": 1},
+                        "tokens": {"input_tokens": 10, "output_tokens": 1}
+                    }
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let response = client(&server).chat("Hello").await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(response.get_text().as_deref(), Some("Hi"));
+        // Actual token counts win over billed ones
+        assert_eq!(total_tokens(response.usage.as_ref().unwrap()), 11);
+    }
+
+    #[tokio::test]
+    async fn chat_maps_error_bodies() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/chat")
+            .with_status(400)
+            .with_body(r#"{"message":"invalid request: message must not be empty"}"#)
+            .create_async()
+            .await;
+
+        match client(&server).chat("").await {
+            Err(CohereClientError::RequestError(body)) => {
+                assert!(body.contains("must not be empty"))
+            }
+            other => panic!("expected a request error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_chat_yields_typed_events() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/chat")
+            .match_body(Matcher::PartialJson(serde_json::json!({"stream": true})))
+            .with_header("content-type", "text/event-stream")
+            .with_body(concat!(
+                "event: message-start\n",
+                "data: {\"type\":\"message-start\",\"delta\":{\"message\":{\"role\":\"assistant\"}}}\n\n",
+                "event: content-delta\n",
+                "data: {\"type\":\"content-delta\",\"index\":0,\"delta\":{\"message\":{\"content\":{\"text\":\"Hi\"}}}}\n\n",
+                "event: message-end\n",
+                "data: {\"type\":\"message-end\",\"delta\":{\"finish_reason\":\"COMPLETE\",",
+                "\"usage\":{\"tokens\":{\"input_tokens\":3,\"output_tokens\":1}}}}\n\n",
+            ))
+            .create_async()
+            .await;
+
+        let events: Vec<ChatStreamEvent> = client(&server)
+            .stream_chat("Hello")
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        let types: Vec<&str> = events
+            .iter()
+            .map(|event| event.event_type.as_str())
+            .collect();
+        assert_eq!(types, ["message-start", "content-delta", "message-end"]);
+        assert_eq!(events[0].get_text(), None);
+        assert_eq!(events[1].get_text().as_deref(), Some("Hi"));
+        let end = events[2].delta.as_ref().unwrap();
+        assert_eq!(end.finish_reason.as_deref(), Some("COMPLETE"));
+        assert_eq!(total_tokens(end.usage.as_ref().unwrap()), 4);
+    }
+
+    #[tokio::test]
+    async fn embed_requests_float_embeddings() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/embed")
+            .match_body(Matcher::Json(serde_json::json!({
+                "model": "embed-english-v3.0",
+                "texts": ["a"],
+                "input_type": "search_query",
+                "embedding_types": ["float"],
+            })))
+            .with_body(r#"{"id": "e-1", "embeddings": {"float": [[0.5, 0.25]]}}"#)
+            .create_async()
+            .await;
+
+        let response = client(&server)
+            .embed("embed-english-v3.0", &["a"], "search_query")
+            .await
+            .unwrap();
+        assert_eq!(response.embeddings.float, vec![vec![0.5, 0.25]]);
+    }
+
+    #[tokio::test]
+    async fn rerank_parses_results_in_rank_order() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/rerank")
+            .match_body(Matcher::Json(serde_json::json!({
+                "model": "rerank-v3.5",
+                "query": "capital of France",
+                "documents": ["Berlin", "Paris", "Rome"],
+                "top_n": 2,
+            })))
+            .with_body(
+                r#"{
+                    "id": "r-1",
+                    "results": [
+                        {"index": 1, "relevance_score": 0.98},
+                        {"index": 2, "relevance_score": 0.12}
+                    ],
+                    "meta": {"billed_units": {"search_units": 1}}
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let results = client(&server)
+            .rerank(
+                "rerank-v3.5",
+                "capital of France",
+                &["Berlin", "Paris", "Rome"],
+                Some(2),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            results,
+            vec![
+                RerankResult {
+                    index: 1,
+                    relevance_score: 0.98
+                },
+                RerankResult {
+                    index: 2,
+                    relevance_score: 0.12
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn rerank_omits_top_n_when_unset() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/rerank")
+            .match_body(Matcher::Json(serde_json::json!({
+                "model": "rerank-v3.5",
+                "query": "q",
+                "documents": ["d"],
+            })))
+            .with_body(r#"{"results": [{"index": 0, "relevance_score": 0.5}]}"#)
+            .create_async()
+            .await;
+
+        let results = client(&server)
+            .rerank("rerank-v3.5", "q", &["d"], None)
+            .await
+            .unwrap();
+        mock.assert_async().await;
+        assert_eq!(results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn usage_ledger_records_chats_and_embeddings_but_not_reranking() {
+        use crate::usage::{BudgetScope, Dimension, ModelPrice, PricingTable};
+
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/chat")
+            .with_body(
+                r#"{
+                    "id": "c-1",
+                    "finish_reason": "COMPLETE",
+                    "message": {"role": "assistant", "content": [{"type": "text", "text": "Hi"}]},
+                    "usage": {
+                        "billed_units": {"input_tokens": 1, "output_tokens": 1},
+                        "tokens": {"input_tokens": 100, "output_tokens": 50}
+                    }
+                }"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/embed")
+            .with_body(
+                r#"{
+                    "id": "e-1",
+                    "embeddings": {"float": [[0.5]]},
+                    "meta": {"billed_units": {"input_tokens": 7}}
+                }"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/rerank")
+            .with_body(r#"{"results": [{"index": 0, "relevance_score": 0.5}]}"#)
+            .create_async()
+            .await;
+        let ledger = UsageLedger::new()
+            .pricing(PricingTable::new().price("command-r", ModelPrice::new(1.0, 2.0)))
+            .budget(BudgetScope::Total, 1.0);
+        let client = client(&server).usage_ledger(ledger.clone());
+
+        client.chat("Hello").await.unwrap();
+        client
+            .embed("embed-english-v3.0", &["a"], "search_query")
+            .await
+            .unwrap();
+        client
+            .rerank("rerank-v3.5", "q", &["d"], None)
+            .await
+            .unwrap();
+
+        let by_model = ledger.totals_by(Dimension::Model);
+        assert_eq!(by_model.len(), 2);
+        let chat = &by_model["command-r-plus"];
+        assert_eq!((chat.prompt_tokens, chat.completion_tokens), (100, 50));
+        assert!((chat.cost - 0.0002).abs() < 1e-9);
+        assert_eq!(by_model["embed-english-v3.0"].prompt_tokens, 7);
+        let remaining = ledger.remaining(&BudgetScope::Total).unwrap();
+        assert!((remaining - (1.0 - 0.0002)).abs() < 1e-9);
+    }
+}
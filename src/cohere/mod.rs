@@ -0,0 +1,8 @@
+pub mod client;
+pub mod types;
+
+pub use client::{CohereClient, CohereClientError};
+pub use types::{
+    ChatMessage, ChatRequest, ChatResponse, ChatStreamEvent, EmbedResponse, RerankResult,
+    ResponseFormat, Tool, ToolCall, Usage,
+};
@@ -0,0 +1,399 @@
+use crate::gemini::types::{FunctionCall, FunctionDeclaration, Tool as GeminiTool, UsageMetadata};
+use crate::usage::TokenUsage;
+use serde::{Deserialize, Serialize};
+
+/// Request structure for Cohere v2 chat
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChatRequest {
+    /// The conversation messages
+    pub messages: Vec<ChatMessage>,
+    /// Tools the model may call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    /// Output format (plain text or JSON, optionally constrained by a schema)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// Temperature for generation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Maximum number of tokens to generate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<i32>,
+    /// Top-k sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub k: Option<i32>,
+    /// Top-p sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p: Option<f32>,
+    /// Stop sequences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    /// Seed for deterministic sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// A message in a Cohere chat
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// The role of the message ("system", "user", "assistant" or "tool")
+    pub role: String,
+    /// The content of the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<MessageContent>,
+    /// Tool calls requested by the assistant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// The assistant's plan before calling tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_plan: Option<String>,
+    /// Identifier of the tool call a "tool" message answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    /// Creates a message with the given role and text
+    pub fn new(role: &str, content: &str) -> Self {
+        ChatMessage {
+            role: role.to_string(),
+            content: Some(MessageContent::Text(content.to_string())),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a "tool" message carrying the result of a tool call
+    pub fn tool_result(tool_call_id: &str, content: &str) -> Self {
+        ChatMessage {
+            role: "tool".to_string(),
+            content: Some(MessageContent::Text(content.to_string())),
+            tool_call_id: Some(tool_call_id.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Message content: either plain text or a list of blocks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    /// Plain text
+    Text(String),
+    /// Content blocks
+    Blocks(Vec<ContentBlock>),
+}
+
+/// A block of message content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentBlock {
+    /// The block type (e.g., "text")
+    #[serde(rename = "type")]
+    pub block_type: String,
+    /// The text of a "text" block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Output format for Cohere chat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseFormat {
+    /// "text" or "json_object"
+    #[serde(rename = "type")]
+    pub format_type: String,
+    /// Optional JSON schema the output must follow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<serde_json::Value>,
+}
+
+impl ResponseFormat {
+    /// JSON mode, optionally constrained by a schema
+    pub fn json_object(schema: Option<serde_json::Value>) -> Self {
+        ResponseFormat {
+            format_type: "json_object".to_string(),
+            json_schema: schema,
+        }
+    }
+}
+
+/// Tool definition for Cohere
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tool {
+    /// Always "function"
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    /// The function definition
+    pub function: Function,
+}
+
+impl Tool {
+    /// Converts the crate's `Tool` definitions into Cohere tools
+    pub fn from_tools(tools: &[GeminiTool]) -> Vec<Tool> {
+        tools
+            .iter()
+            .flat_map(|tool| tool.function_declarations.iter())
+            .map(Tool::from)
+            .collect()
+    }
+}
+
+impl From<&FunctionDeclaration> for Tool {
+    fn from(declaration: &FunctionDeclaration) -> Self {
+        Tool {
+            tool_type: "function".to_string(),
+            function: Function {
+                name: declaration.name.clone(),
+                description: Some(declaration.description.clone()),
+                parameters: declaration.parameters.clone(),
+            },
+        }
+    }
+}
+
+/// Function definition for tools
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Function {
+    /// Name of the function
+    pub name: String,
+    /// Description of the function
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON schema of the parameters
+    pub parameters: serde_json::Value,
+}
+
+/// Tool call requested by the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Identifier of the tool call
+    pub id: String,
+    /// Always "function"
+    #[serde(rename = "type")]
+    pub call_type: String,
+    /// The function to call
+    pub function: ToolCallFunction,
+}
+
+impl ToolCall {
+    /// Converts the call into a `FunctionCall`, parsing the JSON-encoded arguments
+    pub fn to_function_call(&self) -> Result<FunctionCall, serde_json::Error> {
+        Ok(FunctionCall {
+            name: self.function.name.clone(),
+            args: serde_json::from_str(&self.function.arguments)?,
+        })
+    }
+}
+
+/// Function name and arguments of a tool call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallFunction {
+    /// Name of the function
+    pub name: String,
+    /// Arguments as a JSON string
+    pub arguments: String,
+}
+
+/// Response structure for Cohere v2 chat
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatResponse {
+    /// Identifier of the response
+    pub id: String,
+    /// Why generation stopped
+    pub finish_reason: Option<String>,
+    /// The generated message
+    pub message: ChatMessage,
+    /// Token usage
+    pub usage: Option<Usage>,
+}
+
+impl ChatResponse {
+    /// Gets the text of the first text block of the message
+    pub fn get_text(&self) -> Option<String> {
+        match self.message.content.as_ref()? {
+            MessageContent::Text(text) => Some(text.clone()),
+            MessageContent::Blocks(blocks) => blocks.iter().find_map(|block| block.text.clone()),
+        }
+    }
+
+    /// Gets the tool calls requested by the model
+    pub fn function_calls(&self) -> Result<Vec<FunctionCall>, serde_json::Error> {
+        self.message
+            .tool_calls
+            .as_ref()
+            .map(|calls| calls.iter().map(ToolCall::to_function_call).collect())
+            .unwrap_or_else(|| Ok(Vec::new()))
+    }
+}
+
+/// Token usage reported by Cohere
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Usage {
+    /// Billed token counts
+    pub billed_units: Option<UsageTokens>,
+    /// Actual token counts
+    pub tokens: Option<UsageTokens>,
+}
+
+/// Input and output token counts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageTokens {
+    /// Input token count
+    #[serde(default)]
+    pub input_tokens: f64,
+    /// Output token count
+    #[serde(default)]
+    pub output_tokens: f64,
+}
+
+impl Usage {
+    /// Returns the counts as a `TokenUsage` for the usage ledger, preferring the actual
+    /// counts over the billed ones
+    pub fn token_usage(&self) -> TokenUsage {
+        let tokens = self
+            .tokens
+            .as_ref()
+            .or(self.billed_units.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        TokenUsage::new(tokens.input_tokens as u32, tokens.output_tokens as u32)
+    }
+}
+
+impl From<Usage> for UsageMetadata {
+    fn from(usage: Usage) -> Self {
+        let tokens = usage.tokens.or(usage.billed_units).unwrap_or_default();
+        let input = tokens.input_tokens as i32;
+        let output = tokens.output_tokens as i32;
+        UsageMetadata {
+            prompt_token_count: input,
+            candidates_token_count: output,
+            total_token_count: input + output,
+            cached_content_token_count: 0,
+        }
+    }
+}
+
+/// Event emitted by Cohere v2 chat streaming
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatStreamEvent {
+    /// The event type (e.g., "content-delta", "tool-call-start", "message-end")
+    #[serde(rename = "type")]
+    pub event_type: String,
+    /// Index of the content block or tool call
+    pub index: Option<usize>,
+    /// The event payload
+    pub delta: Option<ChatStreamDelta>,
+}
+
+impl ChatStreamEvent {
+    /// Gets the text carried by a "content-delta" event
+    pub fn get_text(&self) -> Option<String> {
+        if self.event_type != "content-delta" {
+            return None;
+        }
+        self.delta
+            .as_ref()?
+            .message
+            .as_ref()?
+            .content
+            .as_ref()?
+            .get("text")?
+            .as_str()
+            .map(|text| text.to_string())
+    }
+}
+
+/// Payload of a streaming event
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatStreamDelta {
+    /// Partial message content, tool plan or tool calls
+    pub message: Option<ChatStreamMessage>,
+    /// Why generation stopped, sent with "message-end"
+    pub finish_reason: Option<String>,
+    /// Token usage, sent with "message-end"
+    pub usage: Option<Usage>,
+}
+
+/// Partial message carried by a streaming event
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatStreamMessage {
+    /// The role, sent with "message-start"
+    pub role: Option<String>,
+    /// Partial content
+    pub content: Option<serde_json::Value>,
+    /// Partial tool plan
+    pub tool_plan: Option<String>,
+    /// Partial tool call
+    pub tool_calls: Option<serde_json::Value>,
+}
+
+/// Request structure for Cohere v2 embed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbedRequest {
+    /// The embedding model to use
+    pub model: String,
+    /// The texts to embed
+    pub texts: Vec<String>,
+    /// "search_document", "search_query", "classification" or "clustering"
+    pub input_type: String,
+    /// The embedding types to return
+    pub embedding_types: Vec<String>,
+}
+
+/// Response structure for Cohere v2 embed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbedResponse {
+    /// Identifier of the response
+    pub id: String,
+    /// The embeddings by type
+    pub embeddings: Embeddings,
+    /// Billing details
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<EmbedMeta>,
+}
+
+/// Billing details of an embed response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbedMeta {
+    /// Billed token counts
+    pub billed_units: Option<UsageTokens>,
+}
+
+/// Embeddings grouped by type
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Embeddings {
+    /// Float embeddings, one per input text
+    #[serde(default)]
+    pub float: Vec<Vec<f32>>,
+}
+
+/// Request structure for Cohere v2 rerank
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RerankRequest {
+    /// The rerank model to use
+    pub model: String,
+    /// The search query
+    pub query: String,
+    /// The documents to rank
+    pub documents: Vec<String>,
+    /// Number of results to return
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_n: Option<usize>,
+}
+
+/// Response structure for Cohere v2 rerank
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RerankResponse {
+    /// Identifier of the response
+    pub id: Option<String>,
+    /// The ranked results, most relevant first
+    pub results: Vec<RerankResult>,
+}
+
+/// A single rerank result
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RerankResult {
+    /// Index of the document in the request
+    pub index: usize,
+    /// Relevance score between 0 and 1
+    pub relevance_score: f32,
+}
@@ -0,0 +1,539 @@
+use crate::conversation::session::{render, ChatModel, Conversation, ConversationError};
+use crate::conversation::types::{Message, Role};
+use crate::gemini::client::GeminiClient;
+use crate::ollama::client::OllamaClient;
+use crate::tokens::{Heuristic, TokenCounter};
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+/// Ollama's default `num_ctx`, used when the model does not set one
+const DEFAULT_OLLAMA_NUM_CTX: u32 = 2048;
+
+/// Tokens kept free for the reply when the conversation sets no `max_tokens`
+const DEFAULT_OUTPUT_RESERVE: u32 = 512;
+
+/// Instructions sent to the summarization model
+const SUMMARY_INSTRUCTIONS: &str = "Summarize the conversation below in a few sentences. \
+Keep names, facts, decisions and open questions. Reply with the summary only.";
+
+/// How a conversation is shortened when it does not fit in the context window
+///
+/// Every strategy keeps the system messages and the latest message. If the prompt is
+/// still too long afterwards, the oldest remaining messages are dropped.
+#[derive(Clone)]
+pub enum ContextStrategy {
+    /// Drops the oldest messages until the prompt fits
+    DropOldest,
+    /// Keeps only the last N messages
+    KeepLast(usize),
+    /// Keeps the most recent messages that fit in the given number of tokens
+    SlidingWindow(u32),
+    /// Replaces older messages with a summary written by another model
+    Summarize(Summarizer),
+}
+
+impl fmt::Debug for ContextStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextStrategy::DropOldest => write!(f, "DropOldest"),
+            ContextStrategy::KeepLast(count) => write!(f, "KeepLast({})", count),
+            ContextStrategy::SlidingWindow(tokens) => write!(f, "SlidingWindow({})", tokens),
+            ContextStrategy::Summarize(summarizer) => {
+                f.debug_tuple("Summarize").field(summarizer).finish()
+            }
+        }
+    }
+}
+
+/// Summarizes older turns with a (usually cheaper) model
+#[derive(Clone)]
+pub struct Summarizer {
+    client: Arc<dyn ChatModel>,
+    model: String,
+    keep_last: usize,
+}
+
+impl fmt::Debug for Summarizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Summarizer")
+            .field("model", &self.model)
+            .field("keep_last", &self.keep_last)
+            .finish()
+    }
+}
+
+impl Summarizer {
+    /// Creates a summarizer that keeps the last 4 messages verbatim
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client writing the summary
+    /// * `model` - The model to use for Ollama clients; Gemini clients use their own model
+    ///
+    /// # Returns
+    ///
+    /// A new `Summarizer`
+    pub fn new(client: impl ChatModel + 'static, model: &str) -> Self {
+        Summarizer {
+            client: Arc::new(client),
+            model: model.to_string(),
+            keep_last: 4,
+        }
+    }
+
+    /// Sets how many recent messages are kept verbatim instead of being summarized
+    pub fn keep_last(mut self, count: usize) -> Self {
+        self.keep_last = count;
+        self
+    }
+
+    /// Replaces all but the last `keep_last` non-system messages with a summary
+    async fn summarize(&self, messages: &[Message]) -> Result<Vec<Message>, ConversationError> {
+        let (system, others) = split_system(messages);
+        let recent = others.len().saturating_sub(self.keep_last.max(1));
+        if recent == 0 {
+            return Ok(messages.to_vec());
+        }
+        let (older, recent) = others.split_at(recent);
+
+        info!("Summarizing {} older messages", older.len());
+        let mut request = Conversation::new(&self.model).system(SUMMARY_INSTRUCTIONS);
+        request.push(Role::User, render(older).trim_end());
+        let summary = self.client.reply(&request).await?;
+        debug!("Conversation summary: {}", summary);
+
+        let mut fitted = system;
+        fitted.push(Message {
+            role: Role::System,
+            content: format!("Summary of the earlier conversation: {}", summary.trim()),
+            created_at: older[older.len() - 1].created_at,
+        });
+        fitted.extend_from_slice(recent);
+        Ok(fitted)
+    }
+}
+
+/// Fits conversations into a model's context window before they are sent
+///
+/// The prompt budget is the window minus the tokens reserved for the reply: the
+/// conversation's `max_tokens` setting, or 512 tokens. Tokens are counted with a
+/// `Heuristic` for the model unless another `TokenCounter` is set.
+#[derive(Debug, Clone)]
+pub struct ContextManager {
+    window: u32,
+    output_reserve: u32,
+    separate_output: bool,
+    strategy: ContextStrategy,
+    counter: Arc<dyn TokenCounter>,
+}
+
+impl ContextManager {
+    /// Creates a manager for a context window of `window` tokens, dropping the oldest messages
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The context window of the model, in tokens
+    ///
+    /// # Returns
+    ///
+    /// A new `ContextManager`
+    pub fn new(window: u32) -> Self {
+        ContextManager {
+            window,
+            output_reserve: DEFAULT_OUTPUT_RESERVE,
+            separate_output: false,
+            strategy: ContextStrategy::DropOldest,
+            counter: Arc::new(Heuristic::default()),
+        }
+    }
+
+    /// Creates a manager for an Ollama model, using the `num_ctx` the model runs with
+    ///
+    /// Falls back to Ollama's default of 2048 tokens when the model does not set `num_ctx`.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The Ollama client
+    /// * `model` - The name of the model
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ContextManager` or a `ConversationError`
+    pub async fn for_ollama(client: &OllamaClient, model: &str) -> Result<Self, ConversationError> {
+        let info = client.show_model_info(model).await?;
+        let window = ollama_num_ctx(&info).unwrap_or(DEFAULT_OLLAMA_NUM_CTX);
+        info!("Context window of {}: {} tokens", model, window);
+        Ok(ContextManager::new(window).counter(Arc::new(Heuristic::for_model(model))))
+    }
+
+    /// Creates a manager for the Gemini client's model, using its `inputTokenLimit`
+    ///
+    /// Gemini counts output tokens separately, so no part of the window is reserved
+    /// for the reply.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The Gemini client
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ContextManager` or a `ConversationError`
+    pub async fn for_gemini(client: &GeminiClient) -> Result<Self, ConversationError> {
+        let info = client.model_info().await?;
+        info!(
+            "Context window of {}: {} tokens",
+            info.name, info.input_token_limit
+        );
+        let mut manager = ContextManager::new(info.input_token_limit)
+            .counter(Arc::new(Heuristic::for_model(&info.name)));
+        manager.separate_output = true;
+        Ok(manager)
+    }
+
+    /// Sets the strategy used when the conversation is too long
+    pub fn strategy(mut self, strategy: ContextStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets the tokens reserved for the reply when the conversation sets no `max_tokens`
+    pub fn output_reserve(mut self, tokens: u32) -> Self {
+        self.output_reserve = tokens;
+        self
+    }
+
+    /// Sets the counter used to measure prompts, e.g. from a `TokenizerRegistry`
+    pub fn counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// Returns the context window, in tokens
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// Returns the tokens available for the prompt of a conversation
+    pub fn prompt_budget(&self, conversation: &Conversation) -> u32 {
+        if self.separate_output {
+            return self.window;
+        }
+        let reserve = match conversation.settings.max_tokens {
+            Some(max_tokens) if max_tokens > 0 => max_tokens as u32,
+            _ => self.output_reserve,
+        };
+        self.window.saturating_sub(reserve)
+    }
+
+    /// Shortens a conversation so its prompt fits in the budget
+    ///
+    /// # Arguments
+    ///
+    /// * `conversation` - The conversation to fit
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a copy of the conversation with the messages to send, or
+    /// `ConversationError::ContextOverflow` if even the system messages and the latest
+    /// message do not fit
+    pub async fn fit(
+        &self,
+        conversation: &Conversation,
+    ) -> Result<Conversation, ConversationError> {
+        let budget = self.prompt_budget(conversation);
+        let messages = &conversation.messages;
+        let counter = self.counter.as_ref();
+        let mut fitted = if counter.count_messages(messages) <= budget {
+            messages.clone()
+        } else {
+            match &self.strategy {
+                ContextStrategy::DropOldest => messages.clone(),
+                ContextStrategy::KeepLast(count) => keep_last(messages, *count),
+                ContextStrategy::SlidingWindow(tokens) => {
+                    sliding_window(counter, messages, (*tokens).min(budget))
+                }
+                ContextStrategy::Summarize(summarizer) => summarizer.summarize(messages).await?,
+            }
+        };
+        drop_oldest(counter, &mut fitted, budget)?;
+        if fitted.len() < messages.len() {
+            info!(
+                "Sending {} of {} messages to fit {} tokens",
+                fitted.len(),
+                messages.len(),
+                budget
+            );
+        }
+
+        let mut prompt = conversation.clone();
+        prompt.messages = fitted;
+        Ok(prompt)
+    }
+}
+
+/// Reads `num_ctx` from the `parameters` of an Ollama `show` response
+fn ollama_num_ctx(info: &Value) -> Option<u32> {
+    info.get("parameters")?.as_str()?.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("num_ctx"), Some(value)) => value.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+/// Splits the system messages from the others, keeping their order
+fn split_system(messages: &[Message]) -> (Vec<Message>, Vec<Message>) {
+    messages
+        .iter()
+        .cloned()
+        .partition(|message| message.role == Role::System)
+}
+
+/// Keeps the system messages and the last `count` other messages
+fn keep_last(messages: &[Message], count: usize) -> Vec<Message> {
+    let others = messages
+        .iter()
+        .filter(|message| message.role != Role::System)
+        .count();
+    let mut skip = others.saturating_sub(count.max(1));
+    messages
+        .iter()
+        .filter(|message| {
+            if message.role == Role::System || skip == 0 {
+                return true;
+            }
+            skip -= 1;
+            false
+        })
+        .cloned()
+        .collect()
+}
+
+/// Keeps the system messages and the most recent other messages that fit in `tokens`
+fn sliding_window(counter: &dyn TokenCounter, messages: &[Message], tokens: u32) -> Vec<Message> {
+    let mut used = counter.count_messages(&split_system(messages).0);
+    let mut start = messages.len();
+    for (index, message) in messages.iter().enumerate().rev() {
+        if message.role == Role::System {
+            continue;
+        }
+        let cost = counter.count_messages(std::slice::from_ref(message));
+        if used + cost > tokens && start < messages.len() {
+            break;
+        }
+        used += cost;
+        start = index;
+    }
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(index, message)| *index >= start || message.role == Role::System)
+        .map(|(_, message)| message.clone())
+        .collect()
+}
+
+/// Drops the oldest non-system messages, never the latest one, until the prompt fits
+fn drop_oldest(
+    counter: &dyn TokenCounter,
+    messages: &mut Vec<Message>,
+    budget: u32,
+) -> Result<(), ConversationError> {
+    let mut needed = counter.count_messages(messages);
+    while needed > budget {
+        let last = messages.len().saturating_sub(1);
+        let oldest = messages[..last]
+            .iter()
+            .position(|message| message.role != Role::System);
+        match oldest {
+            Some(index) => {
+                let removed = messages.remove(index);
+                needed -= counter.count_messages(std::slice::from_ref(&removed));
+            }
+            None => {
+                return Err(ConversationError::ContextOverflow {
+                    needed,
+                    available: budget,
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation::types::ConversationSettings;
+    use futures_util::future::BoxFuture;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Counts one token per word, so each one-word message costs 5 tokens with the overhead
+    #[derive(Debug)]
+    struct Words;
+
+    impl TokenCounter for Words {
+        fn count(&self, text: &str) -> u32 {
+            text.split_whitespace().count() as u32
+        }
+    }
+
+    /// Replies with a fixed summary and keeps the conversations it was sent
+    #[derive(Default)]
+    struct FakeModel {
+        requests: Mutex<Vec<Conversation>>,
+    }
+
+    impl ChatModel for FakeModel {
+        fn reply<'a>(
+            &'a self,
+            conversation: &'a Conversation,
+        ) -> BoxFuture<'a, Result<String, ConversationError>> {
+            self.requests.lock().unwrap().push(conversation.clone());
+            Box::pin(async { Ok(" They talked. ".to_string()) })
+        }
+    }
+
+    /// A system message followed by `turns` one-word messages "m0", "m1", ...
+    fn conversation(turns: usize) -> Conversation {
+        let mut conversation = Conversation::new("llama3").system("instructions");
+        for turn in 0..turns {
+            let role = if turn % 2 == 0 {
+                Role::User
+            } else {
+                Role::Assistant
+            };
+            conversation.push(role, &format!("m{}", turn));
+        }
+        conversation
+    }
+
+    fn manager(window: u32) -> ContextManager {
+        ContextManager::new(window)
+            .output_reserve(0)
+            .counter(Arc::new(Words))
+    }
+
+    fn contents(conversation: &Conversation) -> Vec<&str> {
+        conversation
+            .messages
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn reserves_tokens_for_the_reply() {
+        let mut conversation = conversation(0);
+        assert_eq!(ContextManager::new(1000).prompt_budget(&conversation), 488);
+        assert_eq!(
+            manager(1000)
+                .output_reserve(100)
+                .prompt_budget(&conversation),
+            900
+        );
+
+        conversation.settings = ConversationSettings {
+            max_tokens: Some(300),
+            ..Default::default()
+        };
+        assert_eq!(manager(1000).prompt_budget(&conversation), 700);
+        assert_eq!(manager(100).prompt_budget(&conversation), 0);
+    }
+
+    #[tokio::test]
+    async fn keeps_conversations_that_fit() {
+        let conversation = conversation(4);
+        let fitted = manager(25).fit(&conversation).await.unwrap();
+        assert_eq!(fitted, conversation);
+    }
+
+    #[tokio::test]
+    async fn drops_oldest_messages() {
+        let fitted = manager(20).fit(&conversation(6)).await.unwrap();
+        assert_eq!(contents(&fitted), vec!["instructions", "m3", "m4", "m5"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_last_messages() {
+        let fitted = manager(30)
+            .strategy(ContextStrategy::KeepLast(2))
+            .fit(&conversation(6))
+            .await
+            .unwrap();
+        assert_eq!(contents(&fitted), vec!["instructions", "m4", "m5"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_a_sliding_window() {
+        let fitted = manager(30)
+            .strategy(ContextStrategy::SlidingWindow(15))
+            .fit(&conversation(6))
+            .await
+            .unwrap();
+        assert_eq!(contents(&fitted), vec!["instructions", "m4", "m5"]);
+
+        // The window is capped by the budget
+        let fitted = manager(10)
+            .strategy(ContextStrategy::SlidingWindow(1000))
+            .fit(&conversation(6))
+            .await
+            .unwrap();
+        assert_eq!(contents(&fitted), vec!["instructions", "m5"]);
+    }
+
+    #[tokio::test]
+    async fn summarizes_older_messages() {
+        let model = Arc::new(FakeModel::default());
+        let summarizer = Summarizer::new(model.clone(), "small").keep_last(2);
+        let fitted = manager(30)
+            .strategy(ContextStrategy::Summarize(summarizer))
+            .fit(&conversation(6))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            contents(&fitted),
+            vec![
+                "instructions",
+                "Summary of the earlier conversation: They talked.",
+                "m4",
+                "m5",
+            ]
+        );
+        let requests = model.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].model, "small");
+        assert_eq!(requests[0].messages[0].content, SUMMARY_INSTRUCTIONS);
+        assert!(requests[0].messages[1].content.contains("m3"));
+        assert!(!requests[0].messages[1].content.contains("m4"));
+    }
+
+    #[tokio::test]
+    async fn reports_overflow_when_the_latest_message_does_not_fit() {
+        let mut conversation = conversation(2);
+        conversation.push(Role::User, &"word ".repeat(50));
+
+        match manager(20).fit(&conversation).await {
+            Err(ConversationError::ContextOverflow { needed, available }) => {
+                assert_eq!(needed, 5 + 54);
+                assert_eq!(available, 20);
+            }
+            other => panic!("expected a context overflow, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_num_ctx_from_ollama_parameters() {
+        let info = json!({"parameters": "stop \"<|eot_id|>\"\nnum_ctx    8192\ntemperature 0.7"});
+        assert_eq!(ollama_num_ctx(&info), Some(8192));
+        assert_eq!(
+            ollama_num_ctx(&json!({"parameters": "temperature 0.7"})),
+            None
+        );
+        assert_eq!(ollama_num_ctx(&json!({})), None);
+    }
+}
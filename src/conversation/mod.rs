@@ -0,0 +1,13 @@
+pub mod context;
+pub mod session;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
+pub mod types;
+
+pub use context::{ContextManager, ContextStrategy, Summarizer};
+pub use session::{ChatModel, Conversation, ConversationError};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+pub use store::{ConversationStore, JsonFileStore};
+pub use types::{ConversationSettings, ConversationSummary, Message, Role};
@@ -0,0 +1,627 @@
+use crate::conversation::context::ContextManager;
+use crate::conversation::types::{ConversationSettings, ConversationSummary, Message, Role};
+use crate::gemini::client::{GeminiClient, GeminiClientError};
+use crate::gemini::types::{Content, GenerateContentRequest, GenerationConfig, Part};
+use crate::ollama::client::{OllamaClient, OllamaClientError};
+use crate::ollama::types::GenerateRequest;
+use crate::utils::now;
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum ConversationError {
+    /// Error from the Ollama client
+    OllamaError(OllamaClientError),
+    /// Error from the Gemini client
+    GeminiError(GeminiClientError),
+    /// The model returned no text
+    EmptyResponse,
+    /// The prompt does not fit in the context window, even after applying the strategy
+    ContextOverflow {
+        /// Estimated tokens of the smallest prompt the strategy could build
+        needed: u32,
+        /// Tokens available for the prompt
+        available: u32,
+    },
+    /// No conversation with this id is stored
+    NotFound(String),
+    /// Error while reading or writing a store
+    IoError(io::Error),
+    /// Error while parsing JSON
+    ParseError(serde_json::Error),
+    /// Error from the SQLite store
+    #[cfg(feature = "sqlite")]
+    SqliteError(rusqlite::Error),
+}
+
+impl fmt::Display for ConversationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversationError::OllamaError(err) => write!(f, "Ollama error: {}", err),
+            ConversationError::GeminiError(err) => write!(f, "Gemini error: {}", err),
+            ConversationError::EmptyResponse => write!(f, "The model returned no text"),
+            ConversationError::ContextOverflow { needed, available } => write!(
+                f,
+                "Context overflow: the prompt needs about {} tokens but only {} are available",
+                needed, available
+            ),
+            ConversationError::NotFound(id) => write!(f, "Conversation not found: {}", id),
+            ConversationError::IoError(err) => write!(f, "IO error: {}", err),
+            ConversationError::ParseError(err) => write!(f, "Parse error: {}", err),
+            #[cfg(feature = "sqlite")]
+            ConversationError::SqliteError(err) => write!(f, "SQLite error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConversationError {}
+
+impl From<OllamaClientError> for ConversationError {
+    fn from(err: OllamaClientError) -> Self {
+        ConversationError::OllamaError(err)
+    }
+}
+
+impl From<GeminiClientError> for ConversationError {
+    fn from(err: GeminiClientError) -> Self {
+        ConversationError::GeminiError(err)
+    }
+}
+
+impl From<io::Error> for ConversationError {
+    fn from(err: io::Error) -> Self {
+        ConversationError::IoError(err)
+    }
+}
+
+impl From<serde_json::Error> for ConversationError {
+    fn from(err: serde_json::Error) -> Self {
+        ConversationError::ParseError(err)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for ConversationError {
+    fn from(err: rusqlite::Error) -> Self {
+        ConversationError::SqliteError(err)
+    }
+}
+
+/// A client that can generate the next message of a conversation
+///
+/// Implemented for `OllamaClient` and `GeminiClient`.
+pub trait ChatModel: Send + Sync {
+    /// Generates the assistant reply to the messages of `conversation`
+    fn reply<'a>(
+        &'a self,
+        conversation: &'a Conversation,
+    ) -> BoxFuture<'a, Result<String, ConversationError>>;
+}
+
+impl<T: ChatModel + ?Sized> ChatModel for Arc<T> {
+    fn reply<'a>(
+        &'a self,
+        conversation: &'a Conversation,
+    ) -> BoxFuture<'a, Result<String, ConversationError>> {
+        (**self).reply(conversation)
+    }
+}
+
+/// A chat history with its model, settings and metadata
+///
+/// Messages are provider-neutral, so the same conversation can be continued with any
+/// `ChatModel`. Save it with a `ConversationStore` to resume it later.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    /// Unique id, generated on creation
+    pub id: String,
+    /// Optional human-readable title
+    pub title: Option<String>,
+    /// The model used for Ollama turns; Gemini turns use the client's model
+    pub model: String,
+    /// Generation settings applied to every turn
+    pub settings: ConversationSettings,
+    /// Application-defined metadata
+    pub metadata: BTreeMap<String, Value>,
+    /// The messages, oldest first
+    pub messages: Vec<Message>,
+    /// The id of the conversation this one was forked from
+    pub forked_from: Option<String>,
+    /// When the conversation was created, in seconds since the Unix epoch
+    pub created_at: u64,
+    /// When the conversation last changed, in seconds since the Unix epoch
+    pub updated_at: u64,
+}
+
+impl Conversation {
+    /// Creates a new, empty conversation
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model to use (e.g., "llama3")
+    ///
+    /// # Returns
+    ///
+    /// A new `Conversation` with a fresh id
+    pub fn new(model: &str) -> Self {
+        let now = now();
+        let conversation = Conversation {
+            id: new_id(),
+            title: None,
+            model: model.to_string(),
+            settings: ConversationSettings::default(),
+            metadata: BTreeMap::new(),
+            messages: Vec::new(),
+            forked_from: None,
+            created_at: now,
+            updated_at: now,
+        };
+        info!("Created conversation {}", conversation.id);
+        conversation
+    }
+
+    /// Sets the title
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    /// Adds a system message with instructions for the model
+    pub fn system(mut self, instructions: &str) -> Self {
+        self.push(Role::System, instructions);
+        self
+    }
+
+    /// Sets the generation settings
+    pub fn settings(mut self, settings: ConversationSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Sets a metadata entry
+    pub fn metadata(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.metadata.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Appends a message
+    ///
+    /// # Arguments
+    ///
+    /// * `role` - The author of the message
+    /// * `content` - The text of the message
+    pub fn push(&mut self, role: Role, content: &str) {
+        let now = now();
+        self.messages.push(Message {
+            role,
+            content: content.to_string(),
+            created_at: now,
+        });
+        self.updated_at = now;
+    }
+
+    /// Sends a user message and appends the model's reply
+    ///
+    /// If the model call fails, the user message is removed again, so the turn can be retried.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client generating the reply, e.g. an `OllamaClient` or a `GeminiClient`
+    /// * `text` - The user message
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the assistant `Message` or a `ConversationError`
+    pub async fn send(
+        &mut self,
+        client: &impl ChatModel,
+        text: &str,
+    ) -> Result<&Message, ConversationError> {
+        self.turn(client, text, None).await
+    }
+
+    /// Sends a user message, fitting the prompt into the model's context window first
+    ///
+    /// The full history is kept; only the prompt sent to the model is shortened by the
+    /// manager's strategy.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client generating the reply
+    /// * `text` - The user message
+    /// * `context` - The context manager for the client's model
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the assistant `Message` or a `ConversationError`
+    pub async fn send_within(
+        &mut self,
+        client: &impl ChatModel,
+        text: &str,
+        context: &ContextManager,
+    ) -> Result<&Message, ConversationError> {
+        self.turn(client, text, Some(context)).await
+    }
+
+    async fn turn(
+        &mut self,
+        client: &impl ChatModel,
+        text: &str,
+        context: Option<&ContextManager>,
+    ) -> Result<&Message, ConversationError> {
+        let updated_at = self.updated_at;
+        self.push(Role::User, text);
+        let reply = match context {
+            Some(context) => match context.fit(self).await {
+                Ok(prompt) => client.reply(&prompt).await,
+                Err(err) => Err(err),
+            },
+            None => client.reply(self).await,
+        };
+        match reply {
+            Ok(reply) => {
+                self.push(Role::Assistant, &reply);
+                Ok(&self.messages[self.messages.len() - 1])
+            }
+            Err(err) => {
+                self.messages.pop();
+                self.updated_at = updated_at;
+                Err(err)
+            }
+        }
+    }
+
+    /// Copies the conversation under a new id
+    ///
+    /// # Returns
+    ///
+    /// A new `Conversation` with the same messages, recording this one in `forked_from`
+    pub fn fork(&self) -> Conversation {
+        self.fork_at(self.messages.len())
+    }
+
+    /// Copies the first `len` messages of the conversation under a new id
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The number of messages to keep
+    ///
+    /// # Returns
+    ///
+    /// A new `Conversation`, recording this one in `forked_from`
+    pub fn fork_at(&self, len: usize) -> Conversation {
+        let now = now();
+        let mut fork = self.clone();
+        fork.id = new_id();
+        fork.messages.truncate(len);
+        fork.forked_from = Some(self.id.clone());
+        fork.created_at = now;
+        fork.updated_at = now;
+        info!("Forked conversation {} into {}", self.id, fork.id);
+        fork
+    }
+
+    /// Returns an overview of the conversation
+    pub fn summary(&self) -> ConversationSummary {
+        ConversationSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            model: self.model.clone(),
+            message_count: self.messages.len(),
+            forked_from: self.forked_from.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+
+    /// Renders the messages as a transcript prompt, for completion-only APIs
+    ///
+    /// Each message is written as "User: ...", "Assistant: ..." or "System: ...", and the
+    /// prompt ends with "Assistant:" for the model to continue.
+    pub fn transcript(&self) -> String {
+        let mut prompt = render(&self.messages);
+        prompt.push_str("Assistant:");
+        prompt
+    }
+}
+
+impl ChatModel for OllamaClient {
+    fn reply<'a>(
+        &'a self,
+        conversation: &'a Conversation,
+    ) -> BoxFuture<'a, Result<String, ConversationError>> {
+        Box::pin(async move {
+            let request = GenerateRequest {
+                model: conversation.model.clone(),
+                prompt: conversation.transcript(),
+                stream: Some(false),
+                options: ollama_options(&conversation.settings),
+                template_version: None,
+            };
+            let response = self.generate_completion(request).await?;
+            Ok(response.response.trim().to_string())
+        })
+    }
+}
+
+impl ChatModel for GeminiClient {
+    fn reply<'a>(
+        &'a self,
+        conversation: &'a Conversation,
+    ) -> BoxFuture<'a, Result<String, ConversationError>> {
+        Box::pin(async move {
+            let request = GenerateContentRequest {
+                contents: gemini_contents(&conversation.messages),
+                generation_config: Some(generation_config(&conversation.settings)),
+                safety_settings: None,
+                tools: None,
+                template_version: None,
+            };
+            let response = self.generate_content_with_request(request).await?;
+            response.get_text().ok_or(ConversationError::EmptyResponse)
+        })
+    }
+}
+
+/// Writes each message as "User: ...", "Assistant: ..." or "System: ...", followed by a blank line
+pub(crate) fn render(messages: &[Message]) -> String {
+    let mut text = String::new();
+    for message in messages {
+        let label = match message.role {
+            Role::System => "System",
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+        };
+        text.push_str(&format!("{}: {}\n\n", label, message.content));
+    }
+    text
+}
+
+/// Converts settings to a Gemini generation config
+pub(crate) fn generation_config(settings: &ConversationSettings) -> GenerationConfig {
+    GenerationConfig {
+        temperature: settings.temperature,
+        top_k: settings.top_k,
+        top_p: settings.top_p,
+        max_output_tokens: settings.max_tokens,
+        candidate_count: None,
+        stop_sequences: settings.stop.clone(),
+        response_mime_type: None,
+    }
+}
+
+/// Converts settings to Ollama model options
+pub(crate) fn ollama_options(settings: &ConversationSettings) -> Option<Value> {
+    let mut options = Map::new();
+    if let Some(temperature) = settings.temperature {
+        options.insert("temperature".to_string(), json!(temperature));
+    }
+    if let Some(top_p) = settings.top_p {
+        options.insert("top_p".to_string(), json!(top_p));
+    }
+    if let Some(top_k) = settings.top_k {
+        options.insert("top_k".to_string(), json!(top_k));
+    }
+    if let Some(max_tokens) = settings.max_tokens {
+        options.insert("num_predict".to_string(), json!(max_tokens));
+    }
+    if let Some(stop) = &settings.stop {
+        options.insert("stop".to_string(), json!(stop));
+    }
+    if options.is_empty() {
+        None
+    } else {
+        Some(Value::Object(options))
+    }
+}
+
+/// Converts messages to Gemini contents
+///
+/// Gemini has no system role here, so system messages are sent as leading parts of the
+/// first user message.
+pub(crate) fn gemini_contents(messages: &[Message]) -> Vec<Content> {
+    let mut instructions = Vec::new();
+    let mut contents: Vec<Content> = Vec::new();
+    for message in messages {
+        let role = match message.role {
+            Role::System => {
+                instructions.push(text_part(&message.content));
+                continue;
+            }
+            Role::User => "user",
+            Role::Assistant => "model",
+        };
+        contents.push(Content {
+            role: role.to_string(),
+            parts: vec![text_part(&message.content)],
+        });
+    }
+    if !instructions.is_empty() {
+        match contents.iter_mut().find(|content| content.role == "user") {
+            Some(content) => {
+                instructions.append(&mut content.parts);
+                content.parts = instructions;
+            }
+            None => contents.insert(
+                0,
+                Content {
+                    role: "user".to_string(),
+                    parts: instructions,
+                },
+            ),
+        }
+    }
+    contents
+}
+
+fn text_part(text: &str) -> Part {
+    Part {
+        text: Some(text.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Generates a random-looking 16 character hex id
+fn new_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let seed = format!(
+        "{}-{}-{}",
+        nanos,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    hex::encode(&Sha256::digest(seed.as_bytes())[..8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Replies with the queued results in order and keeps the conversations it was sent
+    #[derive(Default)]
+    struct FakeModel {
+        replies: Mutex<Vec<Result<String, ConversationError>>>,
+        requests: Mutex<Vec<Conversation>>,
+    }
+
+    impl FakeModel {
+        fn new(replies: Vec<Result<String, ConversationError>>) -> Self {
+            FakeModel {
+                replies: Mutex::new(replies),
+                requests: Mutex::default(),
+            }
+        }
+    }
+
+    impl ChatModel for FakeModel {
+        fn reply<'a>(
+            &'a self,
+            conversation: &'a Conversation,
+        ) -> BoxFuture<'a, Result<String, ConversationError>> {
+            self.requests.lock().unwrap().push(conversation.clone());
+            let reply = self.replies.lock().unwrap().remove(0);
+            Box::pin(async move { reply })
+        }
+    }
+
+    fn texts(contents: &[Content]) -> Vec<(&str, Vec<&str>)> {
+        contents
+            .iter()
+            .map(|content| {
+                let parts = content
+                    .parts
+                    .iter()
+                    .map(|part| part.text.as_deref().unwrap_or_default())
+                    .collect();
+                (content.role.as_str(), parts)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn send_appends_the_turn() {
+        let model = FakeModel::new(vec![Ok("Hi there".to_string())]);
+        let mut conversation = Conversation::new("llama3").system("Be brief");
+
+        let reply = conversation.send(&model, "Hello").await.unwrap();
+        assert_eq!(reply.role, Role::Assistant);
+        assert_eq!(reply.content, "Hi there");
+        let roles: Vec<Role> = conversation.messages.iter().map(|m| m.role).collect();
+        assert_eq!(roles, [Role::System, Role::User, Role::Assistant]);
+        // The model saw the user message
+        assert_eq!(model.requests.lock().unwrap()[0].messages.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn send_removes_the_user_message_on_error() {
+        let model = FakeModel::new(vec![
+            Err(ConversationError::EmptyResponse),
+            Ok("Hi".to_string()),
+        ]);
+        let mut conversation = Conversation::new("llama3").system("Be brief");
+        conversation.updated_at = 42;
+        let before = conversation.clone();
+
+        match conversation.send(&model, "Hello").await {
+            Err(ConversationError::EmptyResponse) => {}
+            other => panic!("expected EmptyResponse, got {:?}", other),
+        }
+        assert_eq!(conversation, before);
+
+        // The turn can be retried
+        conversation.send(&model, "Hello").await.unwrap();
+        assert_eq!(conversation.messages.len(), 3);
+    }
+
+    #[test]
+    fn fork_at_copies_the_first_messages() {
+        let mut conversation = Conversation::new("llama3")
+            .title("Jokes")
+            .metadata("user", "ada")
+            .system("Be funny");
+        conversation.push(Role::User, "Tell a joke");
+        conversation.push(Role::Assistant, "Knock knock");
+
+        let fork = conversation.fork_at(2);
+        assert_ne!(fork.id, conversation.id);
+        assert_eq!(fork.forked_from.as_deref(), Some(conversation.id.as_str()));
+        assert_eq!(fork.messages, conversation.messages[..2]);
+        assert_eq!(fork.title, conversation.title);
+        assert_eq!(fork.metadata, conversation.metadata);
+
+        let full = conversation.fork();
+        assert_eq!(full.messages, conversation.messages);
+        // Longer lengths keep every message
+        assert_eq!(conversation.fork_at(10).messages.len(), 3);
+    }
+
+    #[test]
+    fn gemini_contents_fold_system_messages_into_the_first_user_message() {
+        let mut conversation = Conversation::new("gemini").system("Be brief");
+        conversation.push(Role::User, "Hello");
+        conversation.push(Role::Assistant, "Hi");
+        conversation.push(Role::System, "Answer in French");
+        conversation.push(Role::User, "Bye");
+
+        assert_eq!(
+            texts(&gemini_contents(&conversation.messages)),
+            [
+                ("user", vec!["Be brief", "Answer in French", "Hello"]),
+                ("model", vec!["Hi"]),
+                ("user", vec!["Bye"]),
+            ]
+        );
+
+        // Without a user message, the instructions become one
+        let instructions = Conversation::new("gemini").system("Be brief");
+        assert_eq!(
+            texts(&gemini_contents(&instructions.messages)),
+            [("user", vec!["Be brief"])]
+        );
+    }
+
+    #[test]
+    fn transcript_ends_with_the_assistant_label() {
+        let mut conversation = Conversation::new("llama3").system("Be brief");
+        conversation.push(Role::User, "Hello");
+        assert_eq!(
+            conversation.transcript(),
+            "System: Be brief\n\nUser: Hello\n\nAssistant:"
+        );
+    }
+}
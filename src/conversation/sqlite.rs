@@ -0,0 +1,286 @@
+use crate::conversation::session::{Conversation, ConversationError};
+use crate::conversation::store::ConversationStore;
+use crate::conversation::types::{ConversationSummary, Message, Role};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use tracing::info;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS conversations (
+    id TEXT PRIMARY KEY,
+    title TEXT,
+    model TEXT NOT NULL,
+    settings TEXT NOT NULL,
+    metadata TEXT NOT NULL,
+    forked_from TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS messages (
+    conversation_id TEXT NOT NULL REFERENCES conversations (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (conversation_id, position)
+);
+";
+
+/// Store keeping conversations in an embedded SQLite database
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it and its tables if needed
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The database file
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SqliteStore` or a `ConversationError`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ConversationError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Opens a database that only lives in memory, e.g. for tests
+    pub fn in_memory() -> Result<Self, ConversationError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, ConversationError> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteStore {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl ConversationStore for SqliteStore {
+    fn save(&self, conversation: &Conversation) -> Result<(), ConversationError> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO conversations
+                 (id, title, model, settings, metadata, forked_from, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (id) DO UPDATE SET
+                 title = excluded.title,
+                 model = excluded.model,
+                 settings = excluded.settings,
+                 metadata = excluded.metadata,
+                 forked_from = excluded.forked_from,
+                 created_at = excluded.created_at,
+                 updated_at = excluded.updated_at",
+            params![
+                conversation.id,
+                conversation.title,
+                conversation.model,
+                serde_json::to_string(&conversation.settings)?,
+                serde_json::to_string(&conversation.metadata)?,
+                conversation.forked_from,
+                conversation.created_at as i64,
+                conversation.updated_at as i64,
+            ],
+        )?;
+        transaction.execute(
+            "DELETE FROM messages WHERE conversation_id = ?1",
+            params![conversation.id],
+        )?;
+        {
+            let mut insert = transaction.prepare(
+                "INSERT INTO messages (conversation_id, position, role, content, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for (position, message) in conversation.messages.iter().enumerate() {
+                insert.execute(params![
+                    conversation.id,
+                    position as i64,
+                    message.role.as_str(),
+                    message.content,
+                    message.created_at as i64,
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        info!("Saved conversation {}", conversation.id);
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<Conversation, ConversationError> {
+        let connection = self.connection();
+        let row = connection
+            .query_row(
+                "SELECT title, model, settings, metadata, forked_from, created_at, updated_at
+                 FROM conversations WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                        row.get::<_, i64>(5)?,
+                        row.get::<_, i64>(6)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let (title, model, settings, metadata, forked_from, created_at, updated_at) =
+            row.ok_or_else(|| ConversationError::NotFound(id.to_string()))?;
+
+        let mut query = connection.prepare(
+            "SELECT role, content, created_at FROM messages
+             WHERE conversation_id = ?1 ORDER BY position",
+        )?;
+        let rows = query.query_map(params![id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+        let mut messages = Vec::new();
+        for row in rows {
+            let (role, content, created_at) = row?;
+            let role = Role::parse(&role).ok_or_else(|| {
+                rusqlite::Error::InvalidColumnType(
+                    0,
+                    format!("role '{}'", role),
+                    rusqlite::types::Type::Text,
+                )
+            })?;
+            messages.push(Message {
+                role,
+                content,
+                created_at: created_at as u64,
+            });
+        }
+
+        Ok(Conversation {
+            id: id.to_string(),
+            title,
+            model,
+            settings: serde_json::from_str(&settings)?,
+            metadata: serde_json::from_str(&metadata)?,
+            messages,
+            forked_from,
+            created_at: created_at as u64,
+            updated_at: updated_at as u64,
+        })
+    }
+
+    fn list(&self) -> Result<Vec<ConversationSummary>, ConversationError> {
+        let connection = self.connection();
+        let mut query = connection.prepare(
+            "SELECT c.id, c.title, c.model, c.forked_from, c.created_at, c.updated_at,
+                    (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id)
+             FROM conversations c ORDER BY c.updated_at DESC, c.id",
+        )?;
+        let rows = query.query_map([], |row| {
+            Ok(ConversationSummary {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                model: row.get(2)?,
+                forked_from: row.get(3)?,
+                created_at: row.get::<_, i64>(4)? as u64,
+                updated_at: row.get::<_, i64>(5)? as u64,
+                message_count: row.get::<_, i64>(6)? as usize,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, ConversationError> {
+        let deleted = self
+            .connection()
+            .execute("DELETE FROM conversations WHERE id = ?1", params![id])?;
+        if deleted > 0 {
+            info!("Deleted conversation {}", id);
+        }
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conversation(title: &str, updated_at: u64) -> Conversation {
+        let mut conversation = Conversation::new("llama3")
+            .title(title)
+            .system("Be brief")
+            .metadata("user", "ada");
+        conversation.push(Role::User, "Hello");
+        conversation.push(Role::Assistant, "Hi");
+        conversation.updated_at = updated_at;
+        conversation
+    }
+
+    #[test]
+    fn saves_loads_lists_and_deletes() {
+        let store = SqliteStore::in_memory().unwrap();
+        let older = conversation("older", 100);
+        let newer = conversation("newer", 200);
+        store.save(&older).unwrap();
+        store.save(&newer).unwrap();
+
+        assert_eq!(store.load(&older.id).unwrap(), older);
+        let listed = store.list().unwrap();
+        assert_eq!(listed[0], newer.summary());
+        assert_eq!(listed[1], older.summary());
+
+        // Saving again replaces the messages too
+        let mut shorter = older.fork_at(1);
+        shorter.id = older.id.clone();
+        store.save(&shorter).unwrap();
+        assert_eq!(store.load(&older.id).unwrap().messages, shorter.messages);
+
+        assert!(store.delete(&older.id).unwrap());
+        assert!(!store.delete(&older.id).unwrap());
+        match store.load(&older.id) {
+            Err(ConversationError::NotFound(id)) => assert_eq!(id, older.id),
+            other => panic!("expected NotFound, got {:?}", other),
+        }
+        // Deleting a conversation removes its messages
+        let orphans: i64 = store
+            .connection()
+            .query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(orphans, newer.messages.len() as i64);
+    }
+
+    #[test]
+    fn keeps_any_id() {
+        let store = SqliteStore::in_memory().unwrap();
+        let mut conversation = conversation("quoted", 100);
+        conversation.id = "it's ../fine".to_string();
+        store.save(&conversation).unwrap();
+        assert_eq!(store.load("it's ../fine").unwrap(), conversation);
+    }
+
+    #[test]
+    fn fork_saves_a_copy() {
+        let store = SqliteStore::in_memory().unwrap();
+        let original = conversation("original", 100);
+        store.save(&original).unwrap();
+
+        let fork = store.fork(&original.id, None).unwrap();
+        let loaded = store.load(&fork.id).unwrap();
+        assert_eq!(loaded.messages, original.messages);
+        assert_eq!(loaded.forked_from.as_deref(), Some(original.id.as_str()));
+        assert_eq!(store.list().unwrap().len(), 2);
+    }
+}
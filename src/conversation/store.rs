@@ -0,0 +1,265 @@
+use crate::conversation::session::{Conversation, ConversationError};
+use crate::conversation::types::ConversationSummary;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Persistent storage for conversations
+///
+/// Implement this trait to keep conversations in another database.
+pub trait ConversationStore: Send + Sync {
+    /// Saves a conversation, replacing any stored version with the same id
+    fn save(&self, conversation: &Conversation) -> Result<(), ConversationError>;
+
+    /// Loads a conversation, or returns `ConversationError::NotFound`
+    fn load(&self, id: &str) -> Result<Conversation, ConversationError>;
+
+    /// Lists the stored conversations, most recently updated first
+    fn list(&self) -> Result<Vec<ConversationSummary>, ConversationError>;
+
+    /// Deletes a conversation, returning `false` if it was not stored
+    fn delete(&self, id: &str) -> Result<bool, ConversationError>;
+
+    /// Loads a conversation, saves a copy of it under a new id and returns the copy
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the conversation to fork
+    /// * `len` - The number of messages to keep, or `None` to keep all of them
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new `Conversation` or a `ConversationError`
+    fn fork(&self, id: &str, len: Option<usize>) -> Result<Conversation, ConversationError> {
+        let conversation = self.load(id)?;
+        let fork = conversation.fork_at(len.unwrap_or(conversation.messages.len()));
+        self.save(&fork)?;
+        Ok(fork)
+    }
+}
+
+/// Store keeping one JSON file per conversation in a directory
+#[derive(Debug)]
+pub struct JsonFileStore {
+    dir: PathBuf,
+}
+
+impl JsonFileStore {
+    /// Opens a store in `dir`, creating the directory if needed
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory holding the conversation files
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `JsonFileStore`, or an `io::Error` if the directory cannot be created
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        Ok(JsonFileStore { dir })
+    }
+
+    /// Returns the file of a conversation, or `None` if the id cannot be a file name
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        valid_id(id).then(|| self.dir.join(format!("{}.json", id)))
+    }
+}
+
+impl ConversationStore for JsonFileStore {
+    fn save(&self, conversation: &Conversation) -> Result<(), ConversationError> {
+        let path = self.path(&conversation.id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid conversation id: {}", conversation.id),
+            )
+        })?;
+        // Write to a temporary file first so readers never see a partial conversation
+        let temporary = path.with_extension("tmp");
+        let mut file = std::fs::File::create(&temporary)?;
+        serde_json::to_writer_pretty(&mut file, conversation)?;
+        file.write_all(b"\n")?;
+        drop(file);
+        std::fs::rename(temporary, path)?;
+        info!("Saved conversation {}", conversation.id);
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<Conversation, ConversationError> {
+        let path = self
+            .path(id)
+            .ok_or_else(|| ConversationError::NotFound(id.to_string()))?;
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(ConversationError::NotFound(id.to_string()))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    fn list(&self) -> Result<Vec<ConversationSummary>, ConversationError> {
+        let mut summaries = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let content = std::fs::read(&path)?;
+            match serde_json::from_slice::<Conversation>(&content) {
+                Ok(conversation) => summaries.push(conversation.summary()),
+                Err(err) => warn!("Skipping unreadable conversation {:?}: {}", path, err),
+            }
+        }
+        summaries.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(a.id.cmp(&b.id)));
+        Ok(summaries)
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, ConversationError> {
+        let path = match self.path(id) {
+            Some(path) => path,
+            None => return Ok(false),
+        };
+        match std::fs::remove_file(path) {
+            Ok(()) => {
+                info!("Deleted conversation {}", id);
+                Ok(true)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Returns `true` if the id only contains letters, digits, '-' and '_'
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation::types::Role;
+
+    /// A fresh directory under the system temp dir, removed on drop
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "ai_rs-conversations-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn conversation(title: &str, updated_at: u64) -> Conversation {
+        let mut conversation = Conversation::new("llama3")
+            .title(title)
+            .system("Be brief")
+            .metadata("user", "ada");
+        conversation.push(Role::User, "Hello");
+        conversation.push(Role::Assistant, "Hi");
+        conversation.updated_at = updated_at;
+        conversation
+    }
+
+    #[test]
+    fn saves_loads_lists_and_deletes() {
+        let dir = TempDir::new("round-trip");
+        let store = JsonFileStore::new(&dir.0).unwrap();
+        let older = conversation("older", 100);
+        let newer = conversation("newer", 200);
+        store.save(&older).unwrap();
+        store.save(&newer).unwrap();
+
+        assert_eq!(store.load(&older.id).unwrap(), older);
+        let listed: Vec<_> = store.list().unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(listed, [newer.id.clone(), older.id.clone()]);
+
+        // Saving again replaces the stored version
+        let mut renamed = older.clone().title("renamed");
+        renamed.updated_at = 300;
+        store.save(&renamed).unwrap();
+        assert_eq!(
+            store.load(&older.id).unwrap().title.as_deref(),
+            Some("renamed")
+        );
+        assert_eq!(store.list().unwrap()[0].id, older.id);
+
+        assert!(store.delete(&older.id).unwrap());
+        assert!(!store.delete(&older.id).unwrap());
+        match store.load(&older.id) {
+            Err(ConversationError::NotFound(id)) => assert_eq!(id, older.id),
+            other => panic!("expected NotFound, got {:?}", other),
+        }
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_ids_that_are_not_file_names() {
+        let dir = TempDir::new("ids");
+        let store = JsonFileStore::new(&dir.0).unwrap();
+        let mut conversation = conversation("escape", 100);
+        conversation.id = "../escape".to_string();
+
+        match store.save(&conversation) {
+            Err(ConversationError::IoError(err)) => {
+                assert_eq!(err.kind(), io::ErrorKind::InvalidInput)
+            }
+            other => panic!("expected IoError, got {:?}", other),
+        }
+        match store.load("../escape") {
+            Err(ConversationError::NotFound(_)) => {}
+            other => panic!("expected NotFound, got {:?}", other),
+        }
+        assert!(!store.delete("").unwrap());
+        assert!(valid_id("a-Z_09"));
+        assert!(!valid_id("a.b"));
+        assert!(!valid_id("a/b"));
+    }
+
+    #[test]
+    fn list_skips_other_and_unreadable_files() {
+        let dir = TempDir::new("list");
+        let store = JsonFileStore::new(&dir.0).unwrap();
+        store.save(&conversation("kept", 100)).unwrap();
+        std::fs::write(dir.0.join("notes.txt"), "not a conversation").unwrap();
+        std::fs::write(dir.0.join("broken.json"), "{").unwrap();
+
+        let listed = store.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].title.as_deref(), Some("kept"));
+        assert_eq!(listed[0].message_count, 3);
+    }
+
+    #[test]
+    fn fork_saves_a_copy() {
+        let dir = TempDir::new("fork");
+        let store = JsonFileStore::new(&dir.0).unwrap();
+        let original = conversation("original", 100);
+        store.save(&original).unwrap();
+
+        let fork = store.fork(&original.id, Some(2)).unwrap();
+        assert_eq!(fork.forked_from.as_deref(), Some(original.id.as_str()));
+        assert_eq!(store.load(&fork.id).unwrap().messages.len(), 2);
+        assert_eq!(store.load(&original.id).unwrap().messages.len(), 3);
+        match store.fork("missing", None) {
+            Err(ConversationError::NotFound(_)) => {}
+            other => panic!("expected NotFound, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Author of a message in a conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Instructions for the model
+    System,
+    /// A message from the user
+    User,
+    /// A reply from the model
+    Assistant,
+}
+
+impl Role {
+    /// Returns the role name as stored: "system", "user" or "assistant"
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        }
+    }
+
+    /// Parses a stored role name
+    ///
+    /// # Returns
+    ///
+    /// The role, or `None` if the name is not recognized
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "system" => Some(Role::System),
+            "user" => Some(Role::User),
+            "assistant" => Some(Role::Assistant),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A provider-neutral message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    /// The author of the message
+    pub role: Role,
+    /// The text of the message
+    pub content: String,
+    /// When the message was added, in seconds since the Unix epoch
+    pub created_at: u64,
+}
+
+/// Generation settings applied to every turn of a conversation
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversationSettings {
+    /// Temperature for generation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Top-p sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Top-k sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<i32>,
+    /// Maximum number of tokens to generate per turn
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<i32>,
+    /// Stop sequences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+}
+
+/// Overview of a stored conversation, as returned by `ConversationStore::list`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    /// The conversation id
+    pub id: String,
+    /// The conversation title, if any
+    pub title: Option<String>,
+    /// The model of the conversation
+    pub model: String,
+    /// The number of messages
+    pub message_count: usize,
+    /// The id of the conversation this one was forked from
+    pub forked_from: Option<String>,
+    /// When the conversation was created, in seconds since the Unix epoch
+    pub created_at: u64,
+    /// When the conversation last changed, in seconds since the Unix epoch
+    pub updated_at: u64,
+}
@@ -1,11 +1,9 @@
-use log::{debug, info};
-
 pub mod client;
 pub mod types;
 
-pub use client::GeminiClient;
+pub use client::{GeminiClient, GeminiClientBuilder};
 pub use types::{
-    Candidate, Content, GenerateContentRequest, GenerateContentResponse, GenerationConfig,
-    InlineData, Part, SafetyRating, SafetySetting, StreamGenerateContentResponse, Tool,
-    UsageMetadata,
+    Candidate, Content, CountTokensResponse, FunctionCall, FunctionDeclaration, FunctionResponse,
+    GenerateContentRequest, GenerateContentResponse, GenerationConfig, InlineData, ModelInfo, Part,
+    SafetyRating, SafetySetting, StreamGenerateContentResponse, Tool, UsageMetadata,
 };
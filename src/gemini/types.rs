@@ -1,8 +1,20 @@
+use crate::rate_limit::estimate_tokens;
+use crate::usage::TokenUsage;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::fmt;
+
+/// Finish reasons of a candidate that was stopped by a safety filter
+const BLOCKED_FINISH_REASONS: &[&str] = &[
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+    "IMAGE_SAFETY",
+];
 
 /// Request structure for generating content with Gemini
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GenerateContentRequest {
     /// The contents to generate a response for
     pub contents: Vec<Content>,
@@ -12,10 +24,13 @@ pub struct GenerateContentRequest {
     pub safety_settings: Option<Vec<SafetySetting>>,
     /// Tools to use
     pub tools: Option<Vec<Tool>>,
+    /// The version id of the prompt template the contents were rendered from, for tracing only
+    #[serde(skip)]
+    pub template_version: Option<String>,
 }
 
 /// Content structure for Gemini API
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Content {
     /// The role of the content (user, model, etc.)
     pub role: String,
@@ -24,16 +39,41 @@ pub struct Content {
 }
 
 /// Part of content (text, image, etc.)
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Part {
     /// The text content
     pub text: Option<String>,
     /// Inline data (for images, etc.)
     pub inline_data: Option<InlineData>,
+    /// A function call requested by the model
+    #[serde(alias = "functionCall", skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<FunctionCall>,
+    /// The result of a function call, sent back to the model
+    #[serde(alias = "functionResponse", skip_serializing_if = "Option::is_none")]
+    pub function_response: Option<FunctionResponse>,
+}
+
+/// Function call requested by the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCall {
+    /// Name of the function to call
+    pub name: String,
+    /// Arguments for the function as a JSON object
+    #[serde(default)]
+    pub args: serde_json::Value,
+}
+
+/// Result of a function call, sent back to the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionResponse {
+    /// Name of the function that was called
+    pub name: String,
+    /// The result as a JSON object
+    pub response: serde_json::Value,
 }
 
 /// Inline data for parts (images, etc.)
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct InlineData {
     /// MIME type of the data
     pub mime_type: String,
@@ -41,8 +81,21 @@ pub struct InlineData {
     pub data: String,
 }
 
+// Only the size of the base64 data is shown, so logging a request never dumps it
+impl fmt::Debug for InlineData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InlineData")
+            .field("mime_type", &self.mime_type)
+            .field(
+                "data",
+                &format_args!("<{} bytes of base64>", self.data.len()),
+            )
+            .finish()
+    }
+}
+
 /// Generation configuration for Gemini
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GenerationConfig {
     /// Temperature for generation
     pub temperature: Option<f32>,
@@ -56,10 +109,13 @@ pub struct GenerationConfig {
     pub candidate_count: Option<i32>,
     /// Stop sequences
     pub stop_sequences: Option<Vec<String>>,
+    /// MIME type of the response, e.g. "application/json" for JSON mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
 }
 
 /// Safety setting for content generation
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SafetySetting {
     /// The category of safety setting
     pub category: String,
@@ -68,14 +124,14 @@ pub struct SafetySetting {
 }
 
 /// Tool definition for Gemini
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tool {
     /// Function declarations
     pub function_declarations: Vec<FunctionDeclaration>,
 }
 
 /// Function declaration for tools
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionDeclaration {
     /// Name of the function
     pub name: String,
@@ -88,20 +144,28 @@ pub struct FunctionDeclaration {
 /// Response structure for generated content
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GenerateContentResponse {
-    /// The candidates generated
+    /// The candidates generated; empty when the prompt was blocked
+    #[serde(default)]
     pub candidates: Vec<Candidate>,
     /// Prompt feedback
+    #[serde(alias = "promptFeedback")]
     pub prompt_feedback: Option<PromptFeedback>,
     /// Usage metadata
+    #[serde(alias = "usageMetadata")]
     pub usage_metadata: Option<UsageMetadata>,
+    /// The model version that generated the response
+    #[serde(alias = "modelVersion")]
+    pub model_version: Option<String>,
 }
 
 /// Candidate response from Gemini
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Candidate {
-    /// The content of the candidate
+    /// The content of the candidate; empty when the response was blocked
+    #[serde(default)]
     pub content: Content,
     /// The finish reason
+    #[serde(alias = "finishReason")]
     pub finish_reason: Option<String>,
     /// The index of the candidate
     pub index: i32,
@@ -121,7 +185,11 @@ pub struct SafetyRating {
 /// Prompt feedback
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PromptFeedback {
+    /// Why the prompt was blocked, if it was (e.g., "SAFETY")
+    #[serde(alias = "blockReason", default)]
+    pub block_reason: Option<String>,
     /// Safety ratings for the prompt
+    #[serde(alias = "safetyRatings", default)]
     pub safety_ratings: Vec<SafetyRating>,
 }
 
@@ -129,11 +197,52 @@ pub struct PromptFeedback {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UsageMetadata {
     /// Prompt token count
+    #[serde(alias = "promptTokenCount", default)]
     pub prompt_token_count: i32,
     /// Candidates token count
+    #[serde(alias = "candidatesTokenCount", default)]
     pub candidates_token_count: i32,
     /// Total token count
+    #[serde(alias = "totalTokenCount", default)]
     pub total_token_count: i32,
+    /// Prompt tokens served from a context cache, included in `prompt_token_count`
+    #[serde(alias = "cachedContentTokenCount", default)]
+    pub cached_content_token_count: i32,
+}
+
+impl UsageMetadata {
+    /// Returns the counts as a `TokenUsage` for the usage ledger
+    pub fn token_usage(&self) -> TokenUsage {
+        TokenUsage::new(
+            self.prompt_token_count.max(0) as u32,
+            self.candidates_token_count.max(0) as u32,
+        )
+        .cached(self.cached_content_token_count.max(0) as u32)
+    }
+}
+
+/// Model details returned by the `models.get` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// The resource name of the model (e.g., "models/gemini-1.5-pro")
+    pub name: String,
+    /// The human-readable name of the model
+    #[serde(alias = "displayName")]
+    pub display_name: Option<String>,
+    /// Maximum number of input tokens
+    #[serde(alias = "inputTokenLimit", default)]
+    pub input_token_limit: u32,
+    /// Maximum number of output tokens
+    #[serde(alias = "outputTokenLimit", default)]
+    pub output_token_limit: u32,
+}
+
+/// Response of the `countTokens` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CountTokensResponse {
+    /// Number of tokens the request's prompt uses
+    #[serde(alias = "totalTokens", default)]
+    pub total_tokens: u32,
 }
 
 /// Stream response structure for Gemini
@@ -144,7 +253,23 @@ pub struct StreamGenerateContentResponse {
     /// Prompt feedback
     pub prompt_feedback: Option<PromptFeedback>,
     /// Usage metadata
+    #[serde(alias = "usageMetadata")]
     pub usage_metadata: Option<UsageMetadata>,
+    /// The model version that generated the response
+    #[serde(alias = "modelVersion")]
+    pub model_version: Option<String>,
+}
+
+impl GenerateContentRequest {
+    /// Rough count of the input tokens in the request's text parts
+    pub fn estimate_tokens(&self) -> u32 {
+        self.contents
+            .iter()
+            .flat_map(|content| &content.parts)
+            .filter_map(|part| part.text.as_deref())
+            .map(estimate_tokens)
+            .sum()
+    }
 }
 
 impl GenerateContentResponse {
@@ -158,6 +283,41 @@ impl GenerateContentResponse {
                 .and_then(|part| part.text.clone())
         })
     }
+
+    /// Gets the function calls requested in the first candidate
+    pub fn function_calls(&self) -> Vec<FunctionCall> {
+        self.candidates
+            .first()
+            .map(|candidate| {
+                candidate
+                    .content
+                    .parts
+                    .iter()
+                    .filter_map(|part| part.function_call.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Gets the reason the prompt or the first candidate was blocked, if it was
+    ///
+    /// # Returns
+    ///
+    /// The prompt's `blockReason`, or a safety-related `finishReason` of the first candidate
+    /// (e.g., "SAFETY" or "PROHIBITED_CONTENT")
+    pub fn block_reason(&self) -> Option<&str> {
+        if let Some(reason) = self
+            .prompt_feedback
+            .as_ref()
+            .and_then(|feedback| feedback.block_reason.as_deref())
+        {
+            return Some(reason);
+        }
+        self.candidates
+            .first()
+            .and_then(|candidate| candidate.finish_reason.as_deref())
+            .filter(|reason| BLOCKED_FINISH_REASONS.contains(reason))
+    }
 }
 
 impl StreamGenerateContentResponse {
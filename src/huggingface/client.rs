@@ -0,0 +1,686 @@
+use crate::cache::{CacheMode, ResponseCache};
+use crate::huggingface::types::{
+    TgiGenerateRequest, TgiGenerateResponse, TgiInfo, TgiStreamResponse,
+};
+use crate::logging;
+use crate::middleware::{HttpError, Middleware, Request, Stack};
+use crate::ollama::types::{GenerateRequest, GenerateResponse};
+use crate::rate_limit::{RateLimitPermit, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
+use crate::sse::spawn_sse_stream;
+use crate::telemetry::GenAiSpan;
+use crate::utils::format_rfc3339;
+use futures_util::{Stream, StreamExt};
+use reqwest::header::HeaderMap;
+use reqwest::{Client, RequestBuilder};
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::{debug, error, info, trace, warn, Instrument};
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum HuggingFaceClientError {
+    /// Error related to the request
+    RequestError(String),
+    /// Network-related error
+    NetworkError(reqwest::Error),
+    /// Error while parsing JSON
+    ParseError(serde_json::Error),
+}
+
+impl fmt::Display for HuggingFaceClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HuggingFaceClientError::RequestError(msg) => write!(f, "Request error: {}", msg),
+            HuggingFaceClientError::NetworkError(err) => write!(f, "Network error: {}", err),
+            HuggingFaceClientError::ParseError(err) => write!(f, "Parse error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for HuggingFaceClientError {}
+
+impl HuggingFaceClientError {
+    /// Short error type recorded on the tracing span
+    pub(crate) fn error_type(&self) -> &'static str {
+        match self {
+            HuggingFaceClientError::RequestError(_) => "request",
+            HuggingFaceClientError::NetworkError(_) => "network",
+            HuggingFaceClientError::ParseError(_) => "parse",
+        }
+    }
+}
+
+impl From<reqwest::Error> for HuggingFaceClientError {
+    fn from(err: reqwest::Error) -> Self {
+        HuggingFaceClientError::NetworkError(err)
+    }
+}
+
+impl From<serde_json::Error> for HuggingFaceClientError {
+    fn from(err: serde_json::Error) -> Self {
+        HuggingFaceClientError::ParseError(err)
+    }
+}
+
+impl From<HttpError> for HuggingFaceClientError {
+    fn from(err: HttpError) -> Self {
+        match err {
+            HttpError::Status { body, .. } => HuggingFaceClientError::RequestError(body),
+            HttpError::Network(err) => HuggingFaceClientError::NetworkError(err),
+            HttpError::Middleware(msg) => HuggingFaceClientError::RequestError(msg),
+            HttpError::BudgetExceeded(err) => HuggingFaceClientError::RequestError(err.to_string()),
+        }
+    }
+}
+
+/// `gen_ai.system` value for TGI servers
+const GEN_AI_SYSTEM: &str = "huggingface.tgi";
+
+/// Model recorded for native requests, which do not name one since TGI serves a single model
+const NATIVE_MODEL: &str = "tgi";
+
+/// Client for interacting with Hugging Face Text Generation Inference (TGI)
+#[derive(Debug)]
+pub struct TgiClient {
+    base_url: String,
+    api_key: Option<Secret>,
+    client: Client,
+    stack: Stack,
+}
+
+impl TgiClient {
+    /// Creates a new instance of `TgiClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The base URL of the TGI server
+    /// * `api_key` - The Hugging Face token, if the endpoint requires one
+    ///
+    /// # Returns
+    ///
+    /// A new `TgiClient` instance
+    pub fn new(base_url: &str, api_key: Option<&str>) -> Self {
+        info!("Creating new TgiClient with base_url: {}", base_url);
+        TgiClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.map(Secret::new),
+            client: Client::new(),
+            stack: Stack::default(),
+        }
+    }
+
+    /// Sets the policy used to retry failed requests; without one, requests are not retried
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.stack.retry_policy = policy;
+        self
+    }
+
+    /// Sets a cache for model responses; cache hits skip the rate limiter and the server
+    pub fn cache(mut self, cache: ResponseCache) -> Self {
+        self.stack.cache = Some(cache);
+        self
+    }
+
+    /// Sets a rate limiter to wait on before each attempt; it may be shared with other clients
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.stack.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Adds a middleware; it runs inside the retries and the rate limiter, once per attempt
+    pub fn layer(mut self, middleware: impl Middleware) -> Self {
+        self.stack.layers.push(Arc::new(middleware));
+        self
+    }
+
+    fn post(&self, url: &str) -> reqwest::RequestBuilder {
+        let builder = self.client.post(url);
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key.expose()),
+            None => builder,
+        }
+    }
+
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        let builder = self.client.get(url);
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key.expose()),
+            None => builder,
+        }
+    }
+
+    /// Sends the request through the middleware stack
+    ///
+    /// Only model calls are cached.
+    async fn send(
+        &self,
+        builder: RequestBuilder,
+        model_call: bool,
+    ) -> Result<reqwest::Response, HttpError> {
+        let mut request = Request::new(builder.build()?);
+        if model_call {
+            request.extensions_mut().insert(CacheMode::current());
+        }
+        self.stack.send(&self.client, request).await
+    }
+
+    /// Checks if the TGI server is healthy
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the server is healthy, or a `HuggingFaceClientError`
+    pub async fn active(&self) -> Result<bool, HuggingFaceClientError> {
+        let url = format!("{}/health", self.base_url);
+        info!("Checking if the service is active at URL: {}", url);
+        match self.send(self.get(&url), false).await {
+            Ok(_) => {
+                info!("Service is active.");
+                Ok(true)
+            }
+            Err(HttpError::Status { status, .. }) => {
+                warn!("Service is not active. Status: {}", status);
+                Ok(false)
+            }
+            Err(err) => Err(HuggingFaceClientError::from(err)),
+        }
+    }
+
+    /// Gets information about the served model
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TgiInfo` or a `HuggingFaceClientError`
+    pub async fn info(&self) -> Result<TgiInfo, HuggingFaceClientError> {
+        let url = format!("{}/info", self.base_url);
+        info!("Getting server info with URL: {}", url);
+        let response = self.send(self.get(&url), false).await.map_err(|err| {
+            error!("Failed to get server info: {}", err);
+            HuggingFaceClientError::from(err)
+        })?;
+
+        let info: TgiInfo = response.json().await?;
+        debug!("TgiInfo: {:?}", info);
+        Ok(info)
+    }
+
+    /// Generates text with TGI's native request format
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `TgiGenerateRequest` containing the prompt and parameters
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TgiGenerateResponse` or a `HuggingFaceClientError`
+    pub async fn generate(
+        &self,
+        request: TgiGenerateRequest,
+    ) -> Result<TgiGenerateResponse, HuggingFaceClientError> {
+        self.generate_with_headers(request, NATIVE_MODEL)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    /// Generates text in a span for `model`, keeping the response headers for their timings
+    async fn generate_with_headers(
+        &self,
+        request: TgiGenerateRequest,
+        model: &str,
+    ) -> Result<(TgiGenerateResponse, HeaderMap), HuggingFaceClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "text_completion", model);
+        let span = telemetry.span().clone();
+        let result = self
+            .send_generate(request, &telemetry)
+            .instrument(span)
+            .await;
+        if let Err(err) = &result {
+            telemetry.error(err.error_type());
+        }
+        result
+    }
+
+    /// Sends a generation request, recording the response on `telemetry`
+    async fn send_generate(
+        &self,
+        request: TgiGenerateRequest,
+        telemetry: &GenAiSpan,
+    ) -> Result<(TgiGenerateResponse, HeaderMap), HuggingFaceClientError> {
+        let url = format!("{}/generate", self.base_url);
+        info!("Generating text with URL: {}", url);
+        trace!("TgiGenerateRequest: {}", logging::payload_json(&request));
+
+        let response = self
+            .send(self.post(&url).json(&request), true)
+            .await
+            .map_err(|err| {
+                error!("Failed to generate text: {}", err);
+                telemetry.http_error(&err);
+                HuggingFaceClientError::from(err)
+            })?;
+
+        let headers = response.headers().clone();
+        if let Some(permit) = response.extensions().get::<RateLimitPermit>().cloned() {
+            let prompt = header_u32(&headers, "x-prompt-tokens");
+            let generated = header_u32(&headers, "x-generated-tokens");
+            if let (Some(prompt), Some(generated)) = (prompt, generated) {
+                permit.reconcile(prompt + generated);
+            }
+        }
+        let response_text = response.text().await?;
+        trace!("Response text: {}", logging::payload(&response_text));
+        let generate_response: TgiGenerateResponse = serde_json::from_str(&response_text)?;
+        if let Some(details) = &generate_response.details {
+            telemetry.finish_reasons(&details.finish_reason);
+        }
+        telemetry.usage(
+            header_u32(&headers, "x-prompt-tokens"),
+            header_u32(&headers, "x-generated-tokens").or(generate_response
+                .details
+                .as_ref()
+                .map(|details| details.generated_tokens)),
+        );
+        info!("Successfully generated text.");
+        Ok((generate_response, headers))
+    }
+
+    /// Streams generated tokens with TGI's native request format
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `TgiGenerateRequest` containing the prompt and parameters
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `TgiStreamResponse` tokens or a `HuggingFaceClientError`
+    pub async fn stream_generate(
+        &self,
+        request: TgiGenerateRequest,
+    ) -> Result<
+        impl Stream<Item = Result<TgiStreamResponse, HuggingFaceClientError>>,
+        HuggingFaceClientError,
+    > {
+        self.stream_generate_for(request, NATIVE_MODEL).await
+    }
+
+    /// Streams generated tokens in a span for `model`
+    async fn stream_generate_for(
+        &self,
+        request: TgiGenerateRequest,
+        model: &str,
+    ) -> Result<
+        impl Stream<Item = Result<TgiStreamResponse, HuggingFaceClientError>>,
+        HuggingFaceClientError,
+    > {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "text_completion", model);
+        let span = telemetry.span().clone();
+        self.start_stream(request, telemetry).instrument(span).await
+    }
+
+    /// Opens a streaming request; `telemetry` moves into the stream and ends with it
+    async fn start_stream(
+        &self,
+        request: TgiGenerateRequest,
+        mut telemetry: GenAiSpan,
+    ) -> Result<
+        impl Stream<Item = Result<TgiStreamResponse, HuggingFaceClientError>>,
+        HuggingFaceClientError,
+    > {
+        let url = format!("{}/generate_stream", self.base_url);
+        info!("Streaming text with URL: {}", url);
+        trace!("StreamRequest: {}", logging::payload_json(&request));
+
+        // Only failures before the first chunk arrives are retried
+        let response = self
+            .send(self.post(&url).json(&request), true)
+            .await
+            .map_err(|err| {
+                error!("Failed to start streaming: {}", err);
+                telemetry.http_error(&err);
+                HuggingFaceClientError::from(err)
+            })?;
+
+        Ok(spawn_sse_stream(
+            response,
+            move |event| match serde_json::from_str::<TgiStreamResponse>(&event.data) {
+                Ok(token) => {
+                    telemetry.first_token();
+                    if let Some(details) = &token.details {
+                        telemetry.finish_reasons(&details.finish_reason);
+                        telemetry.usage(None, Some(details.generated_tokens));
+                    }
+                    Some(Ok(token))
+                }
+                Err(e) => {
+                    error!("Failed to parse stream event: {}", e);
+                    telemetry.error("parse");
+                    Some(Err(HuggingFaceClientError::ParseError(e)))
+                }
+            },
+        ))
+    }
+
+    /// Generates a completion from an Ollama-style `GenerateRequest`
+    ///
+    /// Ollama `options` such as `num_predict`, `temperature`, `top_p`, `top_k`,
+    /// `repeat_penalty`, `seed` and `stop` are translated to TGI parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the model and prompt
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GenerateResponse` or a `HuggingFaceClientError`
+    pub async fn generate_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<GenerateResponse, HuggingFaceClientError> {
+        let (response, headers) = self
+            .generate_with_headers(TgiGenerateRequest::from(&request), &request.model)
+            .await?;
+
+        let details = response.details.as_ref();
+        let generate_response = GenerateResponse {
+            model: request.model,
+            created_at: format_rfc3339(SystemTime::now()),
+            response: response.generated_text.clone(),
+            done: true,
+            done_reason: details.map(|details| map_finish_reason(&details.finish_reason)),
+            context: None,
+            total_duration: header_millis_as_nanos(&headers, "x-total-time"),
+            load_duration: None,
+            prompt_eval_count: details
+                .filter(|details| !details.prefill.is_empty())
+                .map(|details| details.prefill.len() as u32),
+            prompt_eval_duration: None,
+            eval_count: details.map(|details| details.generated_tokens),
+            eval_duration: header_millis_as_nanos(&headers, "x-inference-time"),
+        };
+        trace!(
+            "GenerateResponse: {}",
+            logging::payload_json(&generate_response)
+        );
+        Ok(generate_response)
+    }
+
+    /// Streams a completion from an Ollama-style `GenerateRequest` chunk by chunk
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the model and prompt
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `GenerateResponse` chunks or a `HuggingFaceClientError`
+    pub async fn stream_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<
+        impl Stream<Item = Result<GenerateResponse, HuggingFaceClientError>>,
+        HuggingFaceClientError,
+    > {
+        let model = request.model.clone();
+        let stream = self
+            .stream_generate_for(TgiGenerateRequest::from(&request), &model)
+            .await?;
+
+        Ok(stream.map(move |event| {
+            event.map(|event| {
+                let details = event.details.as_ref();
+                GenerateResponse {
+                    model: model.clone(),
+                    created_at: format_rfc3339(SystemTime::now()),
+                    response: if event.token.special {
+                        String::new()
+                    } else {
+                        event.token.text.clone()
+                    },
+                    done: event.generated_text.is_some(),
+                    done_reason: details.map(|details| map_finish_reason(&details.finish_reason)),
+                    context: None,
+                    total_duration: None,
+                    load_duration: None,
+                    prompt_eval_count: None,
+                    prompt_eval_duration: None,
+                    eval_count: details.map(|details| details.generated_tokens),
+                    eval_duration: None,
+                }
+            })
+        }))
+    }
+}
+
+/// Maps TGI finish reasons onto Ollama's `done_reason` values
+fn map_finish_reason(finish_reason: &str) -> String {
+    match finish_reason {
+        "length" => "length".to_string(),
+        _ => "stop".to_string(),
+    }
+}
+
+/// Reads a numeric header
+fn header_u32(headers: &HeaderMap, name: &str) -> Option<u32> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u32>().ok())
+}
+
+/// Reads a millisecond timing header as nanoseconds
+fn header_millis_as_nanos(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(|millis| millis * 1_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::huggingface::types::TgiParameters;
+    use mockito::Matcher;
+
+    fn client(server: &mockito::Server) -> TgiClient {
+        TgiClient::new(&server.url(), Some("hf_token")).retry_policy(RetryPolicy::none())
+    }
+
+    fn ollama_request() -> GenerateRequest {
+        GenerateRequest {
+            model: "tgi".to_string(),
+            prompt: "Hello".to_string(),
+            options: Some(serde_json::json!({
+                "num_predict": 16,
+                "temperature": 0.5,
+                "stop": ["\n"],
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn generate_round_trip() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/generate")
+            .match_header("authorization", "Bearer hf_token")
+            .match_body(Matcher::Json(serde_json::json!({
+                "inputs": "Hello",
+                "parameters": {"max_new_tokens": 5},
+            })))
+            .with_body(
+                r#"{"generated_text": " world", "details": {"finish_reason": "eos_token", "generated_tokens": 2, "seed": null}}"#,
+            )
+            .create_async()
+            .await;
+
+        let request = TgiGenerateRequest {
+            inputs: "Hello".to_string(),
+            parameters: TgiParameters {
+                max_new_tokens: Some(5),
+                ..Default::default()
+            },
+        };
+        let response = client(&server).generate(request).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(response.generated_text, " world");
+        assert_eq!(response.details.unwrap().generated_tokens, 2);
+    }
+
+    #[tokio::test]
+    async fn generate_completion_maps_options_and_timings() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/generate")
+            .match_body(Matcher::Json(serde_json::json!({
+                "inputs": "Hello",
+                "parameters": {
+                    "max_new_tokens": 16,
+                    "temperature": 0.5,
+                    "stop": ["\n"],
+                    "details": true,
+                },
+            })))
+            .with_header("x-total-time", "120")
+            .with_header("x-inference-time", "100")
+            .with_body(
+                r#"{"generated_text": "Hi", "details": {"finish_reason": "length", "generated_tokens": 16}}"#,
+            )
+            .create_async()
+            .await;
+
+        let response = client(&server)
+            .generate_completion(ollama_request())
+            .await
+            .unwrap();
+
+        assert_eq!(response.model, "tgi");
+        assert_eq!(response.response, "Hi");
+        assert!(response.done);
+        assert_eq!(response.done_reason.as_deref(), Some("length"));
+        assert_eq!(response.eval_count, Some(16));
+        assert_eq!(response.total_duration, Some(120_000_000));
+        assert_eq!(response.eval_duration, Some(100_000_000));
+    }
+
+    #[tokio::test]
+    async fn generate_reports_error_bodies() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/generate")
+            .with_status(422)
+            .with_body(r#"{"error":"Input validation error: `inputs` must not be empty","error_type":"validation"}"#)
+            .create_async()
+            .await;
+
+        match client(&server)
+            .generate(TgiGenerateRequest::default())
+            .await
+        {
+            Err(HuggingFaceClientError::RequestError(body)) => {
+                assert!(body.contains("Input validation error"))
+            }
+            other => panic!("expected a request error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_completion_maps_tokens() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/generate_stream")
+            .with_header("content-type", "text/event-stream")
+            .with_body(concat!(
+                "data:{\"token\":{\"id\":1,\"text\":\"Hi\",\"logprob\":-0.1,\"special\":false},\"generated_text\":null,\"details\":null}\n\n",
+                "data:{\"token\":{\"id\":2,\"text\":\"</s>\",\"logprob\":-0.2,\"special\":true},\"generated_text\":\"Hi\",",
+                "\"details\":{\"finish_reason\":\"eos_token\",\"generated_tokens\":2,\"seed\":null}}\n\n",
+            ))
+            .create_async()
+            .await;
+
+        let chunks: Vec<GenerateResponse> = client(&server)
+            .stream_completion(ollama_request())
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].response, "Hi");
+        assert!(!chunks[0].done);
+        // Special tokens are not part of the text
+        assert_eq!(chunks[1].response, "");
+        assert!(chunks[1].done);
+        assert_eq!(chunks[1].done_reason.as_deref(), Some("stop"));
+        assert_eq!(chunks[1].eval_count, Some(2));
+    }
+
+    #[tokio::test]
+    async fn stream_generate_reports_bad_events_and_errors() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/generate_stream")
+            .with_body("data:{\"error\":\"overloaded\"}\n\n")
+            .create_async()
+            .await;
+
+        let mut stream = Box::pin(
+            client(&server)
+                .stream_generate(TgiGenerateRequest::default())
+                .await
+                .unwrap(),
+        );
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(HuggingFaceClientError::ParseError(_)))
+        ));
+
+        server
+            .mock("POST", "/generate_stream")
+            .with_status(503)
+            .with_body("Model is loading")
+            .create_async()
+            .await;
+        match client(&server)
+            .stream_generate(TgiGenerateRequest::default())
+            .await
+        {
+            Err(HuggingFaceClientError::RequestError(body)) => assert_eq!(body, "Model is loading"),
+            Err(err) => panic!("expected a request error, got {:?}", err),
+            Ok(_) => panic!("expected a request error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn health_and_info() {
+        let mut server = mockito::Server::new_async().await;
+        server.mock("GET", "/health").create_async().await;
+        server
+            .mock("GET", "/info")
+            .with_body(r#"{"model_id": "bigscience/bloom-560m", "max_total_tokens": 2048}"#)
+            .create_async()
+            .await;
+
+        let client = client(&server);
+        assert!(client.active().await.unwrap());
+        let info = client.info().await.unwrap();
+        assert_eq!(info.model_id, "bigscience/bloom-560m");
+        assert_eq!(info.max_total_tokens, Some(2048));
+
+        let mut down = mockito::Server::new_async().await;
+        down.mock("GET", "/health")
+            .with_status(503)
+            .create_async()
+            .await;
+        assert!(!TgiClient::new(&down.url(), None)
+            .retry_policy(RetryPolicy::none())
+            .active()
+            .await
+            .unwrap());
+    }
+}
@@ -0,0 +1,10 @@
+pub mod client;
+pub mod tei;
+pub mod types;
+
+pub use client::{HuggingFaceClientError, TgiClient};
+pub use tei::TeiClient;
+pub use types::{
+    TeiRank, TgiDetails, TgiGenerateRequest, TgiGenerateResponse, TgiInfo, TgiParameters,
+    TgiStreamDetails, TgiStreamResponse, TgiToken,
+};
@@ -0,0 +1,303 @@
+use crate::cache::{CacheMode, ResponseCache};
+use crate::huggingface::client::HuggingFaceClientError;
+use crate::huggingface::types::{TeiEmbedRequest, TeiRank, TeiRerankRequest};
+use crate::middleware::{HttpError, Middleware, Request, Stack};
+use crate::rate_limit::RateLimiter;
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
+use crate::telemetry::GenAiSpan;
+use reqwest::{Client, RequestBuilder};
+use std::sync::Arc;
+use tracing::{debug, error, info, warn, Instrument};
+
+/// `gen_ai.system` value for TEI servers
+const GEN_AI_SYSTEM: &str = "huggingface.tei";
+
+/// Model recorded on spans, since TEI serves a single model and requests do not name it
+const MODEL: &str = "tei";
+
+/// Client for interacting with Hugging Face Text Embeddings Inference (TEI)
+#[derive(Debug)]
+pub struct TeiClient {
+    base_url: String,
+    api_key: Option<Secret>,
+    client: Client,
+    stack: Stack,
+}
+
+impl TeiClient {
+    /// Creates a new instance of `TeiClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The base URL of the TEI server
+    /// * `api_key` - The Hugging Face token, if the endpoint requires one
+    ///
+    /// # Returns
+    ///
+    /// A new `TeiClient` instance
+    pub fn new(base_url: &str, api_key: Option<&str>) -> Self {
+        info!("Creating new TeiClient with base_url: {}", base_url);
+        TeiClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.map(Secret::new),
+            client: Client::new(),
+            stack: Stack::default(),
+        }
+    }
+
+    /// Sets the policy used to retry failed requests; without one, requests are not retried
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.stack.retry_policy = policy;
+        self
+    }
+
+    /// Sets a cache for model responses; cache hits skip the rate limiter and the server
+    pub fn cache(mut self, cache: ResponseCache) -> Self {
+        self.stack.cache = Some(cache);
+        self
+    }
+
+    /// Sets a rate limiter to wait on before each attempt; it may be shared with other clients
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.stack.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Adds a middleware; it runs inside the retries and the rate limiter, once per attempt
+    pub fn layer(mut self, middleware: impl Middleware) -> Self {
+        self.stack.layers.push(Arc::new(middleware));
+        self
+    }
+
+    fn post(&self, url: &str) -> reqwest::RequestBuilder {
+        let builder = self.client.post(url);
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key.expose()),
+            None => builder,
+        }
+    }
+
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        let builder = self.client.get(url);
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key.expose()),
+            None => builder,
+        }
+    }
+
+    /// Sends the request through the middleware stack
+    ///
+    /// Only model calls are cached.
+    async fn send(
+        &self,
+        builder: RequestBuilder,
+        model_call: bool,
+    ) -> Result<reqwest::Response, HttpError> {
+        let mut request = Request::new(builder.build()?);
+        if model_call {
+            request.extensions_mut().insert(CacheMode::current());
+        }
+        self.stack.send(&self.client, request).await
+    }
+
+    /// Checks if the TEI server is healthy
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the server is healthy, or a `HuggingFaceClientError`
+    pub async fn active(&self) -> Result<bool, HuggingFaceClientError> {
+        let url = format!("{}/health", self.base_url);
+        info!("Checking if the service is active at URL: {}", url);
+        match self.send(self.get(&url), false).await {
+            Ok(_) => {
+                info!("Service is active.");
+                Ok(true)
+            }
+            Err(HttpError::Status { status, .. }) => {
+                warn!("Service is not active. Status: {}", status);
+                Ok(false)
+            }
+            Err(err) => Err(HuggingFaceClientError::from(err)),
+        }
+    }
+
+    /// Creates embeddings for the given texts
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The texts to embed
+    /// * `normalize` - Whether to L2-normalize the embeddings
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing one embedding per input or a `HuggingFaceClientError`
+    pub async fn embed(
+        &self,
+        inputs: &[&str],
+        normalize: bool,
+    ) -> Result<Vec<Vec<f32>>, HuggingFaceClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "embeddings", MODEL);
+        let span = telemetry.span().clone();
+        let result = self
+            .send_embed(inputs, normalize, &telemetry)
+            .instrument(span)
+            .await;
+        if let Err(err) = &result {
+            telemetry.error(err.error_type());
+        }
+        result
+    }
+
+    /// Sends an embed request, recording failures on `telemetry`
+    async fn send_embed(
+        &self,
+        inputs: &[&str],
+        normalize: bool,
+        telemetry: &GenAiSpan,
+    ) -> Result<Vec<Vec<f32>>, HuggingFaceClientError> {
+        let url = format!("{}/embed", self.base_url);
+        info!("Creating embeddings with URL: {}", url);
+
+        let request = TeiEmbedRequest {
+            inputs: inputs.iter().map(|input| input.to_string()).collect(),
+            normalize,
+            truncate: true,
+        };
+
+        let response = self
+            .send(self.post(&url).json(&request), true)
+            .await
+            .map_err(|err| {
+                error!("Failed to create embeddings: {}", err);
+                telemetry.http_error(&err);
+                HuggingFaceClientError::from(err)
+            })?;
+
+        let embeddings: Vec<Vec<f32>> = response.json().await?;
+        info!("Successfully created {} embeddings.", embeddings.len());
+        Ok(embeddings)
+    }
+
+    /// Ranks texts by relevance to a query using a cross-encoder
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The search query
+    /// * `texts` - The texts to rank
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TeiRank`s, most relevant first, or a `HuggingFaceClientError`
+    pub async fn rerank(
+        &self,
+        query: &str,
+        texts: &[&str],
+    ) -> Result<Vec<TeiRank>, HuggingFaceClientError> {
+        let url = format!("{}/rerank", self.base_url);
+        info!("Reranking {} texts with URL: {}", texts.len(), url);
+
+        let request = TeiRerankRequest {
+            query: query.to_string(),
+            texts: texts.iter().map(|text| text.to_string()).collect(),
+            raw_scores: false,
+            truncate: true,
+        };
+
+        let response = self
+            .send(self.post(&url).json(&request), true)
+            .await
+            .map_err(|err| {
+                error!("Failed to rerank texts: {}", err);
+                HuggingFaceClientError::from(err)
+            })?;
+
+        let ranks: Vec<TeiRank> = response.json().await?;
+        debug!("TeiRanks: {:?}", ranks);
+        Ok(ranks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    fn client(server: &mockito::Server) -> TeiClient {
+        TeiClient::new(&server.url(), None).retry_policy(RetryPolicy::none())
+    }
+
+    #[tokio::test]
+    async fn embed_round_trip() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/embed")
+            .match_body(Matcher::Json(serde_json::json!({
+                "inputs": ["a", "b"],
+                "normalize": true,
+                "truncate": true,
+            })))
+            .with_body("[[0.6, 0.8], [1.0, 0.0]]")
+            .create_async()
+            .await;
+
+        let embeddings = client(&server).embed(&["a", "b"], true).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(embeddings, vec![vec![0.6, 0.8], vec![1.0, 0.0]]);
+    }
+
+    #[tokio::test]
+    async fn embed_reports_error_bodies() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/embed")
+            .with_status(413)
+            .with_body(r#"{"error":"batch size 64 > maximum allowed batch size 32","error_type":"Validation"}"#)
+            .create_async()
+            .await;
+
+        match client(&server).embed(&["a"], false).await {
+            Err(HuggingFaceClientError::RequestError(body)) => {
+                assert!(body.contains("maximum allowed batch size"))
+            }
+            other => panic!("expected a request error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn rerank_round_trip() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/rerank")
+            .match_header("authorization", "Bearer hf_token")
+            .match_body(Matcher::Json(serde_json::json!({
+                "query": "capital of France",
+                "texts": ["Berlin", "Paris"],
+                "raw_scores": false,
+                "truncate": true,
+            })))
+            .with_body(r#"[{"index": 1, "score": 0.99}, {"index": 0, "score": 0.01}]"#)
+            .create_async()
+            .await;
+
+        let ranks = TeiClient::new(&server.url(), Some("hf_token"))
+            .rerank("capital of France", &["Berlin", "Paris"])
+            .await
+            .unwrap();
+        assert_eq!(ranks.len(), 2);
+        assert_eq!(ranks[0].index, 1);
+        assert!(ranks[0].score > ranks[1].score);
+    }
+
+    #[tokio::test]
+    async fn active_reports_unhealthy_servers() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/health")
+            .with_status(503)
+            .create_async()
+            .await;
+        assert!(!client(&server).active().await.unwrap());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::ollama::types::GenerateRequest;
+use serde::{Deserialize, Serialize};
+
+/// Request structure for TGI's `/generate` and `/generate_stream` endpoints
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TgiGenerateRequest {
+    /// The prompt
+    pub inputs: String,
+    /// Generation parameters
+    pub parameters: TgiParameters,
+}
+
+impl From<&GenerateRequest> for TgiGenerateRequest {
+    /// Maps an Ollama-style request onto TGI, translating the common Ollama `options`
+    fn from(request: &GenerateRequest) -> Self {
+        let option = |name: &str| {
+            request
+                .options
+                .as_ref()
+                .and_then(|options| options.get(name))
+        };
+        let as_f32 = |name: &str| {
+            option(name)
+                .and_then(|value| value.as_f64())
+                .map(|v| v as f32)
+        };
+        let as_u32 = |name: &str| {
+            option(name)
+                .and_then(|value| value.as_u64())
+                .map(|v| v as u32)
+        };
+
+        TgiGenerateRequest {
+            inputs: request.prompt.clone(),
+            parameters: TgiParameters {
+                max_new_tokens: as_u32("num_predict"),
+                temperature: as_f32("temperature"),
+                top_p: as_f32("top_p"),
+                top_k: as_u32("top_k"),
+                repetition_penalty: as_f32("repeat_penalty"),
+                seed: option("seed").and_then(|value| value.as_u64()),
+                stop: option("stop").and_then(|value| serde_json::from_value(value.clone()).ok()),
+                details: Some(true),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Generation parameters for TGI
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TgiParameters {
+    /// Maximum number of tokens to generate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_new_tokens: Option<u32>,
+    /// Temperature for generation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Top-p sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Top-k sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    /// Repetition penalty
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repetition_penalty: Option<f32>,
+    /// Whether to sample instead of greedy decoding
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub do_sample: Option<bool>,
+    /// Seed for deterministic sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Stop sequences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    /// Whether to return token-level details
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<bool>,
+    /// Whether to return details for the prompt tokens too
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoder_input_details: Option<bool>,
+    /// Number of most likely tokens to return per step
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_n_tokens: Option<u32>,
+    /// Grammar constraint (`{"type": "json", "value": schema}` or `{"type": "regex", ...}`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grammar: Option<serde_json::Value>,
+}
+
+/// Response structure for TGI's `/generate` endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TgiGenerateResponse {
+    /// The generated text
+    pub generated_text: String,
+    /// Token-level details, when requested
+    pub details: Option<TgiDetails>,
+}
+
+/// Token-level details of a generation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TgiDetails {
+    /// Why generation stopped ("length", "eos_token" or "stop_sequence")
+    pub finish_reason: String,
+    /// Number of generated tokens
+    pub generated_tokens: u32,
+    /// The seed used for sampling
+    pub seed: Option<u64>,
+    /// Prompt tokens, when `decoder_input_details` was set
+    #[serde(default)]
+    pub prefill: Vec<TgiToken>,
+    /// Generated tokens with their log-probabilities
+    #[serde(default)]
+    pub tokens: Vec<TgiToken>,
+    /// Most likely alternatives per step, when `top_n_tokens` was set
+    #[serde(default)]
+    pub top_tokens: Vec<Vec<TgiToken>>,
+}
+
+/// A token with its log-probability
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TgiToken {
+    /// The token id
+    pub id: u32,
+    /// The token text
+    pub text: String,
+    /// Log-probability of the token
+    pub logprob: Option<f32>,
+    /// Whether the token is a special token
+    #[serde(default)]
+    pub special: bool,
+}
+
+/// Event streamed by TGI's `/generate_stream` endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TgiStreamResponse {
+    /// The generated token
+    pub token: TgiToken,
+    /// Most likely alternatives for this step
+    #[serde(default)]
+    pub top_tokens: Vec<TgiToken>,
+    /// The full generated text, sent with the last event
+    pub generated_text: Option<String>,
+    /// Generation details, sent with the last event
+    pub details: Option<TgiStreamDetails>,
+}
+
+/// Details sent with the last streamed event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TgiStreamDetails {
+    /// Why generation stopped
+    pub finish_reason: String,
+    /// Number of generated tokens
+    pub generated_tokens: u32,
+    /// The seed used for sampling
+    pub seed: Option<u64>,
+}
+
+/// Model and server information returned by TGI's `/info` endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TgiInfo {
+    /// The served model
+    pub model_id: String,
+    /// The model revision
+    pub model_sha: Option<String>,
+    /// The model dtype
+    pub model_dtype: Option<String>,
+    /// The model device type
+    pub model_device_type: Option<String>,
+    /// Maximum number of input tokens
+    #[serde(alias = "max_input_length")]
+    pub max_input_tokens: Option<u32>,
+    /// Maximum number of input plus generated tokens
+    pub max_total_tokens: Option<u32>,
+    /// Maximum number of concurrent requests
+    pub max_concurrent_requests: Option<u32>,
+    /// Server version
+    pub version: Option<String>,
+}
+
+/// Request structure for TEI's `/embed` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeiEmbedRequest {
+    /// The texts to embed
+    pub inputs: Vec<String>,
+    /// Whether to L2-normalize the embeddings
+    pub normalize: bool,
+    /// Whether to truncate inputs longer than the model's limit
+    pub truncate: bool,
+}
+
+/// Request structure for TEI's `/rerank` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeiRerankRequest {
+    /// The search query
+    pub query: String,
+    /// The texts to rank
+    pub texts: Vec<String>,
+    /// Whether to return raw logits instead of sigmoid scores
+    pub raw_scores: bool,
+    /// Whether to truncate inputs longer than the model's limit
+    pub truncate: bool,
+}
+
+/// A single TEI rerank result
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TeiRank {
+    /// Index of the text in the request
+    pub index: usize,
+    /// Relevance score
+    pub score: f32,
+}
@@ -1,12 +1,61 @@
+pub mod agent;
+pub mod bedrock;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+pub mod cassette;
+pub mod circuit;
+pub mod cohere;
+pub mod conversation;
 pub mod gemini;
+mod http;
+pub mod huggingface;
+pub mod llamacpp;
+#[cfg(feature = "local")]
+pub mod local;
+pub mod logging;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod middleware;
+pub mod mistral;
 pub mod ollama;
+pub mod prompt;
+pub mod rate_limit;
+pub mod retry;
+pub mod routing;
+pub mod secret;
+pub mod sse;
+pub mod telemetry;
+pub mod tokens;
+pub mod usage;
+mod utils;
 
+pub use agent::{Agent, ToolRegistry};
+pub use bedrock::BedrockClient;
+pub use cache::ResponseCache;
+pub use cassette::Cassette;
+pub use circuit::CircuitBreaker;
+pub use cohere::CohereClient;
+pub use conversation::Conversation;
 pub use gemini::{
-    Candidate, Content, GeminiClient, GenerateContentRequest, GenerateContentResponse,
-    GenerationConfig, InlineData, Part, SafetyRating, SafetySetting, StreamGenerateContentResponse,
-    Tool, UsageMetadata,
+    Candidate, Content, FunctionCall, FunctionDeclaration, FunctionResponse, GeminiClient,
+    GeminiClientBuilder, GenerateContentRequest, GenerateContentResponse, GenerationConfig,
+    InlineData, Part, SafetyRating, SafetySetting, StreamGenerateContentResponse, Tool,
+    UsageMetadata,
 };
-pub use ollama::OllamaClient;
+pub use huggingface::{TeiClient, TgiClient};
+pub use llamacpp::LlamaCppClient;
+#[cfg(feature = "local")]
+pub use local::LocalClient;
+pub use middleware::Middleware;
+pub use mistral::MistralClient;
+pub use ollama::{OllamaClient, OllamaClientBuilder, OllamaPool};
+pub use prompt::{PromptLibrary, PromptTemplate};
+pub use rate_limit::RateLimiter;
+pub use retry::RetryPolicy;
+pub use routing::Router;
+pub use secret::Secret;
+pub use usage::UsageLedger;
 
 use dotenv::dotenv;
 
@@ -0,0 +1,541 @@
+use crate::cache::{CacheMode, ResponseCache};
+use crate::llamacpp::types::{
+    CompletionRequest, CompletionResponse, DetokenizeRequest, DetokenizeResponse, EmbeddingRequest,
+    EmbeddingResponse, HealthStatus, SlotActionResponse, SlotInfo, TokenizeRequest,
+    TokenizeResponse,
+};
+use crate::logging;
+use crate::middleware::{HttpError, Middleware, Request, Stack};
+use crate::rate_limit::{RateLimitPermit, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
+use crate::sse::spawn_sse_stream;
+use crate::telemetry::GenAiSpan;
+use futures_util::Stream;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::json;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{debug, error, info, trace, warn, Instrument};
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum LlamaCppClientError {
+    /// Error related to the request
+    RequestError(String),
+    /// Network-related error
+    NetworkError(reqwest::Error),
+    /// Error while parsing JSON
+    ParseError(serde_json::Error),
+}
+
+impl fmt::Display for LlamaCppClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LlamaCppClientError::RequestError(msg) => write!(f, "Request error: {}", msg),
+            LlamaCppClientError::NetworkError(err) => write!(f, "Network error: {}", err),
+            LlamaCppClientError::ParseError(err) => write!(f, "Parse error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for LlamaCppClientError {}
+
+impl LlamaCppClientError {
+    /// Short error type recorded on the tracing span
+    fn error_type(&self) -> &'static str {
+        match self {
+            LlamaCppClientError::RequestError(_) => "request",
+            LlamaCppClientError::NetworkError(_) => "network",
+            LlamaCppClientError::ParseError(_) => "parse",
+        }
+    }
+}
+
+impl From<reqwest::Error> for LlamaCppClientError {
+    fn from(err: reqwest::Error) -> Self {
+        LlamaCppClientError::NetworkError(err)
+    }
+}
+
+impl From<serde_json::Error> for LlamaCppClientError {
+    fn from(err: serde_json::Error) -> Self {
+        LlamaCppClientError::ParseError(err)
+    }
+}
+
+impl From<HttpError> for LlamaCppClientError {
+    fn from(err: HttpError) -> Self {
+        match err {
+            HttpError::Status { body, .. } => LlamaCppClientError::RequestError(body),
+            HttpError::Network(err) => LlamaCppClientError::NetworkError(err),
+            HttpError::Middleware(msg) => LlamaCppClientError::RequestError(msg),
+            HttpError::BudgetExceeded(err) => LlamaCppClientError::RequestError(err.to_string()),
+        }
+    }
+}
+
+/// `gen_ai.system` value for llama.cpp servers
+const GEN_AI_SYSTEM: &str = "llama.cpp";
+
+/// Model recorded on spans, since the server runs a single model and requests do not name it
+const MODEL: &str = "llama.cpp";
+
+/// Client for interacting with the native llama.cpp server API
+#[derive(Debug)]
+pub struct LlamaCppClient {
+    base_url: String,
+    api_key: Option<Secret>,
+    client: Client,
+    stack: Stack,
+}
+
+impl LlamaCppClient {
+    /// Creates a new instance of `LlamaCppClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The base URL of the llama.cpp server (e.g., "http://localhost:8080")
+    /// * `api_key` - The API key if the server was started with `--api-key`
+    ///
+    /// # Returns
+    ///
+    /// A new `LlamaCppClient` instance
+    pub fn new(base_url: &str, api_key: Option<&str>) -> Self {
+        info!("Creating new LlamaCppClient with base_url: {}", base_url);
+        LlamaCppClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.map(Secret::new),
+            client: Client::new(),
+            stack: Stack::default(),
+        }
+    }
+
+    /// Sets the policy used to retry failed requests; without one, requests are not retried
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.stack.retry_policy = policy;
+        self
+    }
+
+    /// Sets a cache for model responses; cache hits skip the rate limiter and the server
+    pub fn cache(mut self, cache: ResponseCache) -> Self {
+        self.stack.cache = Some(cache);
+        self
+    }
+
+    /// Sets a rate limiter to wait on before each attempt; it may be shared with other clients
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.stack.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Adds a middleware; it runs inside the retries and the rate limiter, once per attempt
+    pub fn layer(mut self, middleware: impl Middleware) -> Self {
+        self.stack.layers.push(Arc::new(middleware));
+        self
+    }
+
+    fn post(&self, url: &str) -> reqwest::RequestBuilder {
+        let builder = self.client.post(url);
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key.expose()),
+            None => builder,
+        }
+    }
+
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        let builder = self.client.get(url);
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key.expose()),
+            None => builder,
+        }
+    }
+
+    /// Sends the request through the middleware stack
+    ///
+    /// Only model calls are cached.
+    async fn send(
+        &self,
+        builder: RequestBuilder,
+        model_call: bool,
+    ) -> Result<reqwest::Response, HttpError> {
+        let mut request = Request::new(builder.build()?);
+        if model_call {
+            request.extensions_mut().insert(CacheMode::current());
+        }
+        self.stack.send(&self.client, request).await
+    }
+
+    /// Checks the health of the server
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `HealthStatus` or a `LlamaCppClientError`
+    pub async fn health(&self) -> Result<HealthStatus, LlamaCppClientError> {
+        let url = format!("{}/health", self.base_url);
+        info!("Checking server health at URL: {}", url);
+        let status = match self.send(self.get(&url), false).await {
+            Ok(_) => HealthStatus::Ok,
+            Err(HttpError::Status {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                ..
+            }) => HealthStatus::Loading,
+            Err(HttpError::Status { status, .. }) => {
+                warn!("Server reported an error. Status: {}", status);
+                HealthStatus::Error
+            }
+            Err(err) => return Err(LlamaCppClientError::from(err)),
+        };
+        debug!("HealthStatus: {:?}", status);
+        Ok(status)
+    }
+
+    /// Generates a completion, optionally constrained by a grammar or JSON schema
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `CompletionRequest` containing the prompt and sampling settings
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `CompletionResponse` or a `LlamaCppClientError`
+    pub async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse, LlamaCppClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "text_completion", MODEL);
+        let span = telemetry.span().clone();
+        let result = self
+            .send_completion(request, &telemetry)
+            .instrument(span)
+            .await;
+        if let Err(err) = &result {
+            telemetry.error(err.error_type());
+        }
+        result
+    }
+
+    /// Sends a completion request, recording the response on `telemetry`
+    async fn send_completion(
+        &self,
+        request: CompletionRequest,
+        telemetry: &GenAiSpan,
+    ) -> Result<CompletionResponse, LlamaCppClientError> {
+        let url = format!("{}/completion", self.base_url);
+        info!("Generating completion with URL: {}", url);
+        trace!("CompletionRequest: {}", logging::payload_json(&request));
+
+        let mut body = serde_json::to_value(&request)?;
+        body["stream"] = json!(false);
+
+        let response = self
+            .send(self.post(&url).json(&body), true)
+            .await
+            .map_err(|err| {
+                error!("Failed to generate completion: {}", err);
+                telemetry.http_error(&err);
+                LlamaCppClientError::from(err)
+            })?;
+        let permit = response.extensions().get::<RateLimitPermit>().cloned();
+
+        let response_text = response.text().await?;
+        trace!("Response text: {}", logging::payload(&response_text));
+        let completion: CompletionResponse = serde_json::from_str(&response_text)?;
+        if let (Some(permit), Some(total)) = (permit, total_tokens(&completion)) {
+            permit.reconcile(total);
+        }
+        record_response(telemetry, &completion);
+        info!("Successfully generated completion.");
+        Ok(completion)
+    }
+
+    /// Streams a completion chunk by chunk
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `CompletionRequest` containing the prompt and sampling settings
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `CompletionResponse` chunks or a `LlamaCppClientError`
+    pub async fn stream_completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<
+        impl Stream<Item = Result<CompletionResponse, LlamaCppClientError>>,
+        LlamaCppClientError,
+    > {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "text_completion", MODEL);
+        let span = telemetry.span().clone();
+        self.start_stream(request, telemetry).instrument(span).await
+    }
+
+    /// Opens a streaming request; `telemetry` moves into the stream and ends with it
+    async fn start_stream(
+        &self,
+        request: CompletionRequest,
+        mut telemetry: GenAiSpan,
+    ) -> Result<
+        impl Stream<Item = Result<CompletionResponse, LlamaCppClientError>>,
+        LlamaCppClientError,
+    > {
+        let url = format!("{}/completion", self.base_url);
+        info!("Streaming completion with URL: {}", url);
+        trace!("StreamRequest: {}", logging::payload_json(&request));
+
+        let mut body = serde_json::to_value(&request)?;
+        body["stream"] = json!(true);
+
+        // Only failures before the first chunk arrives are retried
+        let response = self
+            .send(self.post(&url).json(&body), true)
+            .await
+            .map_err(|err| {
+                error!("Failed to start streaming: {}", err);
+                telemetry.http_error(&err);
+                LlamaCppClientError::from(err)
+            })?;
+        let mut permit = response.extensions().get::<RateLimitPermit>().cloned();
+
+        Ok(spawn_sse_stream(
+            response,
+            move |event| match serde_json::from_str::<CompletionResponse>(&event.data) {
+                Ok(chunk) => {
+                    telemetry.first_token();
+                    if let Some(total) = total_tokens(&chunk) {
+                        if let Some(permit) = permit.take() {
+                            permit.reconcile(total);
+                        }
+                    }
+                    if chunk.stop {
+                        record_response(&telemetry, &chunk);
+                    }
+                    Some(Ok(chunk))
+                }
+                Err(e) => {
+                    error!("Failed to parse stream chunk: {}", e);
+                    telemetry.error("parse");
+                    Some(Err(LlamaCppClientError::ParseError(e)))
+                }
+            },
+        ))
+    }
+
+    /// Converts text into token ids using the loaded model's tokenizer
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The text to tokenize
+    /// * `add_special` - Whether to add special tokens such as BOS
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the token ids or a `LlamaCppClientError`
+    pub async fn tokenize(
+        &self,
+        content: &str,
+        add_special: bool,
+    ) -> Result<Vec<i32>, LlamaCppClientError> {
+        let url = format!("{}/tokenize", self.base_url);
+        info!("Tokenizing with URL: {}", url);
+        let request = TokenizeRequest {
+            content: content.to_string(),
+            add_special,
+        };
+
+        let response = self
+            .send(self.post(&url).json(&request), false)
+            .await
+            .map_err(|err| {
+                error!("Failed to tokenize: {}", err);
+                LlamaCppClientError::from(err)
+            })?;
+
+        let tokenize_response: TokenizeResponse = response.json().await?;
+        debug!("Tokenized into {} tokens", tokenize_response.tokens.len());
+        Ok(tokenize_response.tokens)
+    }
+
+    /// Converts token ids back into text
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - The token ids to decode
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decoded text or a `LlamaCppClientError`
+    pub async fn detokenize(&self, tokens: &[i32]) -> Result<String, LlamaCppClientError> {
+        let url = format!("{}/detokenize", self.base_url);
+        info!("Detokenizing with URL: {}", url);
+        let request = DetokenizeRequest {
+            tokens: tokens.to_vec(),
+        };
+
+        let response = self
+            .send(self.post(&url).json(&request), false)
+            .await
+            .map_err(|err| {
+                error!("Failed to detokenize: {}", err);
+                LlamaCppClientError::from(err)
+            })?;
+
+        let detokenize_response: DetokenizeResponse = response.json().await?;
+        Ok(detokenize_response.content)
+    }
+
+    /// Creates an embedding for the given text
+    ///
+    /// The server must be started with `--embedding`.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The text to embed
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the pooled embedding or a `LlamaCppClientError`
+    pub async fn embedding(&self, content: &str) -> Result<Vec<f32>, LlamaCppClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "embeddings", MODEL);
+        let span = telemetry.span().clone();
+        let result = self
+            .send_embedding(content, &telemetry)
+            .instrument(span)
+            .await;
+        if let Err(err) = &result {
+            telemetry.error(err.error_type());
+        }
+        result
+    }
+
+    /// Sends an embedding request, recording failures on `telemetry`
+    async fn send_embedding(
+        &self,
+        content: &str,
+        telemetry: &GenAiSpan,
+    ) -> Result<Vec<f32>, LlamaCppClientError> {
+        let url = format!("{}/embedding", self.base_url);
+        info!("Creating embedding with URL: {}", url);
+        let request = EmbeddingRequest {
+            content: content.to_string(),
+        };
+
+        let response = self
+            .send(self.post(&url).json(&request), true)
+            .await
+            .map_err(|err| {
+                error!("Failed to create embedding: {}", err);
+                telemetry.http_error(&err);
+                LlamaCppClientError::from(err)
+            })?;
+
+        let embedding_response: EmbeddingResponse = response.json().await?;
+        embedding_response.into_embedding().ok_or_else(|| {
+            LlamaCppClientError::RequestError(
+                "Server returned per-token embeddings; enable pooling to get a single vector"
+                    .to_string(),
+            )
+        })
+    }
+
+    /// Lists the server slots
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SlotInfo` of each slot or a `LlamaCppClientError`
+    pub async fn slots(&self) -> Result<Vec<SlotInfo>, LlamaCppClientError> {
+        let url = format!("{}/slots", self.base_url);
+        info!("Listing slots with URL: {}", url);
+        let response = self.send(self.get(&url), false).await.map_err(|err| {
+            error!("Failed to list slots: {}", err);
+            LlamaCppClientError::from(err)
+        })?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Saves the KV cache of a slot to a file on the server
+    ///
+    /// # Arguments
+    ///
+    /// * `id_slot` - The slot to save
+    /// * `filename` - The file name, relative to the server's `--slot-save-path`
+    pub async fn save_slot(
+        &self,
+        id_slot: i32,
+        filename: &str,
+    ) -> Result<SlotActionResponse, LlamaCppClientError> {
+        self.slot_action(id_slot, "save", Some(filename)).await
+    }
+
+    /// Restores the KV cache of a slot from a file on the server
+    ///
+    /// # Arguments
+    ///
+    /// * `id_slot` - The slot to restore into
+    /// * `filename` - The file name, relative to the server's `--slot-save-path`
+    pub async fn restore_slot(
+        &self,
+        id_slot: i32,
+        filename: &str,
+    ) -> Result<SlotActionResponse, LlamaCppClientError> {
+        self.slot_action(id_slot, "restore", Some(filename)).await
+    }
+
+    /// Erases the KV cache of a slot
+    ///
+    /// # Arguments
+    ///
+    /// * `id_slot` - The slot to erase
+    pub async fn erase_slot(
+        &self,
+        id_slot: i32,
+    ) -> Result<SlotActionResponse, LlamaCppClientError> {
+        self.slot_action(id_slot, "erase", None).await
+    }
+
+    async fn slot_action(
+        &self,
+        id_slot: i32,
+        action: &str,
+        filename: Option<&str>,
+    ) -> Result<SlotActionResponse, LlamaCppClientError> {
+        let url = format!("{}/slots/{}?action={}", self.base_url, id_slot, action);
+        info!("Running slot action with URL: {}", url);
+        let body = match filename {
+            Some(filename) => json!({ "filename": filename }),
+            None => json!({}),
+        };
+
+        let response = self
+            .send(self.post(&url).json(&body), false)
+            .await
+            .map_err(|err| {
+                error!("Failed to {} slot {}: {}", action, id_slot, err);
+                LlamaCppClientError::from(err)
+            })?;
+
+        let slot_response: SlotActionResponse = response.json().await?;
+        info!("Slot {} action {} succeeded.", id_slot, action);
+        Ok(slot_response)
+    }
+}
+
+/// Records the model, stop type and token usage of a finished completion
+fn record_response(telemetry: &GenAiSpan, completion: &CompletionResponse) {
+    if let Some(model) = &completion.model {
+        telemetry.response_model(model);
+    }
+    if let Some(stop_type) = &completion.stop_type {
+        telemetry.finish_reasons(stop_type);
+    }
+    telemetry.usage(completion.tokens_evaluated, completion.tokens_predicted);
+}
+
+/// Prompt plus generated tokens, reported once generation has stopped
+fn total_tokens(completion: &CompletionResponse) -> Option<u32> {
+    match (completion.tokens_evaluated, completion.tokens_predicted) {
+        (Some(evaluated), Some(predicted)) => Some(evaluated + predicted),
+        _ => None,
+    }
+}
@@ -0,0 +1,457 @@
+use std::fmt;
+
+/// Error returned when a grammar would not be accepted by llama.cpp
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GbnfError {
+    /// A rule name contains characters other than ASCII letters, digits and '-'
+    InvalidRuleName(String),
+    /// A rule references a rule that is not defined
+    UndefinedRule(String),
+    /// The grammar has no `root` rule
+    MissingRoot,
+}
+
+impl fmt::Display for GbnfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GbnfError::InvalidRuleName(name) => write!(f, "Invalid rule name: {:?}", name),
+            GbnfError::UndefinedRule(name) => write!(f, "Undefined rule: {}", name),
+            GbnfError::MissingRoot => write!(f, "Grammar has no root rule"),
+        }
+    }
+}
+
+impl std::error::Error for GbnfError {}
+
+/// An expression in a GBNF grammar rule
+#[derive(Debug, Clone, PartialEq)]
+pub enum GbnfExpr {
+    /// A literal string, e.g. `"yes"`
+    Literal(String),
+    /// A character class without the brackets, e.g. `0-9a-f` for `[0-9a-f]`
+    CharClass(String),
+    /// A negated character class, e.g. `"` for `[^"]`
+    NegatedCharClass(String),
+    /// A reference to another rule
+    Rule(String),
+    /// A sequence of expressions
+    Sequence(Vec<GbnfExpr>),
+    /// A choice between expressions
+    Alternatives(Vec<GbnfExpr>),
+    /// An expression repeated between `min` and `max` times (`None` means unbounded)
+    Repeat {
+        expr: Box<GbnfExpr>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+impl GbnfExpr {
+    /// A literal string
+    pub fn literal(text: &str) -> Self {
+        GbnfExpr::Literal(text.to_string())
+    }
+
+    /// A character class, e.g. `chars("0-9")` for `[0-9]`
+    pub fn chars(class: &str) -> Self {
+        GbnfExpr::CharClass(class.to_string())
+    }
+
+    /// A negated character class, e.g. `not_chars("\"")` for `[^"]`
+    pub fn not_chars(class: &str) -> Self {
+        GbnfExpr::NegatedCharClass(class.to_string())
+    }
+
+    /// A reference to another rule
+    pub fn rule(name: &str) -> Self {
+        GbnfExpr::Rule(name.to_string())
+    }
+
+    /// A sequence of expressions
+    pub fn seq(exprs: impl IntoIterator<Item = GbnfExpr>) -> Self {
+        GbnfExpr::Sequence(exprs.into_iter().collect())
+    }
+
+    /// A choice between expressions
+    pub fn alt(exprs: impl IntoIterator<Item = GbnfExpr>) -> Self {
+        GbnfExpr::Alternatives(exprs.into_iter().collect())
+    }
+
+    /// A choice between literal strings
+    pub fn one_of<S: AsRef<str>>(values: impl IntoIterator<Item = S>) -> Self {
+        GbnfExpr::alt(
+            values
+                .into_iter()
+                .map(|value| Self::literal(value.as_ref())),
+        )
+    }
+
+    /// Repeats this expression between `min` and `max` times
+    pub fn repeat(self, min: usize, max: Option<usize>) -> Self {
+        GbnfExpr::Repeat {
+            expr: Box::new(self),
+            min,
+            max,
+        }
+    }
+
+    /// Makes this expression optional (`?`)
+    pub fn optional(self) -> Self {
+        self.repeat(0, Some(1))
+    }
+
+    /// Repeats this expression zero or more times (`*`)
+    pub fn zero_or_more(self) -> Self {
+        self.repeat(0, None)
+    }
+
+    /// Repeats this expression one or more times (`+`)
+    pub fn one_or_more(self) -> Self {
+        self.repeat(1, None)
+    }
+
+    /// Returns `true` if the expression needs parentheses before a repetition operator
+    fn needs_group(&self) -> bool {
+        match self {
+            GbnfExpr::Sequence(exprs) | GbnfExpr::Alternatives(exprs) => match exprs.as_slice() {
+                [only] => only.needs_group(),
+                _ => true,
+            },
+            GbnfExpr::Repeat { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the expression renders as a choice, which needs parentheses in a sequence
+    fn is_choice(&self) -> bool {
+        match self {
+            GbnfExpr::Alternatives(exprs) => match exprs.as_slice() {
+                [only] => only.is_choice(),
+                exprs => exprs.len() > 1,
+            },
+            _ => false,
+        }
+    }
+
+    /// Calls `visit` with the name of every rule referenced by this expression
+    fn rule_references<'a>(&'a self, visit: &mut impl FnMut(&'a str)) {
+        match self {
+            GbnfExpr::Rule(name) => visit(name),
+            GbnfExpr::Sequence(exprs) | GbnfExpr::Alternatives(exprs) => {
+                for expr in exprs {
+                    expr.rule_references(visit);
+                }
+            }
+            GbnfExpr::Repeat { expr, .. } => expr.rule_references(visit),
+            GbnfExpr::Literal(_) | GbnfExpr::CharClass(_) | GbnfExpr::NegatedCharClass(_) => {}
+        }
+    }
+}
+
+impl fmt::Display for GbnfExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GbnfExpr::Literal(text) => write!(f, "\"{}\"", escape_literal(text)),
+            GbnfExpr::CharClass(class) => write!(f, "[{}]", class),
+            GbnfExpr::NegatedCharClass(class) => write!(f, "[^{}]", class),
+            GbnfExpr::Rule(name) => write!(f, "{}", name),
+            GbnfExpr::Sequence(exprs) => write_joined(f, exprs, " "),
+            GbnfExpr::Alternatives(exprs) => write_joined(f, exprs, " | "),
+            GbnfExpr::Repeat { expr, min, max } => {
+                if expr.needs_group() {
+                    write!(f, "({})", expr)?;
+                } else {
+                    write!(f, "{}", expr)?;
+                }
+                match (min, max) {
+                    (0, Some(1)) => write!(f, "?"),
+                    (0, None) => write!(f, "*"),
+                    (1, None) => write!(f, "+"),
+                    (min, None) => write!(f, "{{{},}}", min),
+                    (min, Some(max)) if min == max => write!(f, "{{{}}}", min),
+                    (min, Some(max)) => write!(f, "{{{},{}}}", min, max),
+                }
+            }
+        }
+    }
+}
+
+fn write_joined(f: &mut fmt::Formatter<'_>, exprs: &[GbnfExpr], separator: &str) -> fmt::Result {
+    for (i, expr) in exprs.iter().enumerate() {
+        if i > 0 {
+            write!(f, "{}", separator)?;
+        }
+        // Alternatives nested inside a sequence need parentheses to keep their meaning
+        if separator == " " && expr.is_choice() {
+            write!(f, "({})", expr)?;
+        } else {
+            write!(f, "{}", expr)?;
+        }
+    }
+    Ok(())
+}
+
+fn escape_literal(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Builder for GBNF grammars used by llama.cpp's grammar-constrained sampling
+///
+/// The grammar needs a `root` rule, which is where sampling starts. Rule names may only
+/// contain ASCII letters, digits and '-'; `build` checks both before rendering.
+#[derive(Debug, Clone, Default)]
+pub struct GbnfGrammar {
+    rules: Vec<(String, GbnfExpr)>,
+}
+
+impl GbnfGrammar {
+    /// Creates an empty grammar
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rule, replacing any existing rule with the same name
+    pub fn rule(mut self, name: &str, expr: GbnfExpr) -> Self {
+        match self.rules.iter_mut().find(|(existing, _)| existing == name) {
+            Some(rule) => rule.1 = expr,
+            None => self.rules.push((name.to_string(), expr)),
+        }
+        self
+    }
+
+    /// A grammar whose output must be exactly one of `values`
+    pub fn enumeration<S: AsRef<str>>(values: impl IntoIterator<Item = S>) -> Self {
+        Self::new().rule("root", GbnfExpr::one_of(values))
+    }
+
+    /// A grammar whose output must be a JSON string literal
+    pub fn json_string() -> Self {
+        Self::new().rule(
+            "root",
+            GbnfExpr::seq([
+                GbnfExpr::literal("\""),
+                GbnfExpr::alt([
+                    GbnfExpr::not_chars("\"\\\\\\x7F\\x00-\\x1F"),
+                    GbnfExpr::seq([
+                        GbnfExpr::literal("\\"),
+                        GbnfExpr::alt([
+                            GbnfExpr::chars("\"\\\\/bfnrt"),
+                            GbnfExpr::seq([
+                                GbnfExpr::literal("u"),
+                                GbnfExpr::chars("0-9a-fA-F").repeat(4, Some(4)),
+                            ]),
+                        ]),
+                    ]),
+                ])
+                .zero_or_more(),
+                GbnfExpr::literal("\""),
+            ]),
+        )
+    }
+
+    /// Returns `true` if no rules have been added
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Checks that the grammar has a `root` rule and that every rule name is valid and defined
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or a `GbnfError` describing the first problem found
+    pub fn validate(&self) -> Result<(), GbnfError> {
+        if let Some((name, _)) = self.rules.iter().find(|(name, _)| !valid_rule_name(name)) {
+            return Err(GbnfError::InvalidRuleName(name.clone()));
+        }
+        if !self.rules.iter().any(|(name, _)| name == "root") {
+            return Err(GbnfError::MissingRoot);
+        }
+        let mut references = Vec::new();
+        for (_, expr) in &self.rules {
+            expr.rule_references(&mut |name| references.push(name));
+        }
+        for name in references {
+            if !valid_rule_name(name) {
+                return Err(GbnfError::InvalidRuleName(name.to_string()));
+            }
+            if !self.rules.iter().any(|(defined, _)| defined == name) {
+                return Err(GbnfError::UndefinedRule(name.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates the grammar and renders it as GBNF text
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the grammar text or a `GbnfError`
+    pub fn build(&self) -> Result<String, GbnfError> {
+        self.validate()?;
+        Ok(self.to_string())
+    }
+}
+
+/// Returns `true` if the name only contains ASCII letters, digits and '-', as GBNF requires
+fn valid_rule_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+}
+
+impl fmt::Display for GbnfGrammar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, expr) in &self.rules {
+            writeln!(f, "{} ::= {}", name, expr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_literals_and_classes() {
+        assert_eq!(
+            GbnfExpr::literal("say \"hi\"\n").to_string(),
+            r#""say \"hi\"\n""#
+        );
+        assert_eq!(GbnfExpr::chars("0-9").to_string(), "[0-9]");
+        assert_eq!(GbnfExpr::not_chars("\"").to_string(), "[^\"]");
+        assert_eq!(
+            GbnfExpr::one_of(["yes", "no"]).to_string(),
+            r#""yes" | "no""#
+        );
+    }
+
+    #[test]
+    fn renders_repetitions() {
+        let digit = GbnfExpr::chars("0-9");
+        assert_eq!(digit.clone().optional().to_string(), "[0-9]?");
+        assert_eq!(digit.clone().zero_or_more().to_string(), "[0-9]*");
+        assert_eq!(digit.clone().one_or_more().to_string(), "[0-9]+");
+        assert_eq!(digit.clone().repeat(2, None).to_string(), "[0-9]{2,}");
+        assert_eq!(digit.clone().repeat(4, Some(4)).to_string(), "[0-9]{4}");
+        assert_eq!(digit.repeat(1, Some(3)).to_string(), "[0-9]{1,3}");
+    }
+
+    #[test]
+    fn groups_repeated_sequences_and_choices() {
+        let pair = GbnfExpr::seq([GbnfExpr::literal("a"), GbnfExpr::literal("b")]);
+        assert_eq!(pair.clone().zero_or_more().to_string(), r#"("a" "b")*"#);
+        assert_eq!(
+            GbnfExpr::one_of(["a", "b"]).optional().to_string(),
+            r#"("a" | "b")?"#
+        );
+        // A repetition of a repetition is not valid GBNF without parentheses
+        assert_eq!(
+            GbnfExpr::literal("a").optional().zero_or_more().to_string(),
+            r#"("a"?)*"#
+        );
+        // Single-element wrappers are transparent, so their content decides
+        assert_eq!(
+            GbnfExpr::alt([pair.clone()]).one_or_more().to_string(),
+            r#"("a" "b")+"#
+        );
+        assert_eq!(
+            GbnfExpr::seq([GbnfExpr::rule("item")])
+                .one_or_more()
+                .to_string(),
+            "item+"
+        );
+    }
+
+    #[test]
+    fn groups_choices_inside_sequences() {
+        let choice = GbnfExpr::one_of(["a", "b"]);
+        assert_eq!(
+            GbnfExpr::seq([choice.clone(), GbnfExpr::literal("c")]).to_string(),
+            r#"("a" | "b") "c""#
+        );
+        assert_eq!(
+            GbnfExpr::seq([GbnfExpr::alt([choice]), GbnfExpr::literal("c")]).to_string(),
+            r#"("a" | "b") "c""#
+        );
+        // Sequences inside a choice bind tighter and need no parentheses
+        assert_eq!(
+            GbnfExpr::alt([
+                GbnfExpr::seq([GbnfExpr::literal("a"), GbnfExpr::literal("b")]),
+                GbnfExpr::literal("c"),
+            ])
+            .to_string(),
+            r#""a" "b" | "c""#
+        );
+    }
+
+    #[test]
+    fn renders_rules_in_order() {
+        let grammar = GbnfGrammar::new()
+            .rule(
+                "root",
+                GbnfExpr::seq([GbnfExpr::rule("item"), GbnfExpr::rule("item")]),
+            )
+            .rule("item", GbnfExpr::literal("x"))
+            .rule("item", GbnfExpr::one_of(["x", "y"]));
+        assert_eq!(
+            grammar.build().unwrap(),
+            "root ::= item item\nitem ::= \"x\" | \"y\"\n"
+        );
+        assert_eq!(
+            GbnfGrammar::enumeration(["positive", "negative"]).to_string(),
+            "root ::= \"positive\" | \"negative\"\n"
+        );
+    }
+
+    #[test]
+    fn json_string_grammar() {
+        assert_eq!(
+            GbnfGrammar::json_string().build().unwrap(),
+            r#"root ::= "\"" ([^"\\\x7F\x00-\x1F] | "\\" (["\\/bfnrt] | "u" [0-9a-fA-F]{4}))* "\""
+"#
+        );
+    }
+
+    #[test]
+    fn validates_rule_names() {
+        assert!(GbnfGrammar::new().is_empty());
+        assert_eq!(GbnfGrammar::new().validate(), Err(GbnfError::MissingRoot));
+        assert_eq!(
+            GbnfGrammar::new()
+                .rule("root", GbnfExpr::rule("my_item"))
+                .rule("my_item", GbnfExpr::literal("x"))
+                .build(),
+            Err(GbnfError::InvalidRuleName("my_item".to_string()))
+        );
+        assert_eq!(
+            GbnfGrammar::new()
+                .rule("root", GbnfExpr::rule("item").optional())
+                .validate(),
+            Err(GbnfError::UndefinedRule("item".to_string()))
+        );
+        assert_eq!(
+            GbnfGrammar::new()
+                .rule("root", GbnfExpr::rule("list item"))
+                .validate(),
+            Err(GbnfError::InvalidRuleName("list item".to_string()))
+        );
+        assert!(GbnfGrammar::new()
+            .rule("root", GbnfExpr::rule("item-2"))
+            .rule("item-2", GbnfExpr::chars("a-z"))
+            .validate()
+            .is_ok());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod client;
+pub mod grammar;
+pub mod types;
+
+pub use client::{LlamaCppClient, LlamaCppClientError};
+pub use grammar::{GbnfError, GbnfExpr, GbnfGrammar};
+pub use types::{CompletionRequest, CompletionResponse, HealthStatus};
@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+
+/// Request structure for llama.cpp's `/completion` endpoint
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CompletionRequest {
+    /// The prompt to complete
+    pub prompt: String,
+    /// Maximum number of tokens to predict (-1 for unlimited)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_predict: Option<i32>,
+    /// Temperature for generation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Top-k sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<i32>,
+    /// Top-p sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Min-p sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_p: Option<f32>,
+    /// Repetition penalty
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    /// Stop sequences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    /// Seed for deterministic sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// GBNF grammar constraining the output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grammar: Option<String>,
+    /// JSON schema constraining the output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<serde_json::Value>,
+    /// Number of top token probabilities to return per generated token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_probs: Option<u32>,
+    /// The slot to run the completion on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_slot: Option<i32>,
+    /// Whether to reuse the KV cache of a previous request in the slot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_prompt: Option<bool>,
+    /// Additional sampling parameters passed through unchanged
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl CompletionRequest {
+    /// Creates a request for the given prompt with default sampling
+    pub fn new(prompt: &str) -> Self {
+        CompletionRequest {
+            prompt: prompt.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Response structure for llama.cpp's `/completion` endpoint
+///
+/// When streaming, each chunk carries a piece of `content` and the final chunk has
+/// `stop` set along with the timings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionResponse {
+    /// The generated text
+    pub content: String,
+    /// Whether generation has stopped
+    #[serde(default)]
+    pub stop: bool,
+    /// The model that generated the text
+    pub model: Option<String>,
+    /// The slot used for the completion
+    pub id_slot: Option<i32>,
+    /// Number of tokens predicted
+    pub tokens_predicted: Option<u32>,
+    /// Number of prompt tokens evaluated
+    pub tokens_evaluated: Option<u32>,
+    /// Why generation stopped ("eos", "limit" or "word")
+    pub stop_type: Option<String>,
+    /// The stop word that ended generation
+    pub stopping_word: Option<String>,
+    /// Whether the prompt was truncated to fit the context
+    pub truncated: Option<bool>,
+    /// Per-token probabilities, when `n_probs` was set
+    pub completion_probabilities: Option<Vec<TokenProbabilities>>,
+    /// Timing information
+    pub timings: Option<Timings>,
+}
+
+/// Probabilities for one generated token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenProbabilities {
+    /// The generated token id
+    pub id: Option<i32>,
+    /// The generated token text
+    #[serde(alias = "content")]
+    pub token: Option<String>,
+    /// Log-probability of the generated token
+    pub logprob: Option<f32>,
+    /// Most likely alternatives
+    #[serde(alias = "probs", default)]
+    pub top_logprobs: Vec<TopTokenProbability>,
+}
+
+/// A candidate token and its probability
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopTokenProbability {
+    /// The token id
+    pub id: Option<i32>,
+    /// The token text
+    #[serde(alias = "tok_str")]
+    pub token: Option<String>,
+    /// Log-probability of the token
+    pub logprob: Option<f32>,
+    /// Probability of the token, returned by older servers
+    pub prob: Option<f32>,
+}
+
+/// Timing information for a completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timings {
+    /// Number of prompt tokens processed
+    pub prompt_n: Option<u32>,
+    /// Time spent processing the prompt, in milliseconds
+    pub prompt_ms: Option<f64>,
+    /// Number of tokens predicted
+    pub predicted_n: Option<u32>,
+    /// Time spent predicting, in milliseconds
+    pub predicted_ms: Option<f64>,
+    /// Prediction speed in tokens per second
+    pub predicted_per_second: Option<f64>,
+}
+
+/// Request structure for `/tokenize`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenizeRequest {
+    /// The text to tokenize
+    pub content: String,
+    /// Whether to add special tokens such as BOS
+    pub add_special: bool,
+}
+
+/// Response structure for `/tokenize`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenizeResponse {
+    /// The token ids
+    pub tokens: Vec<i32>,
+}
+
+/// Request structure for `/detokenize`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DetokenizeRequest {
+    /// The token ids to convert back to text
+    pub tokens: Vec<i32>,
+}
+
+/// Response structure for `/detokenize`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DetokenizeResponse {
+    /// The decoded text
+    pub content: String,
+}
+
+/// Request structure for `/embedding`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingRequest {
+    /// The text to embed
+    pub content: String,
+}
+
+/// Response shapes returned by `/embedding` across server versions
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingResponse {
+    /// Newer servers return one entry per input, each with per-token or pooled vectors
+    List(Vec<EmbeddingEntry>),
+    /// Older servers return a single pooled vector
+    Single { embedding: Vec<f32> },
+}
+
+impl EmbeddingResponse {
+    /// Returns the pooled embedding of the first input
+    pub fn into_embedding(self) -> Option<Vec<f32>> {
+        match self {
+            EmbeddingResponse::Single { embedding } => Some(embedding),
+            EmbeddingResponse::List(entries) => {
+                let entry = entries.into_iter().next()?;
+                match entry.embedding {
+                    EmbeddingValues::Pooled(vector) => Some(vector),
+                    EmbeddingValues::PerToken(mut vectors) => {
+                        if vectors.len() == 1 {
+                            vectors.pop()
+                        } else {
+                            None
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// One entry of a list-shaped `/embedding` response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingEntry {
+    /// Index of the input
+    pub index: usize,
+    /// The embedding values
+    pub embedding: EmbeddingValues,
+}
+
+/// Pooled or per-token embedding values
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingValues {
+    /// A single pooled vector
+    Pooled(Vec<f32>),
+    /// One vector per token (pooling disabled)
+    PerToken(Vec<Vec<f32>>),
+}
+
+/// Health of the llama.cpp server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// The model is loaded and the server accepts requests
+    Ok,
+    /// The model is still loading
+    Loading,
+    /// The server reported an error
+    Error,
+}
+
+/// State of a server slot, as returned by `/slots`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotInfo {
+    /// The slot id
+    pub id: i32,
+    /// Whether the slot is processing a request
+    #[serde(default)]
+    pub is_processing: bool,
+    /// Context size of the slot
+    pub n_ctx: Option<u32>,
+}
+
+/// Result of a slot save, restore or erase action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotActionResponse {
+    /// The slot id
+    pub id_slot: i32,
+    /// The file the slot was saved to or restored from
+    pub filename: Option<String>,
+    /// Number of tokens saved, restored or erased
+    #[serde(alias = "n_saved", alias = "n_restored", alias = "n_erased")]
+    pub n_tokens: Option<u32>,
+}
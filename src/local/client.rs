@@ -0,0 +1,802 @@
+use crate::local::types::LocalGenerateOptions;
+use crate::logging;
+use crate::ollama::types::{GenerateRequest, GenerateResponse};
+use crate::telemetry::GenAiSpan;
+use crate::utils::format_rfc3339;
+use candle_core::quantized::gguf_file;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::generation::LogitsProcessor;
+use candle_transformers::models::bert::{BertModel, Config as BertConfig, DTYPE};
+use candle_transformers::models::quantized_llama::ModelWeights;
+use candle_transformers::utils::apply_repeat_penalty;
+use futures_util::Stream;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{debug, error, info, trace, Instrument};
+
+/// Tokens that end generation in common chat templates
+const EOS_TOKENS: [&str; 5] = [
+    "</s>",
+    "<|endoftext|>",
+    "<|eot_id|>",
+    "<|im_end|>",
+    "<|end|>",
+];
+
+/// `gen_ai.system` value for in-process models
+const GEN_AI_SYSTEM: &str = "local";
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum LocalClientError {
+    /// Error while loading the model weights, configuration or tokenizer
+    LoadError(String),
+    /// Error related to the request
+    RequestError(String),
+    /// Error raised while running the model
+    InferenceError(String),
+    /// Error while tokenizing or detokenizing
+    TokenizerError(String),
+    /// Error while reading model files
+    IoError(std::io::Error),
+}
+
+impl fmt::Display for LocalClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LocalClientError::LoadError(msg) => write!(f, "Load error: {}", msg),
+            LocalClientError::RequestError(msg) => write!(f, "Request error: {}", msg),
+            LocalClientError::InferenceError(msg) => write!(f, "Inference error: {}", msg),
+            LocalClientError::TokenizerError(msg) => write!(f, "Tokenizer error: {}", msg),
+            LocalClientError::IoError(err) => write!(f, "IO error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for LocalClientError {}
+
+impl LocalClientError {
+    /// Short error type recorded on the tracing span
+    fn error_type(&self) -> &'static str {
+        match self {
+            LocalClientError::LoadError(_) => "load",
+            LocalClientError::RequestError(_) => "request",
+            LocalClientError::InferenceError(_) => "inference",
+            LocalClientError::TokenizerError(_) => "tokenizer",
+            LocalClientError::IoError(_) => "io",
+        }
+    }
+}
+
+impl From<candle_core::Error> for LocalClientError {
+    fn from(err: candle_core::Error) -> Self {
+        LocalClientError::InferenceError(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for LocalClientError {
+    fn from(err: std::io::Error) -> Self {
+        LocalClientError::IoError(err)
+    }
+}
+
+impl From<tokenizers::Error> for LocalClientError {
+    fn from(err: tokenizers::Error) -> Self {
+        LocalClientError::TokenizerError(err.to_string())
+    }
+}
+
+/// Client that runs a small model in-process on the CPU
+///
+/// A client holds either a text generation model (a llama-family GGUF file) or an
+/// embedding model (a BERT-family safetensors checkpoint). Inference runs on a
+/// blocking thread, so the async methods do not stall the runtime.
+pub struct LocalClient {
+    model: String,
+    backend: Arc<Backend>,
+}
+
+enum Backend {
+    Generator(Mutex<Generator>),
+    Embedder(Embedder),
+}
+
+impl fmt::Debug for LocalClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.backend.as_ref() {
+            Backend::Generator(_) => "generator",
+            Backend::Embedder(_) => "embedder",
+        };
+        f.debug_struct("LocalClient")
+            .field("model", &self.model)
+            .field("kind", &kind)
+            .finish()
+    }
+}
+
+impl LocalClient {
+    /// Loads a quantized llama-family model from a GGUF file for text generation
+    ///
+    /// # Arguments
+    ///
+    /// * `model_path` - Path to the `.gguf` file
+    /// * `tokenizer_path` - Path to the model's `tokenizer.json`
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `LocalClient` or a `LocalClientError`
+    pub fn from_gguf(model_path: &str, tokenizer_path: &str) -> Result<Self, LocalClientError> {
+        info!("Loading GGUF model from: {}", model_path);
+        let mut file = std::fs::File::open(model_path)?;
+        let content = gguf_file::Content::read(&mut file)
+            .map_err(|e| LocalClientError::LoadError(format!("{}: {}", model_path, e)))?;
+        let tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| LocalClientError::LoadError(format!("{}: {}", tokenizer_path, e)))?;
+
+        let mut eos_token_ids: Vec<u32> = EOS_TOKENS
+            .iter()
+            .filter_map(|token| tokenizer.token_to_id(token))
+            .collect();
+        if let Some(id) = content
+            .metadata
+            .get("tokenizer.ggml.eos_token_id")
+            .and_then(|value| value.to_u32().ok())
+        {
+            eos_token_ids.push(id);
+        }
+        debug!("EOS token ids: {:?}", eos_token_ids);
+
+        let weights = ModelWeights::from_gguf(content, &mut file, &Device::Cpu)
+            .map_err(|e| LocalClientError::LoadError(format!("{}: {}", model_path, e)))?;
+        info!("Successfully loaded GGUF model.");
+
+        Ok(LocalClient {
+            model: model_name(model_path),
+            backend: Arc::new(Backend::Generator(Mutex::new(Generator {
+                weights,
+                tokenizer,
+                eos_token_ids,
+            }))),
+        })
+    }
+
+    /// Loads a BERT-family embedding model from a directory
+    ///
+    /// The directory must contain `config.json`, `tokenizer.json` and `model.safetensors`,
+    /// as in a Hugging Face checkpoint such as `sentence-transformers/all-MiniLM-L6-v2`.
+    ///
+    /// # Arguments
+    ///
+    /// * `model_dir` - Path to the model directory
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `LocalClient` or a `LocalClientError`
+    pub fn from_safetensors(model_dir: &str) -> Result<Self, LocalClientError> {
+        info!("Loading embedding model from: {}", model_dir);
+        let dir = Path::new(model_dir);
+
+        let config: BertConfig =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("config.json"))?)
+                .map_err(|e| LocalClientError::LoadError(format!("config.json: {}", e)))?;
+
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| LocalClientError::LoadError(format!("tokenizer.json: {}", e)))?;
+        tokenizer.with_padding(Some(PaddingParams::default()));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_position_embeddings,
+                ..Default::default()
+            }))
+            .map_err(|e| LocalClientError::LoadError(format!("tokenizer.json: {}", e)))?;
+
+        let weights = std::fs::read(dir.join("model.safetensors"))?;
+        let vb = VarBuilder::from_buffered_safetensors(weights, DTYPE, &Device::Cpu)?;
+        let model = BertModel::load(vb, &config)
+            .map_err(|e| LocalClientError::LoadError(format!("model.safetensors: {}", e)))?;
+        info!("Successfully loaded embedding model.");
+
+        Ok(LocalClient {
+            model: model_name(model_dir),
+            backend: Arc::new(Backend::Embedder(Embedder { model, tokenizer })),
+        })
+    }
+
+    /// Returns the model name, taken from the model path
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Generates a completion based on the provided request
+    ///
+    /// The request's `model` is echoed back; the loaded model is always used.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the prompt and options
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GenerateResponse` or a `LocalClientError`
+    pub async fn generate_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<GenerateResponse, LocalClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "text_completion", &self.model);
+        telemetry.template_version(request.template_version.as_deref());
+        let span = telemetry.span().clone();
+        let result = self.generate(request).instrument(span).await;
+        match &result {
+            Ok(response) => record_response(&telemetry, response),
+            Err(err) => telemetry.error(err.error_type()),
+        }
+        result
+    }
+
+    /// Runs a generation on the blocking thread pool
+    async fn generate(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<GenerateResponse, LocalClientError> {
+        info!("Generating completion with local model: {}", self.model);
+        trace!("GenerateRequest: {}", logging::payload_json(&request));
+        let backend = self.backend.clone();
+        let span = tracing::Span::current();
+
+        tokio::task::spawn_blocking(move || {
+            let _entered = span.enter();
+            let options = LocalGenerateOptions::from(&request);
+            let mut text = String::new();
+            let stats = backend
+                .generator()?
+                .generate(&request.prompt, &options, |piece| {
+                    text.push_str(piece);
+                    true
+                })?;
+            let response = stats.response(request.model, text);
+            trace!("GenerateResponse: {}", logging::payload_json(&response));
+            Ok(response)
+        })
+        .await
+        .map_err(|e| LocalClientError::InferenceError(e.to_string()))?
+    }
+
+    /// Streams a completion response token by token based on the provided request
+    ///
+    /// Chunks carry `done: false`; a final chunk with an empty `response`,
+    /// `done: true` and the timing statistics ends the stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the prompt and options
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `GenerateResponse` chunks or a `LocalClientError`
+    pub async fn stream_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<impl Stream<Item = Result<GenerateResponse, LocalClientError>>, LocalClientError>
+    {
+        let mut telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "text_completion", &self.model);
+        telemetry.template_version(request.template_version.as_deref());
+        info!("Streaming completion with local model: {}", self.model);
+        trace!("StreamRequest: {}", logging::payload_json(&request));
+        // Fail early instead of returning a stream that only yields an error
+        if let Backend::Embedder(_) = self.backend.as_ref() {
+            let err = unsupported("text generation");
+            telemetry.error(err.error_type());
+            return Err(err);
+        }
+
+        let (tx, rx) = mpsc::channel(32);
+        let backend = self.backend.clone();
+
+        // `telemetry` moves into the task and ends with the stream
+        tokio::task::spawn_blocking(move || {
+            let _entered = telemetry.span().clone().entered();
+            let options = LocalGenerateOptions::from(&request);
+            let model = request.model.clone();
+            let result = backend.generator().and_then(|mut generator| {
+                generator.generate(&request.prompt, &options, |piece| {
+                    telemetry.first_token();
+                    let chunk = GenerateResponse {
+                        model: model.clone(),
+                        created_at: format_rfc3339(SystemTime::now()),
+                        response: piece.to_string(),
+                        done: false,
+                        done_reason: None,
+                        context: None,
+                        total_duration: None,
+                        load_duration: None,
+                        prompt_eval_count: None,
+                        prompt_eval_duration: None,
+                        eval_count: None,
+                        eval_duration: None,
+                    };
+                    // Stop generating once the receiver has been dropped
+                    tx.blocking_send(Ok(chunk)).is_ok()
+                })
+            });
+
+            let last = match result {
+                Ok(stats) => {
+                    let response = stats.response(model, String::new());
+                    record_response(&telemetry, &response);
+                    Ok(response)
+                }
+                Err(e) => {
+                    error!("Local generation failed: {}", e);
+                    telemetry.error(e.error_type());
+                    Err(e)
+                }
+            };
+            let _ = tx.blocking_send(last);
+        });
+
+        Ok(ReceiverStream::new(rx))
+    }
+
+    /// Creates embeddings for the given texts
+    ///
+    /// Token embeddings are mean-pooled over the attention mask.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The texts to embed
+    /// * `normalize` - Whether to L2-normalize the embeddings
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing one embedding per input or a `LocalClientError`
+    pub async fn embed(
+        &self,
+        inputs: &[&str],
+        normalize: bool,
+    ) -> Result<Vec<Vec<f32>>, LocalClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "embeddings", &self.model);
+        let span = telemetry.span().clone();
+        info!(
+            parent: &span,
+            "Creating {} embeddings with local model: {}",
+            inputs.len(),
+            self.model
+        );
+        let inputs: Vec<String> = inputs.iter().map(|input| input.to_string()).collect();
+        let backend = self.backend.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            let _entered = span.enter();
+            match backend.as_ref() {
+                Backend::Embedder(embedder) => embedder.embed(inputs, normalize),
+                Backend::Generator(_) => Err(unsupported("embeddings")),
+            }
+        })
+        .await
+        .map_err(|e| LocalClientError::InferenceError(e.to_string()))
+        .and_then(|result| result);
+        if let Err(err) = &result {
+            telemetry.error(err.error_type());
+        }
+        result
+    }
+}
+
+impl Backend {
+    fn generator(&self) -> Result<std::sync::MutexGuard<'_, Generator>, LocalClientError> {
+        match self {
+            // The KV cache is reset on every request, so a poisoned lock is still usable
+            Backend::Generator(generator) => Ok(generator
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())),
+            Backend::Embedder(_) => Err(unsupported("text generation")),
+        }
+    }
+}
+
+struct Generator {
+    weights: ModelWeights,
+    tokenizer: Tokenizer,
+    eos_token_ids: Vec<u32>,
+}
+
+struct GenerationStats {
+    done_reason: &'static str,
+    prompt_eval_count: usize,
+    prompt_eval_duration: Duration,
+    eval_count: usize,
+    eval_duration: Duration,
+    total_duration: Duration,
+}
+
+impl GenerationStats {
+    fn response(&self, model: String, response: String) -> GenerateResponse {
+        GenerateResponse {
+            model,
+            created_at: format_rfc3339(SystemTime::now()),
+            response,
+            done: true,
+            done_reason: Some(self.done_reason.to_string()),
+            context: None,
+            total_duration: Some(self.total_duration.as_nanos() as u64),
+            load_duration: Some(0),
+            prompt_eval_count: Some(self.prompt_eval_count as u32),
+            prompt_eval_duration: Some(self.prompt_eval_duration.as_nanos() as u64),
+            eval_count: Some(self.eval_count as u32),
+            eval_duration: Some(self.eval_duration.as_nanos() as u64),
+        }
+    }
+}
+
+impl Generator {
+    /// Runs the sampling loop, passing each decoded piece of text to `on_text`
+    ///
+    /// Returning `false` from `on_text` stops generation early.
+    fn generate(
+        &mut self,
+        prompt: &str,
+        options: &LocalGenerateOptions,
+        mut on_text: impl FnMut(&str) -> bool,
+    ) -> Result<GenerationStats, LocalClientError> {
+        let start = Instant::now();
+        let prompt_tokens = self.tokenizer.encode(prompt, true)?.get_ids().to_vec();
+        if prompt_tokens.is_empty() {
+            return Err(LocalClientError::RequestError(
+                "the prompt is empty".to_string(),
+            ));
+        }
+
+        let mut sampler = LogitsProcessor::from_sampling(options.seed, options.sampling());
+        let input = Tensor::new(prompt_tokens.as_slice(), &Device::Cpu)?.unsqueeze(0)?;
+        let mut logits = self.weights.forward(&input, 0)?.squeeze(0)?;
+        let prompt_eval_duration = start.elapsed();
+
+        let eval_start = Instant::now();
+        let mut tokens = prompt_tokens.clone();
+        let mut decoder = TokenDecoder::default();
+        let mut output = StopFilter::new(&options.stop);
+        let mut done_reason = "length";
+        let mut eval_count = 0;
+        // Set when a stop sequence matched or the caller went away; nothing more is emitted
+        let mut cut_off = false;
+
+        while eval_count < options.num_predict {
+            let logits_f32 = logits.to_dtype(DType::F32)?;
+            let logits_f32 = if options.repeat_penalty == 1.0 {
+                logits_f32
+            } else {
+                let from = tokens.len().saturating_sub(options.repeat_last_n);
+                apply_repeat_penalty(&logits_f32, options.repeat_penalty, &tokens[from..])?
+            };
+
+            let next = sampler.sample(&logits_f32)?;
+            if self.eos_token_ids.contains(&next) {
+                done_reason = "stop";
+                break;
+            }
+            tokens.push(next);
+            eval_count += 1;
+
+            if let Some(piece) = decoder.push(&self.tokenizer, next)? {
+                let (text, stopped) = output.push(&piece);
+                if (!text.is_empty() && !on_text(&text)) || stopped {
+                    done_reason = "stop";
+                    cut_off = true;
+                    break;
+                }
+            }
+
+            if eval_count < options.num_predict {
+                let input = Tensor::new(&[next], &Device::Cpu)?.unsqueeze(0)?;
+                logits = self.weights.forward(&input, tokens.len() - 1)?.squeeze(0)?;
+            }
+        }
+
+        if !cut_off {
+            let (text, stopped) = output.push(&decoder.flush(&self.tokenizer)?);
+            let text = if stopped {
+                text
+            } else {
+                text + &output.flush()
+            };
+            if !text.is_empty() {
+                on_text(&text);
+            }
+        }
+
+        Ok(GenerationStats {
+            done_reason,
+            prompt_eval_count: prompt_tokens.len(),
+            prompt_eval_duration,
+            eval_count,
+            eval_duration: eval_start.elapsed(),
+            total_duration: start.elapsed(),
+        })
+    }
+}
+
+/// Turns generated token ids into text, holding back incomplete UTF-8 sequences
+#[derive(Default)]
+struct TokenDecoder {
+    tokens: Vec<u32>,
+    prev_index: usize,
+    current_index: usize,
+}
+
+impl TokenDecoder {
+    fn push(
+        &mut self,
+        tokenizer: &Tokenizer,
+        token: u32,
+    ) -> Result<Option<String>, LocalClientError> {
+        // Decoding a window rather than single tokens keeps leading spaces intact
+        let prev_text =
+            tokenizer.decode(&self.tokens[self.prev_index..self.current_index], true)?;
+        self.tokens.push(token);
+        let text = tokenizer.decode(&self.tokens[self.prev_index..], true)?;
+        if text.len() > prev_text.len() && !text.ends_with('\u{FFFD}') {
+            self.prev_index = self.current_index;
+            self.current_index = self.tokens.len();
+            Ok(text.get(prev_text.len()..).map(|piece| piece.to_string()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn flush(&mut self, tokenizer: &Tokenizer) -> Result<String, LocalClientError> {
+        let prev_text =
+            tokenizer.decode(&self.tokens[self.prev_index..self.current_index], true)?;
+        let text = tokenizer.decode(&self.tokens[self.prev_index..], true)?;
+        self.prev_index = self.tokens.len();
+        self.current_index = self.tokens.len();
+        Ok(text.get(prev_text.len()..).unwrap_or_default().to_string())
+    }
+}
+
+/// Withholds text that could be the start of a stop sequence
+struct StopFilter<'a> {
+    stop: &'a [String],
+    pending: String,
+}
+
+impl<'a> StopFilter<'a> {
+    fn new(stop: &'a [String]) -> Self {
+        StopFilter {
+            stop,
+            pending: String::new(),
+        }
+    }
+
+    /// Returns the text that is safe to emit and whether a stop sequence was hit
+    fn push(&mut self, piece: &str) -> (String, bool) {
+        self.pending.push_str(piece);
+
+        if let Some(index) = self
+            .stop
+            .iter()
+            .filter(|stop| !stop.is_empty())
+            .filter_map(|stop| self.pending.find(stop.as_str()))
+            .min()
+        {
+            let text = self.pending[..index].to_string();
+            self.pending.clear();
+            return (text, true);
+        }
+
+        let keep_from = self
+            .pending
+            .char_indices()
+            .map(|(i, _)| i)
+            .find(|&i| {
+                let tail = &self.pending[i..];
+                self.stop.iter().any(|stop| stop.starts_with(tail))
+            })
+            .unwrap_or(self.pending.len());
+        let text = self.pending[..keep_from].to_string();
+        self.pending.drain(..keep_from);
+        (text, false)
+    }
+
+    fn flush(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+struct Embedder {
+    model: BertModel,
+    tokenizer: Tokenizer,
+}
+
+impl Embedder {
+    fn embed(
+        &self,
+        inputs: Vec<String>,
+        normalize: bool,
+    ) -> Result<Vec<Vec<f32>>, LocalClientError> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let encodings = self.tokenizer.encode_batch(inputs, true)?;
+
+        let ids = encodings
+            .iter()
+            .map(|encoding| Tensor::new(encoding.get_ids(), &Device::Cpu))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mask = encodings
+            .iter()
+            .map(|encoding| Tensor::new(encoding.get_attention_mask(), &Device::Cpu))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ids = Tensor::stack(&ids, 0)?;
+        let mask = Tensor::stack(&mask, 0)?;
+        let token_type_ids = ids.zeros_like()?;
+
+        let hidden = self.model.forward(&ids, &token_type_ids, Some(&mask))?;
+        let mask = mask.to_dtype(DTYPE)?.unsqueeze(2)?;
+        let summed = hidden.broadcast_mul(&mask)?.sum(1)?;
+        let mut pooled = summed.broadcast_div(&mask.sum(1)?)?;
+        if normalize {
+            pooled = pooled.broadcast_div(&pooled.sqr()?.sum_keepdim(1)?.sqrt()?)?;
+        }
+
+        let embeddings = pooled.to_vec2::<f32>()?;
+        info!("Successfully created {} embeddings.", embeddings.len());
+        Ok(embeddings)
+    }
+}
+
+/// Records the finish reason and token usage of a finished generation
+fn record_response(telemetry: &GenAiSpan, response: &GenerateResponse) {
+    if let Some(done_reason) = &response.done_reason {
+        telemetry.finish_reasons(done_reason);
+    }
+    telemetry.usage(response.prompt_eval_count, response.eval_count);
+}
+
+/// Derives a model name from a file or directory path
+fn model_name(path: &str) -> String {
+    let path = Path::new(path.trim_end_matches('/'));
+    path.file_stem()
+        .or_else(|| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn unsupported(capability: &str) -> LocalClientError {
+    LocalClientError::RequestError(format!("the loaded model does not support {}", capability))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn stops(stops: &[&str]) -> Vec<String> {
+        stops.iter().map(|stop| stop.to_string()).collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ai_rs-local-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_stop_filter_passes_text_without_stop_sequences() {
+        let mut filter = StopFilter::new(&[]);
+        assert_eq!(filter.push("Hello"), ("Hello".to_string(), false));
+        assert_eq!(filter.push(" world"), (" world".to_string(), false));
+        assert_eq!(filter.flush(), "");
+    }
+
+    #[test]
+    fn test_stop_filter_cuts_at_stop_sequence() {
+        let stop = stops(&["User:"]);
+        let mut filter = StopFilter::new(&stop);
+        assert_eq!(
+            filter.push("Sure.\nUser: next"),
+            ("Sure.\n".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn test_stop_filter_withholds_partial_stop_sequence() {
+        let stop = stops(&["User:"]);
+        let mut filter = StopFilter::new(&stop);
+        assert_eq!(filter.push("Sure. Us"), ("Sure. ".to_string(), false));
+        assert_eq!(filter.push("er:"), (String::new(), true));
+
+        let mut filter = StopFilter::new(&stop);
+        assert_eq!(filter.push("Sure. Us"), ("Sure. ".to_string(), false));
+        assert_eq!(filter.push("ually"), ("Usually".to_string(), false));
+    }
+
+    #[test]
+    fn test_stop_filter_flushes_withheld_text() {
+        let stop = stops(&["User:"]);
+        let mut filter = StopFilter::new(&stop);
+        assert_eq!(filter.push("Done. Use"), ("Done. ".to_string(), false));
+        assert_eq!(filter.flush(), "Use");
+        assert_eq!(filter.flush(), "");
+    }
+
+    #[test]
+    fn test_stop_filter_uses_earliest_stop_sequence() {
+        let stop = stops(&["world", "\n", ""]);
+        let mut filter = StopFilter::new(&stop);
+        assert_eq!(filter.push("Hi\nworld"), ("Hi".to_string(), true));
+    }
+
+    #[test]
+    fn test_model_name_from_path() {
+        assert_eq!(
+            model_name("/models/llama-3.2-1b.Q4_K_M.gguf"),
+            "llama-3.2-1b.Q4_K_M"
+        );
+        assert_eq!(model_name("/models/all-MiniLM-L6-v2/"), "all-MiniLM-L6-v2");
+        assert_eq!(model_name("model"), "model");
+    }
+
+    #[test]
+    fn test_from_gguf_missing_file() {
+        match LocalClient::from_gguf("/nonexistent/model.gguf", "/nonexistent/tokenizer.json") {
+            Err(LocalClientError::IoError(err)) => {
+                assert_eq!(err.kind(), std::io::ErrorKind::NotFound)
+            }
+            other => panic!("expected IoError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_gguf_invalid_file() {
+        let dir = temp_dir("gguf");
+        let path = dir.join("model.gguf");
+        std::fs::write(&path, b"not a gguf file").unwrap();
+        let result = LocalClient::from_gguf(path.to_str().unwrap(), "tokenizer.json");
+        std::fs::remove_dir_all(&dir).unwrap();
+        match result {
+            Err(LocalClientError::LoadError(msg)) => assert!(msg.contains("model.gguf")),
+            other => panic!("expected LoadError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_safetensors_missing_config() {
+        let dir = temp_dir("missing-config");
+        let result = LocalClient::from_safetensors(dir.to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        match result {
+            Err(LocalClientError::IoError(err)) => {
+                assert_eq!(err.kind(), std::io::ErrorKind::NotFound)
+            }
+            other => panic!("expected IoError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_safetensors_invalid_config() {
+        let dir = temp_dir("invalid-config");
+        std::fs::write(dir.join("config.json"), "{\"hidden_size\": \"large\"}").unwrap();
+        let result = LocalClient::from_safetensors(dir.to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        match result {
+            Err(LocalClientError::LoadError(msg)) => assert!(msg.starts_with("config.json")),
+            other => panic!("expected LoadError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_capability() {
+        let err = unsupported("embeddings");
+        assert_eq!(
+            err.to_string(),
+            "Request error: the loaded model does not support embeddings"
+        );
+    }
+}
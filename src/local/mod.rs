@@ -0,0 +1,5 @@
+pub mod client;
+pub mod types;
+
+pub use client::{LocalClient, LocalClientError};
+pub use types::LocalGenerateOptions;
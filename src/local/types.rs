@@ -0,0 +1,211 @@
+use crate::ollama::types::GenerateRequest;
+use candle_transformers::generation::Sampling;
+
+/// Sampling options for in-process generation
+///
+/// Built from the same Ollama `options` object that `OllamaClient` forwards to the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalGenerateOptions {
+    /// Maximum number of tokens to generate (`num_predict`)
+    pub num_predict: usize,
+    /// Sampling temperature; `0` selects greedy decoding
+    pub temperature: f64,
+    /// Nucleus sampling threshold
+    pub top_p: Option<f64>,
+    /// Top-k sampling
+    pub top_k: Option<usize>,
+    /// Penalty applied to recently generated tokens; `1.0` disables it
+    pub repeat_penalty: f32,
+    /// Number of trailing tokens the repeat penalty looks at
+    pub repeat_last_n: usize,
+    /// Seed for the sampler
+    pub seed: u64,
+    /// Stop sequences; generation ends before the first match
+    pub stop: Vec<String>,
+}
+
+impl Default for LocalGenerateOptions {
+    fn default() -> Self {
+        // Same defaults as Ollama, except for a fixed seed so runs are reproducible
+        LocalGenerateOptions {
+            num_predict: 128,
+            temperature: 0.8,
+            top_p: Some(0.9),
+            top_k: Some(40),
+            repeat_penalty: 1.1,
+            repeat_last_n: 64,
+            seed: 42,
+            stop: Vec::new(),
+        }
+    }
+}
+
+impl From<&GenerateRequest> for LocalGenerateOptions {
+    /// Reads `num_predict`, `temperature`, `top_p`, `top_k`, `repeat_penalty`,
+    /// `repeat_last_n`, `seed` and `stop` from the request's `options`
+    fn from(request: &GenerateRequest) -> Self {
+        let defaults = LocalGenerateOptions::default();
+        let option = |name: &str| {
+            request
+                .options
+                .as_ref()
+                .and_then(|options| options.get(name))
+        };
+        let as_usize = |name: &str| {
+            option(name)
+                .and_then(|value| value.as_u64())
+                .map(|v| v as usize)
+        };
+
+        LocalGenerateOptions {
+            num_predict: as_usize("num_predict").unwrap_or(defaults.num_predict),
+            temperature: option("temperature")
+                .and_then(|value| value.as_f64())
+                .unwrap_or(defaults.temperature),
+            top_p: option("top_p")
+                .and_then(|value| value.as_f64())
+                .or(defaults.top_p),
+            top_k: as_usize("top_k").or(defaults.top_k),
+            repeat_penalty: option("repeat_penalty")
+                .and_then(|value| value.as_f64())
+                .map(|v| v as f32)
+                .unwrap_or(defaults.repeat_penalty),
+            repeat_last_n: as_usize("repeat_last_n").unwrap_or(defaults.repeat_last_n),
+            seed: option("seed")
+                .and_then(|value| value.as_u64())
+                .unwrap_or(defaults.seed),
+            stop: option("stop")
+                .and_then(|value| serde_json::from_value(value.clone()).ok())
+                .unwrap_or(defaults.stop),
+        }
+    }
+}
+
+impl LocalGenerateOptions {
+    /// The sampling strategy these options describe
+    pub fn sampling(&self) -> Sampling {
+        if self.temperature <= 0.0 {
+            return Sampling::ArgMax;
+        }
+        let temperature = self.temperature;
+        match (self.top_k, self.top_p) {
+            (Some(k), Some(p)) => Sampling::TopKThenTopP { k, p, temperature },
+            (Some(k), None) => Sampling::TopK { k, temperature },
+            (None, Some(p)) => Sampling::TopP { p, temperature },
+            (None, None) => Sampling::All { temperature },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(options: serde_json::Value) -> GenerateRequest {
+        GenerateRequest {
+            model: "model".to_string(),
+            prompt: "Hello".to_string(),
+            options: Some(options),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_options_default_without_request_options() {
+        let options = LocalGenerateOptions::from(&GenerateRequest::default());
+        assert_eq!(options, LocalGenerateOptions::default());
+    }
+
+    #[test]
+    fn test_options_read_from_request() {
+        let options = LocalGenerateOptions::from(&request(json!({
+            "num_predict": 16,
+            "temperature": 0.2,
+            "top_p": 0.5,
+            "top_k": 5,
+            "repeat_penalty": 1.3,
+            "repeat_last_n": 8,
+            "seed": 7,
+            "stop": ["\n\n", "User:"]
+        })));
+        assert_eq!(
+            options,
+            LocalGenerateOptions {
+                num_predict: 16,
+                temperature: 0.2,
+                top_p: Some(0.5),
+                top_k: Some(5),
+                repeat_penalty: 1.3,
+                repeat_last_n: 8,
+                seed: 7,
+                stop: vec!["\n\n".to_string(), "User:".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_options_ignore_values_of_the_wrong_type() {
+        let options = LocalGenerateOptions::from(&request(json!({
+            "num_predict": "many",
+            "top_k": -1,
+            "stop": "User:"
+        })));
+        assert_eq!(options.num_predict, 128);
+        assert_eq!(options.top_k, Some(40));
+        assert!(options.stop.is_empty());
+    }
+
+    #[test]
+    fn test_sampling_strategies() {
+        let options = LocalGenerateOptions::default();
+        assert_eq!(
+            options.sampling(),
+            Sampling::TopKThenTopP {
+                k: 40,
+                p: 0.9,
+                temperature: 0.8
+            }
+        );
+
+        let top_k = LocalGenerateOptions {
+            top_p: None,
+            ..LocalGenerateOptions::default()
+        };
+        assert_eq!(
+            top_k.sampling(),
+            Sampling::TopK {
+                k: 40,
+                temperature: 0.8
+            }
+        );
+
+        let top_p = LocalGenerateOptions {
+            top_k: None,
+            ..LocalGenerateOptions::default()
+        };
+        assert_eq!(
+            top_p.sampling(),
+            Sampling::TopP {
+                p: 0.9,
+                temperature: 0.8
+            }
+        );
+
+        let all = LocalGenerateOptions {
+            top_k: None,
+            top_p: None,
+            ..LocalGenerateOptions::default()
+        };
+        assert_eq!(all.sampling(), Sampling::All { temperature: 0.8 });
+    }
+
+    #[test]
+    fn test_zero_temperature_is_greedy() {
+        let options = LocalGenerateOptions {
+            temperature: 0.0,
+            ..LocalGenerateOptions::default()
+        };
+        assert_eq!(options.sampling(), Sampling::ArgMax);
+    }
+}
@@ -0,0 +1,242 @@
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+
+/// Environment variable read for the initial payload logging mode
+pub const PAYLOAD_LOGGING_ENV: &str = "AI_RS_LOG_PAYLOADS";
+
+/// Number of characters kept by the default `PayloadLogging::Truncated` mode
+const DEFAULT_TRUNCATE_CHARS: usize = 512;
+
+/// Strings at least this long that look like base64 are never logged
+const MIN_BINARY_LEN: usize = 256;
+
+static MODE: Mutex<Option<PayloadLogging>> = Mutex::new(None);
+
+/// How prompts, responses and other request payloads appear in the logs
+///
+/// Payloads are only logged at the `trace` level. Base64 data such as inline
+/// images is replaced by its size in every mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadLogging {
+    /// Only the payload size is logged
+    Off,
+    /// The first characters of the payload are logged
+    Truncated(usize),
+    /// A SHA-256 digest is logged, to correlate identical payloads without exposing them
+    Hashed,
+    /// The whole payload is logged
+    Full,
+}
+
+impl Default for PayloadLogging {
+    fn default() -> Self {
+        PayloadLogging::Truncated(DEFAULT_TRUNCATE_CHARS)
+    }
+}
+
+impl PayloadLogging {
+    /// Parses a mode: "off", "truncated", "truncated:<chars>", "hashed" or "full"
+    ///
+    /// # Returns
+    ///
+    /// The mode, or `None` if the value is not recognized
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.split_once(':') {
+            Some(("truncated", chars)) => chars.parse().ok().map(PayloadLogging::Truncated),
+            Some(_) => None,
+            None => match value.as_str() {
+                "off" => Some(PayloadLogging::Off),
+                "truncated" => Some(PayloadLogging::default()),
+                "hashed" => Some(PayloadLogging::Hashed),
+                "full" => Some(PayloadLogging::Full),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Sets how payloads are logged, overriding `AI_RS_LOG_PAYLOADS`
+pub fn set_payload_logging(mode: PayloadLogging) {
+    *MODE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(mode);
+}
+
+/// Returns the current payload logging mode
+///
+/// Until `set_payload_logging` is called, the mode comes from `AI_RS_LOG_PAYLOADS`,
+/// defaulting to `PayloadLogging::Truncated(512)`.
+pub fn payload_logging() -> PayloadLogging {
+    let mut mode = MODE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    *mode.get_or_insert_with(|| {
+        std::env::var(PAYLOAD_LOGGING_ENV)
+            .ok()
+            .and_then(|value| PayloadLogging::parse(&value))
+            .unwrap_or_default()
+    })
+}
+
+/// Formats a text payload, such as a response body, for the logs
+///
+/// JSON documents, and each line of newline-delimited JSON, have their binary fields omitted.
+pub(crate) fn payload(text: &str) -> String {
+    if let Ok(mut value) = serde_json::from_str::<Value>(text) {
+        omit_binary(&mut value);
+        return format_payload(&value.to_string());
+    }
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| match serde_json::from_str::<Value>(line) {
+            Ok(mut value) => {
+                omit_binary(&mut value);
+                value.to_string()
+            }
+            Err(_) if is_binary(line) => omitted(line),
+            Err(_) => line.to_string(),
+        })
+        .collect();
+    format_payload(&lines.join("\n"))
+}
+
+/// Formats a serializable payload, such as a request, for the logs
+pub(crate) fn payload_json<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(mut value) => {
+            omit_binary(&mut value);
+            format_payload(&value.to_string())
+        }
+        Err(err) => format!("<unserializable payload: {}>", err),
+    }
+}
+
+fn format_payload(text: &str) -> String {
+    format_with(payload_logging(), text)
+}
+
+/// Formats a payload for the given mode
+fn format_with(mode: PayloadLogging, text: &str) -> String {
+    match mode {
+        PayloadLogging::Off => format!("<omitted, {} bytes>", text.len()),
+        PayloadLogging::Truncated(chars) => match text.char_indices().nth(chars) {
+            Some((end, _)) => format!("{}... <{} bytes total>", &text[..end], text.len()),
+            None => text.to_string(),
+        },
+        PayloadLogging::Hashed => {
+            let digest = hex::encode(Sha256::digest(text.as_bytes()));
+            format!("<sha256 {}, {} bytes>", &digest[..16], text.len())
+        }
+        PayloadLogging::Full => text.to_string(),
+    }
+}
+
+/// Replaces base64 strings in a JSON value with their size
+fn omit_binary(value: &mut Value) {
+    match value {
+        Value::String(text) if is_binary(text) => *text = omitted(text),
+        Value::Array(items) => items.iter_mut().for_each(omit_binary),
+        Value::Object(map) => map.values_mut().for_each(omit_binary),
+        _ => {}
+    }
+}
+
+/// Returns `true` for long base64 strings and base64 `data:` URLs
+fn is_binary(text: &str) -> bool {
+    if text.len() < MIN_BINARY_LEN {
+        return false;
+    }
+    let encoded = match text.strip_prefix("data:") {
+        Some(url) => match url.split_once(";base64,") {
+            Some((_, encoded)) => encoded,
+            None => return false,
+        },
+        None => text,
+    };
+    encoded.bytes().all(|byte| {
+        byte.is_ascii_alphanumeric() || matches!(byte, b'+' | b'/' | b'=' | b'-' | b'_')
+    })
+}
+
+fn omitted(text: &str) -> String {
+    format!("<{} bytes of base64 omitted>", text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!(PayloadLogging::parse("off"), Some(PayloadLogging::Off));
+        assert_eq!(
+            PayloadLogging::parse(" Truncated "),
+            Some(PayloadLogging::Truncated(DEFAULT_TRUNCATE_CHARS))
+        );
+        assert_eq!(
+            PayloadLogging::parse("truncated:40"),
+            Some(PayloadLogging::Truncated(40))
+        );
+        assert_eq!(
+            PayloadLogging::parse("HASHED"),
+            Some(PayloadLogging::Hashed)
+        );
+        assert_eq!(PayloadLogging::parse("full"), Some(PayloadLogging::Full));
+        assert_eq!(PayloadLogging::parse("truncated:many"), None);
+        assert_eq!(PayloadLogging::parse("hashed:8"), None);
+        assert_eq!(PayloadLogging::parse("verbose"), None);
+    }
+
+    #[test]
+    fn off_logs_the_size_only() {
+        assert_eq!(
+            format_with(PayloadLogging::Off, "secret prompt"),
+            "<omitted, 13 bytes>"
+        );
+    }
+
+    #[test]
+    fn truncated_keeps_the_first_characters() {
+        assert_eq!(
+            format_with(PayloadLogging::Truncated(5), "héllo world"),
+            "héllo... <12 bytes total>"
+        );
+        assert_eq!(format_with(PayloadLogging::Truncated(5), "short"), "short");
+        let long = "x".repeat(DEFAULT_TRUNCATE_CHARS + 1);
+        assert_eq!(
+            format_with(PayloadLogging::default(), &long),
+            format!("{}... <513 bytes total>", &long[..DEFAULT_TRUNCATE_CHARS])
+        );
+    }
+
+    #[test]
+    fn hashed_logs_a_digest() {
+        let hashed = format_with(PayloadLogging::Hashed, "secret prompt");
+        assert!(!hashed.contains("secret"));
+        assert!(hashed.starts_with("<sha256 "));
+        assert!(hashed.ends_with(", 13 bytes>"));
+        assert_eq!(hashed, format_with(PayloadLogging::Hashed, "secret prompt"));
+        assert_ne!(hashed, format_with(PayloadLogging::Hashed, "other prompt"));
+    }
+
+    #[test]
+    fn full_logs_everything() {
+        assert_eq!(
+            format_with(PayloadLogging::Full, "secret prompt"),
+            "secret prompt"
+        );
+    }
+
+    #[test]
+    fn omits_binary_data() {
+        let image = "A".repeat(MIN_BINARY_LEN);
+        let mut value = serde_json::json!({
+            "text": "hi",
+            "parts": [{"data": image}, {"url": format!("data:image/png;base64,{}", image)}],
+        });
+        omit_binary(&mut value);
+        assert_eq!(value["text"], "hi");
+        assert_eq!(value["parts"][0]["data"], "<256 bytes of base64 omitted>");
+        assert_eq!(value["parts"][1]["url"], "<278 bytes of base64 omitted>");
+        assert!(!is_binary("short"));
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+
+/// Content type of the Prometheus text exposition format returned by `render`
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds, in seconds, of the duration histogram buckets
+const SECONDS_BUCKETS: [f64; 14] = [
+    0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+/// Metrics recorded by the clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Metric {
+    Requests,
+    RequestDuration,
+    TimeToFirstToken,
+    Tokens,
+    OllamaLoadDuration,
+    OllamaPromptEvalDuration,
+    OllamaEvalDuration,
+}
+
+impl Metric {
+    const ALL: [Metric; 7] = [
+        Metric::Requests,
+        Metric::RequestDuration,
+        Metric::TimeToFirstToken,
+        Metric::Tokens,
+        Metric::OllamaLoadDuration,
+        Metric::OllamaPromptEvalDuration,
+        Metric::OllamaEvalDuration,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Metric::Requests => "gen_ai_client_requests_total",
+            Metric::RequestDuration => "gen_ai_client_operation_duration_seconds",
+            Metric::TimeToFirstToken => "gen_ai_server_time_to_first_token_seconds",
+            Metric::Tokens => "gen_ai_client_token_usage_total",
+            Metric::OllamaLoadDuration => "ollama_load_duration_seconds",
+            Metric::OllamaPromptEvalDuration => "ollama_prompt_eval_duration_seconds",
+            Metric::OllamaEvalDuration => "ollama_eval_duration_seconds",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Metric::Requests => "Model calls by provider, model and outcome",
+            Metric::RequestDuration => "Duration of model calls, including the whole stream",
+            Metric::TimeToFirstToken => "Time until the first chunk of a streamed response",
+            Metric::Tokens => "Tokens reported by the provider, by type (input or output)",
+            Metric::OllamaLoadDuration => "Time Ollama spent loading the model",
+            Metric::OllamaPromptEvalDuration => "Time Ollama spent evaluating the prompt",
+            Metric::OllamaEvalDuration => "Time Ollama spent generating the response",
+        }
+    }
+
+    fn is_histogram(self) -> bool {
+        !matches!(self, Metric::Requests | Metric::Tokens)
+    }
+}
+
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Debug)]
+enum Series {
+    Counter(f64),
+    Histogram {
+        buckets: [u64; SECONDS_BUCKETS.len()],
+        sum: f64,
+        count: u64,
+    },
+}
+
+type Registry = Mutex<BTreeMap<&'static str, BTreeMap<Labels, Series>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+fn with_series(metric: Metric, labels: &[(&'static str, &str)], update: impl FnOnce(&mut Series)) {
+    let labels: Labels = labels
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect();
+    let mut registry = registry()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let series = registry
+        .entry(metric.name())
+        .or_default()
+        .entry(labels)
+        .or_insert_with(|| {
+            if metric.is_histogram() {
+                Series::Histogram {
+                    buckets: [0; SECONDS_BUCKETS.len()],
+                    sum: 0.0,
+                    count: 0,
+                }
+            } else {
+                Series::Counter(0.0)
+            }
+        });
+    update(series);
+}
+
+/// Adds `value` to a counter
+pub(crate) fn increment(metric: Metric, labels: &[(&'static str, &str)], value: f64) {
+    with_series(metric, labels, |series| {
+        if let Series::Counter(total) = series {
+            *total += value;
+        }
+    });
+}
+
+/// Records one observation, in seconds, in a histogram
+pub(crate) fn observe(metric: Metric, labels: &[(&'static str, &str)], seconds: f64) {
+    with_series(metric, labels, |series| {
+        if let Series::Histogram {
+            buckets,
+            sum,
+            count,
+        } = series
+        {
+            for (bucket, bound) in buckets.iter_mut().zip(SECONDS_BUCKETS) {
+                if seconds <= bound {
+                    *bucket += 1;
+                }
+            }
+            *sum += seconds;
+            *count += 1;
+        }
+    });
+}
+
+/// Renders all recorded metrics in the Prometheus text exposition format
+///
+/// Serve the result with the `CONTENT_TYPE` header, e.g. on `/metrics`.
+///
+/// # Returns
+///
+/// The exposition text; empty until a client has recorded something
+pub fn render() -> String {
+    let registry = registry()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut out = String::new();
+    for metric in Metric::ALL {
+        let family = match registry.get(metric.name()) {
+            Some(family) => family,
+            None => continue,
+        };
+        let name = metric.name();
+        let kind = if metric.is_histogram() {
+            "histogram"
+        } else {
+            "counter"
+        };
+        let _ = writeln!(out, "# HELP {} {}", name, metric.help());
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (labels, series) in family {
+            match series {
+                Series::Counter(total) => {
+                    let _ = writeln!(out, "{}{} {}", name, format_labels(labels, None), total);
+                }
+                Series::Histogram {
+                    buckets,
+                    sum,
+                    count,
+                } => {
+                    for (bucket, bound) in buckets.iter().zip(SECONDS_BUCKETS) {
+                        let le = bound.to_string();
+                        let _ = writeln!(
+                            out,
+                            "{}_bucket{} {}",
+                            name,
+                            format_labels(labels, Some(&le)),
+                            bucket
+                        );
+                    }
+                    let _ = writeln!(
+                        out,
+                        "{}_bucket{} {}",
+                        name,
+                        format_labels(labels, Some("+Inf")),
+                        count
+                    );
+                    let _ = writeln!(out, "{}_sum{} {}", name, format_labels(labels, None), sum);
+                    let _ = writeln!(
+                        out,
+                        "{}_count{} {}",
+                        name,
+                        format_labels(labels, None),
+                        count
+                    );
+                }
+            }
+        }
+    }
+    out
+}
+
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+/// Escapes a label value as required by the exposition format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mistral::MistralClient;
+
+    // The registry is global, so each test records under its own model label
+
+    #[test]
+    fn renders_counters() {
+        let labels = [("provider", "test"), ("model", "counter-model")];
+        increment(
+            Metric::Requests,
+            &[labels[0], labels[1], ("outcome", "success")],
+            1.0,
+        );
+        increment(
+            Metric::Requests,
+            &[labels[0], labels[1], ("outcome", "success")],
+            1.0,
+        );
+        increment(
+            Metric::Requests,
+            &[labels[0], labels[1], ("outcome", "429")],
+            1.0,
+        );
+
+        let out = render();
+        assert!(out.contains(
+            "# HELP gen_ai_client_requests_total Model calls by provider, model and outcome\n\
+             # TYPE gen_ai_client_requests_total counter\n"
+        ));
+        assert!(out.contains(
+            "gen_ai_client_requests_total{provider=\"test\",model=\"counter-model\",outcome=\"success\"} 2\n"
+        ));
+        assert!(out.contains(
+            "gen_ai_client_requests_total{provider=\"test\",model=\"counter-model\",outcome=\"429\"} 1\n"
+        ));
+    }
+
+    #[test]
+    fn renders_histograms() {
+        let labels = [("provider", "test"), ("model", "histogram-model")];
+        observe(Metric::RequestDuration, &labels, 0.25);
+        observe(Metric::RequestDuration, &labels, 2.0);
+
+        let out = render();
+        let series = "gen_ai_client_operation_duration_seconds";
+        let labels = "provider=\"test\",model=\"histogram-model\"";
+        assert!(out.contains(&format!("# TYPE {} histogram\n", series)));
+        assert!(out.contains(&format!("{}_bucket{{{},le=\"0.1\"}} 0\n", series, labels)));
+        assert!(out.contains(&format!("{}_bucket{{{},le=\"0.25\"}} 1\n", series, labels)));
+        assert!(out.contains(&format!("{}_bucket{{{},le=\"2.5\"}} 2\n", series, labels)));
+        assert!(out.contains(&format!("{}_bucket{{{},le=\"+Inf\"}} 2\n", series, labels)));
+        assert!(out.contains(&format!("{}_sum{{{}}} 2.25\n", series, labels)));
+        assert!(out.contains(&format!("{}_count{{{}}} 2\n", series, labels)));
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        increment(
+            Metric::Tokens,
+            &[("model", "say \"hi\"\\\nbye"), ("type", "input")],
+            3.0,
+        );
+        assert!(render().contains(
+            "gen_ai_client_token_usage_total{model=\"say \\\"hi\\\"\\\\\\nbye\",type=\"input\"} 3\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn records_provider_calls() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/chat/completions")
+            .with_body(
+                r#"{
+                    "id": "cmpl-1",
+                    "model": "metrics-model",
+                    "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hi"}, "finish_reason": "stop"}],
+                    "usage": {"prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4}
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let client = MistralClient::new("key", "metrics-model").base_url(&server.url());
+        client.chat("Hello").await.unwrap();
+
+        let out = render();
+        let labels = "provider=\"mistral_ai\",model=\"metrics-model\"";
+        assert!(out.contains(&format!(
+            "gen_ai_client_requests_total{{{},outcome=\"success\"}} 1\n",
+            labels
+        )));
+        assert!(out.contains(&format!(
+            "gen_ai_client_token_usage_total{{{},type=\"input\"}} 3\n",
+            labels
+        )));
+        assert!(out.contains(&format!(
+            "gen_ai_client_token_usage_total{{{},type=\"output\"}} 1\n",
+            labels
+        )));
+        assert!(out.contains(&format!(
+            "gen_ai_client_operation_duration_seconds_count{{{}}} 1\n",
+            labels
+        )));
+    }
+}
@@ -0,0 +1,305 @@
+use crate::cache::ResponseCache;
+use crate::rate_limit::RateLimiter;
+use crate::retry::RetryPolicy;
+use crate::usage::{UsageError, UsageLedger};
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
+use futures_util::{Stream, StreamExt};
+use http::Extensions;
+use reqwest::header::HeaderMap;
+use reqwest::{Body, Client, Method, Response, ResponseBuilderExt, StatusCode, Url};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{error, info};
+
+/// Error returned by the middleware stack
+#[derive(Debug)]
+pub enum HttpError {
+    /// The request could not be completed
+    Network(reqwest::Error),
+    /// The server answered with an unsuccessful status
+    Status { status: StatusCode, body: String },
+    /// A middleware rejected or failed the request
+    Middleware(String),
+    /// The usage ledger rejected the request because it would exceed a budget
+    BudgetExceeded(UsageError),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Network(err) => write!(f, "{}", err),
+            HttpError::Status { status, body } => write!(f, "{}: {}", status, body),
+            HttpError::Middleware(msg) => write!(f, "{}", msg),
+            HttpError::BudgetExceeded(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl From<reqwest::Error> for HttpError {
+    fn from(err: reqwest::Error) -> Self {
+        HttpError::Network(err)
+    }
+}
+
+/// A request travelling through the middleware stack
+///
+/// `extensions` carries typed values between the client and the middleware,
+/// such as `EstimatedTokens` for the rate limiter.
+#[derive(Debug)]
+pub struct Request {
+    inner: reqwest::Request,
+    extensions: Extensions,
+}
+
+impl Request {
+    /// Wraps a built `reqwest::Request`
+    pub fn new(inner: reqwest::Request) -> Self {
+        Request {
+            inner,
+            extensions: Extensions::new(),
+        }
+    }
+
+    /// The HTTP method
+    pub fn method(&self) -> &Method {
+        self.inner.method()
+    }
+
+    /// The request URL
+    pub fn url(&self) -> &Url {
+        self.inner.url()
+    }
+
+    /// The request headers
+    pub fn headers(&self) -> &HeaderMap {
+        self.inner.headers()
+    }
+
+    /// The request headers, e.g. to inject or refresh an auth token
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        self.inner.headers_mut()
+    }
+
+    /// The request body, if it is held in memory
+    pub fn body_bytes(&self) -> Option<&[u8]> {
+        self.inner.body().and_then(|body| body.as_bytes())
+    }
+
+    /// The underlying `reqwest::Request`
+    pub fn inner_mut(&mut self) -> &mut reqwest::Request {
+        &mut self.inner
+    }
+
+    /// Values attached to the request by the client or earlier middleware
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Values attached to the request, mutably
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Copies the request, or returns `None` if its body is a stream
+    pub fn try_clone(&self) -> Option<Request> {
+        Some(Request {
+            inner: self.inner.try_clone()?,
+            extensions: self.extensions.clone(),
+        })
+    }
+
+    /// Unwraps the `reqwest::Request`
+    pub fn into_inner(self) -> reqwest::Request {
+        self.inner
+    }
+}
+
+/// A layer around the HTTP call of a client
+///
+/// A middleware can change the request, answer it without calling `next`, call
+/// `next` several times, or inspect and replace the response.
+pub trait Middleware: Send + Sync + 'static {
+    /// Handles a request, usually by passing it on with `next.run(request)`
+    fn handle<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response, HttpError>>;
+}
+
+/// The rest of the middleware stack, ending with the HTTP call
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    client: &'a Client,
+    middleware: &'a [&'a dyn Middleware],
+}
+
+impl<'a> Next<'a> {
+    /// Passes the request to the next middleware, or sends it when none is left
+    pub fn run(self, request: Request) -> BoxFuture<'a, Result<Response, HttpError>> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.handle(
+                request,
+                Next {
+                    client: self.client,
+                    middleware: rest,
+                },
+            ),
+            None => Box::pin(async move {
+                self.client
+                    .execute(request.into_inner())
+                    .await
+                    .map_err(HttpError::Network)
+            }),
+        }
+    }
+}
+
+/// Middleware that logs each request with its status and duration
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestLog;
+
+impl Middleware for RequestLog {
+    fn handle<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response, HttpError>> {
+        Box::pin(async move {
+            let method = request.method().clone();
+            let url = request.url().clone();
+            let started = Instant::now();
+            let result = next.run(request).await;
+            match &result {
+                Ok(response) => info!(
+                    "{} {} -> {} in {:?}",
+                    method,
+                    url,
+                    response.status(),
+                    started.elapsed()
+                ),
+                Err(err) => error!(
+                    "{} {} failed in {:?}: {}",
+                    method,
+                    url,
+                    started.elapsed(),
+                    err
+                ),
+            }
+            result
+        })
+    }
+}
+
+/// The HTTP path shared by the clients: cache, budgets and retries, then rate limiting and user
+/// middleware once per attempt
+#[derive(Clone)]
+pub(crate) struct Stack {
+    pub cache: Option<ResponseCache>,
+    pub ledger: Option<UsageLedger>,
+    pub rate_limiter: Option<RateLimiter>,
+    pub retry_policy: RetryPolicy,
+    pub layers: Vec<Arc<dyn Middleware>>,
+}
+
+impl fmt::Debug for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stack")
+            .field("cache", &self.cache)
+            .field("ledger", &self.ledger)
+            .field("rate_limiter", &self.rate_limiter)
+            .field("retry_policy", &self.retry_policy)
+            .field("layers", &self.layers.len())
+            .finish()
+    }
+}
+
+impl Default for Stack {
+    /// An empty stack making a single attempt; retries are opt-in through `retry_policy`
+    fn default() -> Self {
+        Stack {
+            cache: None,
+            ledger: None,
+            rate_limiter: None,
+            retry_policy: RetryPolicy::none(),
+            layers: Vec::new(),
+        }
+    }
+}
+
+impl Stack {
+    /// A copy for health probes: one attempt through the user middleware, without the
+    /// cache, budgets or rate limiting
+    pub fn for_probe(&self) -> Stack {
+        Stack {
+            layers: self.layers.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Sends a request through the stack
+    ///
+    /// On success the response has a 2xx status; other statuses become `HttpError::Status`.
+    pub async fn send(&self, client: &Client, request: Request) -> Result<Response, HttpError> {
+        let mut chain: Vec<&dyn Middleware> = Vec::with_capacity(self.layers.len() + 4);
+        if let Some(cache) = &self.cache {
+            chain.push(cache);
+        }
+        if let Some(ledger) = &self.ledger {
+            chain.push(ledger);
+        }
+        chain.push(&self.retry_policy);
+        if let Some(limiter) = &self.rate_limiter {
+            chain.push(limiter);
+        }
+        chain.extend(self.layers.iter().map(|layer| layer.as_ref()));
+
+        let next = Next {
+            client,
+            middleware: &chain,
+        };
+        let response = next.run(request).await?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(response)
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(HttpError::Status { status, body })
+        }
+    }
+}
+
+/// Rebuilds a response around a new body stream, keeping its status, headers, extensions and URL
+///
+/// # Arguments
+///
+/// * `response` - The response to rebuild
+/// * `body` - Maps the original body stream to the new one
+pub(crate) fn map_body<S>(
+    mut response: Response,
+    body: impl FnOnce(BoxStream<'static, reqwest::Result<Bytes>>) -> S,
+) -> Result<Response, HttpError>
+where
+    S: Stream<Item = reqwest::Result<Bytes>> + Send + 'static,
+{
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+    if let Some(extensions) = builder.extensions_mut() {
+        *extensions = std::mem::take(response.extensions_mut());
+    }
+    let builder = builder.url(response.url().clone());
+    let stream = body(response.bytes_stream().boxed());
+    builder
+        .body(Body::wrap_stream(stream))
+        .map(Response::from)
+        .map_err(|err| HttpError::Middleware(err.to_string()))
+}
@@ -0,0 +1,703 @@
+use crate::cache::{CacheMode, ResponseCache};
+use crate::logging;
+use crate::middleware::{HttpError, Middleware, Request, Stack};
+use crate::mistral::types::{
+    ChatChoice, ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, ChatMessage,
+    EmbeddingRequest, EmbeddingResponse, Usage,
+};
+use crate::rate_limit::{RateLimitPermit, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
+use crate::sse::spawn_sse_stream;
+use crate::telemetry::GenAiSpan;
+use crate::usage::{MaxOutputTokens, ModelName, UsageError, UsageLedger, UsagePermit};
+use futures_util::Stream;
+use reqwest::{Client, RequestBuilder};
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{error, info, trace, Instrument};
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum MistralClientError {
+    /// Error related to the request
+    RequestError(String),
+    /// Network-related error
+    NetworkError(reqwest::Error),
+    /// Error while parsing JSON
+    ParseError(serde_json::Error),
+    /// The request would exceed a budget of the usage ledger and was not sent
+    BudgetExceeded(UsageError),
+}
+
+impl fmt::Display for MistralClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MistralClientError::RequestError(msg) => write!(f, "Request error: {}", msg),
+            MistralClientError::NetworkError(err) => write!(f, "Network error: {}", err),
+            MistralClientError::ParseError(err) => write!(f, "Parse error: {}", err),
+            MistralClientError::BudgetExceeded(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for MistralClientError {}
+
+impl MistralClientError {
+    /// Short error type recorded on the tracing span
+    fn error_type(&self) -> &'static str {
+        match self {
+            MistralClientError::RequestError(_) => "request",
+            MistralClientError::NetworkError(_) => "network",
+            MistralClientError::ParseError(_) => "parse",
+            MistralClientError::BudgetExceeded(_) => "budget_exceeded",
+        }
+    }
+}
+
+impl From<reqwest::Error> for MistralClientError {
+    fn from(err: reqwest::Error) -> Self {
+        MistralClientError::NetworkError(err)
+    }
+}
+
+impl From<serde_json::Error> for MistralClientError {
+    fn from(err: serde_json::Error) -> Self {
+        MistralClientError::ParseError(err)
+    }
+}
+
+impl From<HttpError> for MistralClientError {
+    fn from(err: HttpError) -> Self {
+        match err {
+            HttpError::Status { body, .. } => MistralClientError::RequestError(body),
+            HttpError::Network(err) => MistralClientError::NetworkError(err),
+            HttpError::Middleware(msg) => MistralClientError::RequestError(msg),
+            HttpError::BudgetExceeded(err) => MistralClientError::BudgetExceeded(err),
+        }
+    }
+}
+
+/// `gen_ai.system` value of the OpenTelemetry GenAI conventions
+const GEN_AI_SYSTEM: &str = "mistral_ai";
+
+/// Request body with the client's model and streaming flag added
+#[derive(Serialize)]
+struct ModelRequest<'a, T: Serialize> {
+    model: &'a str,
+    stream: bool,
+    #[serde(flatten)]
+    request: &'a T,
+}
+
+/// Client for interacting with the Mistral API
+#[derive(Debug)]
+pub struct MistralClient {
+    api_key: Secret,
+    model: String,
+    base_url: String,
+    client: Client,
+    stack: Stack,
+}
+
+impl MistralClient {
+    /// Creates a new instance of `MistralClient`
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The Mistral API key
+    /// * `model` - The model to use (e.g., "mistral-small-latest")
+    ///
+    /// # Returns
+    ///
+    /// A new `MistralClient` instance
+    pub fn new(api_key: &str, model: &str) -> Self {
+        info!("Creating new MistralClient with model: {}", model);
+        MistralClient {
+            api_key: Secret::new(api_key),
+            model: model.to_string(),
+            base_url: "https://api.mistral.ai/v1".to_string(),
+            client: Client::new(),
+            stack: Stack::default(),
+        }
+    }
+
+    /// Sets the model to use
+    pub fn model(mut self, model: &str) -> Self {
+        info!("Setting model to {}", model);
+        self.model = model.to_string();
+        self
+    }
+
+    /// Overrides the API base URL
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        info!("Setting base_url to {}", base_url);
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sets the policy used to retry failed requests; without one, requests are not retried
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.stack.retry_policy = policy;
+        self
+    }
+
+    /// Sets a cache for model responses; cache hits skip the rate limiter and the provider
+    pub fn cache(mut self, cache: ResponseCache) -> Self {
+        self.stack.cache = Some(cache);
+        self
+    }
+
+    /// Sets a rate limiter to wait on before each attempt; it may be shared with other clients
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.stack.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Sets a usage ledger that checks budgets before each request and records its cost
+    pub fn usage_ledger(mut self, ledger: UsageLedger) -> Self {
+        self.stack.ledger = Some(ledger);
+        self
+    }
+
+    /// Adds a middleware; it runs inside the retries and the rate limiter, once per attempt
+    pub fn layer(mut self, middleware: impl Middleware) -> Self {
+        self.stack.layers.push(Arc::new(middleware));
+        self
+    }
+
+    /// Adds the API key and sends the request through the middleware stack
+    ///
+    /// Model calls pass their model and reply length limit for the ledger; only they are
+    /// cached.
+    async fn send(
+        &self,
+        builder: RequestBuilder,
+        model_call: Option<(&str, Option<u32>)>,
+    ) -> Result<reqwest::Response, HttpError> {
+        let mut request = Request::new(builder.bearer_auth(self.api_key.expose()).build()?);
+        if let Some((model, max_output_tokens)) = model_call {
+            request
+                .extensions_mut()
+                .insert(ModelName(model.to_string()));
+            if let Some(max_output_tokens) = max_output_tokens {
+                request
+                    .extensions_mut()
+                    .insert(MaxOutputTokens(max_output_tokens));
+            }
+            request.extensions_mut().insert(CacheMode::current());
+        }
+        self.stack.send(&self.client, request).await
+    }
+
+    /// Sends a single user prompt as a chat completion
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ChatCompletionResponse` or a `MistralClientError`
+    pub async fn chat(&self, prompt: &str) -> Result<ChatCompletionResponse, MistralClientError> {
+        let request = ChatCompletionRequest {
+            messages: vec![ChatMessage::new("user", prompt)],
+            ..Default::default()
+        };
+
+        self.chat_with_request(request).await
+    }
+
+    /// Sends a structured chat completion request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatCompletionRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ChatCompletionResponse` or a `MistralClientError`
+    pub async fn chat_with_request(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, MistralClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "chat", &self.model);
+        let span = telemetry.span().clone();
+        let result = self.send_chat(request, &telemetry).instrument(span).await;
+        if let Err(err) = &result {
+            telemetry.error(err.error_type());
+        }
+        result
+    }
+
+    /// Sends a chat completion request, recording the response on `telemetry`
+    async fn send_chat(
+        &self,
+        request: ChatCompletionRequest,
+        telemetry: &GenAiSpan,
+    ) -> Result<ChatCompletionResponse, MistralClientError> {
+        let url = format!("{}/chat/completions", self.base_url);
+        info!("Generating chat completion with URL: {}", url);
+        trace!("ChatCompletionRequest: {}", logging::payload_json(&request));
+
+        let response = self
+            .send(
+                self.client.post(&url).json(&ModelRequest {
+                    model: &self.model,
+                    stream: false,
+                    request: &request,
+                }),
+                Some((&self.model, max_tokens(&request))),
+            )
+            .await
+            .map_err(|err| {
+                error!("Failed to generate chat completion: {}", err);
+                telemetry.http_error(&err);
+                MistralClientError::from(err)
+            })?;
+        let permit = response.extensions().get::<RateLimitPermit>().cloned();
+        let usage_permit = response.extensions().get::<UsagePermit>().cloned();
+
+        let response_text = response.text().await?;
+        trace!("Response text: {}", logging::payload(&response_text));
+        let chat_response: ChatCompletionResponse = serde_json::from_str(&response_text)?;
+        if let (Some(permit), Some(usage)) = (permit, &chat_response.usage) {
+            permit.reconcile(usage.total_tokens.max(0) as u32);
+        }
+        if let (Some(permit), Some(usage)) = (usage_permit, &chat_response.usage) {
+            permit.record(usage.token_usage());
+        }
+        record_response(
+            telemetry,
+            &chat_response.model,
+            &chat_response.choices,
+            chat_response.usage.as_ref(),
+        );
+        info!("Successfully generated chat completion.");
+        trace!(
+            "ChatCompletionResponse: {}",
+            logging::payload_json(&chat_response)
+        );
+        Ok(chat_response)
+    }
+
+    /// Streams a chat completion for a single user prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate a response for
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `ChatCompletionChunk`s or a `MistralClientError`
+    pub async fn stream_chat(
+        &self,
+        prompt: &str,
+    ) -> Result<
+        impl Stream<Item = Result<ChatCompletionChunk, MistralClientError>>,
+        MistralClientError,
+    > {
+        let request = ChatCompletionRequest {
+            messages: vec![ChatMessage::new("user", prompt)],
+            ..Default::default()
+        };
+
+        self.stream_chat_with_request(request).await
+    }
+
+    /// Streams a structured chat completion request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatCompletionRequest` containing the messages and configuration
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `ChatCompletionChunk`s or a `MistralClientError`
+    pub async fn stream_chat_with_request(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<
+        impl Stream<Item = Result<ChatCompletionChunk, MistralClientError>>,
+        MistralClientError,
+    > {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "chat", &self.model);
+        let span = telemetry.span().clone();
+        self.start_stream(request, telemetry).instrument(span).await
+    }
+
+    /// Opens a streaming request; `telemetry` moves into the stream and ends with it
+    async fn start_stream(
+        &self,
+        request: ChatCompletionRequest,
+        mut telemetry: GenAiSpan,
+    ) -> Result<
+        impl Stream<Item = Result<ChatCompletionChunk, MistralClientError>>,
+        MistralClientError,
+    > {
+        let url = format!("{}/chat/completions", self.base_url);
+        info!("Streaming chat completion with URL: {}", url);
+        trace!("StreamRequest: {}", logging::payload_json(&request));
+
+        // Only failures before the first chunk arrives are retried
+        let response = self
+            .send(
+                self.client.post(&url).json(&ModelRequest {
+                    model: &self.model,
+                    stream: true,
+                    request: &request,
+                }),
+                Some((&self.model, max_tokens(&request))),
+            )
+            .await
+            .map_err(|err| {
+                error!("Failed to start streaming: {}", err);
+                telemetry.http_error(&err);
+                MistralClientError::from(err)
+            })?;
+        let mut permit = response.extensions().get::<RateLimitPermit>().cloned();
+        let mut usage_permit = response.extensions().get::<UsagePermit>().cloned();
+
+        Ok(spawn_sse_stream(response, move |event| {
+            if event.is_done() {
+                return None;
+            }
+            match serde_json::from_str::<ChatCompletionChunk>(&event.data) {
+                Ok(chunk) => {
+                    telemetry.first_token();
+                    if let (Some(usage), Some(permit)) = (&chunk.usage, permit.take()) {
+                        permit.reconcile(usage.total_tokens.max(0) as u32);
+                    }
+                    if let (Some(usage), Some(permit)) = (&chunk.usage, usage_permit.take()) {
+                        permit.record(usage.token_usage());
+                    }
+                    if let Some(reason) = chunk
+                        .choices
+                        .first()
+                        .and_then(|choice| choice.finish_reason.as_deref())
+                    {
+                        telemetry.response_model(&chunk.model);
+                        telemetry.finish_reasons(reason);
+                    }
+                    if let Some(usage) = &chunk.usage {
+                        record_usage(&telemetry, usage);
+                    }
+                    Some(Ok(chunk))
+                }
+                Err(e) => {
+                    error!("Failed to parse stream chunk: {}", e);
+                    telemetry.error("parse");
+                    Some(Err(MistralClientError::ParseError(e)))
+                }
+            }
+        }))
+    }
+
+    /// Creates embeddings for the given texts
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The embedding model (e.g., "mistral-embed")
+    /// * `inputs` - The texts to embed
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `EmbeddingResponse` or a `MistralClientError`
+    pub async fn embeddings(
+        &self,
+        model: &str,
+        inputs: &[&str],
+    ) -> Result<EmbeddingResponse, MistralClientError> {
+        let telemetry = GenAiSpan::new(GEN_AI_SYSTEM, "embeddings", model);
+        let span = telemetry.span().clone();
+        let result = self
+            .send_embeddings(model, inputs, &telemetry)
+            .instrument(span)
+            .await;
+        if let Err(err) = &result {
+            telemetry.error(err.error_type());
+        }
+        result
+    }
+
+    /// Sends an embeddings request, recording the response on `telemetry`
+    async fn send_embeddings(
+        &self,
+        model: &str,
+        inputs: &[&str],
+        telemetry: &GenAiSpan,
+    ) -> Result<EmbeddingResponse, MistralClientError> {
+        let url = format!("{}/embeddings", self.base_url);
+        info!("Creating embeddings with URL: {}", url);
+
+        let request = EmbeddingRequest {
+            model: model.to_string(),
+            input: inputs.iter().map(|input| input.to_string()).collect(),
+        };
+
+        let response = self
+            .send(
+                self.client.post(&url).json(&request),
+                Some((model, Some(0))),
+            )
+            .await
+            .map_err(|err| {
+                error!("Failed to create embeddings: {}", err);
+                telemetry.http_error(&err);
+                MistralClientError::from(err)
+            })?;
+        let usage_permit = response.extensions().get::<UsagePermit>().cloned();
+
+        let embedding_response: EmbeddingResponse = response.json().await?;
+        telemetry.response_model(&embedding_response.model);
+        if let Some(usage) = &embedding_response.usage {
+            telemetry.usage(Some(usage.prompt_tokens.max(0) as u32), None);
+            if let Some(permit) = usage_permit {
+                permit.record(usage.token_usage());
+            }
+        }
+        info!(
+            "Successfully created {} embeddings.",
+            embedding_response.data.len()
+        );
+        Ok(embedding_response)
+    }
+}
+
+/// Returns the reply length limit of a request, if it sets one
+fn max_tokens(request: &ChatCompletionRequest) -> Option<u32> {
+    request
+        .max_tokens
+        .and_then(|tokens| u32::try_from(tokens).ok())
+}
+
+/// Records the model, finish reasons and token usage of a chat completion
+fn record_response(
+    telemetry: &GenAiSpan,
+    model: &str,
+    choices: &[ChatChoice],
+    usage: Option<&Usage>,
+) {
+    telemetry.response_model(model);
+    let finish_reasons: Vec<&str> = choices
+        .iter()
+        .filter_map(|choice| choice.finish_reason.as_deref())
+        .collect();
+    if !finish_reasons.is_empty() {
+        telemetry.finish_reasons(&finish_reasons.join(","));
+    }
+    if let Some(usage) = usage {
+        record_usage(telemetry, usage);
+    }
+}
+
+fn record_usage(telemetry: &GenAiSpan, usage: &Usage) {
+    telemetry.usage(
+        Some(usage.prompt_tokens.max(0) as u32),
+        Some(usage.completion_tokens.max(0) as u32),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use mockito::Matcher;
+
+    fn client(server: &mockito::Server) -> MistralClient {
+        MistralClient::new("key", "mistral-small-latest")
+            .base_url(&server.url())
+            .retry_policy(RetryPolicy::none())
+    }
+
+    #[tokio::test]
+    async fn chat_round_trip() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .match_header("authorization", "Bearer key")
+            .match_body(Matcher::Json(serde_json::json!({
+                "model": "mistral-small-latest",
+                "stream": false,
+                "messages": [{"role": "user", "content": "Hello"}],
+            })))
+            .with_body(
+                r#"{
+                    "id": "cmpl-1",
+                    "model": "mistral-small-latest",
+                    "created": 1700000000,
+                    "choices": [{
+                        "index": 0,
+                        "message": {
+                            "role": "assistant",
+                            "content": "Hi",
+                            "tool_calls": [{
+                                "id": "call-1",
+                                "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+                            }]
+                        },
+                        "finish_reason": "tool_calls"
+                    }],
+                    "usage": {"prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4}
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let response = client(&server).chat("Hello").await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(response.get_text().as_deref(), Some("Hi"));
+        let calls = response.function_calls().unwrap();
+        assert_eq!(calls[0].name, "get_weather");
+        assert_eq!(calls[0].args, serde_json::json!({"city": "Paris"}));
+        assert_eq!(response.usage.unwrap().total_tokens, 4);
+    }
+
+    #[tokio::test]
+    async fn chat_maps_error_bodies() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/chat/completions")
+            .with_status(401)
+            .with_body(r#"{"message":"Unauthorized"}"#)
+            .create_async()
+            .await;
+
+        match client(&server).chat("Hello").await {
+            Err(MistralClientError::RequestError(body)) => assert!(body.contains("Unauthorized")),
+            other => panic!("expected a request error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_chat_yields_chunks_until_done() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/chat/completions")
+            .match_body(Matcher::PartialJson(serde_json::json!({"stream": true})))
+            .with_header("content-type", "text/event-stream")
+            .with_body(concat!(
+                "data: {\"id\":\"1\",\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"finish_reason\":null}]}\n\n",
+                "data: {\"id\":\"1\",\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}],",
+                "\"usage\":{\"prompt_tokens\":1,\"completion_tokens\":2,\"total_tokens\":3}}\n\n",
+                "data: [DONE]\n\n",
+            ))
+            .create_async()
+            .await;
+
+        let chunks: Vec<ChatCompletionChunk> = client(&server)
+            .stream_chat("Hello")
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        let text: String = chunks.iter().filter_map(|chunk| chunk.get_text()).collect();
+        assert_eq!(text, "Hello");
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            chunks[1].usage.as_ref().map(|usage| usage.total_tokens),
+            Some(3)
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_chat_reports_bad_chunks() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/chat/completions")
+            .with_body("data: not json\n\n")
+            .create_async()
+            .await;
+
+        let mut stream = Box::pin(client(&server).stream_chat("Hello").await.unwrap());
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(MistralClientError::ParseError(_)))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn embeddings_round_trip() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/embeddings")
+            .match_body(Matcher::Json(serde_json::json!({
+                "model": "mistral-embed",
+                "input": ["a", "b"],
+            })))
+            .with_body(
+                r#"{
+                    "model": "mistral-embed",
+                    "data": [{"index": 0, "embedding": [0.1, 0.2]}, {"index": 1, "embedding": [0.3, 0.4]}],
+                    "usage": {"prompt_tokens": 2, "completion_tokens": 0, "total_tokens": 2}
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let response = client(&server)
+            .embeddings("mistral-embed", &["a", "b"])
+            .await
+            .unwrap();
+        assert_eq!(response.data.len(), 2);
+        assert_eq!(response.data[1].embedding, vec![0.3, 0.4]);
+    }
+
+    #[tokio::test]
+    async fn usage_ledger_records_chats_and_enforces_budgets() {
+        use crate::usage::{BudgetScope, ModelPrice, PricingTable};
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .with_body(
+                r#"{
+                    "id": "cmpl-1",
+                    "model": "mistral-small-latest",
+                    "created": 1700000000,
+                    "choices": [{
+                        "index": 0,
+                        "message": {"role": "assistant", "content": "Hi"},
+                        "finish_reason": "stop"
+                    }],
+                    "usage": {"prompt_tokens": 1000, "completion_tokens": 500, "total_tokens": 1500}
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let ledger = UsageLedger::new()
+            .pricing(PricingTable::new().price("mistral-small", ModelPrice::new(1.0, 2.0)))
+            .budget(BudgetScope::Total, 0.0025);
+        let client = client(&server).usage_ledger(ledger.clone());
+
+        let request = ChatCompletionRequest {
+            messages: vec![ChatMessage::new("user", "Hello")],
+            max_tokens: Some(500),
+            ..Default::default()
+        };
+        client.chat_with_request(request).await.unwrap();
+        let records = ledger.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].model, "mistral-small-latest");
+        assert_eq!(records[0].usage.prompt_tokens, 1000);
+        assert!((ledger.totals().cost - 0.002).abs() < 1e-9);
+
+        // The $0.0005 left cannot cover another reply of 500 tokens
+        let request = ChatCompletionRequest {
+            messages: vec![ChatMessage::new("user", "Hello")],
+            max_tokens: Some(500),
+            ..Default::default()
+        };
+        match client.chat_with_request(request).await {
+            Err(MistralClientError::BudgetExceeded(_)) => {}
+            other => panic!("expected BudgetExceeded, got {:?}", other),
+        }
+        mock.assert_async().await;
+    }
+}
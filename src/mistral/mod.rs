@@ -0,0 +1,8 @@
+pub mod client;
+pub mod types;
+
+pub use client::{MistralClient, MistralClientError};
+pub use types::{
+    ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, ChatMessage,
+    EmbeddingResponse, ResponseFormat, Tool, ToolCall, Usage,
+};
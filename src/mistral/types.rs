@@ -0,0 +1,366 @@
+use crate::gemini::types::{FunctionCall, FunctionDeclaration, Tool as GeminiTool, UsageMetadata};
+use crate::usage::TokenUsage;
+use serde::{Deserialize, Serialize};
+
+/// Request structure for Mistral chat completions
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChatCompletionRequest {
+    /// The conversation messages
+    pub messages: Vec<ChatMessage>,
+    /// Temperature for generation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Top-p sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Maximum number of tokens to generate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<i32>,
+    /// Stop sequences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    /// Seed for deterministic sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub random_seed: Option<u64>,
+    /// Output format (plain text, JSON object or JSON schema)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// Tools the model may call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    /// How the model should choose a tool ("auto", "any", "none" or "required")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<String>,
+    /// Whether to let the model call several tools at once
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    /// Whether to inject Mistral's safety prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safe_prompt: Option<bool>,
+}
+
+/// A message in a Mistral chat
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// The role of the message ("system", "user", "assistant" or "tool")
+    pub role: String,
+    /// The text content of the message
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Tool calls requested by the assistant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// Identifier of the tool call a "tool" message answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// Name of the tool a "tool" message comes from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl ChatMessage {
+    /// Creates a message with the given role and text
+    pub fn new(role: &str, content: &str) -> Self {
+        ChatMessage {
+            role: role.to_string(),
+            content: Some(content.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a "tool" message carrying the result of a tool call
+    pub fn tool_result(tool_call_id: &str, name: &str, content: &str) -> Self {
+        ChatMessage {
+            role: "tool".to_string(),
+            content: Some(content.to_string()),
+            tool_call_id: Some(tool_call_id.to_string()),
+            name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Output format for Mistral chat completions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseFormat {
+    /// "text", "json_object" or "json_schema"
+    #[serde(rename = "type")]
+    pub format_type: String,
+    /// The schema when `format_type` is "json_schema"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<JsonSchema>,
+}
+
+impl ResponseFormat {
+    /// JSON mode: the model must return a valid JSON object
+    pub fn json_object() -> Self {
+        ResponseFormat {
+            format_type: "json_object".to_string(),
+            json_schema: None,
+        }
+    }
+
+    /// Structured output: the model must return JSON matching `schema`
+    pub fn json_schema(name: &str, schema: serde_json::Value) -> Self {
+        ResponseFormat {
+            format_type: "json_schema".to_string(),
+            json_schema: Some(JsonSchema {
+                name: name.to_string(),
+                schema,
+                strict: Some(true),
+            }),
+        }
+    }
+}
+
+/// Named JSON schema for structured output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonSchema {
+    /// Name of the schema
+    pub name: String,
+    /// The JSON schema
+    pub schema: serde_json::Value,
+    /// Whether the schema must be followed strictly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+/// Tool definition for Mistral
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tool {
+    /// Always "function"
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    /// The function definition
+    pub function: Function,
+}
+
+impl Tool {
+    /// Converts the crate's `Tool` definitions into Mistral tools
+    pub fn from_tools(tools: &[GeminiTool]) -> Vec<Tool> {
+        tools
+            .iter()
+            .flat_map(|tool| tool.function_declarations.iter())
+            .map(Tool::from)
+            .collect()
+    }
+}
+
+impl From<&FunctionDeclaration> for Tool {
+    fn from(declaration: &FunctionDeclaration) -> Self {
+        Tool {
+            tool_type: "function".to_string(),
+            function: Function {
+                name: declaration.name.clone(),
+                description: Some(declaration.description.clone()),
+                parameters: declaration.parameters.clone(),
+            },
+        }
+    }
+}
+
+/// Function definition for tools
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Function {
+    /// Name of the function
+    pub name: String,
+    /// Description of the function
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON schema of the parameters
+    pub parameters: serde_json::Value,
+}
+
+/// Tool call requested by the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Identifier of the tool call
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Always "function"
+    #[serde(rename = "type", default)]
+    pub call_type: Option<String>,
+    /// The function to call
+    pub function: FunctionCallArguments,
+    /// Index of the call when streamed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+}
+
+impl ToolCall {
+    /// Converts the call into a `FunctionCall`, parsing the JSON-encoded arguments
+    pub fn to_function_call(&self) -> Result<FunctionCall, serde_json::Error> {
+        Ok(FunctionCall {
+            name: self.function.name.clone(),
+            args: self.function.arguments_value()?,
+        })
+    }
+}
+
+/// Function name and arguments of a tool call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCallArguments {
+    /// Name of the function
+    pub name: String,
+    /// Arguments as a JSON string (Mistral may also send a JSON object)
+    pub arguments: serde_json::Value,
+}
+
+impl FunctionCallArguments {
+    /// Returns the arguments as a JSON value, decoding them if they were sent as a string
+    pub fn arguments_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+        match &self.arguments {
+            serde_json::Value::String(raw) => serde_json::from_str(raw),
+            value => Ok(value.clone()),
+        }
+    }
+}
+
+/// Response structure for Mistral chat completions
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatCompletionResponse {
+    /// Identifier of the completion
+    pub id: String,
+    /// The model used
+    pub model: String,
+    /// Creation time as a Unix timestamp
+    pub created: Option<u64>,
+    /// The generated choices
+    pub choices: Vec<ChatChoice>,
+    /// Token usage
+    pub usage: Option<Usage>,
+}
+
+impl ChatCompletionResponse {
+    /// Gets the text of the first choice
+    pub fn get_text(&self) -> Option<String> {
+        self.choices
+            .first()
+            .and_then(|choice| choice.message.content.clone())
+    }
+
+    /// Gets the tool calls of the first choice
+    pub fn function_calls(&self) -> Result<Vec<FunctionCall>, serde_json::Error> {
+        self.choices
+            .first()
+            .and_then(|choice| choice.message.tool_calls.as_ref())
+            .map(|calls| calls.iter().map(ToolCall::to_function_call).collect())
+            .unwrap_or_else(|| Ok(Vec::new()))
+    }
+}
+
+/// A generated choice
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatChoice {
+    /// Index of the choice
+    pub index: i32,
+    /// The generated message
+    pub message: ChatMessage,
+    /// Why generation stopped
+    pub finish_reason: Option<String>,
+}
+
+/// Token usage reported by Mistral
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Usage {
+    /// Prompt token count
+    pub prompt_tokens: i32,
+    /// Completion token count
+    pub completion_tokens: i32,
+    /// Total token count
+    pub total_tokens: i32,
+}
+
+impl Usage {
+    /// Returns the counts as a `TokenUsage` for the usage ledger
+    pub fn token_usage(&self) -> TokenUsage {
+        TokenUsage::new(
+            self.prompt_tokens.max(0) as u32,
+            self.completion_tokens.max(0) as u32,
+        )
+    }
+}
+
+impl From<Usage> for UsageMetadata {
+    fn from(usage: Usage) -> Self {
+        UsageMetadata {
+            prompt_token_count: usage.prompt_tokens,
+            candidates_token_count: usage.completion_tokens,
+            total_token_count: usage.total_tokens,
+            cached_content_token_count: 0,
+        }
+    }
+}
+
+/// Streamed chunk of a Mistral chat completion
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatCompletionChunk {
+    /// Identifier of the completion
+    pub id: String,
+    /// The model used
+    pub model: String,
+    /// The incremental choices
+    pub choices: Vec<ChatChunkChoice>,
+    /// Token usage, sent with the final chunk
+    pub usage: Option<Usage>,
+}
+
+impl ChatCompletionChunk {
+    /// Gets the text delta of the first choice
+    pub fn get_text(&self) -> Option<String> {
+        self.choices
+            .first()
+            .and_then(|choice| choice.delta.content.clone())
+    }
+}
+
+/// Incremental choice in a streamed chunk
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatChunkChoice {
+    /// Index of the choice
+    pub index: i32,
+    /// The delta message
+    pub delta: ChatMessageDelta,
+    /// Why generation stopped
+    pub finish_reason: Option<String>,
+}
+
+/// Incremental message content
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChatMessageDelta {
+    /// The role, sent with the first chunk
+    pub role: Option<String>,
+    /// A piece of text
+    pub content: Option<String>,
+    /// Tool calls
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
+
+/// Request structure for Mistral embeddings
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingRequest {
+    /// The embedding model to use
+    pub model: String,
+    /// The texts to embed
+    pub input: Vec<String>,
+}
+
+/// Response structure for Mistral embeddings
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    /// The model used
+    pub model: String,
+    /// One embedding per input
+    pub data: Vec<Embedding>,
+    /// Token usage
+    pub usage: Option<Usage>,
+}
+
+/// A single embedding
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Embedding {
+    /// Index of the input this embedding belongs to
+    pub index: usize,
+    /// The embedding vector
+    pub embedding: Vec<f32>,
+}
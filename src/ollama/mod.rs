@@ -1,6 +1,11 @@
 pub mod client;
+pub mod pool;
 pub mod types;
 // pub mod utils;
 
-pub use client::OllamaClient;
-pub use types::{GenerateRequest, GenerateResponse, ListModelsResponse, ModelInfo};
+pub use client::{OllamaClient, OllamaClientBuilder};
+pub use pool::{HealthChecks, HostStatus, OllamaPool, PoolLease};
+pub use types::{
+    ChatMessage, ChatRequest, ChatResponse, GenerateRequest, GenerateResponse, ListModelsResponse,
+    ModelInfo, RunningModel, RunningModelsResponse, Tool, ToolCall, ToolCallFunction,
+};
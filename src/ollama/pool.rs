@@ -0,0 +1,693 @@
+use crate::ollama::client::{OllamaClient, OllamaClientError};
+use crate::ollama::types::{ChatRequest, ChatResponse, GenerateRequest, GenerateResponse};
+use crate::retry::ErrorClass;
+use futures_util::future::join_all;
+use futures_util::{Stream, StreamExt};
+use std::collections::HashSet;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Default time between two health checks of the hosts
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Default number of consecutive failures after which a host is drained
+const DEFAULT_UNHEALTHY_AFTER: u32 = 2;
+
+/// Default time after which a health probe counts as failed
+const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What the pool knows about a host
+#[derive(Debug, Default)]
+struct HostState {
+    healthy: bool,
+    draining: bool,
+    failures: u32,
+    models: HashSet<String>,
+    loaded: HashSet<String>,
+}
+
+/// A host of the pool
+#[derive(Debug)]
+struct Host {
+    client: OllamaClient,
+    in_flight: AtomicUsize,
+    state: Mutex<HostState>,
+}
+
+impl Host {
+    fn state(&self) -> MutexGuard<'_, HostState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Counts a failed probe or request, draining the host after too many in a row
+    fn record_failure(&self, unhealthy_after: u32) {
+        let mut state = self.state();
+        state.failures += 1;
+        if state.healthy && state.failures >= unhealthy_after {
+            warn!(
+                "Ollama host {} failed {} times in a row, draining it",
+                self.client.base_url(),
+                state.failures
+            );
+            state.healthy = false;
+        }
+    }
+}
+
+/// A snapshot of the state of one host
+#[derive(Debug, Clone)]
+pub struct HostStatus {
+    /// The base URL of the host
+    pub base_url: String,
+    /// Whether the host passed its last health checks
+    pub healthy: bool,
+    /// Whether the host was drained with `OllamaPool::set_draining`
+    pub draining: bool,
+    /// The number of requests currently running on the host
+    pub in_flight: usize,
+    /// The models available on the host, from `/api/tags`
+    pub models: Vec<String>,
+    /// The models loaded in memory, from `/api/ps`
+    pub loaded: Vec<String>,
+}
+
+/// A host selected for one request
+///
+/// Dereferences to the host's `OllamaClient`. The host counts the request as in flight
+/// until the lease is dropped.
+#[derive(Debug)]
+pub struct PoolLease {
+    host: Arc<Host>,
+}
+
+impl Deref for PoolLease {
+    type Target = OllamaClient;
+
+    fn deref(&self) -> &OllamaClient {
+        &self.host.client
+    }
+}
+
+impl Drop for PoolLease {
+    fn drop(&mut self) {
+        self.host.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The background health checks started by `OllamaPool::spawn_health_checks`
+///
+/// The checks stop when this handle is dropped.
+#[derive(Debug)]
+pub struct HealthChecks {
+    task: JoinHandle<()>,
+}
+
+impl Drop for HealthChecks {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Load balancer over several Ollama hosts
+///
+/// Requests go to a healthy host that already has the model loaded, then to one that has
+/// the model, then to any healthy host, picking the host with the fewest requests in flight.
+/// Hosts that fail `unhealthy_after` health checks or connections in a row stop receiving
+/// requests until a health check succeeds again.
+#[derive(Debug, Clone)]
+pub struct OllamaPool {
+    hosts: Vec<Arc<Host>>,
+    check_interval: Duration,
+    probe_timeout: Duration,
+    unhealthy_after: u32,
+}
+
+impl OllamaPool {
+    /// Creates a pool
+    ///
+    /// Hosts are considered healthy until a health check says otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `clients` - One client per host, each with its own base URL
+    ///
+    /// # Returns
+    ///
+    /// A new `OllamaPool` instance
+    pub fn new(clients: Vec<OllamaClient>) -> Self {
+        info!("Creating new OllamaPool with {} hosts", clients.len());
+        OllamaPool {
+            hosts: clients
+                .into_iter()
+                .map(|client| {
+                    Arc::new(Host {
+                        client,
+                        in_flight: AtomicUsize::new(0),
+                        state: Mutex::new(HostState {
+                            healthy: true,
+                            ..Default::default()
+                        }),
+                    })
+                })
+                .collect(),
+            check_interval: DEFAULT_CHECK_INTERVAL,
+            probe_timeout: DEFAULT_PROBE_TIMEOUT,
+            unhealthy_after: DEFAULT_UNHEALTHY_AFTER,
+        }
+    }
+
+    /// Sets the time between two background health checks (default 10s)
+    pub fn check_interval(mut self, interval: Duration) -> Self {
+        self.check_interval = interval;
+        self
+    }
+
+    /// Sets the time after which a health probe counts as failed (default 2s)
+    pub fn probe_timeout(mut self, timeout: Duration) -> Self {
+        self.probe_timeout = timeout;
+        self
+    }
+
+    /// Sets the number of consecutive failures after which a host is drained (default 2)
+    pub fn unhealthy_after(mut self, failures: u32) -> Self {
+        self.unhealthy_after = failures.max(1);
+        self
+    }
+
+    /// Checks every host once, refreshing its health and its models
+    ///
+    /// A host is probed once, without retries, and fails the check if it does not answer
+    /// within `probe_timeout`. If it is up, its models are read from `list_models` and its
+    /// loaded models from `running_models`.
+    pub async fn check_health(&self) {
+        join_all(self.hosts.iter().map(|host| self.check_host(host))).await;
+    }
+
+    async fn check_host(&self, host: &Host) {
+        let base_url = host.client.base_url();
+        match host.client.probe(self.probe_timeout).await {
+            Ok(true) => {}
+            Ok(false) => {
+                debug!("Ollama host {} is not active", base_url);
+                host.record_failure(self.unhealthy_after);
+                return;
+            }
+            Err(err) => {
+                debug!("Health check of Ollama host {} failed: {}", base_url, err);
+                host.record_failure(self.unhealthy_after);
+                return;
+            }
+        }
+
+        let models = host.client.list_models().await;
+        let loaded = host.client.running_models().await;
+        let mut state = host.state();
+        if !state.healthy {
+            info!("Ollama host {} is healthy again", base_url);
+        }
+        state.healthy = true;
+        state.failures = 0;
+        match models {
+            Ok(models) => {
+                state.models = models.models.into_iter().map(|model| model.name).collect()
+            }
+            Err(err) => warn!("Failed to list models of {}: {}", base_url, err),
+        }
+        match loaded {
+            Ok(loaded) => {
+                state.loaded = loaded.models.into_iter().map(|model| model.name).collect()
+            }
+            Err(err) => warn!("Failed to list running models of {}: {}", base_url, err),
+        }
+    }
+
+    /// Starts checking the hosts in the background, once right away and then every
+    /// `check_interval`
+    ///
+    /// Must be called from within a Tokio runtime.
+    ///
+    /// # Returns
+    ///
+    /// A `HealthChecks` handle; the checks stop when it is dropped
+    pub fn spawn_health_checks(&self) -> HealthChecks {
+        let pool = self.clone();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(pool.check_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                pool.check_health().await;
+            }
+        });
+        HealthChecks { task }
+    }
+
+    /// Stops or resumes sending new requests to a host, e.g. for maintenance
+    ///
+    /// Requests already running on the host are not interrupted.
+    ///
+    /// # Returns
+    ///
+    /// `false` if no host has this base URL
+    pub fn set_draining(&self, base_url: &str, draining: bool) -> bool {
+        match self
+            .hosts
+            .iter()
+            .find(|host| host.client.base_url() == base_url)
+        {
+            Some(host) => {
+                info!(
+                    "Setting draining of Ollama host {} to {}",
+                    base_url, draining
+                );
+                host.state().draining = draining;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns a snapshot of every host, in the order they were added
+    pub fn status(&self) -> Vec<HostStatus> {
+        self.hosts
+            .iter()
+            .map(|host| {
+                let state = host.state();
+                let mut models: Vec<String> = state.models.iter().cloned().collect();
+                let mut loaded: Vec<String> = state.loaded.iter().cloned().collect();
+                models.sort();
+                loaded.sort();
+                HostStatus {
+                    base_url: host.client.base_url().to_string(),
+                    healthy: state.healthy,
+                    draining: state.draining,
+                    in_flight: host.in_flight.load(Ordering::SeqCst),
+                    models,
+                    loaded,
+                }
+            })
+            .collect()
+    }
+
+    /// Selects a host for a request to `model`
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model the request uses (e.g., "llama3")
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PoolLease` of the host, or
+    /// `OllamaClientError::NoHealthyHost` if every host is unhealthy or draining
+    pub fn acquire(&self, model: &str) -> Result<PoolLease, OllamaClientError> {
+        let model = with_default_tag(model);
+        // Lower is better: loaded, then available, then unknown to the host
+        let host = self
+            .hosts
+            .iter()
+            .filter_map(|host| {
+                let state = host.state();
+                if !state.healthy || state.draining {
+                    return None;
+                }
+                let tier = if state.loaded.contains(&model) {
+                    0
+                } else if state.models.contains(&model) {
+                    1
+                } else {
+                    2
+                };
+                Some((tier, host.in_flight.load(Ordering::SeqCst), host))
+            })
+            .min_by_key(|(tier, in_flight, _)| (*tier, *in_flight))
+            .map(|(_, _, host)| host.clone())
+            .ok_or(OllamaClientError::NoHealthyHost)?;
+        host.in_flight.fetch_add(1, Ordering::SeqCst);
+        debug!(
+            "Selected Ollama host {} for model {}",
+            host.client.base_url(),
+            model
+        );
+        Ok(PoolLease { host })
+    }
+
+    /// Generates a completion on the best host for the request's model
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the model and prompt
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GenerateResponse` or an `OllamaClientError`
+    pub async fn generate_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<GenerateResponse, OllamaClientError> {
+        let lease = self.acquire(&request.model)?;
+        let model = request.model.clone();
+        let result = lease.generate_completion(request).await;
+        self.record(&lease, &model, &result);
+        result
+    }
+
+    /// Sends a chat request to the best host for the request's model
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatRequest` containing the model and messages
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ChatResponse` or an `OllamaClientError`
+    pub async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, OllamaClientError> {
+        let lease = self.acquire(&request.model)?;
+        let model = request.model.clone();
+        let result = lease.chat(request).await;
+        self.record(&lease, &model, &result);
+        result
+    }
+
+    /// Streams a completion from the best host for the request's model
+    ///
+    /// The host counts the request as in flight until the stream is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `GenerateRequest` containing the model and prompt
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `GenerateResponse` chunks or an `OllamaClientError`
+    pub async fn stream_completion(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<impl Stream<Item = Result<GenerateResponse, OllamaClientError>>, OllamaClientError>
+    {
+        let lease = self.acquire(&request.model)?;
+        let model = request.model.clone();
+        let result = lease.stream_completion(request).await;
+        self.record(&lease, &model, &result);
+        Ok(self.hold(lease, result?))
+    }
+
+    /// Streams a chat reply from the best host for the request's model
+    ///
+    /// The host counts the request as in flight until the stream is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `ChatRequest` containing the model and messages
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a Stream of `ChatResponse` chunks or an `OllamaClientError`
+    pub async fn stream_chat(
+        &self,
+        request: ChatRequest,
+    ) -> Result<impl Stream<Item = Result<ChatResponse, OllamaClientError>>, OllamaClientError>
+    {
+        let lease = self.acquire(&request.model)?;
+        let model = request.model.clone();
+        let result = lease.stream_chat(request).await;
+        self.record(&lease, &model, &result);
+        Ok(self.hold(lease, result?))
+    }
+
+    /// Keeps the lease until the stream is dropped, counting timeouts and dropped
+    /// connections mid-stream towards draining the host
+    fn hold<T>(
+        &self,
+        lease: PoolLease,
+        stream: impl Stream<Item = Result<T, OllamaClientError>>,
+    ) -> impl Stream<Item = Result<T, OllamaClientError>> {
+        let unhealthy_after = self.unhealthy_after;
+        stream.map(move |chunk| {
+            if let Err(err) = &chunk {
+                if matches!(err.class(), ErrorClass::Connection | ErrorClass::Timeout) {
+                    lease.host.record_failure(unhealthy_after);
+                }
+            }
+            chunk
+        })
+    }
+
+    /// Updates the host after a request: the model is loaded after a success, and
+    /// connection failures and timeouts count towards draining the host
+    fn record<T>(&self, lease: &PoolLease, model: &str, result: &Result<T, OllamaClientError>) {
+        match result {
+            Ok(_) => {
+                let mut state = lease.host.state();
+                state.failures = 0;
+                state.loaded.insert(with_default_tag(model));
+            }
+            Err(err) if matches!(err.class(), ErrorClass::Connection | ErrorClass::Timeout) => {
+                lease.host.record_failure(self.unhealthy_after);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Adds the default ":latest" tag to model names without a tag, as Ollama lists them
+fn with_default_tag(model: &str) -> String {
+    if model.contains(':') {
+        model.to_string()
+    } else {
+        format!("{}:latest", model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Mock, Server, ServerGuard};
+
+    const GENERATED: &str = r#"{"model": "llama3", "created_at": "2024-01-01T00:00:00Z", "response": "Hi", "done": true}"#;
+
+    /// Mocks a host that is up, with `models` available and `loaded` in memory
+    async fn mock_host(server: &mut ServerGuard, models: &[&str], loaded: &[&str]) -> Vec<Mock> {
+        let tags: Vec<String> = models
+            .iter()
+            .map(|name| {
+                format!(
+                    r#"{{"name": "{}", "modified_at": "", "size": 0, "digest": "", "details": {{"format": "gguf", "family": "llama", "parameter_size": "8B", "quantization_level": "Q4_0"}}}}"#,
+                    name
+                )
+            })
+            .collect();
+        let running: Vec<String> = loaded
+            .iter()
+            .map(|name| format!(r#"{{"name": "{}"}}"#, name))
+            .collect();
+        vec![
+            server
+                .mock("GET", "/")
+                .with_body("Ollama is running")
+                .create_async()
+                .await,
+            server
+                .mock("GET", "/api/tags")
+                .with_body(format!(r#"{{"models": [{}]}}"#, tags.join(",")))
+                .create_async()
+                .await,
+            server
+                .mock("GET", "/api/ps")
+                .with_body(format!(r#"{{"models": [{}]}}"#, running.join(",")))
+                .create_async()
+                .await,
+        ]
+    }
+
+    async fn healthy_host(models: &[&str], loaded: &[&str]) -> (ServerGuard, Vec<Mock>) {
+        let mut server = Server::new_async().await;
+        let mocks = mock_host(&mut server, models, loaded).await;
+        (server, mocks)
+    }
+
+    fn pool(servers: &[&ServerGuard]) -> OllamaPool {
+        OllamaPool::new(
+            servers
+                .iter()
+                .map(|server| OllamaClient::new(&server.url(), ""))
+                .collect(),
+        )
+        .probe_timeout(Duration::from_millis(500))
+    }
+
+    fn request(model: &str) -> GenerateRequest {
+        GenerateRequest {
+            model: model.to_string(),
+            prompt: "Hello".to_string(),
+            stream: Some(false),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn prefers_hosts_with_the_model_loaded_then_available() {
+        let (empty, _empty) = healthy_host(&[], &[]).await;
+        let (available, _available) = healthy_host(&["llama3:latest"], &[]).await;
+        let (loaded, _loaded) = healthy_host(&["llama3:latest"], &["llama3:latest"]).await;
+        let pool = pool(&[&empty, &available, &loaded]);
+        pool.check_health().await;
+
+        let first = pool.acquire("llama3").unwrap();
+        assert_eq!(first.base_url(), loaded.url());
+        // The loaded host wins even with more requests in flight
+        let second = pool.acquire("llama3:latest").unwrap();
+        assert_eq!(second.base_url(), loaded.url());
+        drop((first, second));
+
+        pool.set_draining(&loaded.url(), true);
+        assert_eq!(pool.acquire("llama3").unwrap().base_url(), available.url());
+        assert_eq!(pool.status()[1].models, ["llama3:latest"]);
+        assert_eq!(pool.status()[2].loaded, ["llama3:latest"]);
+    }
+
+    #[tokio::test]
+    async fn picks_the_host_with_the_fewest_requests_in_flight() {
+        let (a, _a) = healthy_host(&[], &[]).await;
+        let (b, _b) = healthy_host(&[], &[]).await;
+        let pool = pool(&[&a, &b]);
+
+        let first = pool.acquire("mistral").unwrap();
+        let second = pool.acquire("mistral").unwrap();
+        assert_ne!(first.base_url(), second.base_url());
+        let in_flight: Vec<usize> = pool.status().iter().map(|host| host.in_flight).collect();
+        assert_eq!(in_flight, [1, 1]);
+
+        drop(first);
+        drop(second);
+        assert!(pool.status().iter().all(|host| host.in_flight == 0));
+    }
+
+    #[tokio::test]
+    async fn draining_hosts_get_no_new_requests() {
+        let (a, _a) = healthy_host(&[], &[]).await;
+        let (b, _b) = healthy_host(&[], &[]).await;
+        let pool = pool(&[&a, &b]);
+
+        assert!(pool.set_draining(&a.url(), true));
+        assert!(!pool.set_draining("http://unknown:11434", true));
+        for _ in 0..3 {
+            assert_eq!(pool.acquire("llama3").unwrap().base_url(), b.url());
+        }
+        // A running request keeps its host while it drains
+        let lease = pool.acquire("llama3").unwrap();
+        pool.set_draining(&b.url(), true);
+        assert_eq!(pool.status()[1].in_flight, 1);
+        assert!(pool.status()[1].draining);
+        match pool.acquire("llama3") {
+            Err(OllamaClientError::NoHealthyHost) => {}
+            other => panic!("expected NoHealthyHost, got {:?}", other.map(|_| ())),
+        }
+        drop(lease);
+
+        pool.set_draining(&a.url(), false);
+        assert_eq!(pool.acquire("llama3").unwrap().base_url(), a.url());
+    }
+
+    #[tokio::test]
+    async fn health_checks_drain_failing_hosts_until_they_recover() {
+        let mut server = Server::new_async().await;
+        let down = server
+            .mock("GET", "/")
+            .with_status(503)
+            .create_async()
+            .await;
+        let pool = pool(&[&server]).unhealthy_after(2);
+
+        pool.check_health().await;
+        assert!(pool.status()[0].healthy);
+        pool.check_health().await;
+        assert!(!pool.status()[0].healthy);
+        assert!(matches!(
+            pool.acquire("llama3"),
+            Err(OllamaClientError::NoHealthyHost)
+        ));
+
+        down.remove_async().await;
+        let _up = mock_host(&mut server, &["llama3:latest"], &[]).await;
+        pool.check_health().await;
+        let status = &pool.status()[0];
+        assert!(status.healthy);
+        assert_eq!(status.models, ["llama3:latest"]);
+        assert!(pool.acquire("llama3").is_ok());
+    }
+
+    #[tokio::test]
+    async fn unreachable_hosts_fail_their_checks() {
+        let pool = OllamaPool::new(vec![OllamaClient::new("http://127.0.0.1:1", "")])
+            .probe_timeout(Duration::from_millis(200))
+            .unhealthy_after(1);
+        pool.check_health().await;
+        assert!(!pool.status()[0].healthy);
+    }
+
+    #[tokio::test]
+    async fn requests_go_to_the_selected_host_and_load_the_model() {
+        let (a, _a) = healthy_host(&[], &[]).await;
+        let (mut b, _b) = healthy_host(&[], &[]).await;
+        let generate = b
+            .mock("POST", "/api/generate")
+            .with_body(GENERATED)
+            .expect(2)
+            .create_async()
+            .await;
+        let pool = pool(&[&a, &b]);
+        pool.set_draining(&a.url(), true);
+
+        let response = pool.generate_completion(request("llama3")).await.unwrap();
+        assert_eq!(response.response, "Hi");
+        assert_eq!(pool.status()[1].loaded, ["llama3:latest"]);
+
+        // Now that b has the model loaded, it is preferred over a
+        pool.set_draining(&a.url(), false);
+        pool.generate_completion(request("llama3")).await.unwrap();
+        generate.assert_async().await;
+        assert!(pool.status().iter().all(|host| host.in_flight == 0));
+    }
+
+    #[tokio::test]
+    async fn connection_failures_drain_the_host() {
+        let (up, _up) = healthy_host(&[], &[]).await;
+        let pool = OllamaPool::new(vec![
+            OllamaClient::new("http://127.0.0.1:1", ""),
+            OllamaClient::new(&up.url(), ""),
+        ])
+        .unhealthy_after(1);
+        pool.set_draining(&up.url(), true);
+
+        match pool.generate_completion(request("llama3")).await {
+            Err(OllamaClientError::NetworkError(_)) => {}
+            other => panic!("expected NetworkError, got {:?}", other),
+        }
+        assert!(!pool.status()[0].healthy);
+        pool.set_draining(&up.url(), false);
+        assert_eq!(pool.acquire("llama3").unwrap().base_url(), up.url());
+    }
+
+    #[tokio::test]
+    async fn background_checks_refresh_the_hosts() {
+        let (server, _mocks) = healthy_host(&["llama3:latest"], &[]).await;
+        let pool = pool(&[&server]).check_interval(Duration::from_millis(20));
+
+        let checks = pool.spawn_health_checks();
+        let started = std::time::Instant::now();
+        while pool.status()[0].models.is_empty() {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "no health check ran"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        drop(checks);
+    }
+}
@@ -1,8 +1,10 @@
+use crate::gemini::types::{FunctionCall, FunctionDeclaration, Tool as GeminiTool};
+use crate::usage::TokenUsage;
 use serde::{Deserialize, Serialize};
 // use std::collections::HashMap;
 
 /// Request structure for generating a completion
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct GenerateRequest {
     /// The model to use for generation
     pub model: String,
@@ -13,6 +15,9 @@ pub struct GenerateRequest {
     /// Additional options for the generation
     // pub options: Option<HashMap<String, serde_json::Value>>,
     pub options: Option<serde_json::Value>,
+    /// The version id of the prompt template the prompt was rendered from, for tracing only
+    #[serde(skip)]
+    pub template_version: Option<String>,
 }
 
 /// Response structure for a generated completion
@@ -62,6 +67,188 @@ impl GenerateResponse {
         self.eval_count = other.eval_count.or(self.eval_count);
         self.eval_duration = other.eval_duration.or(self.eval_duration);
     }
+
+    /// Total tokens processed (`prompt_eval_count` + `eval_count`), if the server reported them
+    pub fn token_count(&self) -> Option<u32> {
+        match (self.prompt_eval_count, self.eval_count) {
+            (None, None) => None,
+            (prompt, eval) => Some(prompt.unwrap_or(0) + eval.unwrap_or(0)),
+        }
+    }
+
+    /// The counts as a `TokenUsage` for the usage ledger, if the server reported them
+    pub fn token_usage(&self) -> Option<TokenUsage> {
+        match (self.prompt_eval_count, self.eval_count) {
+            (None, None) => None,
+            (prompt, eval) => Some(TokenUsage::new(prompt.unwrap_or(0), eval.unwrap_or(0))),
+        }
+    }
+}
+
+/// Request structure for the `/api/chat` endpoint
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChatRequest {
+    /// The model to use for generation
+    pub model: String,
+    /// The conversation messages
+    pub messages: Vec<ChatMessage>,
+    /// Tools the model may call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    /// The response format: "json" or a JSON schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<serde_json::Value>,
+    /// Whether to stream the response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    /// Additional options for the generation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<serde_json::Value>,
+}
+
+/// A message in an Ollama chat
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// The role of the message ("system", "user", "assistant" or "tool")
+    pub role: String,
+    /// The text content of the message
+    #[serde(default)]
+    pub content: String,
+    /// Base64-encoded images, for multimodal models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<String>>,
+    /// Tool calls requested by the assistant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// Name of the tool a "tool" message comes from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+}
+
+impl ChatMessage {
+    /// Creates a message with the given role and text
+    pub fn new(role: &str, content: &str) -> Self {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a "tool" message carrying the result of a tool call
+    pub fn tool_result(name: &str, content: &str) -> Self {
+        ChatMessage {
+            role: "tool".to_string(),
+            content: content.to_string(),
+            tool_name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Tool definition for Ollama
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tool {
+    /// Always "function"
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    /// The function definition
+    pub function: FunctionDeclaration,
+}
+
+impl Tool {
+    /// Converts the crate's `Tool` definitions into Ollama tools
+    pub fn from_tools(tools: &[GeminiTool]) -> Vec<Tool> {
+        tools
+            .iter()
+            .flat_map(|tool| tool.function_declarations.iter())
+            .map(Tool::from)
+            .collect()
+    }
+}
+
+impl From<&FunctionDeclaration> for Tool {
+    fn from(declaration: &FunctionDeclaration) -> Self {
+        Tool {
+            tool_type: "function".to_string(),
+            function: declaration.clone(),
+        }
+    }
+}
+
+/// Tool call requested by the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// The function to call
+    pub function: ToolCallFunction,
+}
+
+/// Function name and arguments of a tool call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallFunction {
+    /// Name of the function
+    pub name: String,
+    /// Arguments as a JSON object
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+impl From<&ToolCall> for FunctionCall {
+    fn from(call: &ToolCall) -> Self {
+        FunctionCall {
+            name: call.function.name.clone(),
+            args: call.function.arguments.clone(),
+        }
+    }
+}
+
+/// Response structure for the `/api/chat` endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatResponse {
+    /// The model used for generation
+    pub model: String,
+    /// The creation timestamp of the response
+    pub created_at: String,
+    /// The generated message
+    pub message: ChatMessage,
+    /// Whether the generation is done
+    pub done: bool,
+    /// The reason why the generation is done
+    pub done_reason: Option<String>,
+    /// The total duration of the generation
+    pub total_duration: Option<u64>,
+    /// The count of prompt evaluations
+    pub prompt_eval_count: Option<u32>,
+    /// The count of evaluations
+    pub eval_count: Option<u32>,
+}
+
+impl ChatResponse {
+    /// Gets the tool calls requested by the model
+    pub fn function_calls(&self) -> Vec<FunctionCall> {
+        self.message
+            .tool_calls
+            .iter()
+            .flatten()
+            .map(FunctionCall::from)
+            .collect()
+    }
+
+    /// Total tokens processed (`prompt_eval_count` + `eval_count`), if the server reported them
+    pub fn token_count(&self) -> Option<u32> {
+        match (self.prompt_eval_count, self.eval_count) {
+            (None, None) => None,
+            (prompt, eval) => Some(prompt.unwrap_or(0) + eval.unwrap_or(0)),
+        }
+    }
+
+    /// The counts as a `TokenUsage` for the usage ledger, if the server reported them
+    pub fn token_usage(&self) -> Option<TokenUsage> {
+        match (self.prompt_eval_count, self.eval_count) {
+            (None, None) => None,
+            (prompt, eval) => Some(TokenUsage::new(prompt.unwrap_or(0), eval.unwrap_or(0))),
+        }
+    }
 }
 
 /// Response structure for listing models
@@ -71,6 +258,28 @@ pub struct ListModelsResponse {
     pub models: Vec<ModelInfo>,
 }
 
+/// Response structure for listing the models loaded in memory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunningModelsResponse {
+    /// The loaded models
+    pub models: Vec<RunningModel>,
+}
+
+/// A model loaded in memory, as returned by `/api/ps`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunningModel {
+    /// The name of the model
+    pub name: String,
+    /// The size of the model in memory
+    #[serde(default)]
+    pub size: u64,
+    /// The part of the model held in GPU memory
+    #[serde(default)]
+    pub size_vram: u64,
+    /// When the model will be unloaded
+    pub expires_at: Option<String>,
+}
+
 /// Information about a model
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelInfo {
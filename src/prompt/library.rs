@@ -0,0 +1,119 @@
+use crate::prompt::render::RenderedPrompt;
+use crate::prompt::template::{PromptError, PromptTemplate};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::debug;
+
+/// File extension of the templates loaded by `PromptLibrary::load_dir`
+pub const TEMPLATE_EXTENSION: &str = "prompt";
+
+/// A set of named templates that can include each other as partials
+#[derive(Debug, Clone, Default)]
+pub struct PromptLibrary {
+    templates: HashMap<String, PromptTemplate>,
+}
+
+impl PromptLibrary {
+    /// Creates an empty library
+    pub fn new() -> Self {
+        PromptLibrary::default()
+    }
+
+    /// Adds a template, replacing any template with the same name
+    pub fn register(mut self, template: PromptTemplate) -> Self {
+        self.insert(template);
+        self
+    }
+
+    /// Parses a template from a string and adds it
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The template name, unless the front matter sets one
+    /// * `source` - The template, with optional front matter
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the library or a `PromptError`
+    pub fn register_str(self, name: &str, source: &str) -> Result<Self, PromptError> {
+        Ok(self.register(PromptTemplate::parse(name, source)?))
+    }
+
+    /// Loads every `*.prompt` file of a directory and its subdirectories
+    ///
+    /// Templates are named after their path relative to `dir`, without the extension and
+    /// with `/` separators (e.g. "shared/examples"), unless the front matter sets a name.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory holding the templates
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the library or a `PromptError`
+    pub fn load_dir(mut self, dir: impl AsRef<Path>) -> Result<Self, PromptError> {
+        let dir = dir.as_ref();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            for entry in std::fs::read_dir(&current)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                if path.extension().and_then(|ext| ext.to_str()) != Some(TEMPLATE_EXTENSION) {
+                    continue;
+                }
+                let name = path
+                    .strip_prefix(dir)
+                    .unwrap_or(&path)
+                    .with_extension("")
+                    .components()
+                    .filter_map(|component| component.as_os_str().to_str())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let source = std::fs::read_to_string(&path)?;
+                debug!("Loaded prompt template {} from {}", name, path.display());
+                self.insert(PromptTemplate::parse(&name, &source)?);
+            }
+        }
+        Ok(self)
+    }
+
+    fn insert(&mut self, template: PromptTemplate) {
+        self.templates.insert(template.name().to_string(), template);
+    }
+
+    /// Returns the template with this name
+    pub fn get(&self, name: &str) -> Option<&PromptTemplate> {
+        self.templates.get(name)
+    }
+
+    /// The names of the loaded templates, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.templates.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Renders a template, resolving its partials in this library
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The template name
+    /// * `variables` - The variables, anything that serializes to a JSON object
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RenderedPrompt` or a `PromptError`
+    pub fn render(
+        &self,
+        name: &str,
+        variables: &impl Serialize,
+    ) -> Result<RenderedPrompt, PromptError> {
+        self.get(name)
+            .ok_or_else(|| PromptError::UnknownTemplate(name.to_string()))?
+            .render_with(Some(self), variables)
+    }
+}
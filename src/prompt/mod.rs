@@ -0,0 +1,8 @@
+pub mod library;
+mod parser;
+pub mod render;
+pub mod template;
+
+pub use library::PromptLibrary;
+pub use render::RenderedPrompt;
+pub use template::{PromptError, PromptTemplate, VarType, Variable};
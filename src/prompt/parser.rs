@@ -0,0 +1,414 @@
+use crate::conversation::types::Role;
+use crate::prompt::template::PromptError;
+
+/// A parsed template element
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Node {
+    /// Literal text
+    Text(String),
+    /// `{{path}}`
+    Variable(String),
+    /// `{{#if path}}...{{else}}...{{/if}}`, or `unless` with the branches swapped
+    If {
+        path: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    /// `{{#each path}}...{{else}}...{{/each}}`
+    Each {
+        path: String,
+        body: Vec<Node>,
+        empty: Vec<Node>,
+    },
+    /// `{{> name}}`
+    Partial(String),
+    /// `{{#system}}`, `{{#user}}` or `{{#assistant}}` block
+    Message { role: Role, body: Vec<Node> },
+}
+
+/// A tag or a run of text, before nesting
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    Variable(String),
+    Open { kind: String, path: String },
+    Else,
+    Close(String),
+    Partial(String),
+    Comment,
+}
+
+impl Token {
+    /// Tags that produce no output and are removed with their line when alone on it
+    fn is_standalone(&self) -> bool {
+        !matches!(self, Token::Text(_) | Token::Variable(_))
+    }
+}
+
+/// Parses a template body into nodes
+pub(crate) fn parse(source: &str) -> Result<Vec<Node>, PromptError> {
+    let mut tokens = tokenize(source)?;
+    strip_standalone_lines(&mut tokens);
+    let mut tokens = tokens.into_iter();
+    let (nodes, end) = parse_nodes(&mut tokens, None)?;
+    match end {
+        End::Eof => Ok(nodes),
+        End::Else => Err(syntax("{{else}} outside of a block")),
+        End::Close(name) => Err(syntax(&format!("unexpected {{{{/{}}}}}", name))),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, PromptError> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(Token::Text(rest[..start].to_string()));
+        }
+        let after = &rest[start + 2..];
+        let (tag, remainder) = if let Some(comment) = after.strip_prefix("!--") {
+            let end = comment
+                .find("--}}")
+                .ok_or_else(|| syntax("unclosed {{!-- comment"))?;
+            (None, &comment[end + 4..])
+        } else {
+            let end = after.find("}}").ok_or_else(|| {
+                syntax(&format!(
+                    "unclosed tag at line {}",
+                    line_of(source, rest, start)
+                ))
+            })?;
+            (Some(after[..end].trim()), &after[end + 2..])
+        };
+        tokens.push(match tag {
+            None => Token::Comment,
+            Some(tag) => tag_token(tag)?,
+        });
+        rest = remainder;
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest.to_string()));
+    }
+    Ok(tokens)
+}
+
+fn tag_token(tag: &str) -> Result<Token, PromptError> {
+    if tag.starts_with('!') {
+        return Ok(Token::Comment);
+    }
+    if tag == "else" {
+        return Ok(Token::Else);
+    }
+    if let Some(name) = tag.strip_prefix('>') {
+        // Partials loaded from subdirectories are named like "shared/examples"
+        let name = name.trim();
+        for part in name.split('/') {
+            checked_name(part, tag)?;
+        }
+        return Ok(Token::Partial(name.to_string()));
+    }
+    if let Some(name) = tag.strip_prefix('/') {
+        return Ok(Token::Close(name.trim().to_string()));
+    }
+    if let Some(block) = tag.strip_prefix('#') {
+        let mut words = block.split_whitespace();
+        let kind = words.next().unwrap_or_default().to_string();
+        let path = words.next().unwrap_or_default().to_string();
+        if words.next().is_some() {
+            return Err(syntax(&format!("unexpected arguments in {{{{{}}}}}", tag)));
+        }
+        return Ok(Token::Open { kind, path });
+    }
+    Ok(Token::Variable(checked_name(tag, tag)?))
+}
+
+/// Checks a variable path or partial name
+fn checked_name(name: &str, tag: &str) -> Result<String, PromptError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '@'));
+    if valid {
+        Ok(name.to_string())
+    } else {
+        Err(syntax(&format!("invalid tag {{{{{}}}}}", tag)))
+    }
+}
+
+/// Removes the whitespace and line break around tags that are alone on their line
+fn strip_standalone_lines(tokens: &mut [Token]) {
+    for index in 0..tokens.len() {
+        if !tokens[index].is_standalone() {
+            continue;
+        }
+        let before_ok = match index.checked_sub(1).map(|i| &tokens[i]) {
+            None => true,
+            Some(Token::Text(text)) => {
+                let line = text.rsplit('\n').next().unwrap_or_default();
+                line.trim().is_empty() && (text.contains('\n') || index == 1)
+            }
+            Some(token) => token.is_standalone() && line_start(tokens, index - 1),
+        };
+        let after_ok = match tokens.get(index + 1) {
+            None => true,
+            Some(Token::Text(text)) => {
+                let line = text.split('\n').next().unwrap_or_default();
+                line.trim().is_empty() && (text.contains('\n') || index + 2 == tokens.len())
+            }
+            Some(_) => false,
+        };
+        if !(before_ok && after_ok) {
+            continue;
+        }
+        if let Some(Token::Text(text)) = index.checked_sub(1).map(|i| &mut tokens[i]) {
+            let keep = text.rfind('\n').map_or(0, |newline| newline + 1);
+            text.truncate(keep);
+        }
+        if let Some(Token::Text(text)) = tokens.get_mut(index + 1) {
+            let skip = text.find('\n').map_or(text.len(), |newline| newline + 1);
+            text.drain(..skip);
+        }
+    }
+}
+
+/// Returns `true` if the token at `index` is preceded only by whitespace on its line
+fn line_start(tokens: &[Token], index: usize) -> bool {
+    match index.checked_sub(1).map(|i| &tokens[i]) {
+        None => true,
+        Some(Token::Text(text)) => text.is_empty() || text.ends_with('\n'),
+        Some(_) => false,
+    }
+}
+
+/// How a run of nodes ended
+enum End {
+    Eof,
+    Else,
+    Close(String),
+}
+
+fn parse_nodes(
+    tokens: &mut impl Iterator<Item = Token>,
+    in_message: Option<Role>,
+) -> Result<(Vec<Node>, End), PromptError> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Text(text) => {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(text));
+                }
+            }
+            Token::Variable(path) => nodes.push(Node::Variable(path)),
+            Token::Partial(name) => nodes.push(Node::Partial(name)),
+            Token::Comment => {}
+            Token::Else => return Ok((nodes, End::Else)),
+            Token::Close(name) => return Ok((nodes, End::Close(name))),
+            Token::Open { kind, path } => nodes.push(parse_block(tokens, &kind, path, in_message)?),
+        }
+    }
+    Ok((nodes, End::Eof))
+}
+
+fn parse_block(
+    tokens: &mut impl Iterator<Item = Token>,
+    kind: &str,
+    path: String,
+    in_message: Option<Role>,
+) -> Result<Node, PromptError> {
+    let role = Role::parse(kind);
+    if let Some(role) = role {
+        if let Some(outer) = in_message {
+            return Err(syntax(&format!(
+                "{{{{#{}}}}} block inside a {{{{#{}}}}} block",
+                role, outer
+            )));
+        }
+        if !path.is_empty() {
+            return Err(syntax(&format!("{{{{#{}}}}} takes no arguments", role)));
+        }
+    } else if !matches!(kind, "if" | "unless" | "each") {
+        return Err(syntax(&format!("unknown block {{{{#{}}}}}", kind)));
+    } else if path.is_empty() {
+        return Err(syntax(&format!("{{{{#{}}}}} needs a variable", kind)));
+    }
+
+    let inner = role.or(in_message);
+    let (first, end) = parse_nodes(tokens, inner)?;
+    let (second, end) = match end {
+        End::Else if role.is_none() => parse_nodes(tokens, inner)?,
+        end => (Vec::new(), end),
+    };
+    match end {
+        End::Close(name) if name == kind => {}
+        End::Close(name) => {
+            return Err(syntax(&format!(
+                "{{{{#{}}}}} closed by {{{{/{}}}}}",
+                kind, name
+            )))
+        }
+        End::Else => return Err(syntax(&format!("{{{{else}}}} inside {{{{#{}}}}}", kind))),
+        End::Eof => return Err(syntax(&format!("unclosed {{{{#{}}}}}", kind))),
+    }
+
+    Ok(match (role, kind) {
+        (Some(role), _) => Node::Message { role, body: first },
+        (None, "if") => Node::If {
+            path,
+            then: first,
+            otherwise: second,
+        },
+        (None, "unless") => Node::If {
+            path,
+            then: second,
+            otherwise: first,
+        },
+        _ => Node::Each {
+            path,
+            body: first,
+            empty: second,
+        },
+    })
+}
+
+fn line_of(source: &str, rest: &str, offset: usize) -> usize {
+    let position = source.len() - rest.len() + offset;
+    source[..position].matches('\n').count() + 1
+}
+
+fn syntax(message: &str) -> PromptError {
+    PromptError::SyntaxError(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> Node {
+        Node::Text(value.to_string())
+    }
+
+    fn variable(path: &str) -> Node {
+        Node::Variable(path.to_string())
+    }
+
+    fn syntax_error(source: &str) -> String {
+        match parse(source) {
+            Err(PromptError::SyntaxError(message)) => message,
+            other => panic!("expected a syntax error for {:?}, got {:?}", source, other),
+        }
+    }
+
+    #[test]
+    fn parses_text_and_variables() {
+        assert_eq!(
+            parse("Hello {{ user.name }}, {{@index}}!").unwrap(),
+            vec![
+                text("Hello "),
+                variable("user.name"),
+                text(", "),
+                variable("@index"),
+                text("!"),
+            ]
+        );
+        assert_eq!(parse("no tags").unwrap(), vec![text("no tags")]);
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parses_blocks() {
+        assert_eq!(
+            parse("{{#if a}}yes{{else}}no{{/if}}{{#unless b}}x{{/unless}}").unwrap(),
+            vec![
+                Node::If {
+                    path: "a".to_string(),
+                    then: vec![text("yes")],
+                    otherwise: vec![text("no")],
+                },
+                Node::If {
+                    path: "b".to_string(),
+                    then: Vec::new(),
+                    otherwise: vec![text("x")],
+                },
+            ]
+        );
+        assert_eq!(
+            parse("{{#each items}}{{this}}{{else}}none{{/each}}{{> shared/footer}}").unwrap(),
+            vec![
+                Node::Each {
+                    path: "items".to_string(),
+                    body: vec![variable("this")],
+                    empty: vec![text("none")],
+                },
+                Node::Partial("shared/footer".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_message_blocks() {
+        assert_eq!(
+            parse("{{#system}}Be brief{{/system}}{{#user}}{{#if q}}{{q}}{{/if}}{{/user}}").unwrap(),
+            vec![
+                Node::Message {
+                    role: Role::System,
+                    body: vec![text("Be brief")],
+                },
+                Node::Message {
+                    role: Role::User,
+                    body: vec![Node::If {
+                        path: "q".to_string(),
+                        then: vec![variable("q")],
+                        otherwise: Vec::new(),
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_comments() {
+        assert_eq!(
+            parse("a{{! note }}b{{!-- {{not a tag}} --}}c").unwrap(),
+            vec![text("a"), text("b"), text("c")]
+        );
+    }
+
+    #[test]
+    fn removes_lines_holding_only_block_tags() {
+        let nodes = parse("{{#each items}}\n  - {{this}}\n{{/each}}\ndone\n").unwrap();
+        assert_eq!(
+            nodes,
+            vec![
+                Node::Each {
+                    path: "items".to_string(),
+                    body: vec![text("  - "), variable("this"), text("\n")],
+                    empty: Vec::new(),
+                },
+                text("done\n"),
+            ]
+        );
+
+        // Tags sharing a line with text keep the surrounding whitespace
+        assert_eq!(parse("a {{#if x}}b{{/if}} c").unwrap()[0], text("a "));
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert!(syntax_error("{{name").contains("unclosed tag at line 1"));
+        assert!(syntax_error("line\n{{#if a}}\n{{b").contains("line 3"));
+        assert!(syntax_error("{{!-- open").contains("comment"));
+        assert!(syntax_error("{{#if a}}").contains("unclosed {{#if}}"));
+        assert!(syntax_error("{{#if a}}{{/each}}").contains("closed by {{/each}}"));
+        assert!(syntax_error("{{/if}}").contains("unexpected {{/if}}"));
+        assert!(syntax_error("{{else}}").contains("outside of a block"));
+        assert!(syntax_error("{{#loop a}}{{/loop}}").contains("unknown block"));
+        assert!(syntax_error("{{#if}}{{/if}}").contains("needs a variable"));
+        assert!(syntax_error("{{#if a b}}{{/if}}").contains("unexpected arguments"));
+        assert!(syntax_error("{{#user}}{{else}}{{/user}}").contains("{{else}} inside"));
+        assert!(syntax_error("{{#user}}{{#system}}{{/system}}{{/user}}").contains("inside"));
+        assert!(syntax_error("{{#user x}}{{/user}}").contains("takes no arguments"));
+        assert!(syntax_error("{{bad name}}").contains("invalid tag"));
+        assert!(syntax_error("{{> shared//footer}}").contains("invalid tag"));
+    }
+}
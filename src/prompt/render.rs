@@ -0,0 +1,322 @@
+use crate::conversation::session::{gemini_contents, render};
+use crate::conversation::types::{Message, Role};
+use crate::gemini::types::GenerateContentRequest;
+use crate::ollama::types::GenerateRequest;
+use crate::prompt::library::PromptLibrary;
+use crate::prompt::parser::Node;
+use crate::prompt::template::{json_type, PromptError, PromptTemplate, VarType};
+use crate::utils::now;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::mem;
+
+/// How deeply partials may include each other
+const MAX_PARTIAL_DEPTH: usize = 32;
+
+/// A piece of rendered output
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Message { role: Role, content: String },
+}
+
+/// The output of a template, ready to be sent as a prompt or as chat messages
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedPrompt {
+    /// The version id of the template, see `PromptTemplate::version_id`
+    pub template_version: String,
+    segments: Vec<Segment>,
+}
+
+impl RenderedPrompt {
+    /// Returns `true` if the template has `{{#system}}`, `{{#user}}` or `{{#assistant}}` blocks
+    pub fn is_chat(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Message { .. }))
+    }
+
+    /// The prompt as plain text
+    ///
+    /// Message blocks are written as a "User: ...", "Assistant: ..." transcript, the way
+    /// `Conversation` prompts completion models.
+    pub fn text(&self) -> String {
+        if self.is_chat() {
+            render(&self.messages())
+        } else {
+            self.segments
+                .iter()
+                .filter_map(|segment| match segment {
+                    Segment::Text(text) => Some(text.as_str()),
+                    Segment::Message { .. } => None,
+                })
+                .collect()
+        }
+    }
+
+    /// The prompt as chat messages
+    ///
+    /// A template without message blocks becomes a single user message.
+    pub fn messages(&self) -> Vec<Message> {
+        let created_at = now();
+        let message = |role, content: &str| Message {
+            role,
+            content: content.trim().to_string(),
+            created_at,
+        };
+        if !self.is_chat() {
+            return vec![message(Role::User, &self.text())];
+        }
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Message { role, content } => Some(message(*role, content)),
+                Segment::Text(_) => None,
+            })
+            .collect()
+    }
+
+    /// Builds a completion request for `OllamaClient` and compatible clients
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model to use for generation
+    pub fn generate_request(&self, model: &str) -> GenerateRequest {
+        GenerateRequest {
+            model: model.to_string(),
+            prompt: self.text(),
+            stream: None,
+            options: None,
+            template_version: Some(self.template_version.clone()),
+        }
+    }
+
+    /// Builds a Gemini request from the messages, sending system messages with the first user message
+    pub fn content_request(&self) -> GenerateContentRequest {
+        GenerateContentRequest {
+            contents: gemini_contents(&self.messages()),
+            generation_config: None,
+            safety_settings: None,
+            tools: None,
+            template_version: Some(self.template_version.clone()),
+        }
+    }
+}
+
+/// Variables visible at some point of the template: the root object or a loop item
+struct Scope<'v> {
+    value: &'v Value,
+    index: usize,
+    key: Option<&'v str>,
+    last: bool,
+}
+
+/// Walks the nodes of a template, collecting its output
+pub(crate) struct Renderer<'a> {
+    library: Option<&'a PromptLibrary>,
+    segments: Vec<Segment>,
+    text: String,
+    role: Option<Role>,
+    depth: usize,
+}
+
+impl<'a> Renderer<'a> {
+    pub(crate) fn new(library: Option<&'a PromptLibrary>) -> Self {
+        Renderer {
+            library,
+            segments: Vec::new(),
+            text: String::new(),
+            role: None,
+            depth: 0,
+        }
+    }
+
+    /// Renders a template with variables that have already been checked
+    pub(crate) fn render(
+        mut self,
+        template: &PromptTemplate,
+        variables: &Value,
+    ) -> Result<RenderedPrompt, PromptError> {
+        let mut scopes = vec![Scope {
+            value: variables,
+            index: 0,
+            key: None,
+            last: true,
+        }];
+        self.nodes(&template.nodes, &mut scopes)?;
+        self.flush_text();
+
+        let chat = self
+            .segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Message { .. }));
+        let stray = self.segments.iter().any(|segment| match segment {
+            Segment::Text(text) => !text.trim().is_empty(),
+            Segment::Message { .. } => false,
+        });
+        if chat && stray {
+            return Err(PromptError::SyntaxError(
+                "text outside of a message block in a chat template".to_string(),
+            ));
+        }
+        Ok(RenderedPrompt {
+            template_version: template.version_id(),
+            segments: self.segments,
+        })
+    }
+
+    fn nodes<'v>(
+        &mut self,
+        nodes: &[Node],
+        scopes: &mut Vec<Scope<'v>>,
+    ) -> Result<(), PromptError> {
+        for node in nodes {
+            self.node(node, scopes)?;
+        }
+        Ok(())
+    }
+
+    fn node<'v>(&mut self, node: &Node, scopes: &mut Vec<Scope<'v>>) -> Result<(), PromptError> {
+        match node {
+            Node::Text(text) => self.text.push_str(text),
+            Node::Variable(path) => match lookup(path, scopes) {
+                Some(value) => write_value(&mut self.text, &value),
+                None => return Err(PromptError::MissingVariable(path.clone())),
+            },
+            Node::If {
+                path,
+                then,
+                otherwise,
+            } => {
+                let truthy = lookup(path, scopes).is_some_and(|value| is_truthy(&value));
+                self.nodes(if truthy { then } else { otherwise }, scopes)?;
+            }
+            Node::Each { path, body, empty } => self.each(path, body, empty, scopes)?,
+            Node::Partial(name) => {
+                let partial = self
+                    .library
+                    .and_then(|library| library.get(name))
+                    .ok_or_else(|| PromptError::UnknownPartial(name.clone()))?;
+                if self.depth == MAX_PARTIAL_DEPTH {
+                    return Err(PromptError::RecursivePartial(name.clone()));
+                }
+                self.depth += 1;
+                self.nodes(&partial.nodes, scopes)?;
+                self.depth -= 1;
+            }
+            Node::Message { role, body } => {
+                // The parser rejects nested blocks, but partials can still nest them
+                if let Some(outer) = self.role {
+                    return Err(PromptError::SyntaxError(format!(
+                        "{{{{#{}}}}} block inside a {{{{#{}}}}} block",
+                        role, outer
+                    )));
+                }
+                self.flush_text();
+                self.role = Some(*role);
+                self.nodes(body, scopes)?;
+                self.role = None;
+                self.segments.push(Segment::Message {
+                    role: *role,
+                    content: mem::take(&mut self.text),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn each<'v>(
+        &mut self,
+        path: &str,
+        body: &[Node],
+        empty: &[Node],
+        scopes: &mut Vec<Scope<'v>>,
+    ) -> Result<(), PromptError> {
+        let items: Vec<(Option<&'v str>, &'v Value)> = match lookup(path, scopes) {
+            None | Some(Cow::Borrowed(Value::Null)) => Vec::new(),
+            Some(Cow::Borrowed(Value::Array(items))) => {
+                items.iter().map(|item| (None, item)).collect()
+            }
+            Some(Cow::Borrowed(Value::Object(fields))) => fields
+                .iter()
+                .map(|(key, item)| (Some(key.as_str()), item))
+                .collect(),
+            Some(value) => {
+                return Err(PromptError::TypeMismatch {
+                    variable: path.to_string(),
+                    expected: VarType::List,
+                    found: json_type(&value),
+                })
+            }
+        };
+        if items.is_empty() {
+            return self.nodes(empty, scopes);
+        }
+        let count = items.len();
+        for (index, (key, value)) in items.into_iter().enumerate() {
+            scopes.push(Scope {
+                value,
+                index,
+                key,
+                last: index + 1 == count,
+            });
+            let result = self.nodes(body, scopes);
+            scopes.pop();
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Moves text written outside of message blocks into its own segment
+    fn flush_text(&mut self) {
+        if !self.text.is_empty() {
+            self.segments.push(Segment::Text(mem::take(&mut self.text)));
+        }
+    }
+}
+
+/// Resolves a dotted path against the innermost scope that has its first segment
+fn lookup<'v>(path: &str, scopes: &[Scope<'v>]) -> Option<Cow<'v, Value>> {
+    let mut segments = path.split('.');
+    let first = segments.next()?;
+    let current = scopes.last()?;
+    let mut value = match first {
+        "this" => current.value,
+        "@index" => return Some(Cow::Owned(Value::from(current.index))),
+        "@first" => return Some(Cow::Owned(Value::Bool(current.index == 0))),
+        "@last" => return Some(Cow::Owned(Value::Bool(current.last))),
+        "@key" => return current.key.map(|key| Cow::Owned(Value::from(key))),
+        name => scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.value.get(name))?,
+    };
+    for segment in segments {
+        value = match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+            value => value.get(segment)?,
+        };
+    }
+    Some(Cow::Borrowed(value))
+}
+
+/// Writes strings as they are, null as nothing and other values as JSON
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::String(text) => out.push_str(text),
+        Value::Null => {}
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+/// `false`, `null`, zero and empty strings, lists and objects are false
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(fields) => !fields.is_empty(),
+    }
+}
@@ -0,0 +1,603 @@
+use crate::prompt::library::PromptLibrary;
+use crate::prompt::parser::{self, Node};
+use crate::prompt::render::{RenderedPrompt, Renderer};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum PromptError {
+    /// Error while reading a template file
+    IoError(io::Error),
+    /// The template or its front matter is malformed
+    SyntaxError(String),
+    /// The variables could not be converted to a JSON object
+    InvalidVariables(String),
+    /// A required variable was not given, or a referenced one is missing
+    MissingVariable(String),
+    /// The template uses a variable it does not declare
+    UnknownVariable(String),
+    /// A variable has the wrong type
+    TypeMismatch {
+        /// The variable path
+        variable: String,
+        /// The declared or required type
+        expected: VarType,
+        /// The JSON type of the given value
+        found: &'static str,
+    },
+    /// No template with this name is loaded
+    UnknownTemplate(String),
+    /// A partial is not loaded, or the template was rendered without a library
+    UnknownPartial(String),
+    /// Partials include each other too deeply, usually in a cycle
+    RecursivePartial(String),
+}
+
+impl fmt::Display for PromptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptError::IoError(err) => write!(f, "IO error: {}", err),
+            PromptError::SyntaxError(msg) => write!(f, "Template syntax error: {}", msg),
+            PromptError::InvalidVariables(msg) => write!(f, "Invalid variables: {}", msg),
+            PromptError::MissingVariable(name) => write!(f, "Missing variable: {}", name),
+            PromptError::UnknownVariable(name) => write!(f, "Undeclared variable: {}", name),
+            PromptError::TypeMismatch {
+                variable,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Variable {} should be a {} but is a {}",
+                variable, expected, found
+            ),
+            PromptError::UnknownTemplate(name) => write!(f, "Template not found: {}", name),
+            PromptError::UnknownPartial(name) => write!(f, "Partial not found: {}", name),
+            PromptError::RecursivePartial(name) => {
+                write!(f, "Partial {} is nested too deeply", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PromptError {}
+
+impl From<io::Error> for PromptError {
+    fn from(err: io::Error) -> Self {
+        PromptError::IoError(err)
+    }
+}
+
+/// Type of a template variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarType {
+    /// A JSON string
+    String,
+    /// A JSON number
+    Number,
+    /// A JSON boolean
+    Bool,
+    /// A JSON array, e.g. few-shot examples
+    List,
+    /// A JSON object
+    Object,
+    /// Any value
+    Any,
+}
+
+impl VarType {
+    /// Returns the type name as written in front matter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VarType::String => "string",
+            VarType::Number => "number",
+            VarType::Bool => "bool",
+            VarType::List => "list",
+            VarType::Object => "object",
+            VarType::Any => "any",
+        }
+    }
+
+    /// Parses a type name from front matter
+    ///
+    /// # Returns
+    ///
+    /// The type, or `None` if the name is not recognized
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(VarType::String),
+            "number" => Some(VarType::Number),
+            "bool" | "boolean" => Some(VarType::Bool),
+            "list" | "array" => Some(VarType::List),
+            "object" => Some(VarType::Object),
+            "any" => Some(VarType::Any),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if `value` has this type
+    pub fn matches(&self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (VarType::Any, _)
+                | (VarType::String, Value::String(_))
+                | (VarType::Number, Value::Number(_))
+                | (VarType::Bool, Value::Bool(_))
+                | (VarType::List, Value::Array(_))
+                | (VarType::Object, Value::Object(_))
+        )
+    }
+}
+
+impl fmt::Display for VarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A variable declared by a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    /// The variable name
+    pub name: String,
+    /// The expected type
+    pub kind: VarType,
+    /// Whether rendering fails when the variable is missing or null
+    pub required: bool,
+}
+
+/// A parsed prompt template
+///
+/// Templates use a Handlebars-like syntax: `{{name}}` and `{{example.input}}` variables,
+/// `{{#if}}`/`{{#unless}}` sections, `{{#each}}` loops with `this`, `@index`, `@first`,
+/// `@last` and `@key`, `{{> partial}}` includes resolved through a `PromptLibrary`,
+/// `{{!-- comments --}}`, and `{{#system}}`, `{{#user}}` and `{{#assistant}}` blocks
+/// for chat messages. A tag alone on its line takes the line with it.
+///
+/// Front matter between `---` lines may set the `name`, `version` and `description`,
+/// and declare typed `variables`, where a trailing `?` marks an optional one:
+///
+/// ```text
+/// ---
+/// name: classify
+/// version: 3
+/// variables:
+///   text: string
+///   examples: list?
+/// ---
+/// ```
+///
+/// Once variables are declared, rendering checks their types and rejects references
+/// to undeclared variables outside of loops.
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    name: String,
+    version: Option<String>,
+    description: Option<String>,
+    variables: Vec<Variable>,
+    source: String,
+    pub(crate) nodes: Vec<Node>,
+}
+
+impl PromptTemplate {
+    /// Parses a template from a string
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The template name, unless the front matter sets one
+    /// * `source` - The template, with optional front matter
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PromptTemplate` or a `PromptError`
+    pub fn parse(name: &str, source: &str) -> Result<Self, PromptError> {
+        let (front_matter, body) = split_front_matter(source)?;
+        let mut template = PromptTemplate {
+            name: name.to_string(),
+            version: None,
+            description: None,
+            variables: Vec::new(),
+            source: source.to_string(),
+            nodes: parser::parse(body)?,
+        };
+        if let Some(front_matter) = front_matter {
+            template.apply_front_matter(front_matter)?;
+        }
+        Ok(template)
+    }
+
+    /// Loads a template from a file, named after the file stem unless the front matter sets a name
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the template file, e.g. "prompts/classify.prompt"
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PromptTemplate` or a `PromptError`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PromptError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)?;
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        PromptTemplate::parse(name, &source)
+    }
+
+    /// Sets the version, replacing the one from the front matter
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// Declares a required variable, replacing any declaration with the same name
+    pub fn variable(mut self, name: &str, kind: VarType) -> Self {
+        self.declare(name, kind, true);
+        self
+    }
+
+    /// Declares an optional variable, replacing any declaration with the same name
+    pub fn optional_variable(mut self, name: &str, kind: VarType) -> Self {
+        self.declare(name, kind, false);
+        self
+    }
+
+    fn declare(&mut self, name: &str, kind: VarType, required: bool) {
+        self.variables.retain(|variable| variable.name != name);
+        self.variables.push(Variable {
+            name: name.to_string(),
+            kind,
+            required,
+        });
+    }
+
+    /// The template name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The description from the front matter, if any
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// The declared variables
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    /// The template source, including front matter
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The id recorded on rendered requests
+    ///
+    /// This is "name@version" when a version is set, or "name@" followed by the first
+    /// 12 hex digits of the SHA-256 of the source, so any edit gives a new id.
+    pub fn version_id(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}@{}", self.name, version),
+            None => {
+                let digest = Sha256::digest(self.source.as_bytes());
+                format!("{}@{}", self.name, &hex::encode(digest)[..12])
+            }
+        }
+    }
+
+    /// Renders the template without partials
+    ///
+    /// # Arguments
+    ///
+    /// * `variables` - The variables, anything that serializes to a JSON object
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RenderedPrompt` or a `PromptError`
+    pub fn render(&self, variables: &impl Serialize) -> Result<RenderedPrompt, PromptError> {
+        self.render_with(None, variables)
+    }
+
+    /// Renders the template, resolving partials through `library` if given
+    pub(crate) fn render_with(
+        &self,
+        library: Option<&PromptLibrary>,
+        variables: &impl Serialize,
+    ) -> Result<RenderedPrompt, PromptError> {
+        let variables = serde_json::to_value(variables)
+            .map_err(|err| PromptError::InvalidVariables(err.to_string()))?;
+        if !variables.is_object() {
+            return Err(PromptError::InvalidVariables(
+                "variables must serialize to a JSON object".to_string(),
+            ));
+        }
+        self.check_variables(&variables)?;
+        Renderer::new(library).render(self, &variables)
+    }
+
+    /// Checks the given variables against the declarations
+    fn check_variables(&self, variables: &Value) -> Result<(), PromptError> {
+        if self.variables.is_empty() {
+            return Ok(());
+        }
+        for variable in &self.variables {
+            match variables.get(&variable.name) {
+                None | Some(Value::Null) if variable.required => {
+                    return Err(PromptError::MissingVariable(variable.name.clone()))
+                }
+                None | Some(Value::Null) => {}
+                Some(value) if !variable.kind.matches(value) => {
+                    return Err(PromptError::TypeMismatch {
+                        variable: variable.name.clone(),
+                        expected: variable.kind,
+                        found: json_type(value),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        let mut roots = BTreeSet::new();
+        collect_roots(&self.nodes, &mut roots);
+        match roots
+            .into_iter()
+            .find(|root| !self.variables.iter().any(|variable| variable.name == *root))
+        {
+            Some(root) => Err(PromptError::UnknownVariable(root.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    fn apply_front_matter(&mut self, front_matter: &str) -> Result<(), PromptError> {
+        let mut in_variables = false;
+        for line in front_matter.lines() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| front_matter_error(line))?;
+            let value = value.trim();
+            let indented = line.starts_with(char::is_whitespace);
+            if indented && in_variables {
+                let (kind, required) = match value.strip_suffix('?') {
+                    Some(kind) => (kind.trim(), false),
+                    None => (value, true),
+                };
+                let kind = VarType::parse(kind).ok_or_else(|| {
+                    PromptError::SyntaxError(format!("unknown variable type \"{}\"", kind))
+                })?;
+                self.declare(key.trim(), kind, required);
+                continue;
+            }
+            if indented {
+                return Err(front_matter_error(line));
+            }
+            in_variables = false;
+            match key {
+                "name" => self.name = unquote(value).to_string(),
+                "version" => self.version = Some(unquote(value).to_string()),
+                "description" => self.description = Some(unquote(value).to_string()),
+                "variables" if value.is_empty() => in_variables = true,
+                _ => return Err(front_matter_error(line)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits `---` front matter from the template body
+fn split_front_matter(source: &str) -> Result<(Option<&str>, &str), PromptError> {
+    let Some(rest) = source
+        .strip_prefix("---\n")
+        .or_else(|| source.strip_prefix("---\r\n"))
+    else {
+        return Ok((None, source));
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Ok((Some(&rest[..offset]), &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    Err(PromptError::SyntaxError(
+        "unclosed front matter".to_string(),
+    ))
+}
+
+/// Collects the first segment of variable paths used outside of loops
+fn collect_roots<'a>(nodes: &'a [Node], roots: &mut BTreeSet<&'a str>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) | Node::Partial(_) => {}
+            Node::Variable(path) => add_root(path, roots),
+            Node::If {
+                path,
+                then,
+                otherwise,
+            } => {
+                add_root(path, roots);
+                collect_roots(then, roots);
+                collect_roots(otherwise, roots);
+            }
+            Node::Each { path, empty, .. } => {
+                add_root(path, roots);
+                collect_roots(empty, roots);
+            }
+            Node::Message { body, .. } => collect_roots(body, roots),
+        }
+    }
+}
+
+fn add_root<'a>(path: &'a str, roots: &mut BTreeSet<&'a str>) {
+    let root = path.split('.').next().unwrap_or_default();
+    if root != "this" && !root.starts_with('@') {
+        roots.insert(root);
+    }
+}
+
+/// Returns the JSON type name of a value, for error messages
+pub(crate) fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "list",
+        Value::Object(_) => "object",
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn front_matter_error(line: &str) -> PromptError {
+    PromptError::SyntaxError(format!("invalid front matter line \"{}\"", line.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation::types::Role;
+    use serde_json::json;
+
+    const CLASSIFY: &str = "---
+name: classify
+version: 2
+description: \"Sorts a ticket\"
+variables:
+  text: string
+  labels: list
+  urgent: bool?
+---
+{{#system}}
+Pick one of:
+{{#each labels}}
+- {{this}}{{#if @last}}.{{/if}}
+{{/each}}
+{{/system}}
+{{#user}}
+{{#if urgent}}URGENT: {{/if}}{{text}}
+{{/user}}
+";
+
+    #[test]
+    fn reads_front_matter() {
+        let template = PromptTemplate::parse("file-name", CLASSIFY).unwrap();
+        assert_eq!(template.name(), "classify");
+        assert_eq!(template.description(), Some("Sorts a ticket"));
+        assert_eq!(template.version_id(), "classify@2");
+        let declared: Vec<_> = template
+            .variables()
+            .iter()
+            .map(|variable| (variable.name.as_str(), variable.kind, variable.required))
+            .collect();
+        assert_eq!(
+            declared,
+            vec![
+                ("text", VarType::String, true),
+                ("labels", VarType::List, true),
+                ("urgent", VarType::Bool, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_chat_messages() {
+        let rendered = PromptTemplate::parse("classify", CLASSIFY)
+            .unwrap()
+            .render(&json!({"text": "Printer on fire", "labels": ["bug", "fire"], "urgent": true}))
+            .unwrap();
+
+        assert!(rendered.is_chat());
+        assert_eq!(rendered.template_version, "classify@2");
+        let messages: Vec<_> = rendered
+            .messages()
+            .into_iter()
+            .map(|message| (message.role, message.content))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (Role::System, "Pick one of:\n- bug\n- fire.".to_string()),
+                (Role::User, "URGENT: Printer on fire".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_plain_text() {
+        let template =
+            PromptTemplate::parse("greet", "Hello {{#if name}}{{name}}{{else}}you{{/if}}!")
+                .unwrap();
+        let rendered = template.render(&json!({"name": "Ada"})).unwrap();
+        assert!(!rendered.is_chat());
+        assert_eq!(rendered.text(), "Hello Ada!");
+        assert_eq!(template.render(&json!({})).unwrap().text(), "Hello you!");
+        assert!(matches!(
+            PromptTemplate::parse("greet", "Hello {{name}}").unwrap().render(&json!({})),
+            Err(PromptError::MissingVariable(name)) if name == "name"
+        ));
+
+        // Without a version the id follows the source
+        let other = PromptTemplate::parse("greet", "Hi {{name}}").unwrap();
+        assert!(template.version_id().starts_with("greet@"));
+        assert_eq!(template.version_id().len(), "greet@".len() + 12);
+        assert_ne!(template.version_id(), other.version_id());
+    }
+
+    #[test]
+    fn checks_declared_variables() {
+        let template = PromptTemplate::parse("classify", CLASSIFY).unwrap();
+        assert!(matches!(
+            template.render(&json!({"labels": []})),
+            Err(PromptError::MissingVariable(name)) if name == "text"
+        ));
+        assert!(matches!(
+            template.render(&json!({"text": "hi", "labels": "bug"})),
+            Err(PromptError::TypeMismatch { variable, expected: VarType::List, found: "string" })
+                if variable == "labels"
+        ));
+        assert!(matches!(
+            template.render(&"not an object"),
+            Err(PromptError::InvalidVariables(_))
+        ));
+
+        let undeclared = PromptTemplate::parse("t", "{{a}} {{b}}")
+            .unwrap()
+            .variable("a", VarType::String);
+        assert!(matches!(
+            undeclared.render(&json!({"a": "x"})),
+            Err(PromptError::UnknownVariable(name)) if name == "b"
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_front_matter() {
+        for source in [
+            "---\nname: x\n",
+            "---\nauthor: me\n---\n",
+            "---\nvariables:\n  a: date\n---\n",
+            "---\n  indented: yes\n---\n",
+        ] {
+            assert!(
+                matches!(
+                    PromptTemplate::parse("t", source),
+                    Err(PromptError::SyntaxError(_))
+                ),
+                "{:?} should be rejected",
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use crate::middleware::{HttpError, Middleware, Next, Request};
+use futures_util::future::BoxFuture;
+use reqwest::Response;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Rough token count of a text, assuming about four characters per token
+pub fn estimate_tokens(text: &str) -> u32 {
+    text.chars().count().div_ceil(4) as u32
+}
+
+/// Expected token usage of a request, attached to its extensions for the rate limiter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EstimatedTokens(pub u32);
+
+/// A bucket refilled continuously at `capacity` units per minute
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    available: f64,
+}
+
+impl Bucket {
+    fn new(per_minute: u32) -> Self {
+        Bucket {
+            capacity: per_minute as f64,
+            available: per_minute as f64,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        let refilled = elapsed.as_secs_f64() * self.capacity / 60.0;
+        self.available = (self.available + refilled).min(self.capacity);
+    }
+
+    /// Time until `amount` units are available; larger amounts wait for a full bucket
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing * 60.0 / self.capacity)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    last_refill: Instant,
+}
+
+impl Buckets {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.last_refill;
+        self.last_refill = now;
+        if let Some(bucket) = &mut self.requests {
+            bucket.refill(elapsed);
+        }
+        if let Some(bucket) = &mut self.tokens {
+            bucket.refill(elapsed);
+        }
+    }
+}
+
+struct Shared {
+    buckets: Mutex<Buckets>,
+    // Held while waiting so callers are served in arrival order
+    queue: tokio::sync::Mutex<()>,
+    // Wakes the waiting caller when reconciliation returns tokens
+    released: tokio::sync::Notify,
+}
+
+/// Client-side rate limiter with request-per-minute and token-per-minute buckets
+///
+/// Clones share the same buckets, so one limiter can be handed to several clients
+/// and tasks that draw from the same quota. Callers wait for capacity instead of
+/// failing.
+#[derive(Clone)]
+pub struct RateLimiter {
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let buckets = self
+            .shared
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f.debug_struct("RateLimiter")
+            .field("requests", &buckets.requests)
+            .field("tokens", &buckets.tokens)
+            .finish()
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter {
+            shared: Arc::new(Shared {
+                buckets: Mutex::new(Buckets {
+                    requests: None,
+                    tokens: None,
+                    last_refill: Instant::now(),
+                }),
+                queue: tokio::sync::Mutex::new(()),
+                released: tokio::sync::Notify::new(),
+            }),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Creates a limiter with no limits; add them with the setters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the number of requests per minute
+    pub fn requests_per_minute(self, limit: u32) -> Self {
+        self.buckets().requests = Some(Bucket::new(limit.max(1)));
+        self
+    }
+
+    /// Limits the number of tokens per minute
+    pub fn tokens_per_minute(self, limit: u32) -> Self {
+        self.buckets().tokens = Some(Bucket::new(limit.max(1)));
+        self
+    }
+
+    fn buckets(&self) -> std::sync::MutexGuard<'_, Buckets> {
+        self.shared
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Waits until one request and `estimated_tokens` tokens are available, then takes them
+    ///
+    /// A request estimated above the token limit waits for a full bucket rather than forever.
+    ///
+    /// # Arguments
+    ///
+    /// * `estimated_tokens` - The expected token usage of the request
+    ///
+    /// # Returns
+    ///
+    /// A `RateLimitPermit` to reconcile once the actual usage is known
+    pub async fn acquire(&self, estimated_tokens: u32) -> RateLimitPermit {
+        let _turn = self.shared.queue.lock().await;
+        loop {
+            let released = self.shared.released.notified();
+            let wait = {
+                let mut buckets = self.buckets();
+                buckets.refill();
+                let wait = buckets
+                    .requests
+                    .as_ref()
+                    .map(|bucket| bucket.wait_for(1.0))
+                    .unwrap_or_default()
+                    .max(
+                        buckets
+                            .tokens
+                            .as_ref()
+                            .map(|bucket| bucket.wait_for(estimated_tokens as f64))
+                            .unwrap_or_default(),
+                    );
+                if wait.is_zero() {
+                    if let Some(bucket) = &mut buckets.requests {
+                        bucket.available -= 1.0;
+                    }
+                    if let Some(bucket) = &mut buckets.tokens {
+                        bucket.available -= estimated_tokens as f64;
+                    }
+                    debug!("Rate limiter granted {} tokens", estimated_tokens);
+                    return RateLimitPermit {
+                        limiter: self.clone(),
+                        reserved_tokens: estimated_tokens,
+                        settled: Arc::new(AtomicBool::new(false)),
+                    };
+                }
+                wait
+            };
+            info!("Rate limit reached, waiting {:?}", wait);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = released => {}
+            }
+        }
+    }
+}
+
+/// Waits for capacity before passing the request on
+///
+/// The token estimate comes from the `EstimatedTokens` extension, or from the body
+/// size when it is missing. The `RateLimitPermit` is added to the response
+/// extensions so the caller can reconcile it once the usage is known.
+impl Middleware for RateLimiter {
+    fn handle<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response, HttpError>> {
+        Box::pin(async move {
+            let estimated_tokens = match request.extensions().get::<EstimatedTokens>() {
+                Some(EstimatedTokens(tokens)) => *tokens,
+                None => request
+                    .body_bytes()
+                    .map(|body| estimate_tokens(&String::from_utf8_lossy(body)))
+                    .unwrap_or_default(),
+            };
+            let permit = self.acquire(estimated_tokens).await;
+            let mut response = next.run(request).await?;
+            if response.status().is_success() {
+                response.extensions_mut().insert(permit);
+            } else {
+                // The attempt still counts as a request, but its tokens were not used
+                permit.reconcile(0);
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Capacity taken from a `RateLimiter` for one request
+///
+/// Clones share the reservation; only the first `reconcile` takes effect.
+#[derive(Debug, Clone)]
+pub struct RateLimitPermit {
+    limiter: RateLimiter,
+    reserved_tokens: u32,
+    settled: Arc<AtomicBool>,
+}
+
+impl RateLimitPermit {
+    /// Corrects the token bucket with the usage reported by the provider
+    ///
+    /// Over-estimates are returned to the bucket and under-estimates are taken
+    /// from it, possibly leaving it in debt so later requests wait longer.
+    pub fn reconcile(self, actual_tokens: u32) {
+        if self.settled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let mut buckets = self.limiter.buckets();
+        buckets.refill();
+        if let Some(bucket) = &mut buckets.tokens {
+            bucket.available = (bucket.available + self.reserved_tokens as f64
+                - actual_tokens as f64)
+                .min(bucket.capacity);
+        }
+        drop(buckets);
+        if actual_tokens < self.reserved_tokens {
+            self.limiter.shared.released.notify_waiters();
+        }
+        debug!(
+            "Reconciled {} estimated tokens with {} used",
+            self.reserved_tokens, actual_tokens
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::Stack;
+    use crate::retry::RetryPolicy;
+
+    fn available_tokens(limiter: &RateLimiter) -> f64 {
+        limiter.buckets().tokens.as_ref().unwrap().available
+    }
+
+    #[test]
+    fn estimates_four_characters_per_token() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+
+    #[test]
+    fn bucket_waits_for_missing_units() {
+        let mut bucket = Bucket::new(60);
+        bucket.available = 0.0;
+        assert_eq!(bucket.wait_for(1.0), Duration::from_secs(1));
+        // Amounts above the capacity wait for a full bucket
+        assert_eq!(bucket.wait_for(600.0), Duration::from_secs(60));
+
+        bucket.refill(Duration::from_secs(30));
+        assert_eq!(bucket.available, 30.0);
+        bucket.refill(Duration::from_secs(600));
+        assert_eq!(bucket.available, 60.0);
+        assert_eq!(bucket.wait_for(1.0), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn waits_when_requests_run_out() {
+        let limiter = RateLimiter::new().requests_per_minute(2);
+        limiter.acquire(0).await;
+        limiter.acquire(0).await;
+
+        let third = tokio::time::timeout(Duration::from_millis(50), limiter.acquire(0)).await;
+        assert!(third.is_err());
+    }
+
+    #[tokio::test]
+    async fn reconcile_corrects_the_token_bucket() {
+        let limiter = RateLimiter::new().tokens_per_minute(100);
+        let permit = limiter.acquire(80).await;
+        assert!(available_tokens(&limiter) < 21.0);
+
+        permit.clone().reconcile(30);
+        let after = available_tokens(&limiter);
+        assert!((70.0..71.0).contains(&after));
+
+        // Only the first reconcile of a reservation counts
+        permit.reconcile(0);
+        assert!(available_tokens(&limiter) < after + 1.0);
+
+        // Under-estimates leave the bucket in debt
+        limiter.acquire(10).await.reconcile(200);
+        assert!(available_tokens(&limiter) < 0.0);
+    }
+
+    #[tokio::test]
+    async fn reconcile_wakes_waiting_callers() {
+        let limiter = RateLimiter::new().tokens_per_minute(100);
+        let permit = limiter.acquire(90).await;
+
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire(50).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        permit.reconcile(10);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("waiter should wake once tokens are returned")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn middleware_attaches_permits_and_releases_failed_attempts() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/ok")
+            .with_body("done")
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/fail")
+            .with_status(500)
+            .create_async()
+            .await;
+
+        let limiter = RateLimiter::new().tokens_per_minute(1000);
+        let stack = Stack {
+            rate_limiter: Some(limiter.clone()),
+            retry_policy: RetryPolicy::none(),
+            ..Default::default()
+        };
+        let client = reqwest::Client::new();
+
+        let mut request = Request::new(
+            client
+                .post(format!("{}/ok", server.url()))
+                .body("ignored")
+                .build()
+                .unwrap(),
+        );
+        request.extensions_mut().insert(EstimatedTokens(300));
+        let response = stack.send(&client, request).await.unwrap();
+        let permit = response.extensions().get::<RateLimitPermit>().cloned();
+        assert_eq!(
+            permit.as_ref().map(|permit| permit.reserved_tokens),
+            Some(300)
+        );
+        assert!(available_tokens(&limiter) < 701.0);
+        permit.unwrap().reconcile(100);
+
+        // The estimate falls back to the body size, and is returned when the attempt fails
+        let before = available_tokens(&limiter);
+        let request = Request::new(
+            client
+                .post(format!("{}/fail", server.url()))
+                .body("x".repeat(400))
+                .build()
+                .unwrap(),
+        );
+        assert!(stack.send(&client, request).await.is_err());
+        assert!(available_tokens(&limiter) >= before);
+    }
+}
@@ -0,0 +1,700 @@
+use crate::middleware::{HttpError, Middleware, Next, Request};
+use crate::utils::parse_http_date;
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Body, Response, ResponseBuilderExt, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+/// Kind of failure, used to decide whether an attempt is retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// HTTP 429 Too Many Requests
+    RateLimited,
+    /// HTTP 503 Service Unavailable or 529 Overloaded
+    Overloaded,
+    /// Any other HTTP 5xx status
+    ServerError,
+    /// HTTP 408 or 504, or a request that timed out locally
+    Timeout,
+    /// The connection failed or dropped before a response was read
+    Connection,
+    /// Any other HTTP 4xx status
+    ClientError,
+    /// Anything else, such as a redirect loop or an invalid request
+    Other,
+}
+
+impl ErrorClass {
+    /// Classifies an unsuccessful HTTP status
+    pub fn from_status(status: StatusCode) -> Self {
+        match status.as_u16() {
+            429 => ErrorClass::RateLimited,
+            503 | 529 => ErrorClass::Overloaded,
+            408 | 504 => ErrorClass::Timeout,
+            500..=599 => ErrorClass::ServerError,
+            400..=499 => ErrorClass::ClientError,
+            _ => ErrorClass::Other,
+        }
+    }
+
+    /// Classifies a transport error
+    pub fn from_error(err: &reqwest::Error) -> Self {
+        if err.is_timeout() {
+            ErrorClass::Timeout
+        } else if err.is_connect() || err.is_request() || err.is_body() {
+            ErrorClass::Connection
+        } else if let Some(status) = err.status() {
+            ErrorClass::from_status(status)
+        } else {
+            ErrorClass::Other
+        }
+    }
+}
+
+/// How randomness is applied to the computed backoff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jitter {
+    /// Wait exactly the computed backoff
+    None,
+    /// Wait a random time between zero and the computed backoff
+    Full,
+    /// Wait half the computed backoff plus a random time up to the other half
+    Equal,
+}
+
+/// Details of a failed attempt, passed to the retry hooks
+#[derive(Debug, Clone)]
+pub struct RetryEvent {
+    /// The attempt that failed, starting at 1
+    pub attempt: u32,
+    /// The number of attempts allowed for this error class
+    pub max_attempts: u32,
+    /// The class of the failure
+    pub class: ErrorClass,
+    /// The HTTP status, if a response was received
+    pub status: Option<u16>,
+    /// The wait before the next attempt; zero when giving up
+    pub delay: Duration,
+    /// Whether the delay came from `Retry-After` or a `RetryInfo.retryDelay` hint
+    pub server_hint: bool,
+    /// A description of the failure
+    pub error: String,
+}
+
+type Hook = Arc<dyn Fn(&RetryEvent) + Send + Sync>;
+
+/// Policy deciding whether and when failed requests are retried
+///
+/// The default policy makes up to 3 attempts with full-jitter exponential backoff
+/// starting at 500ms, and retries rate limiting, overload, server errors, timeouts
+/// and connection failures. Server hints (`Retry-After` and Google `RetryInfo`)
+/// replace the computed backoff when present.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: Jitter,
+    max_server_delay: Duration,
+    respect_server_delay: bool,
+    rules: HashMap<ErrorClass, u32>,
+    on_retry: Vec<Hook>,
+    on_give_up: Vec<Hook>,
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .field("jitter", &self.jitter)
+            .field("max_server_delay", &self.max_server_delay)
+            .field("respect_server_delay", &self.respect_server_delay)
+            .field("rules", &self.rules)
+            .field("on_retry", &self.on_retry.len())
+            .field("on_give_up", &self.on_give_up.len())
+            .finish()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        let mut rules = HashMap::new();
+        rules.insert(ErrorClass::ClientError, 1);
+        rules.insert(ErrorClass::Other, 1);
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: Jitter::Full,
+            max_server_delay: Duration::from_secs(120),
+            respect_server_delay: true,
+            rules,
+            on_retry: Vec::new(),
+            on_give_up: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates the default policy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy that makes a single attempt
+    pub fn none() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    /// Sets the maximum number of attempts, including the first one
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the backoff before the first retry
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the upper bound of the computed backoff
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Sets the factor the backoff grows by after each attempt
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Sets how randomness is applied to the backoff
+    pub fn jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets whether `Retry-After` and `RetryInfo.retryDelay` hints are honored
+    pub fn respect_server_delay(mut self, respect: bool) -> Self {
+        self.respect_server_delay = respect;
+        self
+    }
+
+    /// Sets the longest server-requested delay to wait for; longer hints give up instead
+    pub fn max_server_delay(mut self, delay: Duration) -> Self {
+        self.max_server_delay = delay;
+        self
+    }
+
+    /// Overrides the maximum number of attempts for one class of error
+    ///
+    /// # Arguments
+    ///
+    /// * `class` - The error class the rule applies to
+    /// * `max_attempts` - Attempts allowed for this class; `1` disables retries for it
+    pub fn rule(mut self, class: ErrorClass, max_attempts: u32) -> Self {
+        self.rules.insert(class, max_attempts.max(1));
+        self
+    }
+
+    /// Registers a hook called before each retry
+    pub fn on_retry(mut self, hook: impl Fn(&RetryEvent) + Send + Sync + 'static) -> Self {
+        self.on_retry.push(Arc::new(hook));
+        self
+    }
+
+    /// Registers a hook called when a failure is returned to the caller
+    pub fn on_give_up(mut self, hook: impl Fn(&RetryEvent) + Send + Sync + 'static) -> Self {
+        self.on_give_up.push(Arc::new(hook));
+        self
+    }
+
+    /// Returns the number of attempts allowed for a class of error
+    pub fn attempts_for(&self, class: ErrorClass) -> u32 {
+        self.rules.get(&class).copied().unwrap_or(self.max_attempts)
+    }
+
+    /// Returns the computed backoff before retrying after `attempt`, without jitter
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .powi(attempt.saturating_sub(1).min(i32::MAX as u32) as i32);
+        let secs = self.initial_backoff.as_secs_f64() * factor;
+        if secs.is_finite() && secs < self.max_backoff.as_secs_f64() {
+            Duration::from_secs_f64(secs)
+        } else {
+            self.max_backoff
+        }
+    }
+
+    fn jittered(&self, backoff: Duration) -> Duration {
+        match self.jitter {
+            Jitter::None => backoff,
+            Jitter::Full => backoff.mul_f64(random_unit()),
+            Jitter::Equal => backoff / 2 + (backoff / 2).mul_f64(random_unit()),
+        }
+    }
+
+    /// Decides the wait before the next attempt, or `None` to give up
+    fn next_delay(
+        &self,
+        attempt: u32,
+        class: ErrorClass,
+        hint: Option<Duration>,
+    ) -> Option<(Duration, bool)> {
+        if attempt >= self.attempts_for(class) {
+            return None;
+        }
+        match hint.filter(|_| self.respect_server_delay) {
+            Some(hint) if hint > self.max_server_delay => None,
+            Some(hint) => Some((hint, true)),
+            None => Some((self.jittered(self.backoff(attempt)), false)),
+        }
+    }
+
+    /// Runs `attempt_fn` until it succeeds or the policy gives up
+    async fn run<T, F, Fut>(&self, mut attempt_fn: F) -> Result<T, HttpError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, FailedAttempt>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let failed = match attempt_fn().await {
+                Ok(value) => return Ok(value),
+                Err(failed) => failed,
+            };
+
+            let mut event = RetryEvent {
+                attempt,
+                max_attempts: self.attempts_for(failed.class),
+                class: failed.class,
+                status: failure_status(&failed.failure),
+                delay: Duration::ZERO,
+                server_hint: false,
+                error: failed.failure.to_string(),
+            };
+
+            match self.next_delay(attempt, failed.class, failed.hint) {
+                Some((delay, server_hint)) => {
+                    event.delay = delay;
+                    event.server_hint = server_hint;
+                    warn!(
+                        "Attempt {}/{} failed ({:?}), retrying in {:?}",
+                        attempt, event.max_attempts, failed.class, delay
+                    );
+                    for hook in &self.on_retry {
+                        hook(&event);
+                    }
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => {
+                    debug!(
+                        "Giving up after attempt {}/{} ({:?})",
+                        attempt, event.max_attempts, failed.class
+                    );
+                    for hook in &self.on_give_up {
+                        hook(&event);
+                    }
+                    return Err(failed.failure);
+                }
+            }
+        }
+    }
+}
+
+struct FailedAttempt {
+    class: ErrorClass,
+    hint: Option<Duration>,
+    failure: HttpError,
+}
+
+impl FailedAttempt {
+    fn network(err: reqwest::Error) -> Self {
+        FailedAttempt {
+            class: ErrorClass::from_error(&err),
+            hint: None,
+            failure: HttpError::Network(err),
+        }
+    }
+
+    async fn status(response: Response) -> Self {
+        let status = response.status();
+        let header_hint = retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        FailedAttempt {
+            class: ErrorClass::from_status(status),
+            hint: header_hint.or_else(|| retry_info_delay(&body)),
+            failure: HttpError::Status { status, body },
+        }
+    }
+
+    fn from_error(err: HttpError) -> Self {
+        match err {
+            HttpError::Network(err) => FailedAttempt::network(err),
+            HttpError::Status { status, .. } => FailedAttempt {
+                class: ErrorClass::from_status(status),
+                hint: None,
+                failure: err,
+            },
+            HttpError::Middleware(_) | HttpError::BudgetExceeded(_) => FailedAttempt {
+                class: ErrorClass::Other,
+                hint: None,
+                failure: err,
+            },
+        }
+    }
+}
+
+fn failure_status(failure: &HttpError) -> Option<u16> {
+    match failure {
+        HttpError::Status { status, .. } => Some(status.as_u16()),
+        HttpError::Network(err) => err.status().map(|status| status.as_u16()),
+        HttpError::Middleware(_) | HttpError::BudgetExceeded(_) => None,
+    }
+}
+
+/// Retries the rest of the stack according to the policy
+///
+/// An attempt succeeds once the response has a 2xx status and its first body chunk
+/// has arrived, so a stream that fails before sending anything is retried too. Later
+/// failures are reported while reading the body instead. Requests with a streamed
+/// body cannot be replayed and are sent once.
+impl Middleware for RetryPolicy {
+    fn handle<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response, HttpError>> {
+        Box::pin(async move {
+            if request.try_clone().is_none() {
+                debug!("Request body cannot be replayed, sending it once");
+                return next.run(request).await;
+            }
+            self.run(|| async {
+                let attempt = request.try_clone().ok_or_else(|| {
+                    FailedAttempt::from_error(HttpError::Middleware(
+                        "request body cannot be replayed".to_string(),
+                    ))
+                })?;
+                match next.run(attempt).await {
+                    Ok(response) if response.status().is_success() => {
+                        with_first_chunk(response).await
+                    }
+                    Ok(response) => Err(FailedAttempt::status(response).await),
+                    Err(err) => Err(FailedAttempt::from_error(err)),
+                }
+            })
+            .await
+        })
+    }
+}
+
+/// Waits for the first body chunk, then rebuilds the response around the buffered stream
+async fn with_first_chunk(mut response: Response) -> Result<Response, FailedAttempt> {
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+    if let Some(extensions) = builder.extensions_mut() {
+        *extensions = std::mem::take(response.extensions_mut());
+    }
+    let builder = builder.url(response.url().clone());
+
+    let mut stream = response.bytes_stream();
+    let first = match stream.next().await {
+        Some(Ok(first)) => first,
+        Some(Err(err)) => return Err(FailedAttempt::network(err)),
+        None => Bytes::new(),
+    };
+    let body = Body::wrap_stream(futures_util::stream::iter([Ok(first)]).chain(stream));
+    builder
+        .body(body)
+        .map(Response::from)
+        .map_err(|err| FailedAttempt::from_error(HttpError::Middleware(err.to_string())))
+}
+
+/// Reads a `Retry-After` header given in seconds or as an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    parse_http_date(value).map(|date| {
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO)
+    })
+}
+
+/// Reads `retryDelay` from a Google `RetryInfo` error detail, e.g. `"retryDelay": "36s"`
+fn retry_info_delay(body: &str) -> Option<Duration> {
+    let json: serde_json::Value = serde_json::from_str(body).ok()?;
+    json.get("error")?
+        .get("details")?
+        .as_array()?
+        .iter()
+        .filter(|detail| {
+            detail
+                .get("@type")
+                .and_then(|kind| kind.as_str())
+                .is_some_and(|kind| kind.ends_with("google.rpc.RetryInfo"))
+        })
+        .find_map(|detail| detail.get("retryDelay")?.as_str())
+        .and_then(|delay| delay.strip_suffix('s')?.parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+/// A random number in `[0, 1)`, seeded by the standard library's per-process hash keys
+fn random_unit() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::Stack;
+    use reqwest::header::HeaderValue;
+    use std::sync::Mutex;
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(1))
+            .jitter(Jitter::None)
+    }
+
+    #[test]
+    fn classifies_statuses() {
+        let class = |code| ErrorClass::from_status(StatusCode::from_u16(code).unwrap());
+        assert_eq!(class(429), ErrorClass::RateLimited);
+        assert_eq!(class(503), ErrorClass::Overloaded);
+        assert_eq!(class(529), ErrorClass::Overloaded);
+        assert_eq!(class(504), ErrorClass::Timeout);
+        assert_eq!(class(408), ErrorClass::Timeout);
+        assert_eq!(class(500), ErrorClass::ServerError);
+        assert_eq!(class(404), ErrorClass::ClientError);
+        assert_eq!(class(302), ErrorClass::Other);
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_cap() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(100))
+            .multiplier(3.0)
+            .max_backoff(Duration::from_secs(1));
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(300));
+        assert_eq!(policy.backoff(3), Duration::from_millis(900));
+        assert_eq!(policy.backoff(4), Duration::from_secs(1));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let backoff = Duration::from_millis(1000);
+        for _ in 0..100 {
+            let full = RetryPolicy::new().jitter(Jitter::Full).jittered(backoff);
+            assert!(full < backoff);
+            let equal = RetryPolicy::new().jitter(Jitter::Equal).jittered(backoff);
+            assert!(equal >= backoff / 2 && equal < backoff);
+        }
+        assert_eq!(
+            RetryPolicy::new().jitter(Jitter::None).jittered(backoff),
+            backoff
+        );
+    }
+
+    #[test]
+    fn rules_limit_attempts_per_class() {
+        let policy = fast_policy()
+            .max_attempts(4)
+            .rule(ErrorClass::RateLimited, 2);
+        assert_eq!(policy.attempts_for(ErrorClass::ServerError), 4);
+        assert_eq!(policy.attempts_for(ErrorClass::RateLimited), 2);
+        assert_eq!(policy.attempts_for(ErrorClass::ClientError), 1);
+
+        assert!(policy
+            .next_delay(1, ErrorClass::RateLimited, None)
+            .is_some());
+        assert!(policy
+            .next_delay(2, ErrorClass::RateLimited, None)
+            .is_none());
+        assert!(policy
+            .next_delay(1, ErrorClass::ClientError, None)
+            .is_none());
+        assert!(RetryPolicy::none()
+            .next_delay(1, ErrorClass::ServerError, None)
+            .is_none());
+    }
+
+    #[test]
+    fn server_hints_replace_the_backoff() {
+        let policy = fast_policy().max_server_delay(Duration::from_secs(60));
+        assert_eq!(
+            policy.next_delay(1, ErrorClass::RateLimited, Some(Duration::from_secs(5))),
+            Some((Duration::from_secs(5), true))
+        );
+        // Hints longer than the limit give up instead of waiting
+        assert_eq!(
+            policy.next_delay(1, ErrorClass::RateLimited, Some(Duration::from_secs(90))),
+            None
+        );
+        assert_eq!(
+            policy.respect_server_delay(false).next_delay(
+                1,
+                ErrorClass::RateLimited,
+                Some(Duration::from_secs(90))
+            ),
+            Some((Duration::from_millis(1), false))
+        );
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static(" 7 "));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        // A date in the past means retry now
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn parses_retry_info() {
+        let body = r#"{
+            "error": {
+                "code": 429,
+                "details": [
+                    {"@type": "type.googleapis.com/google.rpc.QuotaFailure"},
+                    {"@type": "type.googleapis.com/google.rpc.RetryInfo", "retryDelay": "36.5s"}
+                ]
+            }
+        }"#;
+        assert_eq!(retry_info_delay(body), Some(Duration::from_millis(36_500)));
+        assert_eq!(retry_info_delay(r#"{"error":{"details":[]}}"#), None);
+        assert_eq!(retry_info_delay("not json"), None);
+    }
+
+    #[tokio::test]
+    async fn retries_until_success_and_reports_hooks() {
+        let mut server = mockito::Server::new_async().await;
+        let unavailable = server
+            .mock("GET", "/")
+            .with_status(503)
+            .with_header("retry-after", "0")
+            .expect(2)
+            .create_async()
+            .await;
+        let ok = server
+            .mock("GET", "/")
+            .with_body("done")
+            .create_async()
+            .await;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let stack = Stack {
+            retry_policy: fast_policy()
+                .on_retry(move |event| recorded.lock().unwrap().push(event.clone())),
+            ..Default::default()
+        };
+        let client = reqwest::Client::new();
+        let request = Request::new(client.get(server.url()).build().unwrap());
+        let response = stack.send(&client, request).await.unwrap();
+
+        assert_eq!(response.text().await.unwrap(), "done");
+        unavailable.assert_async().await;
+        ok.assert_async().await;
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].attempt, 1);
+        assert_eq!(events[1].attempt, 2);
+        assert_eq!(events[0].class, ErrorClass::Overloaded);
+        assert_eq!(events[0].status, Some(503));
+        assert!(events[0].server_hint);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_client_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/")
+            .with_status(400)
+            .with_body("bad request")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let gave_up = Arc::new(Mutex::new(None));
+        let recorded = gave_up.clone();
+        let stack = Stack {
+            retry_policy: fast_policy()
+                .on_give_up(move |event| *recorded.lock().unwrap() = Some(event.clone())),
+            ..Default::default()
+        };
+        let client = reqwest::Client::new();
+        let request = Request::new(client.get(server.url()).build().unwrap());
+        let err = stack.send(&client, request).await.unwrap_err();
+
+        mock.assert_async().await;
+        assert!(matches!(
+            err,
+            HttpError::Status { status, ref body } if status == 400 && body == "bad request"
+        ));
+        let event = gave_up.lock().unwrap().clone().unwrap();
+        assert_eq!(event.class, ErrorClass::ClientError);
+        assert_eq!(event.delay, Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn default_stack_makes_a_single_attempt() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = reqwest::Client::new();
+        let request = Request::new(client.get(server.url()).build().unwrap());
+        let err = Stack::default().send(&client, request).await.unwrap_err();
+
+        mock.assert_async().await;
+        assert!(matches!(err, HttpError::Status { status, .. } if status == 503));
+    }
+}
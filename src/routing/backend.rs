@@ -0,0 +1,409 @@
+use crate::circuit::CircuitKey;
+use crate::conversation::session::{gemini_contents, generation_config, ollama_options};
+use crate::conversation::types::{ConversationSettings, Message, Role};
+use crate::gemini::client::{GeminiClient, GeminiClientError};
+use crate::gemini::types::{
+    FunctionCall, FunctionDeclaration, GenerateContentRequest, InlineData, Part, Tool,
+};
+use crate::ollama::client::{OllamaClient, OllamaClientError};
+use crate::ollama::types::{ChatMessage, ChatRequest, Tool as OllamaTool};
+use crate::retry::ErrorClass;
+use crate::utils::now;
+use futures_util::future::BoxFuture;
+use serde_json::json;
+use std::fmt;
+use std::sync::Arc;
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum RouterError {
+    /// Error from the Gemini client
+    GeminiError(GeminiClientError),
+    /// Error from the Ollama client
+    OllamaError(OllamaClientError),
+    /// The provider blocked the prompt or the reply, with the reason it gave
+    SafetyBlock(String),
+    /// The model returned neither text nor tool calls
+    EmptyResponse,
+    /// A rule names an endpoint that was not added to the router
+    UnknownEndpoint(String),
+    /// No endpoint of the chain has the capabilities the request requires
+    NoEndpoint,
+    /// Every endpoint of the chain failed with an error that allows fallback
+    Exhausted(Vec<FailedAttempt>),
+}
+
+impl fmt::Display for RouterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouterError::GeminiError(err) => write!(f, "Gemini error: {}", err),
+            RouterError::OllamaError(err) => write!(f, "Ollama error: {}", err),
+            RouterError::SafetyBlock(reason) => write!(f, "Blocked by the provider: {}", reason),
+            RouterError::EmptyResponse => write!(f, "The model returned no response"),
+            RouterError::UnknownEndpoint(name) => write!(f, "Unknown endpoint: {}", name),
+            RouterError::NoEndpoint => {
+                write!(f, "No endpoint has the capabilities the request requires")
+            }
+            RouterError::Exhausted(attempts) => {
+                write!(f, "All {} endpoints failed", attempts.len())?;
+                if let Some(last) = attempts.last() {
+                    write!(f, "; last error from {}: {}", last.endpoint, last.error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for RouterError {}
+
+impl From<GeminiClientError> for RouterError {
+    fn from(err: GeminiClientError) -> Self {
+        RouterError::GeminiError(err)
+    }
+}
+
+impl From<OllamaClientError> for RouterError {
+    fn from(err: OllamaClientError) -> Self {
+        RouterError::OllamaError(err)
+    }
+}
+
+impl RouterError {
+    /// Classifies the error to decide whether the next endpoint is tried
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            RouterError::GeminiError(err) => FailureKind::Error(err.class()),
+            RouterError::OllamaError(err) => FailureKind::Error(err.class()),
+            RouterError::SafetyBlock(_) => FailureKind::SafetyBlock,
+            RouterError::EmptyResponse => FailureKind::EmptyResponse,
+            RouterError::UnknownEndpoint(_)
+            | RouterError::NoEndpoint
+            | RouterError::Exhausted(_) => FailureKind::Error(ErrorClass::Other),
+        }
+    }
+}
+
+/// Kind of backend failure, used to decide whether the next endpoint is tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureKind {
+    /// A request failure, classified like a failed attempt of the retry policy
+    Error(ErrorClass),
+    /// The provider blocked the prompt or the reply for safety reasons
+    SafetyBlock,
+    /// The model returned neither text nor tool calls
+    EmptyResponse,
+    /// The endpoint was skipped because its circuit is open
+    CircuitOpen,
+}
+
+/// An endpoint that failed while routing a request
+#[derive(Debug, Clone)]
+pub struct FailedAttempt {
+    /// The name of the endpoint
+    pub endpoint: String,
+    /// The class of the failure
+    pub kind: FailureKind,
+    /// A description of the failure
+    pub error: String,
+}
+
+/// A feature a request needs from the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Image inputs
+    Vision,
+    /// Function calling
+    Tools,
+    /// JSON output mode
+    Json,
+}
+
+/// A provider-neutral request that a `Router` can send to any endpoint
+#[derive(Debug, Clone, Default)]
+pub struct RouteRequest {
+    /// The messages, oldest first
+    pub messages: Vec<Message>,
+    /// Generation settings
+    pub settings: ConversationSettings,
+    /// Images attached to the last user message
+    pub images: Vec<InlineData>,
+    /// Tools the model may call
+    pub tools: Vec<FunctionDeclaration>,
+    /// Whether the reply must be a JSON document
+    pub json: bool,
+    /// Application-defined tags matched by routing rules (e.g., "batch")
+    pub tags: Vec<String>,
+}
+
+impl RouteRequest {
+    /// Creates a request with a single user prompt
+    pub fn new(prompt: &str) -> Self {
+        Self::from_messages(vec![Message {
+            role: Role::User,
+            content: prompt.to_string(),
+            created_at: now(),
+        }])
+    }
+
+    /// Creates a request from a list of messages
+    pub fn from_messages(messages: Vec<Message>) -> Self {
+        RouteRequest {
+            messages,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the generation settings
+    pub fn settings(mut self, settings: ConversationSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Attaches an image to the last user message
+    ///
+    /// # Arguments
+    ///
+    /// * `mime_type` - The MIME type of the image (e.g., "image/png")
+    /// * `data` - The base64-encoded image
+    pub fn image(mut self, mime_type: &str, data: &str) -> Self {
+        self.images.push(InlineData {
+            mime_type: mime_type.to_string(),
+            data: data.to_string(),
+        });
+        self
+    }
+
+    /// Sets the tools the model may call
+    pub fn tools(mut self, tools: Vec<FunctionDeclaration>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Requires the reply to be a JSON document
+    pub fn json(mut self) -> Self {
+        self.json = true;
+        self
+    }
+
+    /// Adds a tag for routing rules
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    /// Returns the capabilities an endpoint needs to serve the request
+    pub fn required_capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = Vec::new();
+        if !self.images.is_empty() {
+            capabilities.push(Capability::Vision);
+        }
+        if !self.tools.is_empty() {
+            capabilities.push(Capability::Tools);
+        }
+        if self.json {
+            capabilities.push(Capability::Json);
+        }
+        capabilities
+    }
+
+    /// Returns `true` if the request has the tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|candidate| candidate == tag)
+    }
+}
+
+/// The reply of an endpoint
+#[derive(Debug, Clone)]
+pub struct Completion {
+    /// The text of the reply, empty if the model only called tools
+    pub text: String,
+    /// The tool calls requested by the model, in order
+    pub function_calls: Vec<FunctionCall>,
+    /// The model that generated the reply
+    pub model: String,
+    /// Tokens in the prompt, if the provider reported them
+    pub prompt_tokens: Option<u32>,
+    /// Tokens in the reply, if the provider reported them
+    pub completion_tokens: Option<u32>,
+}
+
+/// A model that can serve a `RouteRequest`
+///
+/// Implemented for `GeminiClient` and `OllamaBackend`.
+pub trait Backend: Send + Sync {
+    /// The base URL and model requests are sent to, used as the circuit breaker key
+    fn circuit_key(&self) -> CircuitKey;
+
+    /// Generates the reply to the request
+    fn complete<'a>(
+        &'a self,
+        request: &'a RouteRequest,
+    ) -> BoxFuture<'a, Result<Completion, RouterError>>;
+}
+
+impl<T: Backend + ?Sized> Backend for Arc<T> {
+    fn circuit_key(&self) -> CircuitKey {
+        (**self).circuit_key()
+    }
+
+    fn complete<'a>(
+        &'a self,
+        request: &'a RouteRequest,
+    ) -> BoxFuture<'a, Result<Completion, RouterError>> {
+        (**self).complete(request)
+    }
+}
+
+impl Backend for GeminiClient {
+    fn circuit_key(&self) -> CircuitKey {
+        CircuitKey::new(self.base_url(), self.model_name())
+    }
+
+    fn complete<'a>(
+        &'a self,
+        request: &'a RouteRequest,
+    ) -> BoxFuture<'a, Result<Completion, RouterError>> {
+        Box::pin(async move {
+            let mut contents = gemini_contents(&request.messages);
+            if let Some(content) = contents.iter_mut().rev().find(|c| c.role == "user") {
+                content
+                    .parts
+                    .extend(request.images.iter().map(|image| Part {
+                        inline_data: Some(image.clone()),
+                        ..Default::default()
+                    }));
+            }
+            let mut config = generation_config(&request.settings);
+            if request.json {
+                config.response_mime_type = Some("application/json".to_string());
+            }
+            let tools = (!request.tools.is_empty()).then(|| {
+                vec![Tool {
+                    function_declarations: request.tools.clone(),
+                }]
+            });
+            let generate_request = GenerateContentRequest {
+                contents,
+                generation_config: Some(config),
+                safety_settings: None,
+                tools,
+                template_version: None,
+            };
+            let response = self.generate_content_with_request(generate_request).await?;
+            if let Some(reason) = response.block_reason() {
+                return Err(RouterError::SafetyBlock(reason.to_string()));
+            }
+            let function_calls = response.function_calls();
+            let text: String = response
+                .candidates
+                .first()
+                .map(|candidate| {
+                    candidate
+                        .content
+                        .parts
+                        .iter()
+                        .filter_map(|part| part.text.as_deref())
+                        .collect()
+                })
+                .unwrap_or_default();
+            if text.is_empty() && function_calls.is_empty() {
+                return Err(RouterError::EmptyResponse);
+            }
+            let usage = response.usage_metadata.as_ref();
+            Ok(Completion {
+                text,
+                function_calls,
+                model: response
+                    .model_version
+                    .clone()
+                    .unwrap_or_else(|| self.model_name().to_string()),
+                prompt_tokens: usage.map(|usage| usage.prompt_token_count.max(0) as u32),
+                completion_tokens: usage.map(|usage| usage.candidates_token_count.max(0) as u32),
+            })
+        })
+    }
+}
+
+/// An `OllamaClient` with the model to use, for use with `Router`
+#[derive(Debug, Clone)]
+pub struct OllamaBackend {
+    client: Arc<OllamaClient>,
+    model: String,
+}
+
+impl OllamaBackend {
+    /// Creates a backend
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The Ollama client, shared with other backends of the same host
+    /// * `model` - The model to use (e.g., "llama3.2:1b")
+    pub fn new(client: Arc<OllamaClient>, model: &str) -> Self {
+        OllamaBackend {
+            client,
+            model: model.to_string(),
+        }
+    }
+
+    /// Returns the model requests are sent to
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Returns the client
+    pub fn client(&self) -> &OllamaClient {
+        &self.client
+    }
+}
+
+impl Backend for OllamaBackend {
+    fn circuit_key(&self) -> CircuitKey {
+        CircuitKey::new(self.client.base_url(), &self.model)
+    }
+
+    fn complete<'a>(
+        &'a self,
+        request: &'a RouteRequest,
+    ) -> BoxFuture<'a, Result<Completion, RouterError>> {
+        Box::pin(async move {
+            let mut messages: Vec<ChatMessage> = request
+                .messages
+                .iter()
+                .map(|message| ChatMessage::new(message.role.as_str(), &message.content))
+                .collect();
+            if !request.images.is_empty() {
+                if let Some(message) = messages.iter_mut().rev().find(|m| m.role == "user") {
+                    message.images = Some(
+                        request
+                            .images
+                            .iter()
+                            .map(|image| image.data.clone())
+                            .collect(),
+                    );
+                }
+            }
+            let tools: Vec<OllamaTool> = request.tools.iter().map(OllamaTool::from).collect();
+            let chat_request = ChatRequest {
+                model: self.model.clone(),
+                messages,
+                tools: (!tools.is_empty()).then_some(tools),
+                format: request.json.then(|| json!("json")),
+                stream: None,
+                options: ollama_options(&request.settings),
+            };
+            let response = self.client.chat(chat_request).await?;
+            let function_calls = response.function_calls();
+            if response.message.content.is_empty() && function_calls.is_empty() {
+                return Err(RouterError::EmptyResponse);
+            }
+            Ok(Completion {
+                text: response.message.content,
+                function_calls,
+                model: response.model,
+                prompt_tokens: response.prompt_eval_count,
+                completion_tokens: response.eval_count,
+            })
+        })
+    }
+}
@@ -0,0 +1,8 @@
+pub mod backend;
+pub mod router;
+
+pub use backend::{
+    Backend, Capability, Completion, FailedAttempt, FailureKind, OllamaBackend, RouteRequest,
+    RouterError,
+};
+pub use router::{Endpoint, Routed, Router, Rule};
@@ -0,0 +1,538 @@
+use crate::circuit::CircuitBreaker;
+use crate::retry::ErrorClass;
+use crate::routing::backend::{
+    Backend, Capability, Completion, FailedAttempt, FailureKind, RouteRequest, RouterError,
+};
+use crate::tokens::{Heuristic, TokenCounter};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// A named backend with the capabilities of its model
+#[derive(Clone)]
+pub struct Endpoint {
+    name: String,
+    backend: Arc<dyn Backend>,
+    capabilities: HashSet<Capability>,
+}
+
+impl fmt::Debug for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Endpoint")
+            .field("name", &self.name)
+            .field("capabilities", &self.capabilities)
+            .finish()
+    }
+}
+
+impl Endpoint {
+    /// Creates an endpoint without capabilities, serving text-only requests
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A unique name, used by rules and reported in `Routed::endpoint`
+    /// * `backend` - The model, e.g. a `GeminiClient` or an `OllamaBackend`
+    pub fn new(name: &str, backend: impl Backend + 'static) -> Self {
+        Endpoint {
+            name: name.to_string(),
+            backend: Arc::new(backend),
+            capabilities: HashSet::new(),
+        }
+    }
+
+    /// Sets the capabilities of the endpoint's model
+    pub fn capabilities(mut self, capabilities: &[Capability]) -> Self {
+        self.capabilities = capabilities.iter().copied().collect();
+        self
+    }
+
+    /// Returns the name of the endpoint
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns `true` if the endpoint has every capability the request requires
+    pub fn supports(&self, request: &RouteRequest) -> bool {
+        request
+            .required_capabilities()
+            .iter()
+            .all(|capability| self.capabilities.contains(capability))
+    }
+}
+
+/// A rule sending matching requests to its own chain of endpoints
+///
+/// A rule without conditions matches every request. All conditions must hold for a
+/// request to match.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    chain: Vec<String>,
+    min_prompt_tokens: Option<u32>,
+    max_prompt_tokens: Option<u32>,
+    capabilities: Vec<Capability>,
+    tags: Vec<String>,
+}
+
+impl Rule {
+    /// Creates a rule
+    ///
+    /// # Arguments
+    ///
+    /// * `chain` - The names of the endpoints to try, in order
+    pub fn new(chain: &[&str]) -> Self {
+        Rule {
+            chain: chain.iter().map(|name| name.to_string()).collect(),
+            min_prompt_tokens: None,
+            max_prompt_tokens: None,
+            capabilities: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Matches prompts of at least `tokens` estimated tokens
+    pub fn min_prompt_tokens(mut self, tokens: u32) -> Self {
+        self.min_prompt_tokens = Some(tokens);
+        self
+    }
+
+    /// Matches prompts of at most `tokens` estimated tokens
+    pub fn max_prompt_tokens(mut self, tokens: u32) -> Self {
+        self.max_prompt_tokens = Some(tokens);
+        self
+    }
+
+    /// Matches requests that require the capability
+    pub fn requires(mut self, capability: Capability) -> Self {
+        self.capabilities.push(capability);
+        self
+    }
+
+    /// Matches requests with the tag
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    /// Returns `true` if the request matches every condition of the rule
+    fn matches(&self, request: &RouteRequest, prompt_tokens: u32) -> bool {
+        let required = request.required_capabilities();
+        self.min_prompt_tokens
+            .is_none_or(|min| prompt_tokens >= min)
+            && self
+                .max_prompt_tokens
+                .is_none_or(|max| prompt_tokens <= max)
+            && self
+                .capabilities
+                .iter()
+                .all(|capability| required.contains(capability))
+            && self.tags.iter().all(|tag| request.has_tag(tag))
+    }
+}
+
+/// A reply and the endpoint that served it
+#[derive(Debug, Clone)]
+pub struct Routed {
+    /// The reply
+    pub completion: Completion,
+    /// The name of the endpoint that served the request
+    pub endpoint: String,
+    /// The endpoints that failed before it, in order
+    pub fallbacks: Vec<FailedAttempt>,
+}
+
+/// Sends requests to a chain of endpoints, falling back to the next one on failure
+///
+/// The chain is chosen by the first matching rule, or is every endpoint in the order
+/// they were added. Endpoints lacking a capability the request requires are skipped.
+/// By default, rate limiting, overload, server errors, timeouts, connection failures
+/// and safety blocks fall back to the next endpoint; other failures are returned at once.
+/// With a circuit breaker, endpoints whose circuit is open are skipped without a request.
+#[derive(Clone)]
+pub struct Router {
+    endpoints: Vec<Endpoint>,
+    rules: Vec<Rule>,
+    fallback_on: HashSet<FailureKind>,
+    counter: Arc<dyn TokenCounter>,
+    breaker: Option<CircuitBreaker>,
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field("endpoints", &self.endpoints)
+            .field("rules", &self.rules)
+            .field("fallback_on", &self.fallback_on)
+            .field("counter", &self.counter)
+            .field("breaker", &self.breaker)
+            .finish()
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Router {
+            endpoints: Vec::new(),
+            rules: Vec::new(),
+            fallback_on: [
+                FailureKind::Error(ErrorClass::RateLimited),
+                FailureKind::Error(ErrorClass::Overloaded),
+                FailureKind::Error(ErrorClass::ServerError),
+                FailureKind::Error(ErrorClass::Timeout),
+                FailureKind::Error(ErrorClass::Connection),
+                FailureKind::SafetyBlock,
+            ]
+            .into_iter()
+            .collect(),
+            counter: Arc::new(Heuristic::default()),
+            breaker: None,
+        }
+    }
+}
+
+impl Router {
+    /// Creates a router without endpoints
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an endpoint to the end of the default chain
+    pub fn endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.endpoints.push(endpoint);
+        self
+    }
+
+    /// Adds a rule, checked after the rules added before it
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Replaces the kinds of failure that fall back to the next endpoint
+    pub fn fallback_on(mut self, kinds: &[FailureKind]) -> Self {
+        self.fallback_on = kinds.iter().copied().collect();
+        self
+    }
+
+    /// Sets the counter used to estimate prompt length for rules
+    pub fn token_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// Sets the circuit breaker consulted before each endpoint and told of each outcome
+    ///
+    /// The breaker can be shared with other routers to pool what they learn about the
+    /// endpoints.
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    /// Returns the endpoint with the given name
+    pub fn get(&self, name: &str) -> Option<&Endpoint> {
+        self.endpoints.iter().find(|endpoint| endpoint.name == name)
+    }
+
+    /// Returns the endpoints that would be tried for the request, in order
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the chain, `RouterError::UnknownEndpoint` if the matching rule
+    /// names an endpoint that was not added, or `RouterError::NoEndpoint` if no endpoint of
+    /// the chain has the required capabilities
+    pub fn chain(&self, request: &RouteRequest) -> Result<Vec<&Endpoint>, RouterError> {
+        let prompt_tokens = self.counter.count_messages(&request.messages);
+        let chain = match self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(request, prompt_tokens))
+        {
+            Some(rule) => rule
+                .chain
+                .iter()
+                .map(|name| {
+                    self.get(name)
+                        .ok_or_else(|| RouterError::UnknownEndpoint(name.clone()))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => self.endpoints.iter().collect(),
+        };
+        let chain: Vec<&Endpoint> = chain
+            .into_iter()
+            .filter(|endpoint| {
+                let supported = endpoint.supports(request);
+                if !supported {
+                    debug!("Skipping endpoint {}: missing capabilities", endpoint.name);
+                }
+                supported
+            })
+            .collect();
+        if chain.is_empty() {
+            return Err(RouterError::NoEndpoint);
+        }
+        Ok(chain)
+    }
+
+    /// Sends the request along its chain until an endpoint serves it
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the reply with the endpoint that served it, the first error
+    /// that does not allow fallback, or `RouterError::Exhausted` if every endpoint failed
+    /// or was skipped
+    pub async fn route(&self, request: &RouteRequest) -> Result<Routed, RouterError> {
+        let mut fallbacks = Vec::new();
+        for endpoint in self.chain(request)? {
+            let permit = match &self.breaker {
+                Some(breaker) => match breaker.acquire(&endpoint.backend.circuit_key()) {
+                    Ok(permit) => Some(permit),
+                    Err(open) => {
+                        debug!("Skipping endpoint {}: {}", endpoint.name, open);
+                        fallbacks.push(FailedAttempt {
+                            endpoint: endpoint.name.clone(),
+                            kind: FailureKind::CircuitOpen,
+                            error: open.to_string(),
+                        });
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let result = endpoint.backend.complete(request).await;
+            if let Some(permit) = permit {
+                match result.as_ref().map_err(RouterError::kind) {
+                    Err(FailureKind::Error(class)) => permit.failure(class),
+                    _ => permit.success(),
+                }
+            }
+            match result {
+                Ok(completion) => {
+                    info!(
+                        "Request served by endpoint {} after {} fallbacks",
+                        endpoint.name,
+                        fallbacks.len()
+                    );
+                    return Ok(Routed {
+                        completion,
+                        endpoint: endpoint.name.clone(),
+                        fallbacks,
+                    });
+                }
+                Err(err) => {
+                    let kind = err.kind();
+                    if !self.fallback_on.contains(&kind) {
+                        warn!("Endpoint {} failed ({:?}): {}", endpoint.name, kind, err);
+                        return Err(err);
+                    }
+                    warn!(
+                        "Endpoint {} failed ({:?}), falling back: {}",
+                        endpoint.name, kind, err
+                    );
+                    fallbacks.push(FailedAttempt {
+                        endpoint: endpoint.name.clone(),
+                        kind,
+                        error: err.to_string(),
+                    });
+                }
+            }
+        }
+        Err(RouterError::Exhausted(fallbacks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini::types::FunctionDeclaration;
+    use crate::ollama::client::OllamaClientError;
+    use futures_util::future::BoxFuture;
+    use reqwest::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A backend that answers every request the same way and counts its calls
+    struct Fake {
+        model: &'static str,
+        status: Option<u16>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Fake {
+        fn ok(model: &'static str) -> Self {
+            Fake {
+                model,
+                status: None,
+                calls: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        fn failing(model: &'static str, status: u16) -> Self {
+            Fake {
+                status: Some(status),
+                ..Fake::ok(model)
+            }
+        }
+    }
+
+    impl Backend for Fake {
+        fn circuit_key(&self) -> crate::circuit::CircuitKey {
+            crate::circuit::CircuitKey::new("http://fake", self.model)
+        }
+
+        fn complete<'a>(
+            &'a self,
+            _request: &'a RouteRequest,
+        ) -> BoxFuture<'a, Result<Completion, RouterError>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                match self.status {
+                    Some(status) => Err(RouterError::OllamaError(OllamaClientError::StatusError(
+                        StatusCode::from_u16(status).unwrap(),
+                        "failed".to_string(),
+                    ))),
+                    None => Ok(Completion {
+                        text: format!("reply from {}", self.model),
+                        function_calls: Vec::new(),
+                        model: self.model.to_string(),
+                        prompt_tokens: None,
+                        completion_tokens: None,
+                    }),
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn falls_back_on_retryable_failures() {
+        let router = Router::new()
+            .endpoint(Endpoint::new("primary", Fake::failing("a", 503)))
+            .endpoint(Endpoint::new("secondary", Fake::failing("b", 500)))
+            .endpoint(Endpoint::new("tertiary", Fake::ok("c")));
+
+        let routed = router.route(&RouteRequest::new("Hello")).await.unwrap();
+
+        assert_eq!(routed.endpoint, "tertiary");
+        assert_eq!(routed.completion.text, "reply from c");
+        let fallbacks: Vec<_> = routed
+            .fallbacks
+            .iter()
+            .map(|attempt| (attempt.endpoint.as_str(), attempt.kind))
+            .collect();
+        assert_eq!(
+            fallbacks,
+            vec![
+                ("primary", FailureKind::Error(ErrorClass::Overloaded)),
+                ("secondary", FailureKind::Error(ErrorClass::ServerError)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn returns_other_failures_at_once() {
+        let last = Fake::ok("b");
+        let calls = last.calls.clone();
+        let router = Router::new()
+            .endpoint(Endpoint::new("primary", Fake::failing("a", 400)))
+            .endpoint(Endpoint::new("secondary", last));
+
+        let err = router.route(&RouteRequest::new("Hello")).await.unwrap_err();
+
+        assert_eq!(err.kind(), FailureKind::Error(ErrorClass::ClientError));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        // Unless the router is told to fall back on them
+        let router = router.fallback_on(&[FailureKind::Error(ErrorClass::ClientError)]);
+        let routed = router.route(&RouteRequest::new("Hello")).await.unwrap();
+        assert_eq!(routed.endpoint, "secondary");
+    }
+
+    #[tokio::test]
+    async fn reports_exhausted_chains() {
+        let router = Router::new()
+            .endpoint(Endpoint::new("primary", Fake::failing("a", 429)))
+            .endpoint(Endpoint::new("secondary", Fake::failing("b", 504)));
+
+        match router.route(&RouteRequest::new("Hello")).await {
+            Err(RouterError::Exhausted(attempts)) => {
+                assert_eq!(attempts.len(), 2);
+                assert_eq!(attempts[1].kind, FailureKind::Error(ErrorClass::Timeout));
+            }
+            other => panic!(
+                "expected an exhausted chain, got {:?}",
+                other.map(|r| r.endpoint)
+            ),
+        }
+    }
+
+    #[tokio::test]
+    async fn rules_and_capabilities_choose_the_chain() {
+        let router = Router::new()
+            .endpoint(Endpoint::new("local", Fake::ok("small")))
+            .endpoint(
+                Endpoint::new("cloud", Fake::ok("large"))
+                    .capabilities(&[Capability::Vision, Capability::Json]),
+            )
+            .rule(Rule::new(&["cloud", "local"]).tag("batch"))
+            .rule(Rule::new(&["local"]).max_prompt_tokens(1000));
+
+        let names = |request: &RouteRequest| -> Vec<String> {
+            router
+                .chain(request)
+                .unwrap()
+                .iter()
+                .map(|endpoint| endpoint.name().to_string())
+                .collect()
+        };
+        assert_eq!(names(&RouteRequest::new("Hi")), vec!["local"]);
+        assert_eq!(
+            names(&RouteRequest::new("Hi").tag("batch")),
+            vec!["cloud", "local"]
+        );
+        // Long prompts match no rule and use every endpoint
+        assert_eq!(
+            names(&RouteRequest::new(&"word ".repeat(2000)).json()),
+            vec!["cloud"]
+        );
+        assert_eq!(
+            names(&RouteRequest::new(&"word ".repeat(2000))),
+            vec!["local", "cloud"]
+        );
+
+        let with_tools = RouteRequest::new("Hi").tools(vec![FunctionDeclaration {
+            name: "get_weather".to_string(),
+            description: "Gets the weather".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+        }]);
+        assert!(matches!(
+            router.chain(&with_tools),
+            Err(RouterError::NoEndpoint)
+        ));
+
+        let router = Router::new().rule(Rule::new(&["missing"]));
+        assert!(matches!(
+            router.chain(&RouteRequest::new("Hi")),
+            Err(RouterError::UnknownEndpoint(name)) if name == "missing"
+        ));
+    }
+
+    #[tokio::test]
+    async fn skips_endpoints_with_open_circuits() {
+        let primary = Fake::failing("a", 503);
+        let primary_calls = primary.calls.clone();
+        let router = Router::new()
+            .endpoint(Endpoint::new("primary", primary))
+            .endpoint(Endpoint::new("secondary", Fake::ok("b")))
+            .circuit_breaker(CircuitBreaker::new().failure_threshold(1));
+
+        router.route(&RouteRequest::new("Hello")).await.unwrap();
+        let routed = router.route(&RouteRequest::new("Hello")).await.unwrap();
+
+        assert_eq!(primary_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(routed.endpoint, "secondary");
+        assert_eq!(routed.fallbacks[0].kind, FailureKind::CircuitOpen);
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+
+/// A credential, such as an API key, that is redacted in `Debug` and `Display` output
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Wraps a credential
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    /// Returns the credential, e.g. to put it in a request header
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns `true` if the credential is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Secret(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting_hides_the_value() {
+        let secret = Secret::new("sk-123");
+        assert_eq!(format!("{:?}", secret), "<redacted>");
+        assert_eq!(format!("{}", secret), "<redacted>");
+        assert_eq!(
+            format!("{:?}", Some(Secret::from("sk-123"))),
+            "Some(<redacted>)"
+        );
+        assert_eq!(secret.expose(), "sk-123");
+        assert!(!secret.is_empty());
+        assert!(Secret::default().is_empty());
+    }
+}
@@ -0,0 +1,324 @@
+use crate::logging;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, trace, Instrument};
+
+/// A single server-sent event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    /// The event type from the `event:` field, if any
+    pub event: Option<String>,
+    /// The event data, with multiple `data:` lines joined by newlines
+    pub data: String,
+    /// The event id from the `id:` field, if any
+    pub id: Option<String>,
+}
+
+impl SseEvent {
+    /// Returns `true` for the `[DONE]` sentinel used by OpenAI-style APIs
+    pub fn is_done(&self) -> bool {
+        self.data.trim() == "[DONE]"
+    }
+}
+
+/// Incremental decoder for `text/event-stream` bodies
+///
+/// Handles events, lines and UTF-8 sequences split across network chunks.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+    id: Option<String>,
+}
+
+impl SseDecoder {
+    /// Creates a new, empty decoder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a chunk of bytes and returns the events it completed
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(position) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=position).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some(event) = self.process_line(line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Flushes any event left when the body ends without a trailing blank line
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        if !self.buffer.is_empty() {
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+            if let Some(event) = self.process_line(line.trim_end_matches('\r')) {
+                return Some(event);
+            }
+        }
+        self.dispatch()
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            // Comment / keep-alive
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            "id" => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        if self.data.is_empty() {
+            self.event = None;
+            return None;
+        }
+        Some(SseEvent {
+            event: self.event.take(),
+            data: std::mem::take(&mut self.data).join("\n"),
+            id: self.id.clone(),
+        })
+    }
+}
+
+/// Reads an SSE response body on a background task and maps each event through `parse`
+///
+/// `parse` returns `None` to skip an event (e.g. keep-alives or `[DONE]`).
+pub(crate) fn spawn_sse_stream<T, E, F>(
+    response: reqwest::Response,
+    parse: F,
+) -> ReceiverStream<Result<T, E>>
+where
+    T: Send + 'static,
+    E: From<reqwest::Error> + Send + 'static,
+    F: FnMut(SseEvent) -> Option<Result<T, E>> + Send + 'static,
+{
+    spawn_sse_stream_from(response.bytes_stream(), None, parse)
+}
+
+/// How long a stream may go without data, and the error reported when it does
+pub(crate) struct IdleTimeout<E> {
+    pub duration: Duration,
+    pub error: fn(Duration) -> E,
+}
+
+/// Same as `spawn_sse_stream`, for a body that is already a stream of bytes
+///
+/// With an `idle_timeout`, the stream ends with its error when no chunk arrives in time.
+pub(crate) fn spawn_sse_stream_from<S, T, E, F>(
+    mut stream: S,
+    idle_timeout: Option<IdleTimeout<E>>,
+    mut parse: F,
+) -> ReceiverStream<Result<T, E>>
+where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + Unpin + 'static,
+    T: Send + 'static,
+    E: From<reqwest::Error> + Send + 'static,
+    F: FnMut(SseEvent) -> Option<Result<T, E>> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(100);
+
+    // Keep the caller's span so events from the task stay correlated with the call
+    let span = tracing::Span::current();
+    tokio::spawn(
+        async move {
+            let mut decoder = SseDecoder::new();
+            loop {
+                let chunk = match &idle_timeout {
+                    Some(idle) => match tokio::time::timeout(idle.duration, stream.next()).await {
+                        Ok(chunk) => chunk,
+                        Err(_) => {
+                            error!("Stream idle for {:?}, giving up", idle.duration);
+                            let _ = tx.send(Err((idle.error)(idle.duration))).await;
+                            return;
+                        }
+                    },
+                    None => stream.next().await,
+                };
+                match chunk {
+                    Some(Ok(bytes)) => {
+                        for event in decoder.push(&bytes) {
+                            trace!(
+                                "Received SSE event {:?}: {}",
+                                event.event,
+                                logging::payload(&event.data)
+                            );
+                            if let Some(item) = parse(event) {
+                                if tx.send(item).await.is_err() {
+                                    // Receiver dropped, stop reading
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                    Some(Err(e)) => {
+                        error!("Stream error: {}", e);
+                        let _ = tx.send(Err(E::from(e))).await;
+                        return;
+                    }
+                    None => break,
+                }
+            }
+            if let Some(event) = decoder.finish() {
+                if let Some(item) = parse(event) {
+                    let _ = tx.send(item).await;
+                }
+            }
+        }
+        .instrument(span),
+    );
+
+    ReceiverStream::new(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(events: &[SseEvent]) -> Vec<&str> {
+        events.iter().map(|event| event.data.as_str()).collect()
+    }
+
+    #[test]
+    fn decodes_events_split_across_chunks() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(b"event: message\nda").is_empty());
+        assert!(decoder.push(b"ta: {\"a\":").is_empty());
+        let events = decoder.push(b"1}\n\ndata: second\n");
+        assert_eq!(
+            events,
+            vec![SseEvent {
+                event: Some("message".to_string()),
+                data: "{\"a\":1}".to_string(),
+                id: None,
+            }]
+        );
+        assert_eq!(data(&decoder.push(b"\n")), ["second"]);
+    }
+
+    #[test]
+    fn handles_utf8_split_across_chunks() {
+        let text = "data: héllo\n\n".as_bytes();
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(&text[..8]).is_empty());
+        assert_eq!(data(&decoder.push(&text[8..])), ["héllo"]);
+    }
+
+    #[test]
+    fn accepts_crlf_line_endings() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.push(b"id: 7\r\ndata: one\r\n\r\ndata: two\r");
+        assert_eq!(data(&events), ["one"]);
+        assert_eq!(events[0].id.as_deref(), Some("7"));
+        assert_eq!(data(&decoder.push(b"\n\r\n")), ["two"]);
+    }
+
+    #[test]
+    fn joins_multi_line_data() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.push(b"data: first\ndata:second\ndata\n\n");
+        assert_eq!(data(&events), ["first\nsecond\n"]);
+    }
+
+    #[test]
+    fn skips_comments_and_empty_events() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.push(b": keep-alive\n\nevent: ping\n\n:\ndata: real\n\n");
+        assert_eq!(events.len(), 1);
+        // The event type of an event without data does not leak into the next one
+        assert_eq!(events[0].event, None);
+        assert_eq!(events[0].data, "real");
+    }
+
+    #[test]
+    fn recognizes_done() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.push(b"data: {}\n\ndata: [DONE]\n\n");
+        assert!(!events[0].is_done());
+        assert!(events[1].is_done());
+    }
+
+    #[test]
+    fn finish_flushes_the_last_event() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(b"data: a\ndata: b").is_empty());
+        assert_eq!(
+            decoder.finish().map(|event| event.data),
+            Some("a\nb".to_string())
+        );
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[tokio::test]
+    async fn stream_parses_events_and_flushes_at_the_end() {
+        let chunks: Vec<Result<Bytes, reqwest::Error>> = vec![
+            Ok(Bytes::from_static(b"data: 1\n\nda")),
+            Ok(Bytes::from_static(b"ta: [DONE]\n\ndata: 2")),
+        ];
+        let stream = spawn_sse_stream_from(
+            futures_util::stream::iter(chunks),
+            None,
+            |event: SseEvent| (!event.is_done()).then(|| Ok::<_, reqwest::Error>(event.data)),
+        );
+        let items: Vec<String> = stream.map(Result::unwrap).collect().await;
+        assert_eq!(items, ["1", "2"]);
+    }
+
+    #[derive(Debug)]
+    enum StreamError {
+        Idle(Duration),
+        Http,
+    }
+
+    impl From<reqwest::Error> for StreamError {
+        fn from(_: reqwest::Error) -> Self {
+            StreamError::Http
+        }
+    }
+
+    #[tokio::test]
+    async fn stalled_stream_hits_the_idle_timeout() {
+        let chunks: Vec<Result<Bytes, reqwest::Error>> =
+            vec![Ok(Bytes::from_static(b"data: 1\n\n"))];
+        let stalled = futures_util::stream::iter(chunks).chain(futures_util::stream::pending());
+        let mut stream = spawn_sse_stream_from(
+            stalled,
+            Some(IdleTimeout {
+                duration: Duration::from_millis(50),
+                error: StreamError::Idle,
+            }),
+            |event: SseEvent| Some(Ok(event.data)),
+        );
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), "1");
+        match stream.next().await {
+            Some(Err(StreamError::Idle(duration))) => {
+                assert_eq!(duration, Duration::from_millis(50))
+            }
+            other => panic!("expected an idle timeout, got {:?}", other),
+        }
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,195 @@
+#[cfg(feature = "metrics")]
+use crate::metrics::{self, Metric};
+use crate::middleware::HttpError;
+use std::sync::OnceLock;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::Span;
+
+/// Span of one model call, with attributes from the OpenTelemetry GenAI semantic conventions
+///
+/// With the `metrics` feature the same values are recorded as metrics. The operation
+/// duration and outcome are recorded when the value is dropped, so a streaming call
+/// keeps it alive until the stream ends.
+#[derive(Debug)]
+pub(crate) struct GenAiSpan {
+    span: Span,
+    started: Instant,
+    first_token_seen: bool,
+    error_type: OnceLock<String>,
+    #[cfg(feature = "metrics")]
+    system: String,
+    #[cfg(feature = "metrics")]
+    model: String,
+}
+
+impl GenAiSpan {
+    /// Opens the span for a call
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - The `gen_ai.system` value, e.g. "gcp.gemini"
+    /// * `operation` - The `gen_ai.operation.name` value, e.g. "text_completion"
+    /// * `model` - The requested model
+    pub fn new(system: &str, operation: &str, model: &str) -> Self {
+        let span = tracing::info_span!(
+            "gen_ai",
+            otel.name = %format!("{} {}", operation, model),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            gen_ai.system = system,
+            gen_ai.operation.name = operation,
+            gen_ai.request.model = model,
+            gen_ai.prompt.template.version = Empty,
+            gen_ai.response.model = Empty,
+            gen_ai.response.finish_reasons = Empty,
+            gen_ai.usage.input_tokens = Empty,
+            gen_ai.usage.output_tokens = Empty,
+            gen_ai.server.time_to_first_token = Empty,
+            gen_ai.client.operation.duration = Empty,
+            error.type = Empty,
+        );
+        GenAiSpan {
+            span,
+            started: Instant::now(),
+            first_token_seen: false,
+            error_type: OnceLock::new(),
+            #[cfg(feature = "metrics")]
+            system: system.to_string(),
+            #[cfg(feature = "metrics")]
+            model: model.to_string(),
+        }
+    }
+
+    /// Provider and model labels for the metrics
+    #[cfg(feature = "metrics")]
+    fn labels(&self) -> [(&'static str, &str); 2] {
+        [("provider", &self.system), ("model", &self.model)]
+    }
+
+    /// The underlying span, to instrument the call with
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Records the version id of the prompt template the request was rendered from
+    pub fn template_version(&self, version: Option<&str>) {
+        if let Some(version) = version {
+            self.span.record("gen_ai.prompt.template.version", version);
+        }
+    }
+
+    /// Records the model that served the response
+    pub fn response_model(&self, model: &str) {
+        self.span.record("gen_ai.response.model", model);
+    }
+
+    /// Records the finish reasons of the response, comma separated
+    pub fn finish_reasons(&self, reasons: &str) {
+        self.span.record("gen_ai.response.finish_reasons", reasons);
+    }
+
+    /// Records the token usage reported by the provider
+    pub fn usage(&self, input_tokens: Option<u32>, output_tokens: Option<u32>) {
+        if let Some(tokens) = input_tokens {
+            self.span.record("gen_ai.usage.input_tokens", tokens);
+            #[cfg(feature = "metrics")]
+            self.count_tokens("input", tokens);
+        }
+        if let Some(tokens) = output_tokens {
+            self.span.record("gen_ai.usage.output_tokens", tokens);
+            #[cfg(feature = "metrics")]
+            self.count_tokens("output", tokens);
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn count_tokens(&self, kind: &str, tokens: u32) {
+        let [provider, model] = self.labels();
+        metrics::increment(
+            Metric::Tokens,
+            &[provider, model, ("type", kind)],
+            tokens as f64,
+        );
+    }
+
+    /// Records the time to the first streamed chunk; later calls are ignored
+    pub fn first_token(&mut self) {
+        if !self.first_token_seen {
+            self.first_token_seen = true;
+            let elapsed = self.started.elapsed().as_secs_f64();
+            self.span
+                .record("gen_ai.server.time_to_first_token", elapsed);
+            #[cfg(feature = "metrics")]
+            metrics::observe(Metric::TimeToFirstToken, &self.labels(), elapsed);
+        }
+    }
+
+    /// Marks the call as failed with the given error type; only the first error is kept
+    pub fn error(&self, error_type: &str) {
+        if self.error_type.set(error_type.to_string()).is_err() {
+            return;
+        }
+        self.span.record("otel.status_code", "ERROR");
+        self.span.record("error.type", error_type);
+    }
+
+    /// Marks the call as failed in the HTTP stack, using the status code as the error type
+    pub fn http_error(&self, err: &HttpError) {
+        match err {
+            HttpError::Status { status, .. } => self.error(status.as_str()),
+            HttpError::Network(err) if err.is_timeout() => self.error("timeout"),
+            HttpError::Network(_) => self.error("network"),
+            HttpError::Middleware(_) => self.error("middleware"),
+            HttpError::BudgetExceeded(_) => self.error("budget_exceeded"),
+        }
+    }
+}
+
+impl Drop for GenAiSpan {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        self.span
+            .record("gen_ai.client.operation.duration", elapsed);
+        #[cfg(feature = "metrics")]
+        {
+            let [provider, model] = self.labels();
+            let outcome = self.error_type.get().map_or("success", String::as_str);
+            metrics::observe(Metric::RequestDuration, &[provider, model], elapsed);
+            metrics::increment(
+                Metric::Requests,
+                &[provider, model, ("outcome", outcome)],
+                1.0,
+            );
+        }
+    }
+}
+
+/// Installs a global `tracing` subscriber that exports spans through OpenTelemetry
+///
+/// Events are also printed to stderr, filtered by `RUST_LOG` (default `info`).
+///
+/// # Arguments
+///
+/// * `tracer` - A tracer from a configured OpenTelemetry tracer provider
+///
+/// # Returns
+///
+/// An error if a global subscriber is already installed
+#[cfg(feature = "otel")]
+pub fn init_opentelemetry<T>(tracer: T) -> Result<(), tracing_subscriber::util::TryInitError>
+where
+    T: opentelemetry::trace::Tracer + Send + Sync + 'static,
+    T::Span: Send + Sync,
+{
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::EnvFilter;
+
+    dotenv::dotenv().ok();
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+}
@@ -0,0 +1,48 @@
+use crate::conversation::types::Message;
+use crate::gemini::types::Tool;
+use serde_json::json;
+use std::fmt;
+
+/// Estimated tokens added to each message for its role marker and separators
+pub const MESSAGE_OVERHEAD: u32 = 4;
+
+/// Estimated tokens added to each function declaration for its framing
+pub const TOOL_OVERHEAD: u32 = 8;
+
+/// Counts the tokens of text for one model
+///
+/// Messages and tool schemas are counted from their text plus a fixed overhead, so every
+/// counter measures prompts the same way.
+pub trait TokenCounter: fmt::Debug + Send + Sync {
+    /// Counts the tokens of a text
+    fn count(&self, text: &str) -> u32;
+
+    /// Adjusts the counter with the actual token count of a text, e.g. from `UsageMetadata`
+    ///
+    /// Exact counters ignore this.
+    fn calibrate(&self, _text: &str, _actual_tokens: u32) {}
+
+    /// Counts the tokens of chat messages
+    fn count_messages(&self, messages: &[Message]) -> u32 {
+        messages
+            .iter()
+            .map(|message| self.count(&message.content) + MESSAGE_OVERHEAD)
+            .sum()
+    }
+
+    /// Counts the tokens of tool schemas as they are sent with a request
+    fn count_tools(&self, tools: &[Tool]) -> u32 {
+        tools
+            .iter()
+            .flat_map(|tool| &tool.function_declarations)
+            .map(|declaration| {
+                let schema = json!({
+                    "name": declaration.name,
+                    "description": declaration.description,
+                    "parameters": declaration.parameters,
+                });
+                self.count(&schema.to_string()) + TOOL_OVERHEAD
+            })
+            .sum()
+    }
+}
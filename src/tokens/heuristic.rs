@@ -0,0 +1,160 @@
+use crate::tokens::counter::TokenCounter;
+use crate::tokens::registry::normalize_model;
+use std::sync::Mutex;
+
+/// Characters per token of non-ASCII text, such as CJK scripts
+const NON_ASCII_CHARS_PER_TOKEN: f64 = 1.5;
+
+/// Weight of a new observation when calibrating
+const CALIBRATION_WEIGHT: f64 = 0.2;
+
+/// Characters per token of English prose, by model family
+const FAMILIES: &[(&str, f64)] = &[
+    ("command", 4.2),
+    ("gemini", 4.2),
+    ("gemma", 4.2),
+    ("llama", 3.6),
+    ("llama-3", 4.2),
+    ("llama3", 4.2),
+    ("mistral", 3.6),
+    ("mixtral", 3.6),
+    ("phi", 3.8),
+    ("qwen", 4.0),
+];
+
+/// Estimates token counts from the number of characters
+///
+/// The estimate is multiplied by a scale that `calibrate` moves towards observed counts,
+/// so a heuristic fed with actual usage gets closer to the model's tokenizer over time.
+#[derive(Debug)]
+pub struct Heuristic {
+    chars_per_token: f64,
+    scale: Mutex<f64>,
+}
+
+impl Default for Heuristic {
+    fn default() -> Self {
+        Heuristic::new(4.0)
+    }
+}
+
+impl Heuristic {
+    /// Creates a heuristic for a tokenizer averaging `chars_per_token` characters of ASCII text
+    pub fn new(chars_per_token: f64) -> Self {
+        Heuristic {
+            chars_per_token,
+            scale: Mutex::new(1.0),
+        }
+    }
+
+    /// Creates a heuristic with the ratio of the model's family, or 4 characters per token
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model name (e.g., "llama3:8b" or "models/gemini-1.5-pro")
+    pub fn for_model(model: &str) -> Self {
+        match family(&normalize_model(model)) {
+            Some((_, chars_per_token)) => Heuristic::new(chars_per_token),
+            None => Heuristic::default(),
+        }
+    }
+
+    /// Returns the calibration scale, 1.0 until `calibrate` is called
+    pub fn scale(&self) -> f64 {
+        *self
+            .scale
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Uncalibrated estimate
+    fn raw(&self, text: &str) -> f64 {
+        let (ascii, other) = text.chars().fold((0usize, 0usize), |(ascii, other), c| {
+            if c.is_ascii() {
+                (ascii + 1, other)
+            } else {
+                (ascii, other + 1)
+            }
+        });
+        ascii as f64 / self.chars_per_token + other as f64 / NON_ASCII_CHARS_PER_TOKEN
+    }
+}
+
+impl TokenCounter for Heuristic {
+    fn count(&self, text: &str) -> u32 {
+        (self.raw(text) * self.scale()).ceil() as u32
+    }
+
+    fn calibrate(&self, text: &str, actual_tokens: u32) {
+        let raw = self.raw(text);
+        if raw < 1.0 || actual_tokens == 0 {
+            return;
+        }
+        let mut scale = self
+            .scale
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *scale += (actual_tokens as f64 / raw - *scale) * CALIBRATION_WEIGHT;
+    }
+}
+
+/// Returns the known family of a normalized model name, with its characters per token
+pub(crate) fn family(model: &str) -> Option<(&'static str, f64)> {
+    FAMILIES
+        .iter()
+        .filter(|(family, _)| model.starts_with(family))
+        .max_by_key(|(family, _)| family.len())
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_ascii_and_other_characters() {
+        let heuristic = Heuristic::new(4.0);
+        assert_eq!(heuristic.count(""), 0);
+        assert_eq!(heuristic.count("abcd"), 1);
+        assert_eq!(heuristic.count("abcde"), 2);
+        // Three CJK characters count as two tokens
+        assert_eq!(heuristic.count("日本語"), 2);
+        assert_eq!(heuristic.count("abcd日本語"), 3);
+    }
+
+    #[test]
+    fn picks_the_longest_family() {
+        assert_eq!(family("llama3:8b"), Some(("llama3", 4.2)));
+        assert_eq!(family("llama-3-8b"), Some(("llama-3", 4.2)));
+        assert_eq!(family("llama2"), Some(("llama", 3.6)));
+        assert_eq!(family("gpt-4"), None);
+
+        let text = "a".repeat(36);
+        assert_eq!(
+            Heuristic::for_model("meta-llama/Llama-2-7b").count(&text),
+            10
+        );
+        assert_eq!(Heuristic::for_model("gpt-4").count(&text), 9);
+    }
+
+    #[test]
+    fn calibration_moves_towards_actual_counts() {
+        let heuristic = Heuristic::new(4.0);
+        let text = "a".repeat(40);
+        assert_eq!(heuristic.scale(), 1.0);
+
+        heuristic.calibrate(&text, 20);
+        assert!((heuristic.scale() - 1.2).abs() < 1e-9);
+        for _ in 0..50 {
+            heuristic.calibrate(&text, 20);
+        }
+        assert!((heuristic.scale() - 2.0).abs() < 0.01);
+        assert_eq!(heuristic.count(&text), 20);
+
+        // Texts too short to measure and empty counts are ignored
+        let scale = heuristic.scale();
+        heuristic.calibrate("ab", 5);
+        heuristic.calibrate(&text, 0);
+        assert_eq!(heuristic.scale(), scale);
+    }
+}
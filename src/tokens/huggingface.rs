@@ -0,0 +1,98 @@
+use crate::rate_limit::estimate_tokens;
+use crate::tokens::counter::TokenCounter;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use tokenizers::Tokenizer;
+use tracing::{info, warn};
+
+/// Custom error type to handle different error scenarios
+#[derive(Debug)]
+pub enum TokenizerError {
+    /// Error while reading tokenizer files
+    IoError(io::Error),
+    /// The tokenizer file could not be loaded
+    LoadError(String),
+}
+
+impl fmt::Display for TokenizerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizerError::IoError(err) => write!(f, "IO error: {}", err),
+            TokenizerError::LoadError(msg) => write!(f, "Load error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TokenizerError {}
+
+impl From<io::Error> for TokenizerError {
+    fn from(err: io::Error) -> Self {
+        TokenizerError::IoError(err)
+    }
+}
+
+/// Exact counter using a Hugging Face `tokenizer.json`
+///
+/// Use the tokenizer of the model's original checkpoint, e.g. for a local Ollama model.
+pub struct HfTokenizer {
+    tokenizer: Tokenizer,
+}
+
+// The tokenizer itself is large, so only its vocabulary size is shown
+impl fmt::Debug for HfTokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HfTokenizer")
+            .field("vocab_size", &self.tokenizer.get_vocab_size(true))
+            .finish()
+    }
+}
+
+impl HfTokenizer {
+    /// Loads a tokenizer from a `tokenizer.json` file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the `tokenizer.json` file
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `HfTokenizer` or a `TokenizerError`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, TokenizerError> {
+        let path = path.as_ref();
+        info!("Loading tokenizer from: {:?}", path);
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Loads a tokenizer from the contents of a `tokenizer.json` file
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The file contents
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `HfTokenizer` or a `TokenizerError`
+    pub fn from_bytes(json: &[u8]) -> Result<Self, TokenizerError> {
+        let mut tokenizer =
+            Tokenizer::from_bytes(json).map_err(|e| TokenizerError::LoadError(e.to_string()))?;
+        // Count whole texts, whatever length the checkpoint truncates or pads to
+        tokenizer
+            .with_truncation(None)
+            .map_err(|e| TokenizerError::LoadError(e.to_string()))?;
+        tokenizer.with_padding(None);
+        Ok(HfTokenizer { tokenizer })
+    }
+}
+
+impl TokenCounter for HfTokenizer {
+    fn count(&self, text: &str) -> u32 {
+        match self.tokenizer.encode(text, false) {
+            Ok(encoding) => encoding.len() as u32,
+            Err(err) => {
+                warn!("Failed to tokenize text, estimating instead: {}", err);
+                estimate_tokens(text)
+            }
+        }
+    }
+}
@@ -0,0 +1,11 @@
+pub mod counter;
+pub mod heuristic;
+#[cfg(feature = "tokenizers")]
+pub mod huggingface;
+pub mod registry;
+
+pub use counter::TokenCounter;
+pub use heuristic::Heuristic;
+#[cfg(feature = "tokenizers")]
+pub use huggingface::{HfTokenizer, TokenizerError};
+pub use registry::TokenizerRegistry;
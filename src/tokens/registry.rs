@@ -0,0 +1,252 @@
+use crate::conversation::types::Message;
+use crate::gemini::types::Tool;
+use crate::tokens::counter::TokenCounter;
+use crate::tokens::heuristic::{family, Heuristic};
+#[cfg(feature = "tokenizers")]
+use crate::tokens::huggingface::{HfTokenizer, TokenizerError};
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "tokenizers")]
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Heuristics created on demand, by model family ("" for unknown families)
+type Heuristics = Mutex<HashMap<&'static str, Arc<Heuristic>>>;
+
+/// Maps model names to token counters
+///
+/// Counters are registered for a model name prefix (e.g., "llama3" matches "llama3:8b"),
+/// and the longest matching prefix wins, then the latest registration. Models without a
+/// registered counter get a `Heuristic` for their family, shared by every model of that
+/// family so calibration carries over. Clones of a registry share these heuristics.
+#[derive(Clone, Default)]
+pub struct TokenizerRegistry {
+    counters: Vec<(String, Arc<dyn TokenCounter>)>,
+    heuristics: Arc<Heuristics>,
+}
+
+impl fmt::Debug for TokenizerRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.counters
+                    .iter()
+                    .map(|(prefix, counter)| (prefix, counter)),
+            )
+            .finish()
+    }
+}
+
+impl TokenizerRegistry {
+    /// Creates a registry that only uses heuristics
+    pub fn new() -> Self {
+        TokenizerRegistry::default()
+    }
+
+    /// Registers a counter for the models starting with `prefix`
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The model name prefix (e.g., "llama3")
+    /// * `counter` - The counter for these models
+    pub fn register(mut self, prefix: &str, counter: impl TokenCounter + 'static) -> Self {
+        self.counters
+            .push((normalize_model(prefix), Arc::new(counter)));
+        self
+    }
+
+    /// Registers a Hugging Face `tokenizer.json` for the models starting with `prefix`
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The model name prefix (e.g., "llama3")
+    /// * `path` - Path to the `tokenizer.json` file
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the registry or a `TokenizerError`
+    #[cfg(feature = "tokenizers")]
+    pub fn register_file(
+        self,
+        prefix: &str,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, TokenizerError> {
+        Ok(self.register(prefix, HfTokenizer::from_file(path)?))
+    }
+
+    /// Registers every `<dir>/<model>/tokenizer.json`, using the directory name as prefix
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory holding one subdirectory per model
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the registry or a `TokenizerError`
+    #[cfg(feature = "tokenizers")]
+    pub fn register_dir(mut self, dir: impl AsRef<Path>) -> Result<Self, TokenizerError> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path().join("tokenizer.json");
+            let prefix = path
+                .parent()
+                .and_then(|model_dir| model_dir.file_name())
+                .and_then(|name| name.to_str())
+                .map(str::to_string);
+            if let (true, Some(prefix)) = (path.is_file(), prefix) {
+                self = self.register_file(&prefix, &path)?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Returns the counter for a model
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model name (e.g., "llama3:8b" or "models/gemini-1.5-pro")
+    ///
+    /// # Returns
+    ///
+    /// The registered counter with the longest matching prefix, or the family heuristic
+    pub fn counter(&self, model: &str) -> Arc<dyn TokenCounter> {
+        let model = normalize_model(model);
+        let registered = self
+            .counters
+            .iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len());
+        if let Some((prefix, counter)) = registered {
+            debug!("Counting tokens of {} with the {} counter", model, prefix);
+            return counter.clone();
+        }
+
+        let mut heuristics = self
+            .heuristics
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let family = family(&model).map_or("", |(family, _)| family);
+        heuristics
+            .entry(family)
+            .or_insert_with(|| Arc::new(Heuristic::for_model(&model)))
+            .clone()
+    }
+
+    /// Counts the tokens of a text for a model
+    pub fn count(&self, model: &str, text: &str) -> u32 {
+        self.counter(model).count(text)
+    }
+
+    /// Counts the tokens of chat messages for a model
+    pub fn count_messages(&self, model: &str, messages: &[Message]) -> u32 {
+        self.counter(model).count_messages(messages)
+    }
+
+    /// Counts the tokens of tool schemas for a model
+    pub fn count_tools(&self, model: &str, tools: &[Tool]) -> u32 {
+        self.counter(model).count_tools(tools)
+    }
+
+    /// Calibrates the model's counter with the actual token count of a text
+    pub fn calibrate(&self, model: &str, text: &str, actual_tokens: u32) {
+        self.counter(model).calibrate(text, actual_tokens);
+    }
+}
+
+/// Lowercases a model name and drops any path, e.g. "models/" or "meta-llama/"
+pub(crate) fn normalize_model(model: &str) -> String {
+    let model = model.trim().to_ascii_lowercase();
+    match model.rsplit_once('/') {
+        Some((_, name)) => name.to_string(),
+        None => model,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts every text as the same number of tokens
+    #[derive(Debug)]
+    struct Fixed(u32);
+
+    impl TokenCounter for Fixed {
+        fn count(&self, _text: &str) -> u32 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn normalizes_model_names() {
+        assert_eq!(normalize_model(" models/Gemini-1.5-Pro "), "gemini-1.5-pro");
+        assert_eq!(normalize_model("meta-llama/Llama-3-8B"), "llama-3-8b");
+        assert_eq!(normalize_model("llama3:8b"), "llama3:8b");
+    }
+
+    #[test]
+    fn longest_registered_prefix_wins() {
+        let registry = TokenizerRegistry::new()
+            .register("llama", Fixed(1))
+            .register("Llama3", Fixed(3))
+            .register("llama3:70b", Fixed(70));
+
+        assert_eq!(registry.count("llama2", "text"), 1);
+        assert_eq!(registry.count("llama3:8b", "text"), 3);
+        assert_eq!(registry.count("meta/LLAMA3:70b-instruct", "text"), 70);
+    }
+
+    #[test]
+    fn latest_registration_wins_for_the_same_prefix() {
+        let registry = TokenizerRegistry::new()
+            .register("qwen", Fixed(1))
+            .register("qwen", Fixed(2));
+        assert_eq!(registry.count("qwen2", "text"), 2);
+    }
+
+    #[test]
+    fn unregistered_models_share_their_family_heuristic() {
+        let registry = TokenizerRegistry::new().register("llama3", Fixed(3));
+        let text = "a".repeat(40);
+
+        // "gemini" averages 4.2 characters per token, unknown families 4
+        assert_eq!(registry.count("gemini-1.5-pro", &text), 10);
+        assert_eq!(registry.count("unknown-model", &text), 10);
+        assert_eq!(registry.count("mistral-large", &text), 12);
+
+        registry.calibrate("gemini-1.5-pro", &text, 20);
+        let clone = registry.clone();
+        assert!(clone.count("gemini-1.5-flash", &text) > 10);
+        // Other families are not affected
+        assert_eq!(registry.count("mistral-large", &text), 12);
+        // Registered counters ignore calibration
+        registry.calibrate("llama3", &text, 100);
+        assert_eq!(registry.count("llama3", &text), 3);
+    }
+
+    #[test]
+    fn counts_messages_and_tools_with_their_overhead() {
+        use crate::conversation::types::Role;
+        use crate::gemini::types::FunctionDeclaration;
+        use crate::tokens::counter::{MESSAGE_OVERHEAD, TOOL_OVERHEAD};
+
+        let registry = TokenizerRegistry::new().register("llama3", Fixed(5));
+        let message = Message {
+            role: Role::User,
+            content: "Hello".to_string(),
+            created_at: 0,
+        };
+        assert_eq!(
+            registry.count_messages("llama3", &[message.clone(), message]),
+            2 * (5 + MESSAGE_OVERHEAD)
+        );
+
+        let tool = Tool {
+            function_declarations: vec![FunctionDeclaration {
+                name: "get_weather".to_string(),
+                description: "Gets the weather".to_string(),
+                parameters: serde_json::json!({}),
+            }],
+        };
+        assert_eq!(registry.count_tools("llama3", &[tool]), 5 + TOOL_OVERHEAD);
+    }
+}
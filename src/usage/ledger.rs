@@ -0,0 +1,822 @@
+use crate::middleware::{HttpError, Middleware, Next, Request};
+use crate::rate_limit::{estimate_tokens, EstimatedTokens};
+use crate::usage::pricing::{ModelPrice, PricingTable, TokenUsage};
+use crate::utils::now;
+use futures_util::future::BoxFuture;
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use tracing::{debug, warn};
+
+tokio::task_local! {
+    static ATTRIBUTION: Attribution;
+}
+
+/// Error returned by the usage ledger
+#[derive(Debug)]
+pub enum UsageError {
+    /// Error while reading a pricing file
+    IoError(io::Error),
+    /// Error while parsing a pricing table
+    ParseError(serde_json::Error),
+    /// The request would exceed a budget and was not sent
+    BudgetExceeded {
+        /// The budget that would be exceeded
+        scope: BudgetScope,
+        /// The limit of the budget, in USD
+        limit: f64,
+        /// The cost already spent or reserved, in USD
+        spent: f64,
+        /// The estimated cost of the request, in USD
+        estimated: f64,
+    },
+    /// The model is missing from the pricing table and the ledger rejects unpriced models
+    UnpricedModel(String),
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UsageError::IoError(err) => write!(f, "IO error: {}", err),
+            UsageError::ParseError(err) => write!(f, "Parse error: {}", err),
+            UsageError::BudgetExceeded {
+                scope,
+                limit,
+                spent,
+                estimated,
+            } => write!(
+                f,
+                "Budget exceeded for {}: ${:.4} spent and ${:.4} estimated, limit ${:.4}",
+                scope, spent, estimated, limit
+            ),
+            UsageError::UnpricedModel(model) => write!(f, "No price for model {}", model),
+        }
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+impl From<io::Error> for UsageError {
+    fn from(err: io::Error) -> Self {
+        UsageError::IoError(err)
+    }
+}
+
+impl From<serde_json::Error> for UsageError {
+    fn from(err: serde_json::Error) -> Self {
+        UsageError::ParseError(err)
+    }
+}
+
+/// Default number of reply tokens reserved for calls that do not limit their reply
+const DEFAULT_OUTPUT_TOKENS: u32 = 1024;
+
+/// The model a request calls, attached to its extensions for the usage ledger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelName(pub String);
+
+/// The most reply tokens a request asks for, attached to its extensions for the usage ledger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxOutputTokens(pub u32);
+
+/// Who or what a model call is billed to
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Attribution {
+    /// The user the call is made for
+    pub user: Option<String>,
+    /// The feature making the call (e.g., "summarize")
+    pub feature: Option<String>,
+    /// Free-form tags
+    pub tags: Vec<String>,
+}
+
+impl Attribution {
+    /// Creates an empty attribution
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the user
+    pub fn user(mut self, user: &str) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    /// Sets the feature
+    pub fn feature(mut self, feature: &str) -> Self {
+        self.feature = Some(feature.to_string());
+        self
+    }
+
+    /// Adds a tag
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    /// The attribution set by the enclosing `with_attribution`, or an empty one
+    pub fn current() -> Attribution {
+        ATTRIBUTION
+            .try_with(|attribution| attribution.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Runs `future` with the given attribution for every model call it makes
+///
+/// # Arguments
+///
+/// * `attribution` - The user, feature and tags to bill the calls to
+/// * `future` - The calls to run
+///
+/// # Returns
+///
+/// The output of `future`
+pub async fn with_attribution<F: Future>(attribution: Attribution, future: F) -> F::Output {
+    ATTRIBUTION.scope(attribution, future).await
+}
+
+/// What a budget limits
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BudgetScope {
+    /// Every call recorded by the ledger
+    Total,
+    /// Calls attributed to a user
+    User(String),
+    /// Calls attributed to a feature
+    Feature(String),
+    /// Calls with a tag
+    Tag(String),
+}
+
+impl BudgetScope {
+    fn applies_to(&self, attribution: &Attribution) -> bool {
+        match self {
+            BudgetScope::Total => true,
+            BudgetScope::User(user) => attribution.user.as_ref() == Some(user),
+            BudgetScope::Feature(feature) => attribution.feature.as_ref() == Some(feature),
+            BudgetScope::Tag(tag) => attribution.tags.contains(tag),
+        }
+    }
+}
+
+impl fmt::Display for BudgetScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetScope::Total => write!(f, "total"),
+            BudgetScope::User(user) => write!(f, "user {}", user),
+            BudgetScope::Feature(feature) => write!(f, "feature {}", feature),
+            BudgetScope::Tag(tag) => write!(f, "tag {}", tag),
+        }
+    }
+}
+
+/// How `UsageLedger::totals_by` groups records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dimension {
+    /// By user; unattributed calls are left out
+    User,
+    /// By feature; unattributed calls are left out
+    Feature,
+    /// By tag; a call with several tags counts once per tag
+    Tag,
+    /// By model
+    Model,
+}
+
+/// One recorded model call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// When the call was recorded, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// The model called
+    pub model: String,
+    /// Who or what the call is billed to
+    pub attribution: Attribution,
+    /// The tokens used
+    pub usage: TokenUsage,
+    /// The cost in USD, from the pricing table at the time of the call
+    pub cost: f64,
+}
+
+/// Aggregated usage of a group of calls
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct UsageTotals {
+    /// The number of calls
+    pub calls: u64,
+    /// Prompt tokens, including cached ones
+    pub prompt_tokens: u64,
+    /// Prompt tokens served from a context cache
+    pub cached_tokens: u64,
+    /// Reply tokens
+    pub completion_tokens: u64,
+    /// Cost in USD
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, record: &UsageRecord) {
+        self.calls += 1;
+        self.prompt_tokens += record.usage.prompt_tokens as u64;
+        self.cached_tokens += record.usage.cached_tokens as u64;
+        self.completion_tokens += record.usage.completion_tokens as u64;
+        self.cost += record.cost;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Budget {
+    limit: f64,
+    spent: f64,
+    reserved: f64,
+}
+
+#[derive(Debug, Default)]
+struct Ledger {
+    records: Vec<UsageRecord>,
+    budgets: HashMap<BudgetScope, Budget>,
+}
+
+impl Ledger {
+    fn budgets_for<'a>(
+        &'a mut self,
+        attribution: &'a Attribution,
+    ) -> impl Iterator<Item = (&'a BudgetScope, &'a mut Budget)> + 'a {
+        self.budgets
+            .iter_mut()
+            .filter(move |(scope, _)| scope.applies_to(attribution))
+    }
+}
+
+/// Records the tokens and cost of model calls and enforces budgets
+///
+/// Costs come from a `PricingTable` that can be replaced at any time; models missing
+/// from it are recorded as free unless `reject_unpriced` is set. Before a call is sent,
+/// the cost of its prompt and of its longest reply is estimated and reserved against
+/// every budget that applies to its attribution; a call that would exceed one is
+/// rejected with `UsageError::BudgetExceeded`. Once the provider reports the usage, the
+/// reservation is replaced by the actual cost.
+///
+/// The Gemini, Ollama, Mistral, Cohere and Bedrock clients accept a ledger. The
+/// self-hosted TGI, TEI and llama.cpp servers are not billed per token and do not.
+///
+/// Clones share their records, budgets and prices.
+#[derive(Debug, Clone)]
+pub struct UsageLedger {
+    pricing: Arc<RwLock<PricingTable>>,
+    ledger: Arc<Mutex<Ledger>>,
+    default_output_tokens: u32,
+    reject_unpriced: bool,
+}
+
+impl Default for UsageLedger {
+    fn default() -> Self {
+        UsageLedger {
+            pricing: Arc::default(),
+            ledger: Arc::default(),
+            default_output_tokens: DEFAULT_OUTPUT_TOKENS,
+            reject_unpriced: false,
+        }
+    }
+}
+
+impl UsageLedger {
+    /// Creates a ledger without prices or budgets
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the pricing table
+    pub fn pricing(self, pricing: PricingTable) -> Self {
+        self.set_pricing(pricing);
+        self
+    }
+
+    /// Adds a budget, replacing any previous budget for the same scope
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - What the budget limits
+    /// * `limit` - The most that may be spent, in USD
+    pub fn budget(self, scope: BudgetScope, limit: f64) -> Self {
+        self.set_budget(scope, limit);
+        self
+    }
+
+    /// Sets the reply tokens reserved for calls that do not limit their reply (default 1024)
+    pub fn default_output_tokens(mut self, tokens: u32) -> Self {
+        self.default_output_tokens = tokens;
+        self
+    }
+
+    /// Rejects calls to models missing from the pricing table with
+    /// `UsageError::UnpricedModel` instead of recording them as free
+    pub fn reject_unpriced(mut self, reject: bool) -> Self {
+        self.reject_unpriced = reject;
+        self
+    }
+
+    fn ledger(&self) -> MutexGuard<'_, Ledger> {
+        self.ledger
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Replaces the pricing table; past records keep their cost
+    pub fn set_pricing(&self, pricing: PricingTable) {
+        *self
+            .pricing
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = pricing;
+    }
+
+    /// Sets the price of every model starting with `prefix`
+    pub fn set_price(&self, prefix: &str, price: ModelPrice) {
+        self.pricing
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .set(prefix, price);
+    }
+
+    /// Sets the limit of a budget, keeping what it has already spent
+    pub fn set_budget(&self, scope: BudgetScope, limit: f64) {
+        self.ledger()
+            .budgets
+            .entry(scope)
+            .and_modify(|budget| budget.limit = limit)
+            .or_insert(Budget {
+                limit,
+                spent: 0.0,
+                reserved: 0.0,
+            });
+    }
+
+    /// Computes the cost of a call with the current prices
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> f64 {
+        self.pricing
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .cost(model, usage)
+    }
+
+    /// Reserves the estimated cost of a call against every budget that applies to it
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model the call uses
+    /// * `attribution` - Who or what the call is billed to
+    /// * `estimated_prompt_tokens` - The estimated length of the prompt
+    /// * `max_output_tokens` - The longest reply the call may produce
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a `UsagePermit` to record the actual usage with, or
+    /// `UsageError::BudgetExceeded` or `UsageError::UnpricedModel` if the call must not
+    /// be sent
+    pub fn reserve(
+        &self,
+        model: &str,
+        attribution: Attribution,
+        estimated_prompt_tokens: u32,
+        max_output_tokens: u32,
+    ) -> Result<UsagePermit, UsageError> {
+        if self.reject_unpriced
+            && self
+                .pricing
+                .read()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .get(model)
+                .is_none()
+        {
+            warn!("Rejecting call to {}: the model has no price", model);
+            return Err(UsageError::UnpricedModel(model.to_string()));
+        }
+        let estimated = self.cost(
+            model,
+            &TokenUsage::new(estimated_prompt_tokens, max_output_tokens),
+        );
+        let mut ledger = self.ledger();
+        for (scope, budget) in ledger.budgets_for(&attribution) {
+            let spent = budget.spent + budget.reserved;
+            if spent + estimated > budget.limit {
+                warn!("Rejecting call to {}: budget for {} exceeded", model, scope);
+                return Err(UsageError::BudgetExceeded {
+                    scope: scope.clone(),
+                    limit: budget.limit,
+                    spent,
+                    estimated,
+                });
+            }
+        }
+        for (_, budget) in ledger.budgets_for(&attribution) {
+            budget.reserved += estimated;
+        }
+        debug!("Reserved ${:.6} for a call to {}", estimated, model);
+        Ok(UsagePermit {
+            reservation: Arc::new(Reservation {
+                ledger: self.clone(),
+                model: model.to_string(),
+                attribution,
+                reserved: estimated,
+                settled: AtomicBool::new(false),
+            }),
+        })
+    }
+
+    /// Records a call made without a reservation
+    ///
+    /// # Returns
+    ///
+    /// The cost of the call in USD
+    pub fn record(&self, model: &str, attribution: Attribution, usage: TokenUsage) -> f64 {
+        self.settle(model, attribution, 0.0, Some(usage))
+    }
+
+    /// Releases a reservation and records the actual usage, if any
+    fn settle(
+        &self,
+        model: &str,
+        attribution: Attribution,
+        reserved: f64,
+        usage: Option<TokenUsage>,
+    ) -> f64 {
+        let cost = usage.map_or(0.0, |usage| self.cost(model, &usage));
+        let mut ledger = self.ledger();
+        for (_, budget) in ledger.budgets_for(&attribution) {
+            budget.reserved = (budget.reserved - reserved).max(0.0);
+            budget.spent += cost;
+        }
+        if let Some(usage) = usage {
+            debug!(
+                "Recorded {} prompt and {} completion tokens of {} for ${:.6}",
+                usage.prompt_tokens, usage.completion_tokens, model, cost
+            );
+            ledger.records.push(UsageRecord {
+                timestamp: now(),
+                model: model.to_string(),
+                attribution,
+                usage,
+                cost,
+            });
+        }
+        cost
+    }
+
+    /// Returns every recorded call, oldest first
+    pub fn records(&self) -> Vec<UsageRecord> {
+        self.ledger().records.clone()
+    }
+
+    /// Returns the totals of every recorded call
+    pub fn totals(&self) -> UsageTotals {
+        let mut totals = UsageTotals::default();
+        for record in &self.ledger().records {
+            totals.add(record);
+        }
+        totals
+    }
+
+    /// Returns the totals of the recorded calls, grouped by user, feature, tag or model
+    pub fn totals_by(&self, dimension: Dimension) -> BTreeMap<String, UsageTotals> {
+        let mut groups: BTreeMap<String, UsageTotals> = BTreeMap::new();
+        for record in &self.ledger().records {
+            let keys: Vec<&String> = match dimension {
+                Dimension::User => record.attribution.user.iter().collect(),
+                Dimension::Feature => record.attribution.feature.iter().collect(),
+                Dimension::Tag => record.attribution.tags.iter().collect(),
+                Dimension::Model => vec![&record.model],
+            };
+            for key in keys {
+                groups.entry(key.clone()).or_default().add(record);
+            }
+        }
+        groups
+    }
+
+    /// Returns what is left of a budget in USD, or `None` if the scope has no budget
+    pub fn remaining(&self, scope: &BudgetScope) -> Option<f64> {
+        self.ledger()
+            .budgets
+            .get(scope)
+            .map(|budget| budget.limit - budget.spent - budget.reserved)
+    }
+
+    /// Clears the records and the spending of every budget, e.g. at the start of a
+    /// billing period
+    ///
+    /// Reservations of calls in flight are kept.
+    pub fn reset(&self) {
+        let mut ledger = self.ledger();
+        ledger.records.clear();
+        for budget in ledger.budgets.values_mut() {
+            budget.spent = 0.0;
+        }
+    }
+}
+
+/// Checks the budgets before passing the request on
+///
+/// The model comes from the `ModelName` extension; requests without it are passed on
+/// unchecked. The prompt estimate comes from the `EstimatedTokens` extension, or from
+/// the body size when it is missing. The reply is assumed to be as long as the
+/// `MaxOutputTokens` extension allows, or `default_output_tokens` without it. The
+/// `UsagePermit` is added to the response extensions so the caller can record the
+/// usage once it is known.
+impl Middleware for UsageLedger {
+    fn handle<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response, HttpError>> {
+        Box::pin(async move {
+            let model = match request.extensions().get::<ModelName>() {
+                Some(ModelName(model)) => model.clone(),
+                None => return next.run(request).await,
+            };
+            let estimated_tokens = match request.extensions().get::<EstimatedTokens>() {
+                Some(EstimatedTokens(tokens)) => *tokens,
+                None => request
+                    .body_bytes()
+                    .map(|body| estimate_tokens(&String::from_utf8_lossy(body)))
+                    .unwrap_or_default(),
+            };
+            let max_output_tokens = match request.extensions().get::<MaxOutputTokens>() {
+                Some(MaxOutputTokens(tokens)) => *tokens,
+                None => self.default_output_tokens,
+            };
+            let permit = self
+                .reserve(
+                    &model,
+                    Attribution::current(),
+                    estimated_tokens,
+                    max_output_tokens,
+                )
+                .map_err(HttpError::BudgetExceeded)?;
+            let mut response = next.run(request).await?;
+            response.extensions_mut().insert(permit);
+            Ok(response)
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Reservation {
+    ledger: UsageLedger,
+    model: String,
+    attribution: Attribution,
+    reserved: f64,
+    settled: AtomicBool,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if !self.settled.swap(true, Ordering::AcqRel) {
+            self.ledger.settle(
+                &self.model,
+                std::mem::take(&mut self.attribution),
+                self.reserved,
+                None,
+            );
+        }
+    }
+}
+
+/// A cost reserved in a `UsageLedger` for one call
+///
+/// Clones share the reservation; only the first `record` takes effect. The reservation
+/// is released when the last clone is dropped without recording.
+#[derive(Debug, Clone)]
+pub struct UsagePermit {
+    reservation: Arc<Reservation>,
+}
+
+impl UsagePermit {
+    /// Replaces the reservation with the usage reported by the provider
+    ///
+    /// # Returns
+    ///
+    /// The cost of the call in USD, or `None` if it was already recorded
+    pub fn record(self, usage: TokenUsage) -> Option<f64> {
+        let reservation = &self.reservation;
+        if reservation.settled.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some(reservation.ledger.settle(
+            &reservation.model,
+            reservation.attribution.clone(),
+            reservation.reserved,
+            Some(usage),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::Stack;
+    use crate::retry::RetryPolicy;
+
+    const MILLION: u32 = 1_000_000;
+
+    /// A ledger where a million prompt or reply tokens of "model" cost $1
+    fn ledger() -> UsageLedger {
+        UsageLedger::new().pricing(PricingTable::new().price("model", ModelPrice::new(1.0, 1.0)))
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    fn alice() -> Attribution {
+        Attribution::new().user("alice").feature("chat").tag("beta")
+    }
+
+    #[test]
+    fn records_usage_and_totals() {
+        let ledger = ledger();
+        let cost = ledger.record("model", alice(), TokenUsage::new(MILLION, MILLION));
+        assert_close(cost, 2.0);
+        ledger.record("other", Attribution::new(), TokenUsage::new(10, 5));
+
+        let totals = ledger.totals();
+        assert_eq!(totals.calls, 2);
+        assert_eq!(totals.prompt_tokens, MILLION as u64 + 10);
+        assert_close(totals.cost, 2.0);
+
+        let by_user = ledger.totals_by(Dimension::User);
+        assert_eq!(by_user.keys().collect::<Vec<_>>(), vec!["alice"]);
+        let by_model = ledger.totals_by(Dimension::Model);
+        assert_eq!(by_model["other"].completion_tokens, 5);
+        assert_eq!(ledger.totals_by(Dimension::Tag)["beta"].calls, 1);
+
+        ledger.reset();
+        assert!(ledger.records().is_empty());
+    }
+
+    #[test]
+    fn rejects_calls_over_a_budget() {
+        let ledger = ledger()
+            .budget(BudgetScope::User("alice".to_string()), 1.0)
+            .budget(BudgetScope::Total, 10.0);
+
+        // The reservation covers the prompt and the longest reply
+        let permit = ledger.reserve("model", alice(), 300_000, 300_000).unwrap();
+        assert_close(
+            ledger
+                .remaining(&BudgetScope::User("alice".to_string()))
+                .unwrap(),
+            0.4,
+        );
+
+        match ledger.reserve("model", alice(), 300_000, 200_000) {
+            Err(UsageError::BudgetExceeded {
+                scope,
+                limit,
+                spent,
+                estimated,
+            }) => {
+                assert_eq!(scope, BudgetScope::User("alice".to_string()));
+                assert_close(limit, 1.0);
+                assert_close(spent, 0.6);
+                assert_close(estimated, 0.5);
+            }
+            other => panic!("expected a budget error, got {:?}", other.map(|_| ())),
+        }
+
+        // Other users only draw from the total budget
+        let bob = Attribution::new().user("bob");
+        assert!(ledger.reserve("model", bob, 300_000, 200_000).is_ok());
+
+        // Recording replaces the reservation with the actual cost
+        assert_close(
+            permit.clone().record(TokenUsage::new(100_000, 0)).unwrap(),
+            0.1,
+        );
+        assert!(permit.record(TokenUsage::new(1, 1)).is_none());
+        assert_close(
+            ledger
+                .remaining(&BudgetScope::User("alice".to_string()))
+                .unwrap(),
+            0.9,
+        );
+        assert!(ledger
+            .remaining(&BudgetScope::Feature("chat".to_string()))
+            .is_none());
+    }
+
+    #[test]
+    fn releases_dropped_reservations() {
+        let ledger = ledger().budget(BudgetScope::Tag("beta".to_string()), 1.0);
+        let scope = BudgetScope::Tag("beta".to_string());
+
+        let permit = ledger.reserve("model", alice(), MILLION, 0).unwrap();
+        assert_close(ledger.remaining(&scope).unwrap(), 0.0);
+        drop(permit);
+        assert_close(ledger.remaining(&scope).unwrap(), 1.0);
+        assert!(ledger.records().is_empty());
+
+        // Unpriced models are free and never exceed a budget
+        assert!(ledger
+            .reserve("unpriced", alice(), MILLION, MILLION)
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_unpriced_models_when_asked() {
+        let ledger = ledger().reject_unpriced(true);
+        match ledger.reserve("unpriced", alice(), 10, 10) {
+            Err(UsageError::UnpricedModel(model)) => assert_eq!(model, "unpriced"),
+            other => panic!(
+                "expected an unpriced model error, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+        assert!(ledger.reserve("models/model-2", alice(), 10, 10).is_ok());
+
+        // Calls recorded without a reservation are kept, as they were already made
+        assert_close(
+            ledger.record("unpriced", alice(), TokenUsage::new(10, 10)),
+            0.0,
+        );
+        assert_eq!(ledger.records().len(), 1);
+    }
+
+    #[test]
+    fn keeps_spending_when_a_budget_changes() {
+        let ledger = ledger().budget(BudgetScope::Total, 1.0);
+        ledger.record("model", alice(), TokenUsage::new(MILLION / 2, 0));
+        ledger.set_budget(BudgetScope::Total, 2.0);
+        assert_close(ledger.remaining(&BudgetScope::Total).unwrap(), 1.5);
+
+        // New prices apply to later calls only
+        ledger.set_price("model", ModelPrice::new(10.0, 10.0));
+        assert_close(ledger.totals().cost, 0.5);
+        assert_close(ledger.cost("model", &TokenUsage::new(MILLION, 0)), 10.0);
+    }
+
+    #[tokio::test]
+    async fn attribution_follows_the_task() {
+        assert_eq!(Attribution::current(), Attribution::new());
+        let current = with_attribution(alice(), async { Attribution::current() }).await;
+        assert_eq!(current, alice());
+    }
+
+    #[tokio::test]
+    async fn middleware_reserves_before_sending() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/")
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let ledger = ledger()
+            .default_output_tokens(1000)
+            .budget(BudgetScope::Total, 0.01);
+        let stack = Stack {
+            ledger: Some(ledger.clone()),
+            retry_policy: RetryPolicy::none(),
+            ..Default::default()
+        };
+        let client = reqwest::Client::new();
+        let send = |max_output_tokens: Option<u32>| {
+            let mut request = Request::new(client.post(server.url()).body("{}").build().unwrap());
+            request
+                .extensions_mut()
+                .insert(ModelName("model".to_string()));
+            request.extensions_mut().insert(EstimatedTokens(1000));
+            if let Some(tokens) = max_output_tokens {
+                request.extensions_mut().insert(MaxOutputTokens(tokens));
+            }
+            stack.send(&client, request)
+        };
+
+        let response = send(None).await.unwrap();
+        assert_close(ledger.remaining(&BudgetScope::Total).unwrap(), 0.008);
+        let permit = response.extensions().get::<UsagePermit>().cloned().unwrap();
+        permit.record(TokenUsage::new(1000, 500));
+        assert_close(ledger.remaining(&BudgetScope::Total).unwrap(), 0.0085);
+
+        // A long reply would exceed the budget, so the request is never sent
+        let err = send(Some(10_000)).await.unwrap_err();
+        assert!(matches!(
+            err,
+            HttpError::BudgetExceeded(UsageError::BudgetExceeded {
+                scope: BudgetScope::Total,
+                ..
+            })
+        ));
+        mock.assert_async().await;
+    }
+}
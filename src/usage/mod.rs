@@ -0,0 +1,8 @@
+pub mod ledger;
+pub mod pricing;
+
+pub use ledger::{
+    with_attribution, Attribution, BudgetScope, Dimension, MaxOutputTokens, ModelName, UsageError,
+    UsageLedger, UsagePermit, UsageRecord, UsageTotals,
+};
+pub use pricing::{ModelPrice, PriceTier, PricingTable, TokenUsage};
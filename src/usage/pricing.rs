@@ -0,0 +1,289 @@
+use crate::tokens::registry::normalize_model;
+use crate::usage::ledger::UsageError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::debug;
+
+/// Tokens used by one call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Tokens in the prompt, including cached ones
+    pub prompt_tokens: u32,
+    /// Prompt tokens served from a context cache
+    pub cached_tokens: u32,
+    /// Tokens in the reply
+    pub completion_tokens: u32,
+}
+
+impl TokenUsage {
+    /// Creates a usage without cached tokens
+    pub fn new(prompt_tokens: u32, completion_tokens: u32) -> Self {
+        TokenUsage {
+            prompt_tokens,
+            cached_tokens: 0,
+            completion_tokens,
+        }
+    }
+
+    /// Sets the number of prompt tokens served from a cache
+    pub fn cached(mut self, cached_tokens: u32) -> Self {
+        self.cached_tokens = cached_tokens.min(self.prompt_tokens);
+        self
+    }
+}
+
+/// Prices that apply to prompts longer than a threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceTier {
+    /// The tier applies to prompts of more than this many tokens
+    pub above_prompt_tokens: u32,
+    /// Price per million prompt tokens
+    pub input: f64,
+    /// Price per million reply tokens
+    pub output: f64,
+    /// Price per million cached prompt tokens; the input price when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
+}
+
+/// The price of a model, per million tokens
+///
+/// With tiers, the prices of the highest tier whose threshold the prompt exceeds apply
+/// to the whole call, as with Gemini's long-context pricing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// Price per million prompt tokens
+    pub input: f64,
+    /// Price per million reply tokens
+    pub output: f64,
+    /// Price per million cached prompt tokens; the input price when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
+    /// Prices for long prompts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tiers: Vec<PriceTier>,
+}
+
+impl ModelPrice {
+    /// Creates a price
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Price per million prompt tokens
+    /// * `output` - Price per million reply tokens
+    pub fn new(input: f64, output: f64) -> Self {
+        ModelPrice {
+            input,
+            output,
+            cached_input: None,
+            tiers: Vec::new(),
+        }
+    }
+
+    /// Sets the price per million cached prompt tokens
+    pub fn cached_input(mut self, price: f64) -> Self {
+        self.cached_input = Some(price);
+        self
+    }
+
+    /// Adds prices for prompts of more than `above_prompt_tokens` tokens
+    pub fn tier(mut self, above_prompt_tokens: u32, input: f64, output: f64) -> Self {
+        self.tiers.push(PriceTier {
+            above_prompt_tokens,
+            input,
+            output,
+            cached_input: None,
+        });
+        self.tiers.sort_by_key(|tier| tier.above_prompt_tokens);
+        self
+    }
+
+    /// Computes the cost of a call
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let (input, output, cached_input) = match self
+            .tiers
+            .iter()
+            .filter(|tier| usage.prompt_tokens > tier.above_prompt_tokens)
+            .max_by_key(|tier| tier.above_prompt_tokens)
+        {
+            Some(tier) => (tier.input, tier.output, tier.cached_input),
+            None => (self.input, self.output, self.cached_input),
+        };
+        let cached = usage.cached_tokens.min(usage.prompt_tokens);
+        let uncached = usage.prompt_tokens - cached;
+        (uncached as f64 * input
+            + cached as f64 * cached_input.unwrap_or(input)
+            + usage.completion_tokens as f64 * output)
+            / 1_000_000.0
+    }
+}
+
+/// Prices of models, looked up by the longest matching model name prefix
+///
+/// Serializes as a JSON object from model prefix to `ModelPrice`, so the table can be
+/// kept in a file and reloaded when prices change. Prefixes are normalized like model
+/// names when the table is deserialized.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(
+    from = "BTreeMap<String, ModelPrice>",
+    into = "BTreeMap<String, ModelPrice>"
+)]
+pub struct PricingTable {
+    models: BTreeMap<String, ModelPrice>,
+}
+
+impl From<BTreeMap<String, ModelPrice>> for PricingTable {
+    fn from(models: BTreeMap<String, ModelPrice>) -> Self {
+        let mut table = PricingTable::new();
+        for (prefix, price) in models {
+            table.set(&prefix, price);
+        }
+        table
+    }
+}
+
+impl From<PricingTable> for BTreeMap<String, ModelPrice> {
+    fn from(table: PricingTable) -> Self {
+        table.models
+    }
+}
+
+impl PricingTable {
+    /// Creates an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the price of every model starting with `prefix` (e.g., "gemini-1.5-flash")
+    pub fn price(mut self, prefix: &str, price: ModelPrice) -> Self {
+        self.set(prefix, price);
+        self
+    }
+
+    /// Sets the price of every model starting with `prefix`, replacing any previous one
+    pub fn set(&mut self, prefix: &str, price: ModelPrice) {
+        self.models.insert(normalize_model(prefix), price);
+    }
+
+    /// Parses a table from JSON
+    pub fn from_json(json: &str) -> Result<Self, UsageError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Reads a table from a JSON file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, UsageError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Returns the price of a model
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The model name (e.g., "llama3:8b" or "models/gemini-1.5-pro-002")
+    ///
+    /// # Returns
+    ///
+    /// The price with the longest matching prefix, or `None` if the model is not priced
+    pub fn get(&self, model: &str) -> Option<&ModelPrice> {
+        let model = normalize_model(model);
+        let price = self
+            .models
+            .iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| price);
+        if price.is_none() {
+            debug!("No price for model {}", model);
+        }
+        price
+    }
+
+    /// Computes the cost of a call; unpriced models are free
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> f64 {
+        self.get(model).map_or(0.0, |price| price.cost(usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn prices_prompt_reply_and_cached_tokens() {
+        let price = ModelPrice::new(1.0, 4.0);
+        assert_close(price.cost(&TokenUsage::new(1_000_000, 500_000)), 3.0);
+
+        let price = price.cached_input(0.25);
+        let usage = TokenUsage::new(1_000_000, 0).cached(400_000);
+        assert_close(price.cost(&usage), 0.6 + 0.1);
+
+        // Cached tokens never exceed the prompt
+        assert_eq!(TokenUsage::new(10, 0).cached(50).cached_tokens, 10);
+    }
+
+    #[test]
+    fn applies_the_highest_matching_tier() {
+        let price = ModelPrice::new(1.0, 2.0)
+            .tier(200_000, 3.0, 6.0)
+            .tier(128_000, 2.0, 4.0);
+        assert_eq!(price.tiers[0].above_prompt_tokens, 128_000);
+
+        assert_close(price.cost(&TokenUsage::new(100_000, 0)), 0.1);
+        assert_close(price.cost(&TokenUsage::new(128_000, 0)), 0.128);
+        assert_close(price.cost(&TokenUsage::new(150_000, 100_000)), 0.3 + 0.4);
+        assert_close(price.cost(&TokenUsage::new(300_000, 0)), 0.9);
+    }
+
+    #[test]
+    fn looks_up_the_longest_prefix() {
+        let table = PricingTable::new()
+            .price("gemini-1.5", ModelPrice::new(1.0, 1.0))
+            .price("gemini-1.5-flash", ModelPrice::new(0.1, 0.1));
+
+        assert_eq!(table.get("gemini-1.5-pro-002").unwrap().input, 1.0);
+        assert_eq!(table.get("gemini-1.5-flash-8b").unwrap().input, 0.1);
+        assert_eq!(table.get("models/Gemini-1.5-Flash").unwrap().input, 0.1);
+        assert!(table.get("llama3").is_none());
+        assert_eq!(table.cost("llama3", &TokenUsage::new(1_000_000, 0)), 0.0);
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let table = PricingTable::new().price(
+            "gemini-1.5-pro",
+            ModelPrice::new(1.25, 5.0)
+                .cached_input(0.3125)
+                .tier(128_000, 2.5, 10.0),
+        );
+        let json = serde_json::to_string(&table).unwrap();
+        assert_eq!(PricingTable::from_json(&json).unwrap(), table);
+    }
+
+    #[test]
+    fn normalizes_keys_when_parsing() {
+        let table = PricingTable::from_json(
+            r#"{"models/Gemini-1.5-Flash": {"input": 0.075, "output": 0.3}}"#,
+        )
+        .unwrap();
+        assert_eq!(table.get("gemini-1.5-flash-002").unwrap().output, 0.3);
+
+        assert!(matches!(
+            PricingTable::from_json(r#"{"llama3": {"input": 1}}"#),
+            Err(UsageError::ParseError(_))
+        ));
+        assert!(matches!(
+            PricingTable::from_file("/nonexistent/prices.json"),
+            Err(UsageError::IoError(_))
+        ));
+    }
+}